@@ -0,0 +1,57 @@
+//! Compare reading whole METADATA files with reading only their headers, line
+//! by line through a `BufReader` and memory mapped, on a synthetic environment
+//! with large embedded descriptions:
+//! `cargo bench --bench metadata_header -- [PACKAGES] [DESCRIPTION_KB]`
+
+use rdeptree::utils::{metadata_header, read_header, read_header_buffered, read_text};
+use std::fs;
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+fn create_env(packages: usize, description_kb: usize) -> Vec<PathBuf> {
+    let root = std::env::temp_dir().join(format!("rdeptree-bench-{}", std::process::id()));
+    let description =
+        "Long description line of the package readme.\n".repeat(description_kb * 1024 / 46);
+    (0..packages)
+        .map(|i| {
+            let dir = root.join(format!("pkg{}-1.0.dist-info", i));
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("METADATA");
+            let metadata = format!(
+                "Metadata-Version: 2.1\nName: pkg{}\nVersion: 1.0\nRequires-Dist: six>=1.0\n\n{}",
+                i, description
+            );
+            fs::write(&path, metadata).unwrap();
+            path
+        })
+        .collect()
+}
+
+fn measure(files: &[PathBuf], read: impl Fn(&PathBuf) -> usize) -> Duration {
+    let start = Instant::now();
+    for file in files {
+        black_box(read(file));
+    }
+    start.elapsed()
+}
+
+fn main() {
+    // cargo passes --bench to harness-less benches
+    let args: Vec<usize> = std::env::args().filter_map(|a| a.parse().ok()).collect();
+    let packages = args.first().copied().unwrap_or(100);
+    let description_kb = args.get(1).copied().unwrap_or(256);
+    let files = create_env(packages, description_kb);
+
+    for round in 1..=3 {
+        let whole = measure(&files, |f| metadata_header(&read_text(f).unwrap()).len());
+        let buffered = measure(&files, |f| read_header_buffered(f).unwrap().len());
+        let mapped = measure(&files, |f| read_header(f).unwrap().len());
+        println!(
+            "round {}: {} packages with {} KB descriptions, whole file {:?}, \
+             buffered header {:?}, mapped header {:?}",
+            round, packages, description_kb, whole, buffered, mapped
+        );
+    }
+    fs::remove_dir_all(files[0].parent().unwrap().parent().unwrap()).unwrap();
+}
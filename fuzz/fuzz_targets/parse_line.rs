@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rdeptree::dag::{parse_line, split_dependency_str, ParsedLine};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(line) = std::str::from_utf8(data) {
+        if let Some(ParsedLine::Dependency(_, dependency_str)) = parse_line(line) {
+            let _ = split_dependency_str(&dependency_str);
+        }
+    }
+});
@@ -0,0 +1,210 @@
+//! Status badge of the environment as a flat SVG image,
+//! for dashboards and READMEs embedding live status images
+
+use crate::dag::{DependencyDag, DistributionName};
+use crate::renderer::Summary;
+use crate::utils::compare_versions;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const GREEN: &str = "#4c1";
+const YELLOW: &str = "#dfb317";
+const RED: &str = "#e05d44";
+const GREY: &str = "#9f9f9f";
+const LABEL_COLOR: &str = "#555";
+
+/// Approximate width of a character of 11px Verdana, and padding of both halves
+const CHAR_WIDTH: usize = 7;
+const PADDING: usize = 10;
+
+/// Two-part badge, e.g. `deps | 3 outdated / 1 missing`
+#[derive(Debug, PartialEq)]
+pub struct Badge {
+    pub label: String,
+    pub message: String,
+    /// css color of the message part
+    pub color: &'static str,
+}
+
+/// What the badge knows about newer releases of installed packages
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Outdated {
+    /// the index was not queried
+    NotChecked,
+    /// the newest release of some package could not be looked up
+    Unknown,
+    /// number of packages older than their newest release
+    Count(usize),
+}
+
+/// Installed packages older than the newest release on the index,
+/// comparing parsed versions
+pub fn count_outdated(
+    dag: &DependencyDag,
+    latest_versions: &HashMap<DistributionName, String>,
+) -> usize {
+    latest_versions
+        .iter()
+        .filter(|(name, latest)| {
+            dag.get(*name).is_some_and(|meta| {
+                compare_versions(&meta.installed_version, latest) == Some(Ordering::Less)
+            })
+        })
+        .count()
+}
+
+impl Badge {
+    /// Badge of the report totals: red with conflicts or missing packages,
+    /// yellow with outdated ones only. Outdated packages are counted only
+    /// when the latest releases are known, the badge is grey `unknown`
+    /// when some of them are not and nothing else is wrong
+    pub fn of(summary: &Summary, outdated: Outdated) -> Self {
+        let mut parts = Vec::new();
+        if let Outdated::Count(count) = outdated {
+            if count > 0 {
+                parts.push(format!("{} outdated", count));
+            }
+        }
+        if summary.conflicts > 0 {
+            parts.push(format!("{} conflicts", summary.conflicts));
+        }
+        if summary.missing > 0 {
+            parts.push(format!("{} missing", summary.missing));
+        }
+        let color = if summary.conflicts > 0 || summary.missing > 0 {
+            RED
+        } else if !parts.is_empty() {
+            YELLOW
+        } else if outdated == Outdated::Unknown {
+            GREY
+        } else {
+            GREEN
+        };
+        let message = match (parts.is_empty(), outdated) {
+            (false, _) => parts.join(" / "),
+            (true, Outdated::Count(_)) => String::from("up to date"),
+            (true, Outdated::Unknown) => String::from("unknown"),
+            (true, Outdated::NotChecked) => String::from("ok"),
+        };
+        Self {
+            label: String::from("deps"),
+            message,
+            color,
+        }
+    }
+
+    pub fn render_svg(&self, w: &mut dyn Write) -> io::Result<()> {
+        let label_width = self.label.chars().count() * CHAR_WIDTH + PADDING;
+        let message_width = self.message.chars().count() * CHAR_WIDTH + PADDING;
+        let width = label_width + message_width;
+        let label = xml_escape(&self.label);
+        let message = xml_escape(&self.message);
+        writeln!(
+            w,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">"#
+        )?;
+        writeln!(w, "<title>{label}: {message}</title>")?;
+        writeln!(
+            w,
+            r##"<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>"##
+        )?;
+        writeln!(
+            w,
+            r##"<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>"##
+        )?;
+        writeln!(
+            w,
+            r#"<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="{}"/><rect x="{label_width}" width="{message_width}" height="20" fill="{}"/><rect width="{width}" height="20" fill="url(#s)"/></g>"#,
+            LABEL_COLOR, self.color
+        )?;
+        writeln!(
+            w,
+            r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11"><text x="{}" y="14">{label}</text><text x="{}" y="14">{message}</text></g>"##,
+            label_width / 2,
+            label_width + message_width / 2
+        )?;
+        writeln!(w, "</svg>")
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn summary(conflicts: usize, missing: usize) -> Summary {
+        Summary {
+            packages: 10,
+            direct: 2,
+            conflicts,
+            missing,
+        }
+    }
+
+    #[test]
+    fn badge_message_and_color() {
+        let badge = |conflicts, missing, outdated| {
+            let badge = Badge::of(&summary(conflicts, missing), outdated);
+            (badge.message, badge.color)
+        };
+        assert_eq!(
+            badge(0, 0, Outdated::NotChecked),
+            (String::from("ok"), GREEN)
+        );
+        assert_eq!(
+            badge(0, 0, Outdated::Count(0)),
+            (String::from("up to date"), GREEN)
+        );
+        assert_eq!(
+            badge(0, 0, Outdated::Count(3)),
+            (String::from("3 outdated"), YELLOW)
+        );
+        assert_eq!(
+            badge(0, 0, Outdated::Unknown),
+            (String::from("unknown"), GREY)
+        );
+        assert_eq!(
+            badge(0, 1, Outdated::Unknown),
+            (String::from("1 missing"), RED)
+        );
+        assert_eq!(
+            badge(1, 2, Outdated::Count(3)),
+            (String::from("3 outdated / 1 conflicts / 2 missing"), RED)
+        );
+
+        let mut svg = Vec::new();
+        Badge::of(&summary(0, 1), Outdated::NotChecked)
+            .render_svg(&mut svg)
+            .unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="111" "#));
+        assert!(svg.contains("<title>deps: 1 missing</title>"));
+        assert!(svg.contains(&format!(r#"fill="{}""#, RED)));
+        assert_eq!(xml_escape(r#"<a & "b">"#), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+
+    #[test]
+    fn outdated_by_parsed_versions() {
+        let dag = crate::fixture::SitePackages::new("badge-outdated")
+            .dist_info("six", "1.9", &[])
+            .dist_info("lib", "1.10", &[])
+            .dist_info("tool", "2.0", &[]);
+        let dag = crate::builder::DagBuilder::new()
+            .paths([dag.path()])
+            .build()
+            .unwrap();
+        let latest = HashMap::from([
+            (String::from("six"), String::from("1.10")),
+            (String::from("lib"), String::from("1.9")),
+            (String::from("tool"), String::from("2.0.0")),
+        ]);
+        assert_eq!(count_outdated(&dag, &latest), 1);
+    }
+}
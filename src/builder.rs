@@ -0,0 +1,252 @@
+use crate::dag::{insert_distribution, scan_env, DependencyDag, MergeStrategy};
+use crate::deadline;
+use crate::lazy::LazyDag;
+use crate::marker::MarkerEnv;
+use crate::profile::Profile;
+use crate::scanner::EnvScanner;
+use crate::source::{open_source, MetadataSource, OpenedRoots};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Single entry point to build a dependency dag out of site-packages dirs
+///
+/// ```no_run
+/// use rdeptree::builder::DagBuilder;
+///
+/// let dag = DagBuilder::new()
+///     .paths(["/venv/lib/python3.12/site-packages"])
+///     .include_extras(["socks"])
+///     .evaluate_markers(true)
+///     .follow_egg_info(true)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DagBuilder {
+    paths: Vec<PathBuf>,
+    inherited_paths: Vec<PathBuf>,
+    merge_strategy: MergeStrategy,
+    extras: Vec<String>,
+    evaluate_markers: bool,
+    marker_env: MarkerEnv,
+    follow_egg_info: bool,
+    strict: bool,
+}
+
+impl DagBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Site-packages dirs or archives of them (.zip, .tar, .tar.gz),
+    /// scanned in the given order
+    pub fn paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.paths.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Site-packages dirs of the base interpreter a virtual env inherits
+    /// (include-system-site-packages), scanned after `paths`. Distributions
+    /// found there are marked as inherited, dirs already in `paths` are skipped
+    pub fn inherited_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.inherited_paths
+            .extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// How to treat the same distribution found in several dirs
+    pub fn merge_strategy(mut self, strategy: MergeStrategy) -> Self {
+        self.merge_strategy = strategy;
+        self
+    }
+
+    /// Extras considered requested when markers are evaluated
+    pub fn include_extras<I, S>(mut self, extras: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.extras
+            .extend(extras.into_iter().map(|e| e.as_ref().to_string()));
+        self
+    }
+
+    /// Drop dependencies whose markers don't hold, i.e. extras which
+    /// were not requested and requirements of other platforms
+    pub fn evaluate_markers(mut self, evaluate: bool) -> Self {
+        self.evaluate_markers = evaluate;
+        self
+    }
+
+    /// Values of marker variables (python_version, sys_platform, ...),
+    /// markers with unknown variables are considered true
+    pub fn marker_values(mut self, values: HashMap<String, String>) -> Self {
+        self.marker_env = self.marker_env.with_values(values);
+        self
+    }
+
+    /// Also parse legacy *.egg-info dirs
+    pub fn follow_egg_info(mut self, follow: bool) -> Self {
+        self.follow_egg_info = follow;
+        self
+    }
+
+    /// Fail on Name, Version or Requires-Dist lines the parser does not
+    /// understand instead of skipping them
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn build(&self) -> Result<DependencyDag, String> {
+        self.build_profiled(&mut Profile::default())
+    }
+
+    /// Same as `build`, but metadata files are parsed only when
+    /// a distribution or its subtree is queried, see `LazyDag`
+    pub fn build_lazy(&self) -> Result<LazyDag, String> {
+        LazyDag::new(
+            self.sources()?,
+            self.marker_env(),
+            self.merge_strategy,
+            self.follow_egg_info,
+            self.strict,
+        )
+    }
+
+    /// Stream parsed distributions instead of collecting them into
+    /// a dag, see `EnvScanner`
+    pub fn scanner(&self) -> Result<EnvScanner, String> {
+        Ok(EnvScanner::new(
+            self.sources()?,
+            self.marker_env(),
+            self.follow_egg_info,
+            self.strict,
+        ))
+    }
+
+    /// Marker env the requirements are evaluated in, None without evaluation
+    fn marker_env(&self) -> Option<MarkerEnv> {
+        self.evaluate_markers
+            .then(|| self.marker_env.clone().with_extras(&self.extras))
+    }
+
+    /// Scanned roots in order, flagged when they are inherited
+    fn roots(&self) -> Vec<(&PathBuf, bool)> {
+        let inherited = self
+            .inherited_paths
+            .iter()
+            .filter(|path| !self.paths.contains(path));
+        self.paths
+            .iter()
+            .map(|path| (path, false))
+            .chain(inherited.map(|path| (path, true)))
+            .collect()
+    }
+
+    /// Opened scanned roots in order, flagged when they are inherited
+    fn sources(&self) -> Result<OpenedRoots, String> {
+        self.roots()
+            .into_iter()
+            .map(|(path, inherited)| Ok((Self::open(path)?, inherited)))
+            .collect()
+    }
+
+    fn open(path: &Path) -> Result<Box<dyn MetadataSource>, String> {
+        open_source(path).map_err(|err| format!("Can not read {}: {}", path.display(), err))
+    }
+
+    /// Same as `build`, recording time of the scan, parse and graph
+    /// stages and of every parsed metadata file into the profile
+    pub fn build_profiled(&self, profile: &mut Profile) -> Result<DependencyDag, String> {
+        let marker_env = self.marker_env();
+
+        let mut dependency_dag = DependencyDag::new();
+        for (env_path, inherited) in self.roots() {
+            let source = Self::open(env_path)?;
+            let distributions = scan_env(
+                source.as_ref(),
+                marker_env.as_ref(),
+                self.follow_egg_info,
+                self.strict,
+                profile,
+            )?;
+            for (name, mut meta) in distributions {
+                meta.inherited = inherited;
+                profile.time("graph", || {
+                    insert_distribution(&mut dependency_dag, name, meta, self.merge_strategy)
+                })?;
+            }
+            // the rest of the roots is not scanned, what was found is returned
+            if deadline::expired() {
+                break;
+            }
+        }
+        Ok(dependency_dag)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::SitePackages;
+
+    #[test]
+    fn build_with_markers_and_egg_info() {
+        let site = SitePackages::new("builder")
+            .dist_info(
+                "requests",
+                "2.31.0",
+                &[
+                    "idna<4,>=2.5",
+                    "PySocks!=1.5.7,>=1.5.6; extra == \"socks\"",
+                    "chardet<6,>=3.0.2; extra == \"use_chardet_on_py3\"",
+                    "pywin32>=1.0; sys_platform == \"win32\"",
+                ],
+            )
+            .dist_info("idna", "3.6", &[])
+            .egg_info(
+                "legacy",
+                "1.0",
+                "idna>=2.0\n\n[test]\npytest>=7.0\n\n[:sys_platform == \"win32\"]\npywin32>=1.0\n",
+            );
+
+        let dag = DagBuilder::new().paths([site.path()]).build().unwrap();
+        assert_eq!(dag["requests"].dependencies.len(), 4);
+        assert!(!dag.contains_key("legacy"));
+
+        let dag = DagBuilder::new()
+            .paths([site.path()])
+            .include_extras(["Socks"])
+            .evaluate_markers(true)
+            .marker_values(HashMap::from([(
+                "sys_platform".to_string(),
+                "linux".to_string(),
+            )]))
+            .follow_egg_info(true)
+            .build()
+            .unwrap();
+        let mut requests_deps: Vec<&str> = dag["requests"]
+            .dependencies
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        requests_deps.sort();
+        assert_eq!(requests_deps, vec!["idna", "pysocks"]);
+
+        let legacy_deps: Vec<&str> = dag["legacy"]
+            .dependencies
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(legacy_deps, vec!["idna"]);
+    }
+}
@@ -0,0 +1,187 @@
+//! On-disk cache of expensive lookups, e.g. RECORD hashing and index queries,
+//! shared between runs. Entries expire after a TTL, cache problems are never fatal:
+//! unreadable entries are recomputed and failed writes are ignored
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    /// key the entry was stored for, guards against digest collisions
+    key: String,
+    /// unix timestamp
+    stored_at: u64,
+    value: T,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Cache of computed values by kind and key, e.g. ("upload-time", "requests==2.31.0").
+/// A cache without dir stores nothing
+#[derive(Debug, Default)]
+pub struct ResultCache {
+    dir: Option<PathBuf>,
+    ttl_secs: u64,
+    refresh: bool,
+}
+
+impl ResultCache {
+    /// Cache in the given dir, entries older than `ttl_secs` are recomputed
+    pub fn new(dir: PathBuf, ttl_secs: u64) -> Self {
+        Self {
+            dir: Some(dir),
+            ttl_secs,
+            refresh: false,
+        }
+    }
+
+    /// Cache which stores nothing, every value is computed
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Ignore stored entries and recompute every value, storing the new ones
+    pub fn refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    fn entry_path(&self, kind: &str, key: &str) -> Option<PathBuf> {
+        let digest: String = Sha256::digest(key.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Some(
+            self.dir
+                .as_ref()?
+                .join(kind)
+                .join(format!("{}.json", digest)),
+        )
+    }
+
+    fn load<T: DeserializeOwned>(&self, kind: &str, key: &str) -> Option<T> {
+        if self.refresh {
+            return None;
+        }
+        let content = fs::read(self.entry_path(kind, key)?).ok()?;
+        let entry: Entry<T> = serde_json::from_slice(&content).ok()?;
+        let fresh = unix_now().saturating_sub(entry.stored_at) < self.ttl_secs;
+        (entry.key == key && fresh).then_some(entry.value)
+    }
+
+    fn store<T: Serialize>(&self, kind: &str, key: &str, value: &T) {
+        let Some(path) = self.entry_path(kind, key) else {
+            return;
+        };
+        let entry = Entry {
+            key: key.to_string(),
+            stored_at: unix_now(),
+            value,
+        };
+        let Ok(content) = serde_json::to_vec(&entry) else {
+            return;
+        };
+        // written aside and renamed, so parallel runs never read a partial entry
+        let partial = path.with_extension(format!("{}.partial", std::process::id()));
+        let stored = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&partial, content))
+            .and_then(|_| fs::rename(&partial, &path));
+        if stored.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+    }
+
+    /// Stored value of the kind and key, or the computed one, which is stored
+    /// when computed successfully. Errors are not cached
+    pub fn get_or_try_insert_with<T, E>(
+        &self,
+        kind: &str,
+        key: &str,
+        compute: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        if let Some(value) = self.load(kind, key) {
+            return Ok(value);
+        }
+        let value = compute()?;
+        self.store(kind, key, &value);
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::SitePackages;
+    use std::cell::Cell;
+
+    #[test]
+    fn values_are_reused_within_ttl() {
+        let dir = SitePackages::new("result-cache");
+        let calls = Cell::new(0);
+        let compute = |value: u64| {
+            calls.set(calls.get() + 1);
+            Ok::<u64, String>(value)
+        };
+
+        let cache = ResultCache::new(dir.path().join("cache"), 3600);
+        assert_eq!(
+            cache.get_or_try_insert_with("kind", "six==1.16.0", || compute(1)),
+            Ok(1)
+        );
+        assert_eq!(
+            cache.get_or_try_insert_with("kind", "six==1.16.0", || compute(2)),
+            Ok(1)
+        );
+        assert_eq!(
+            cache.get_or_try_insert_with("other", "six==1.16.0", || compute(3)),
+            Ok(3)
+        );
+        assert_eq!(calls.get(), 2);
+
+        let failed = cache.get_or_try_insert_with("kind", "lib==2.0", || Err::<u64, _>("offline"));
+        assert_eq!(failed, Err("offline"));
+        assert_eq!(
+            cache.get_or_try_insert_with("kind", "lib==2.0", || compute(4)),
+            Ok(4)
+        );
+
+        let refreshing = ResultCache::new(dir.path().join("cache"), 3600).refresh(true);
+        assert_eq!(
+            refreshing.get_or_try_insert_with("kind", "six==1.16.0", || compute(5)),
+            Ok(5)
+        );
+        assert_eq!(
+            cache.get_or_try_insert_with("kind", "six==1.16.0", || compute(6)),
+            Ok(5)
+        );
+
+        let expired = ResultCache::new(dir.path().join("cache"), 0);
+        assert_eq!(
+            expired.get_or_try_insert_with("kind", "six==1.16.0", || compute(7)),
+            Ok(7)
+        );
+
+        let disabled = ResultCache::disabled();
+        assert_eq!(
+            disabled.get_or_try_insert_with("kind", "x", || compute(8)),
+            Ok(8)
+        );
+        assert_eq!(
+            disabled.get_or_try_insert_with("kind", "x", || compute(9)),
+            Ok(9)
+        );
+    }
+}
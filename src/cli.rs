@@ -0,0 +1,751 @@
+use clap::builder::{BoolishValueParser, PossibleValuesParser};
+use clap::{Parser, Subcommand, ValueEnum};
+use rdeptree::dag::{MergeStrategy, RootStrategy};
+use rdeptree::graph::EdgeKindFilter;
+#[cfg(feature = "network")]
+use rdeptree::health::HealthOrder;
+use rdeptree::i18n::{Lang, LogFormat};
+#[cfg(feature = "network")]
+use rdeptree::index::DEFAULT_INDEX_URL;
+use rdeptree::pep::{ParseError, VersionFilter};
+#[cfg(feature = "network")]
+use rdeptree::pep::{Requirement, MARKER_VARIABLES};
+use rdeptree::renderer::RendererRegistry;
+#[cfg(feature = "network")]
+use rdeptree::update::DEFAULT_RELEASE_URL;
+use rdeptree::utils::{parse_age, parse_size};
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+/// Simple python project dependencies explorer
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// Scan given site-packages dir(s) or .zip/.tar/.tar.gz archives of them
+    /// instead of locating the python env, may be repeated
+    /// [env: RDEPTREE_PATH, separated like PATH]
+    #[arg(long, global = true, value_name = "DIR")]
+    pub path: Vec<PathBuf>,
+
+    /// Locate the python env of this interpreter instead of the active venv
+    /// or the `python3` found on PATH
+    #[arg(
+        long,
+        global = true,
+        env = "RDEPTREE_PYTHON",
+        value_name = "PATH",
+        conflicts_with = "path"
+    )]
+    pub python: Option<PathBuf>,
+
+    /// Scan the given python minor version, e.g. 3.11, installed under the prefix
+    /// of the located interpreter, instead of the one `python3` resolves to
+    #[arg(long, global = true, value_name = "X.Y", conflicts_with = "path")]
+    pub python_version: Option<String>,
+
+    /// How to treat the same package found in several scanned dirs
+    #[arg(long, global = true, env = "RDEPTREE_MERGE_STRATEGY", value_enum, default_value_t = MergeStrategyArg::First)]
+    pub merge_strategy: MergeStrategyArg,
+
+    /// Drop dependencies whose environment markers don't hold for the
+    /// located interpreter, e.g. extras which were not requested
+    #[arg(long, global = true, env = "RDEPTREE_EVALUATE_MARKERS", value_parser = BoolishValueParser::new())]
+    pub evaluate_markers: bool,
+
+    /// Consider given extra requested when evaluating markers, may be repeated
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        requires = "evaluate_markers"
+    )]
+    pub extra: Vec<String>,
+
+    /// Also read packages installed as legacy *.egg-info dirs and zipped *.egg files
+    #[arg(long, global = true, env = "RDEPTREE_FOLLOW_EGG_INFO", value_parser = BoolishValueParser::new())]
+    pub follow_egg_info: bool,
+
+    /// Keep only dependencies of given kinds in every view and export:
+    /// `runtime`, `extra`, `extra:<name>`, `conditional` or `conditional:<text>`
+    /// (markers containing the text). Packages required only through other
+    /// dependencies are left out too
+    #[arg(
+        long,
+        global = true,
+        env = "RDEPTREE_EDGE_KINDS",
+        value_name = "KIND",
+        value_delimiter = ','
+    )]
+    pub edge_kinds: Vec<EdgeKindFilter>,
+
+    /// Render only the dependency tree of given package(s)
+    #[arg(short, long, value_name = "NAME")]
+    pub package: Vec<String>,
+
+    /// How root packages are chosen without --package: `auto` (not required by
+    /// anyone), `requested` (installed explicitly) or `explicit=<name>,<name>`
+    #[arg(
+        long,
+        env = "RDEPTREE_ROOTS",
+        value_name = "STRATEGY",
+        default_value = "auto"
+    )]
+    pub roots: RootStrategy,
+
+    /// Don't append package totals to the text output
+    #[arg(long)]
+    pub no_summary: bool,
+
+    /// Wrap the json output into `{"packages": [...], "summary": {...}}`
+    /// instead of a plain list of packages
+    #[arg(long)]
+    pub json_summary: bool,
+
+    /// Append to the text output how many transitive dependencies only each
+    /// root requires, i.e. how many packages would go away together with it
+    #[arg(long)]
+    pub root_cost: bool,
+
+    /// Annotate every package with the tool which installed it (pip, uv, poetry, ...)
+    #[arg(long, global = true)]
+    pub show_installer: bool,
+
+    /// Annotate every package with the python versions it supports (Requires-Python)
+    #[arg(long, global = true)]
+    pub show_requires_python: bool,
+
+    /// Annotate every package with the OS package (dpkg or rpm) owning it,
+    /// telling apt/dnf-managed packages from pip-managed ones
+    #[arg(long, global = true)]
+    pub show_os_package: bool,
+
+    /// Mark packages the user installed explicitly (REQUESTED marker) with [direct]
+    #[arg(long, global = true)]
+    pub show_requested: bool,
+
+    /// Append the list of direct reverse dependencies to every package
+    #[arg(long, global = true)]
+    pub show_requirers: bool,
+
+    /// Annotate every package with the age of its installed release,
+    /// taken from upload times on the index
+    #[cfg(feature = "network")]
+    #[arg(long, global = true)]
+    pub show_age: bool,
+
+    /// With --show-age, flag releases older than given age, e.g. 90d, 6m or 2y
+    #[cfg(feature = "network")]
+    #[arg(long, global = true, value_name = "AGE", value_parser = parse_age, requires = "show_age")]
+    pub max_age: Option<u64>,
+
+    /// Query the index for the newest release of every package,
+    /// shown in the latest column of the markdown format
+    #[cfg(feature = "network")]
+    #[arg(long, global = true)]
+    pub show_latest: bool,
+
+    /// Base url of an index serving the PyPI JSON API, queried with curl
+    #[cfg(feature = "network")]
+    #[arg(long, global = true, env = "RDEPTREE_INDEX_URL", value_name = "URL", default_value = DEFAULT_INDEX_URL)]
+    pub index_url: String,
+
+    /// Hide standard packaging tooling (pip, setuptools, wheel, ...)
+    /// and packages required only by it
+    #[arg(long, global = true, env = "RDEPTREE_EXCLUDE_DEFAULTS", value_parser = BoolishValueParser::new())]
+    pub exclude_defaults: bool,
+
+    /// Hide packages matching a glob pattern, e.g. 'internal-*', and packages
+    /// required only by them; may be repeated. Added after the patterns of
+    /// .rdeptreeignore, the last matching pattern wins, `!pattern` un-hides
+    #[arg(long, global = true, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Don't read .rdeptreeignore from the current dir or its parents up to the git root
+    #[arg(long, global = true, env = "RDEPTREE_NO_IGNORE_FILE", value_parser = BoolishValueParser::new())]
+    pub no_ignore_file: bool,
+
+    /// Keep given package even if it is excluded by --exclude-defaults,
+    /// --exclude or .rdeptreeignore
+    #[arg(long, global = true, value_name = "NAME")]
+    pub keep: Vec<String>,
+
+    /// Group top level trees by given property; with `origin` every package
+    /// is also colored by its source (index, vcs, local path, direct url),
+    /// `prefix` clusters packages sharing a name prefix, e.g. `aws-*`
+    #[arg(long, global = true, value_enum, value_name = "PROPERTY")]
+    pub group_by: Option<GroupBy>,
+
+    /// Output format of the dependency trees
+    #[arg(long, alias = "output", env = "RDEPTREE_OUTPUT", default_value = "text", value_parser = PossibleValuesParser::new(RendererRegistry::default().names()))]
+    pub format: String,
+
+    /// When to use colors in the output
+    #[arg(long, global = true, env = "RDEPTREE_COLOR", value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Language of diagnostics (en, ru) [default: from LC_ALL/LC_MESSAGES/LANG]
+    #[arg(long, global = true, env = "RDEPTREE_LANG", value_name = "LANG", value_parser = parse_lang)]
+    pub lang: Option<Lang>,
+
+    /// When a package name is not found, proceed with the closest installed one
+    #[arg(long, global = true)]
+    pub fuzzy: bool,
+
+    /// Format of diagnostics printed to stderr
+    #[arg(long, global = true, env = "RDEPTREE_LOG_FORMAT", value_enum, default_value_t = LogFormatArg::Text)]
+    pub log_format: LogFormatArg,
+
+    /// Print nothing but the requested output and errors: warnings and
+    /// informational messages are suppressed, for scripts parsing the output
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "RDEPTREE_QUIET",
+        value_parser = BoolishValueParser::new(),
+        visible_alias = "machine"
+    )]
+    pub quiet: bool,
+
+    /// Print diagnostics for reporting misread metadata: metadata lines which
+    /// were not understood and the file and line every field was read from
+    #[arg(long, global = true, env = "RDEPTREE_VERBOSE", value_parser = BoolishValueParser::new())]
+    pub verbose: bool,
+
+    /// Fail when a Name, Version or Requires-Dist line of metadata is not
+    /// understood, naming the file and line, instead of skipping the line
+    #[arg(long, global = true, env = "RDEPTREE_STRICT", value_parser = BoolishValueParser::new())]
+    pub strict: bool,
+
+    /// Reuse results of RECORD hashing and index queries stored in this dir
+    /// by previous runs, e.g. by jobs of a CI matrix sharing the dir
+    #[arg(long, global = true, env = "RDEPTREE_CACHE_DIR", value_name = "DIR")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Cached results older than this are recomputed, e.g. 1d or 2w
+    #[arg(long, global = true, value_name = "AGE", value_parser = parse_age, default_value = "1d")]
+    pub cache_ttl: u64,
+
+    /// Ignore cached results and compute them again, storing the new ones
+    #[arg(long, global = true)]
+    pub refresh: bool,
+
+    /// Write progress events as json lines to this file, e.g. scan_started,
+    /// package_parsed, check_finished and warning, for GUI wrappers
+    #[arg(long, global = true, env = "RDEPTREE_EVENTS_FILE", value_name = "FILE")]
+    pub events_file: Option<PathBuf>,
+
+    /// Write progress events to this already open file descriptor instead,
+    /// e.g. a pipe set up by the wrapper
+    #[cfg(unix)]
+    #[arg(long, global = true, value_name = "N", conflicts_with = "events_file")]
+    pub events_fd: Option<u32>,
+
+    /// Skip the virtual env of a pipx/uvx wrapper rdeptree runs from while
+    /// locating the python env, so its own distributions are not reported
+    #[arg(long, global = true, env = "RDEPTREE_IGNORE_WRAPPER", value_parser = BoolishValueParser::new())]
+    pub ignore_wrapper: bool,
+
+    /// Disable every network access: commands and flags querying the index
+    /// or the release endpoint fail right away, for air-gapped hosts
+    #[arg(long, global = true, env = "RDEPTREE_OFFLINE", value_parser = BoolishValueParser::new())]
+    pub offline: bool,
+
+    /// Give up after this many seconds: interpreters, git, plugins and index
+    /// queries are killed, scanning stops, and whatever was found is printed
+    /// before exiting with code 124
+    #[arg(long, global = true, env = "RDEPTREE_TIMEOUT", value_name = "SECONDS")]
+    pub timeout: Option<u64>,
+
+    /// Print wall time of every pipeline stage (locate, scan, parse, graph, render)
+    /// and the slowest metadata files to stderr
+    #[arg(long, global = true)]
+    pub profile: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+/// Site-packages dirs used when --path is not given, separated like PATH
+const PATH_ENV_VAR: &str = "RDEPTREE_PATH";
+
+impl Cli {
+    /// Parse arguments, options which were not given fall back to
+    /// RDEPTREE_* env vars and then to their defaults
+    pub fn parse_with_env() -> Self {
+        let mut cli = Cli::parse();
+        if cli.path.is_empty() {
+            if let Some(paths) = std::env::var_os(PATH_ENV_VAR) {
+                cli.path = std::env::split_paths(&paths)
+                    .filter(|path| !path.as_os_str().is_empty())
+                    .collect();
+            }
+        }
+        cli.command = cli.command.map(Commands::unalias);
+        cli
+    }
+}
+
+impl Commands {
+    /// Top level command a `snapshot` alias stands for, other commands as is
+    fn unalias(self) -> Self {
+        match self {
+            Commands::Snapshot {
+                action:
+                    SnapshotAction::Check {
+                        max_env_size,
+                        max_package_size,
+                    },
+            } => Commands::Check {
+                max_env_size,
+                max_package_size,
+                requirements: None,
+                suggest: false,
+                write: false,
+                plugin: Vec::new(),
+                python_upgrade: None,
+                source_drift: false,
+                permissions: false,
+            },
+            Commands::Snapshot {
+                action: SnapshotAction::Fingerprint,
+            } => Commands::Fingerprint,
+            command => command,
+        }
+    }
+}
+
+fn parse_lang(lang: &str) -> Result<Lang, String> {
+    Lang::from_locale(lang).ok_or(format!("unsupported language '{}'", lang))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum MergeStrategyArg {
+    /// first found copy wins, as python imports do
+    First,
+    /// last found copy wins
+    Last,
+    /// fail when copies have different versions
+    Error,
+}
+
+impl From<MergeStrategyArg> for MergeStrategy {
+    fn from(arg: MergeStrategyArg) -> Self {
+        match arg {
+            MergeStrategyArg::First => MergeStrategy::First,
+            MergeStrategyArg::Last => MergeStrategy::Last,
+            MergeStrategyArg::Error => MergeStrategy::Error,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LogFormatArg {
+    /// human readable lines
+    Text,
+    /// one json object per line, with level and message
+    Json,
+}
+
+impl From<LogFormatArg> for LogFormat {
+    fn from(arg: LogFormatArg) -> Self {
+        match arg {
+            LogFormatArg::Text => LogFormat::Text,
+            LogFormatArg::Json => LogFormat::Json,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum GroupBy {
+    Origin,
+    Prefix,
+}
+
+/// Output format of environment diffs
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DiffFormat {
+    /// `+`/`-`/`~` lines
+    Text,
+    /// graphviz digraph of both states, changes colored
+    Dot,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Resolve `auto` by checking whether stdout is a terminal,
+    /// NO_COLOR env var (https://no-color.org) disables colors as well
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Show why a package is installed, i.e. all packages requiring it
+    Why {
+        /// Name of the installed package
+        package: String,
+    },
+    /// Show a package together with its nearest requirers and dependencies
+    Focus {
+        /// Name of the installed package
+        package: String,
+
+        /// Levels of requirers to show above the package
+        #[arg(long, default_value_t = 2)]
+        up: usize,
+
+        /// Levels of dependencies to show below the package
+        #[arg(long, default_value_t = 2)]
+        down: usize,
+    },
+    /// Recompute hashes of installed files listed in RECORD and report
+    /// modified or missing ones
+    Verify {
+        /// Name of the installed package, all packages are verified if omitted
+        package: Option<String>,
+    },
+    /// Check the environment against policy rules, exits with
+    /// non-zero code when any rule is violated
+    Check {
+        /// Max total installed size of the environment, e.g. 500MB
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_env_size: Option<u64>,
+
+        /// Max installed size of a single package, e.g. 100MB
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_package_size: Option<u64>,
+
+        /// Requirements file to compare with installed packages: differing pins,
+        /// listed packages which are not installed and unlisted top level ones are violations
+        #[arg(short = 'r', long, value_name = "FILE")]
+        requirements: Option<PathBuf>,
+
+        /// Print edits of the requirements file which fix the drift
+        #[arg(long, requires = "requirements")]
+        suggest: bool,
+
+        /// Apply edits to the requirements file, keeping comments and ordering;
+        /// fixed drift is not reported as a violation
+        #[arg(long, requires = "requirements")]
+        write: bool,
+
+        /// Run executable as an additional check, may be repeated.
+        /// It gets installed packages as json on stdin and prints
+        /// a json list of {rule, package, message} violations
+        #[arg(long, value_name = "PATH")]
+        plugin: Vec<PathBuf>,
+
+        /// Report packages whose Requires-Python excludes given python version,
+        /// the next minor version of the env interpreter if omitted
+        #[arg(long, value_name = "X.Y", num_args = 0..=1)]
+        python_upgrade: Option<Option<String>>,
+
+        /// Report local path and vcs installs whose source dir was removed,
+        /// checked out at another commit or has uncommitted changes (runs git)
+        #[arg(long)]
+        source_drift: bool,
+
+        /// Report packages with world-writable files or dirs and files owned
+        /// by other users than the owner of site-packages and root
+        #[arg(long)]
+        permissions: bool,
+    },
+    /// Print a stable digest of installed packages and their requirements,
+    /// equal fingerprints mean identical environments
+    Fingerprint,
+    /// Save current environment state to a file or compare two saved states
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Save a package and everything it requires as a snapshot,
+    /// e.g. to share or analyze a single stack
+    Extract {
+        /// Name of the installed package
+        package: String,
+
+        /// Output file
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Write packages, their requirements and findings of the default checks
+    /// into the rdeptree_* tables of a SQLite database for ad-hoc queries
+    Export {
+        /// Database file, its rdeptree_* tables are replaced, other tables are kept
+        #[arg(long, value_name = "FILE")]
+        sqlite: PathBuf,
+    },
+    /// Print `name==version` of a package and everything it transitively requires,
+    /// dependencies before their requirers, e.g. to vendor it into an offline bundle
+    VendorList {
+        /// Name of the installed package
+        package: String,
+    },
+    /// Compare a package between two environments given with `--path A --path B`:
+    /// its version and added, removed or changed direct requirements
+    DiffPackage {
+        /// Name of the package
+        package: String,
+    },
+    /// Find every virtual env (dir with pyvenv.cfg) under a dir and report conflicts,
+    /// missing requirements and duplicate installs of each in one table,
+    /// exits with non-zero code when any env has problems
+    ScanAll {
+        /// Dir to search for virtual envs, e.g. /srv/venvs
+        root: PathBuf,
+
+        /// Ask the interpreter of every env for its site-packages dir instead of
+        /// relying on the env layout, which is slower but follows custom layouts
+        #[arg(long)]
+        probe_interpreters: bool,
+
+        /// Number of envs audited at once [default: number of cores]
+        #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: Option<u16>,
+    },
+    /// Record environment states over time and report what changed
+    History {
+        /// History store location [default: ~/.local/share/rdeptree/history.jsonl]
+        #[arg(long, value_name = "FILE")]
+        store: Option<PathBuf>,
+
+        #[command(subcommand)]
+        action: HistoryAction,
+    },
+    /// Check for a newer release of rdeptree or update the binary to it (uses the network)
+    #[cfg(feature = "network")]
+    #[command(name = "self")]
+    SelfManage {
+        /// Endpoint describing the latest release in the GitHub releases API format
+        #[arg(long, value_name = "URL", default_value = DEFAULT_RELEASE_URL)]
+        release_url: String,
+
+        #[command(subcommand)]
+        action: SelfAction,
+    },
+    /// List every dir python may import packages from (site-packages, user site,
+    /// PYTHONPATH entries) and whether it is scanned, with the reason
+    Paths,
+    /// List distributions available offline in wheel caches, e.g. ~/.cache/pip/wheels
+    /// or ~/.cache/uv, before installing on an air-gapped host
+    CacheScan {
+        /// Cache dirs, searched recursively for .whl files and unpacked wheels
+        #[arg(required = true, value_name = "DIR")]
+        dirs: Vec<PathBuf>,
+
+        /// Print requirements of every cached version of the package instead
+        #[arg(long, value_name = "NAME")]
+        requires: Option<String>,
+    },
+    /// List python minor versions installed under the prefix of the located
+    /// interpreter, which can be scanned with --python-version
+    PythonVersions,
+    /// Parse a single Name/Version/Requires-Dist line from stdin
+    /// and print how the grammar understood it
+    #[command(hide = true)]
+    ParseLine,
+    /// Print Name, Version and every Requires-Dist line of an installed package
+    /// split into components the way the parser understood them
+    DumpMeta {
+        /// Name of the installed package
+        package: String,
+    },
+    /// List top level packages which were not installed explicitly, i.e. leftovers
+    /// of uninstalled packages which are likely safe to remove
+    Orphans,
+    /// List installed packages with their versions
+    List {
+        /// Keep packages whose installed version matches a PEP 440 specifier,
+        /// e.g. 'version<1' or '>=2,<2.3'
+        #[arg(long = "where", value_name = "SPEC", value_parser = parse_version_filter)]
+        filter: Option<VersionFilter>,
+    },
+    /// Show details of an installed package
+    Info {
+        /// Name of the installed package
+        package: String,
+    },
+    /// List every distinct specifier installed packages place on a package,
+    /// their intersection and whether the installed version is inside it
+    Constraints {
+        /// Name of the package, may be not installed
+        package: String,
+    },
+    /// Write an SVG status badge of the env, e.g. "deps: 3 outdated / 1 conflicts",
+    /// outdated packages are counted when built with index support
+    Badge {
+        /// Output file, stdout if not given
+        #[arg(short, long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+    /// Answer lookups of editor plugins about the scanned env
+    Lookup {
+        /// Serve JSON-RPC 2.0 requests, one per line on stdin: resolveImport {"module"},
+        /// getVersion {"package"} and getDependencies {"package"}
+        #[arg(long, required = true)]
+        stdin_jsonrpc: bool,
+    },
+    /// Show declared extras of a package and the subtrees each of them pulls in
+    Extras {
+        /// Name of the installed package
+        package: String,
+    },
+    /// Resolve the dependency tree of a package published on the index
+    /// without installing it, to the newest versions matching requirements
+    #[cfg(feature = "network")]
+    RemoteTree {
+        /// Root requirement, e.g. requests==2.31.0 or 'requests[socks]>=2'
+        #[arg(value_parser = parse_requirement)]
+        requirement: Requirement,
+
+        /// Levels of dependencies to resolve below the root
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+
+        /// Environment marker value of the target env, e.g. python_version=3.11,
+        /// may be repeated; markers with unknown variables are considered true
+        #[arg(long, value_name = "NAME=VALUE", value_parser = parse_marker_value)]
+        marker_value: Vec<(String, String)>,
+    },
+    /// Find the newest version of an installed package which can be installed
+    /// without breaking requirements of other installed packages
+    #[cfg(feature = "network")]
+    PlanUpgrade {
+        /// Name of the installed package
+        package: String,
+
+        /// Also consider upgrading installed packages which exclude the new version
+        #[arg(long)]
+        with_requirers: bool,
+    },
+    /// Score installed packages by release history on the index: age of the newest
+    /// release, yanked installed version and the number of recent releases
+    #[cfg(feature = "network")]
+    Health {
+        /// Releases uploaded within this many months are recent, at most 1200
+        #[arg(long, value_name = "N", default_value_t = 12, value_parser = clap::value_parser!(u64).range(1..=1200))]
+        months: u64,
+
+        /// Order of the report
+        #[arg(long, value_enum, default_value_t = HealthSortArg::Score)]
+        sort: HealthSortArg,
+    },
+}
+
+#[cfg(feature = "network")]
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum HealthSortArg {
+    /// lowest score first
+    Score,
+    Name,
+    /// the longest without a release first
+    Age,
+    /// the fewest recent releases first
+    Releases,
+}
+
+#[cfg(feature = "network")]
+impl From<HealthSortArg> for HealthOrder {
+    fn from(arg: HealthSortArg) -> Self {
+        match arg {
+            HealthSortArg::Score => HealthOrder::Score,
+            HealthSortArg::Name => HealthOrder::Name,
+            HealthSortArg::Age => HealthOrder::Age,
+            HealthSortArg::Releases => HealthOrder::Releases,
+        }
+    }
+}
+
+#[cfg(feature = "network")]
+fn parse_requirement(requirement: &str) -> Result<Requirement, String> {
+    requirement
+        .parse()
+        .map_err(|err: ParseError| err.to_string())
+}
+
+fn parse_version_filter(filter: &str) -> Result<VersionFilter, String> {
+    filter.parse().map_err(|err: ParseError| err.to_string())
+}
+
+#[cfg(feature = "network")]
+fn parse_marker_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, value)) if MARKER_VARIABLES.contains(&name.trim()) => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        Some((name, _)) => Err(format!("unknown environment marker '{}'", name.trim())),
+        None => Err(String::from("expected NAME=VALUE")),
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotAction {
+    /// Same as the top level `check` with size rules only
+    Check {
+        /// Max total installed size of the environment, e.g. 500MB
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_env_size: Option<u64>,
+
+        /// Max installed size of a single package, e.g. 100MB
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_package_size: Option<u64>,
+    },
+    /// Same as the top level `fingerprint`
+    Fingerprint,
+    /// Save current environment state as json
+    Save {
+        /// Output file
+        file: PathBuf,
+    },
+    /// Show what changed between two saved states
+    Diff {
+        /// Older snapshot file
+        old: PathBuf,
+        /// Newer snapshot file
+        new: PathBuf,
+
+        #[arg(long, alias = "output", value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+    },
+}
+
+#[cfg(feature = "network")]
+#[derive(Subcommand, Debug)]
+pub enum SelfAction {
+    /// Compare the running version with the latest release
+    CheckUpdate,
+    /// Replace the running binary with the latest release built for this platform
+    Update,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HistoryAction {
+    /// Append current environment state to the history store
+    Record,
+    /// List recorded states
+    List,
+    /// Show what changed between two recorded states, by default between the last two
+    Diff {
+        /// Index of the older state, as shown by `history list`
+        from: Option<usize>,
+        /// Index of the newer state, as shown by `history list`
+        to: Option<usize>,
+
+        #[arg(long, alias = "output", value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+    },
+}
@@ -1,79 +1,781 @@
+use crate::deadline;
+use crate::events;
+use crate::i18n::{debug, trf, warn, Msg};
+use crate::marker::{MarkerEnv, MarkerExpr};
+use crate::normalize::{canonical_specifier, pep503};
+use crate::origin::{read_direct_url, DirectUrl};
 use crate::parser::DepParser;
 use crate::parser::Rule;
-use crate::utils::{get_lnreader, get_meta_dirs};
+#[cfg(feature = "pep508")]
+use crate::pep::Requirement;
+use crate::policy::leftover_reason;
+use crate::profile::Profile;
+use crate::source::MetadataSource;
+use crate::utils::{MetaDirEntry, MetaDirKind};
 
 use pest::Parser;
-use regex::Regex;
-use std::collections::{HashMap, HashSet};
-use std::fs;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::json;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
 
-fn normalize_name(name: &str, replace_to: &str) -> String {
-    let re_name_normalize = Regex::new(DISTRMETA_NAME_NORMALIZE_REGEX).unwrap();
-    re_name_normalize
-        .replace_all(name, replace_to)
-        .to_lowercase()
-}
+pub type DistributionName = String;
 
-/// from https://packaging.python.org/en/latest/specifications/name-normalization/#name-normalization
-const DISTRMETA_NAME_NORMALIZE_REGEX: &'static str = r"[-_.]+";
+/// Metadata file and 1-based line number a field was parsed from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceLine {
+    pub file: PathBuf,
+    pub line: usize,
+}
 
-pub type DistributionName = String;
+impl fmt::Display for SourceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
 
-#[derive(Eq, PartialEq, Hash, Debug)]
+/// Serialized as `{"name", "specifier", "marker"?}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RequiredDistribution {
     pub name: DistributionName,
+    /// canonical specifier, empty for any version
+    #[serde(rename = "specifier")]
     pub required_version: String,
+    /// environment marker of the Requires-Dist line, e.g. `python_version < "3.11"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+    /// first Requires-Dist line the requirement was parsed from, diagnostics only
+    #[serde(skip)]
+    pub source: Option<SourceLine>,
 }
 
 impl RequiredDistribution {
     fn from_str(name: &str, version: &str) -> Self {
         Self {
-            name: normalize_name(name, "-"),
-            required_version: version.to_string(),
+            name: pep503(name),
+            required_version: canonical_specifier(version),
+            marker: None,
+            source: None,
+        }
+    }
+}
+
+/// Why a requirement is an edge of the graph, told by its marker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// required unconditionally
+    Runtime,
+    /// pulled in only by the given extras, normalized
+    Extra(Vec<String>),
+    /// required where the marker holds, e.g. `sys_platform == "win32"`
+    Conditional(String),
+}
+
+impl RequiredDistribution {
+    /// Kind of the edge: requirements whose marker compares `extra` are pulled in
+    /// by extras, any other marker makes the requirement conditional
+    pub fn kind(&self) -> EdgeKind {
+        let Some(marker) = &self.marker else {
+            return EdgeKind::Runtime;
+        };
+        match MarkerExpr::parse(marker).map(|expr| expr.extras()) {
+            Ok(extras) if !extras.is_empty() => EdgeKind::Extra(extras),
+            _ => EdgeKind::Conditional(marker.clone()),
         }
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
+/// Requirements are identified by name and specifier, markers of the same
+/// requirement are joined when it is parsed, source lines are not compared
+impl PartialEq for RequiredDistribution {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.required_version == other.required_version
+    }
+}
+
+impl Eq for RequiredDistribution {}
+
+impl Hash for RequiredDistribution {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.required_version.hash(state);
+    }
+}
+
+/// Ordered by name and specifier, the same fields as equality
+impl Ord for RequiredDistribution {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.name, &self.required_version).cmp(&(&other.name, &other.required_version))
+    }
+}
+
+impl PartialOrd for RequiredDistribution {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Requirements in a stable order, by name and specifier
+struct SortedRequirements<'a>(&'a HashSet<RequiredDistribution>);
+
+impl Serialize for SortedRequirements<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut requirements: Vec<&RequiredDistribution> = self.0.iter().collect();
+        requirements.sort_unstable();
+        serializer.collect_seq(requirements)
+    }
+}
+
+fn serialize_requirements<S: Serializer>(
+    requirements: &HashSet<RequiredDistribution>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    SortedRequirements(requirements).serialize(serializer)
+}
+
+fn serialize_extras<S: Serializer>(
+    extras: &BTreeMap<String, HashSet<RequiredDistribution>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        extras
+            .iter()
+            .map(|(extra, requirements)| (extra, SortedRequirements(requirements))),
+    )
+}
+
+/// Markers of the same requirement listed several times, e.g. once per extra,
+/// joined into one. A line without marker makes the requirement unconditional.
+/// Requirements merged after evaluation all hold at once, their specifiers are
+/// intersected, so their markers are joined with `and` and unconditional lines
+/// add nothing
+fn join_markers(markers: Vec<Option<&str>>, merged: bool) -> Option<String> {
+    let mut markers: Vec<&str> = if merged {
+        markers.into_iter().flatten().collect()
+    } else {
+        markers.into_iter().collect::<Option<_>>()?
+    };
+    markers.sort_unstable();
+    markers.dedup();
+    match markers.as_slice() {
+        [] => None,
+        [marker] => Some(marker.to_string()),
+        _ => Some(
+            markers
+                .iter()
+                .map(|m| format!("({})", m))
+                .collect::<Vec<_>>()
+                .join(if merged { " and " } else { " or " }),
+        ),
+    }
+}
+
+/// Parsed metadata of an installed distribution. The serialized form is the wire
+/// model of exports and integrations: fields keep their names, empty optional
+/// ones are omitted and parsing diagnostics are not serialized
+#[derive(Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct DistributionMeta {
+    #[serde(rename = "version")]
     pub installed_version: String,
+    #[serde(default, serialize_with = "serialize_requirements")]
     pub dependencies: HashSet<RequiredDistribution>,
+    /// tool which installed the distribution (pip, uv, poetry, ...),
+    /// taken from the INSTALLER file of dist-info dir
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installer: Option<String>,
+    /// location of the dist-info dir the distribution was parsed from
+    #[serde(rename = "location", default)]
+    pub meta_dir: PathBuf,
+    /// the user asked for the distribution explicitly rather than it being
+    /// pulled in as a dependency, i.e. dist-info has a REQUESTED marker file
+    #[serde(default)]
+    pub requested: bool,
+    /// core metadata version the METADATA file is written in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_version: Option<String>,
+    /// fields declared as Dynamic (metadata 2.2+), lowercased
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dynamic: Vec<String>,
+    /// SPDX license expression (metadata 2.4)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license_expression: Option<String>,
+    /// license files shipped with the distribution (metadata 2.4)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub license_files: Vec<String>,
+    /// found in site-packages of the base interpreter of a virtual env
+    /// created with --system-site-packages
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inherited: bool,
+    /// extras declared with Provides-Extra, each with the requirements
+    /// pulled in only when the extra is requested
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "serialize_extras"
+    )]
+    pub extras: BTreeMap<String, HashSet<RequiredDistribution>>,
+    /// extras asked of dependencies, e.g. `socks` of `requests[socks]`, by dependency name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub requested_extras: BTreeMap<DistributionName, BTreeSet<String>>,
+    /// requirements whose marker is false in the evaluated env and with every
+    /// declared extra, e.g. windows only ones on linux. Empty unless markers are evaluated
+    #[serde(
+        default,
+        skip_serializing_if = "HashSet::is_empty",
+        serialize_with = "serialize_requirements"
+    )]
+    pub excluded_by_marker: HashSet<RequiredDistribution>,
+    /// versions of python the distribution supports, as declared in Requires-Python
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires_python: Option<String>,
+    /// links declared with Project-URL, in the declared order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub project_urls: Vec<ProjectUrl>,
+    /// name as declared in the Name field, e.g. `PyYAML`, for human output.
+    /// The normalized name keys the dag and is used for matching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub declared_name: Option<String>,
+    /// lines the Name and Version fields were parsed from
+    #[serde(skip)]
+    pub name_source: Option<SourceLine>,
+    #[serde(skip)]
+    pub version_source: Option<SourceLine>,
+    /// direct reference the distribution was installed from (vcs, local path, url)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direct_url: Option<DirectUrl>,
+    /// other copies of the same distribution found in scanned roots,
+    /// which were not selected by the merge strategy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shadowed: Vec<ShadowedCopy>,
+}
+
+/// Link declared with Project-URL, e.g. `Changelog, https://example.org/changes`
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectUrl {
+    pub label: String,
+    pub url: String,
+}
+
+/// Project links users reviewing an upgrade look for
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum LinkKind {
+    Changelog,
+    Repository,
+    Documentation,
+}
+
+impl LinkKind {
+    pub const ALL: [LinkKind; 3] = [
+        LinkKind::Changelog,
+        LinkKind::Repository,
+        LinkKind::Documentation,
+    ];
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LinkKind::Changelog => "Changelog",
+            LinkKind::Repository => "Repository",
+            LinkKind::Documentation => "Documentation",
+        })
+    }
+}
+
+impl ProjectUrl {
+    /// Split a `label, url` value, None without label
+    fn parse(value: &str) -> Option<Self> {
+        let (label, url) = value.split_once(',')?;
+        let (label, url) = (label.trim(), url.trim());
+        (!label.is_empty() && !url.is_empty()).then(|| Self {
+            label: label.to_string(),
+            url: url.to_string(),
+        })
+    }
+
+    /// Kind of the link by its label, compared with punctuation, whitespace
+    /// and case removed the way PEP 753 does, e.g. `Release Notes` is a changelog
+    pub fn kind(&self) -> Option<LinkKind> {
+        let label: String = self
+            .label
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect();
+        match label.as_str() {
+            "changelog" | "changes" | "releasenotes" | "news" | "whatsnew" | "history" => {
+                Some(LinkKind::Changelog)
+            }
+            "repository" | "source" | "sourcecode" | "code" | "github" | "gitlab" => {
+                Some(LinkKind::Repository)
+            }
+            "documentation" | "docs" => Some(LinkKind::Documentation),
+            _ => None,
+        }
+    }
+}
+
+/// Installed copy of a distribution hidden by another copy with the same name
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub struct ShadowedCopy {
+    #[serde(rename = "version")]
+    pub installed_version: String,
+    #[serde(rename = "location")]
+    pub meta_dir: PathBuf,
+    /// found in site-packages of the base interpreter, hidden by the venv copy
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub inherited: bool,
+}
+
+/// How to handle the same distribution found several times in scanned roots
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Default)]
+pub enum MergeStrategy {
+    /// first found copy wins, the same way as python resolves imports along sys.path
+    #[default]
+    First,
+    /// last found copy wins, e.g. upper layer of a layered image
+    Last,
+    /// refuse to merge copies with different versions
+    Error,
 }
 
 impl DistributionMeta {
+    /// Name to show to users: the declared one, the normalized key without it
+    pub fn display_name<'a>(&'a self, name: &'a DistributionName) -> &'a str {
+        self.declared_name.as_deref().unwrap_or(name)
+    }
+
+    /// First declared project link of the given kind
+    pub fn project_url(&self, kind: LinkKind) -> Option<&str> {
+        self.project_urls
+            .iter()
+            .find(|link| link.kind() == Some(kind))
+            .map(|link| link.url.as_str())
+    }
+
+    /// Dependencies in the canonical order of exports, by name and specifier,
+    /// so exports of an unchanged env are byte-identical
+    pub fn sorted_dependencies(&self) -> Vec<&RequiredDistribution> {
+        let mut dependencies: Vec<&RequiredDistribution> = self.dependencies.iter().collect();
+        dependencies.sort_unstable();
+        dependencies
+    }
+
+    /// Whether other copies of the distribution are installed in the env.
+    /// Copies inherited from the base interpreter and hidden by the venv
+    /// copy are expected and don't count
+    pub fn has_conflicts(&self) -> bool {
+        self.shadowed.iter().any(|copy| !copy.inherited)
+    }
+
+    /// Dependency information may be incomplete when
+    /// Requires-Dist is declared as Dynamic
+    pub fn has_dynamic_dependencies(&self) -> bool {
+        self.dynamic.iter().any(|f| f == "requires-dist")
+    }
+
+    /// Dependencies with markers evaluated to false in the given env are dropped,
+    /// the ones not pulled in by any extra are kept aside as excluded by marker,
+    /// without env all of them are kept. Dependencies whose marker holds only
+    /// when one of the declared extras is requested are also listed under that extra.
+    /// With env, the remaining requirements of the same distribution all apply,
+    /// so they are merged into one edge with the intersection of their specifiers
     fn from_parsed_file(
         installed_version: String,
-        dependencies: HashSet<(String, String)>,
+        dependencies: Vec<(String, String, SourceLine)>,
+        declared_extras: &[String],
+        marker_env: Option<&MarkerEnv>,
     ) -> Result<Self, &'static str> {
-        let mut parsed_deps = HashSet::new();
-        for (dep_name, version_expr) in dependencies {
-            let parse_pair = DepParser::parse(Rule::version_comparison, &version_expr)
-                .map_err(|_| "Failed to parse dependency version expression")?
-                .next()
-                .unwrap();
+        let default_env;
+        let base_env = match marker_env {
+            Some(env) => env,
+            None => {
+                default_env = MarkerEnv::default();
+                &default_env
+            }
+        };
+        // cloned from the base env only once a marker does not hold in it
+        let mut extra_envs: Option<Vec<(&String, MarkerEnv)>> = None;
 
-            parsed_deps.insert(RequiredDistribution::from_str(
-                &dep_name,
-                parse_pair.as_str(),
-            ));
+        type Occurrences<'a> = Vec<(Option<&'a str>, &'a SourceLine)>;
+        let mut parsed_deps: HashMap<(String, String), Occurrences> = HashMap::new();
+        let mut extras: BTreeMap<String, HashSet<RequiredDistribution>> = declared_extras
+            .iter()
+            .map(|extra| (extra.clone(), HashSet::new()))
+            .collect();
+        let mut excluded_by_marker: HashSet<RequiredDistribution> = HashSet::new();
+        let mut requested_extras: BTreeMap<DistributionName, BTreeSet<String>> = BTreeMap::new();
+        for (dep_name, version_expr, source) in &dependencies {
+            let (specifier, marker) = split_dependency_str(version_expr)?;
+            // markers not understood by the evaluator keep the dependency
+            if let Some(marker) = marker.filter(|m| base_env.evaluate(m) == Ok(false)) {
+                let mut dep = RequiredDistribution::from_str(dep_name, specifier);
+                dep.marker = Some(marker.to_string());
+                dep.source = Some(source.clone());
+                let mut optional = false;
+                let extra_envs = extra_envs.get_or_insert_with(|| {
+                    declared_extras
+                        .iter()
+                        .map(|extra| (extra, base_env.clone().with_extras([extra])))
+                        .collect()
+                });
+                for (extra, env) in extra_envs.iter() {
+                    if env.evaluate(marker) == Ok(true) {
+                        optional = true;
+                        extras.get_mut(*extra).unwrap().insert(dep.clone());
+                    }
+                }
+                if marker_env.is_some() {
+                    if !optional {
+                        excluded_by_marker.insert(dep);
+                    }
+                    continue;
+                }
+            }
+            let dep = RequiredDistribution::from_str(dep_name, specifier);
+            let (requested, _) = split_requested_extras(version_expr);
+            // extras asked for only under a marker which does not hold, e.g. by
+            // a requirement of an extra which is not requested, are not needed
+            let applies = marker.is_none_or(|m| base_env.evaluate(m) != Ok(false));
+            if applies && !requested.is_empty() {
+                requested_extras
+                    .entry(dep.name.clone())
+                    .or_default()
+                    .extend(requested);
+            }
+            parsed_deps
+                .entry((dep.name, dep.required_version))
+                .or_default()
+                .push((marker, source));
+        }
+        if marker_env.is_some() {
+            let mut merged: HashMap<String, (Vec<String>, Occurrences)> = HashMap::new();
+            for ((name, required_version), occurrences) in parsed_deps {
+                let (specifiers, merged_occurrences) = merged.entry(name).or_default();
+                specifiers.push(required_version);
+                merged_occurrences.extend(occurrences);
+            }
+            parsed_deps = merged
+                .into_iter()
+                .map(|(name, (specifiers, occurrences))| {
+                    (
+                        (name, canonical_specifier(&specifiers.join(","))),
+                        occurrences,
+                    )
+                })
+                .collect();
         }
+        let parsed_deps = parsed_deps
+            .into_iter()
+            .map(
+                |((name, required_version), occurrences)| RequiredDistribution {
+                    name,
+                    required_version,
+                    source: occurrences
+                        .iter()
+                        .map(|(_, source)| *source)
+                        .min_by_key(|source| source.line)
+                        .cloned(),
+                    marker: join_markers(
+                        occurrences.into_iter().map(|(m, _)| m).collect(),
+                        marker_env.is_some(),
+                    ),
+                },
+            )
+            .collect();
 
         Ok(Self {
             installed_version,
             dependencies: parsed_deps,
+            extras,
+            excluded_by_marker,
+            requested_extras,
+            ..Default::default()
         })
     }
 }
 
 pub type DependencyDag = HashMap<DistributionName, DistributionMeta>;
 
-enum ParsedLine {
+/// Distributions which are not required by any other installed distribution, sorted by name
+pub fn get_top_level_distributions(dag: &DependencyDag) -> Vec<&DistributionName> {
+    let required_names: HashSet<&DistributionName> = dag
+        .values()
+        .flat_map(|v| &v.dependencies)
+        .map(|v| &v.name)
+        .collect();
+
+    let mut top_level: Vec<&DistributionName> =
+        dag.keys().filter(|k| !required_names.contains(k)).collect();
+    top_level.sort();
+    top_level
+}
+
+/// Top level distributions which were not requested by the user, i.e. leftovers
+/// of uninstalled requirers, sorted by name. Only dist-info dirs can carry the
+/// REQUESTED marker, so packages read from egg-info are never reported
+pub fn get_orphans(dag: &DependencyDag) -> Vec<&DistributionName> {
+    get_top_level_distributions(dag)
+        .into_iter()
+        .filter(|name| {
+            let meta = &dag[*name];
+            !meta.requested && meta.meta_dir.extension().is_some_and(|e| e == "dist-info")
+        })
+        .collect()
+}
+
+/// Distributions the user installed explicitly, i.e. dist-info has
+/// a REQUESTED marker, sorted by name
+pub fn get_requested_distributions(dag: &DependencyDag) -> Vec<&DistributionName> {
+    let mut requested: Vec<&DistributionName> = dag
+        .iter()
+        .filter(|(_, meta)| meta.requested)
+        .map(|(name, _)| name)
+        .collect();
+    requested.sort();
+    requested
+}
+
+/// Function choosing root distributions of the output out of the dag
+pub type RootSelector = for<'a> fn(&'a DependencyDag) -> Vec<&'a DistributionName>;
+
+/// How root distributions of the output are chosen
+#[derive(Debug, Clone, Default)]
+pub enum RootStrategy {
+    /// distributions not required by any other one; distributions which
+    /// only require each other, e.g. meta-packages, never become roots
+    #[default]
+    Auto,
+    /// distributions installed explicitly, by the REQUESTED marker
+    Requested,
+    /// given distributions, names are normalized
+    Explicit(Vec<DistributionName>),
+    /// any other selection, for library users
+    Custom(RootSelector),
+}
+
+impl RootStrategy {
+    /// Roots of the dag, distributions which are not installed are skipped
+    pub fn select<'a>(&'a self, dag: &'a DependencyDag) -> Vec<&'a DistributionName> {
+        match self {
+            RootStrategy::Auto => get_top_level_distributions(dag),
+            RootStrategy::Requested => get_requested_distributions(dag),
+            RootStrategy::Explicit(names) => {
+                names.iter().filter(|n| dag.contains_key(*n)).collect()
+            }
+            RootStrategy::Custom(select) => select(dag),
+        }
+    }
+}
+
+/// `auto`, `requested` or `explicit=<name>,<name>...`
+impl FromStr for RootStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None if s == "auto" => Ok(RootStrategy::Auto),
+            None if s == "requested" => Ok(RootStrategy::Requested),
+            Some(("explicit", names)) => {
+                let names: Vec<DistributionName> = names
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                    .map(pep503)
+                    .collect();
+                if names.is_empty() {
+                    return Err("explicit roots need at least one package name".to_string());
+                }
+                Ok(RootStrategy::Explicit(names))
+            }
+            _ => Err(format!(
+                "unknown root strategy '{}', expected auto, requested or explicit=<list>",
+                s
+            )),
+        }
+    }
+}
+
+/// Direct reverse dependencies of a distribution: every installed
+/// distribution which lists it in its requirements, together with the
+/// requirement itself
+pub fn get_requirers<'a>(
+    dag: &'a DependencyDag,
+    name: &DistributionName,
+) -> Vec<(&'a DistributionName, &'a RequiredDistribution)> {
+    let mut requirers: Vec<(&DistributionName, &RequiredDistribution)> = dag
+        .iter()
+        .flat_map(|(k, v)| v.dependencies.iter().map(move |d| (k, d)))
+        .filter(|(_, d)| &d.name == name)
+        .collect();
+    requirers.sort_by(|a, b| {
+        a.0.cmp(b.0)
+            .then(a.1.required_version.cmp(&b.1.required_version))
+    });
+    requirers
+}
+
+/// Specifiers installed packages place on a distribution
+#[derive(Debug, PartialEq)]
+pub struct Constraints<'a> {
+    /// distinct specifiers, each with its sorted requirers
+    pub specifiers: BTreeMap<&'a str, Vec<&'a DistributionName>>,
+    /// all specifiers joined, empty when nothing restricts the version
+    pub intersection: String,
+}
+
+/// Collect every distinct specifier placed on the distribution and their intersection
+pub fn get_constraints<'a>(dag: &'a DependencyDag, name: &DistributionName) -> Constraints<'a> {
+    let mut specifiers: BTreeMap<&str, Vec<&DistributionName>> = BTreeMap::new();
+    for (requirer, requirement) in get_requirers(dag, name) {
+        specifiers
+            .entry(&requirement.required_version)
+            .or_default()
+            .push(requirer);
+    }
+    // empty specifiers allow any version and drop out of the join
+    let joined = specifiers.keys().copied().collect::<Vec<_>>().join(",");
+    Constraints {
+        specifiers,
+        intersection: canonical_specifier(&joined),
+    }
+}
+
+/// Split parsed dependency string into version specifier
+/// and optional environment marker
+pub fn split_dependency_str(dependency_str: &str) -> Result<(&str, Option<&str>), &'static str> {
+    let (_, dependency_str) = split_requested_extras(dependency_str);
+    // requirements without version are produced only by the strict parser
+    let unversioned = dependency_str.trim_start();
+    let specifier = if unversioned.is_empty() || unversioned.starts_with(';') {
+        ""
+    } else {
+        DepParser::parse(Rule::version_comparison, dependency_str)
+            .map_err(|_| "Failed to parse dependency version expression")?
+            .next()
+            .unwrap()
+            .as_str()
+    };
+    let marker = dependency_str
+        .split_once(';')
+        .map(|(_, m)| m.trim())
+        .filter(|m| !m.is_empty());
+
+    Ok((specifier, marker))
+}
+
+/// Extras the requirement asks of the required distribution, e.g. `socks`
+/// of `[socks]>=2.0`, normalized, and the rest of the dependency string
+pub fn split_requested_extras(dependency_str: &str) -> (Vec<String>, &str) {
+    let extras = dependency_str
+        .trim_start()
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'));
+    match extras {
+        Some((extras, rest)) => (
+            extras
+                .split(',')
+                .map(|extra| pep503(extra.trim()))
+                .filter(|extra| !extra.is_empty())
+                .collect(),
+            rest,
+        ),
+        None => (Vec::new(), dependency_str),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParsedLine {
     Meta(String, String),       // key,value of meta-parameter such as name, version
     Dependency(String, String), // name and parameters of dependency
 }
 
-fn parse_line(line: &str) -> Option<ParsedLine> {
+/// Parse Requires-Dist value with the strict PEP 508 parser,
+/// invalid requirements are dropped the same way as unrecognized lines
+#[cfg(feature = "pep508")]
+fn parse_requirement(line: &str) -> Option<Option<ParsedLine>> {
+    let requirement: Option<Requirement> = field_value(line, "Requires-Dist:")?.parse().ok();
+    Some(requirement.map(|r| {
+        let dependency_str = match r.extras.is_empty() {
+            true => r.dependency_str(),
+            false => format!("[{}]{}", r.extras.join(","), r.dependency_str()),
+        };
+        ParsedLine::Dependency(r.name.clone(), dependency_str)
+    }))
+}
+
+/// Why the strict PEP 508 parser rejected a Requires-Dist line
+#[cfg(feature = "pep508")]
+fn requirement_error(line: &str) -> Option<String> {
+    let value = field_value(line, "Requires-Dist:")?;
+    value.parse::<Requirement>().err().map(|e| e.to_string())
+}
+
+#[cfg(not(feature = "pep508"))]
+fn requirement_error(_line: &str) -> Option<String> {
+    None
+}
+
+/// Value of the line if it starts with the given key, compared case-insensitively
+fn field_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.get(..key.len())
+        .filter(|k| k.eq_ignore_ascii_case(key))
+        .map(|_| &line[key.len()..])
+}
+
+/// Fields needed to build the tree, their lines not understood
+/// by the parser are reported in verbose mode
+const TRACKED_FIELDS: [&str; 3] = ["Name:", "Version:", "Requires-Dist:"];
+
+/// Why a line of a tracked field was not parsed, with its location,
+/// None for lines of other fields
+fn misread_line_message(file: &Path, line_number: usize, line: &str) -> Option<String> {
+    if !TRACKED_FIELDS
+        .iter()
+        .any(|key| field_value(line, key).is_some())
+    {
+        return None;
+    }
+    let location = SourceLine {
+        file: file.to_path_buf(),
+        line: line_number,
+    }
+    .to_string();
+    Some(match requirement_error(line) {
+        Some(reason) => trf(Msg::MetadataLineRejected, &[&location, &line, &reason]),
+        None => trf(Msg::MetadataLineMisread, &[&location, &line]),
+    })
+}
+
+/// Why metadata lines could not be turned into a distribution
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    /// a required field is missing or invalid
+    Invalid(&'static str),
+    /// a tracked field line was not understood in strict mode, the message has its location
+    Misread(String),
+}
+
+impl From<&'static str> for ParseError {
+    fn from(reason: &'static str) -> Self {
+        Self::Invalid(reason)
+    }
+}
+
+/// Parse single line of METADATA file, lines not
+/// recognized by the grammar are dropped
+pub fn parse_line(line: &str) -> Option<ParsedLine> {
+    #[cfg(feature = "pep508")]
+    if let Some(parsed_line) = parse_requirement(line) {
+        return parsed_line;
+    }
+
     let rules = [
         (
             Rule::distribution_name_row,
@@ -85,6 +787,33 @@ fn parse_line(line: &str) -> Option<ParsedLine> {
             Rule::distribution_version_kw,
             Rule::distribution_version,
         ),
+        (
+            Rule::metadata_version_row,
+            Rule::metadata_version_kw,
+            Rule::distribution_version,
+        ),
+        (Rule::dynamic_row, Rule::dynamic_kw, Rule::field_name),
+        (
+            Rule::license_expression_row,
+            Rule::license_expression_kw,
+            Rule::free_text,
+        ),
+        (
+            Rule::license_file_row,
+            Rule::license_file_kw,
+            Rule::free_text,
+        ),
+        (
+            Rule::requires_python_row,
+            Rule::requires_python_kw,
+            Rule::free_text,
+        ),
+        (Rule::project_url_row, Rule::project_url_kw, Rule::free_text),
+        (
+            Rule::provides_extra_row,
+            Rule::provides_extra_kw,
+            Rule::distribution_name,
+        ),
         (
             Rule::required_distribution_row,
             Rule::distribution_name,
@@ -93,7 +822,7 @@ fn parse_line(line: &str) -> Option<ParsedLine> {
     ];
 
     for (row_rule, key_rule, value_rule) in rules {
-        if let Ok(mut parse_pair) = DepParser::parse(row_rule, line.as_ref()) {
+        if let Ok(mut parse_pair) = DepParser::parse(row_rule, line) {
             let inner_pair = parse_pair
                 .next()
                 .expect("Can not access inner objects for parsed string")
@@ -110,80 +839,541 @@ fn parse_line(line: &str) -> Option<ParsedLine> {
                 }
             }
 
-            if key.starts_with("name") || key.starts_with("version") {
-                return Some(ParsedLine::Meta(key, value));
-            } else {
+            if row_rule == Rule::required_distribution_row {
                 return Some(ParsedLine::Dependency(key, value));
+            } else {
+                return Some(ParsedLine::Meta(key, value));
             }
         }
     }
     None
 }
 
+/// Parse metadata lines without known location, line numbers start from 1
+#[cfg(test)]
 fn node_from_file_iter<I, S>(
     source_iter: I,
-) -> Result<(DistributionName, DistributionMeta), &'static str>
+    marker_env: Option<&MarkerEnv>,
+) -> Result<(DistributionName, DistributionMeta), ParseError>
 where
     I: IntoIterator<Item = S>,
     S: AsRef<str>,
 {
-    let mut name: Option<String> = None;
-    let mut version: Option<String> = None;
-    let mut dependencies: HashSet<(String, String)> = HashSet::new();
+    let lines: Vec<S> = source_iter.into_iter().collect();
+    node_from_located_lines(
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| (Path::new(""), i + 1, line.as_ref())),
+        marker_env,
+        false,
+    )
+}
+
+/// Parse metadata lines given with their file and 1-based line number. Lines
+/// of tracked fields which are not understood are reported in verbose mode,
+/// in strict mode the first of them fails the parsing
+fn node_from_located_lines<'a, I>(
+    source_iter: I,
+    marker_env: Option<&MarkerEnv>,
+    strict: bool,
+) -> Result<(DistributionName, DistributionMeta), ParseError>
+where
+    I: IntoIterator<Item = (&'a Path, usize, &'a str)>,
+{
+    let mut name: Option<(String, SourceLine)> = None;
+    let mut version: Option<(String, SourceLine)> = None;
+    let mut metadata_version: Option<String> = None;
+    let mut dynamic: Vec<String> = Vec::new();
+    let mut license_expression: Option<String> = None;
+    let mut license_files: Vec<String> = Vec::new();
+    let mut requires_python: Option<String> = None;
+    let mut project_urls: Vec<ProjectUrl> = Vec::new();
+    let mut extras: Vec<String> = Vec::new();
+    let mut dependencies: Vec<(String, String, SourceLine)> = Vec::new();
 
     // iterate over all lines and get parsed strings for required keys
-    for line in source_iter {
-        if let Some(parsed_line) = parse_line(line.as_ref()) {
-            match parsed_line {
-                ParsedLine::Meta(k, v) => {
-                    if k.starts_with("name") {
-                        name = Some(v);
-                    } else if k.starts_with("version") {
-                        version = Some(v);
+    for (file, line_number, line) in source_iter {
+        let Some(parsed_line) = parse_line(line) else {
+            match misread_line_message(file, line_number, line) {
+                Some(message) if strict => return Err(ParseError::Misread(message)),
+                Some(message) => debug(&message),
+                None => (),
+            }
+            continue;
+        };
+        let source = || SourceLine {
+            file: file.to_path_buf(),
+            line: line_number,
+        };
+        match parsed_line {
+            ParsedLine::Meta(k, v) => match k.as_str() {
+                "name" => name = Some((v, source())),
+                "version" => version = Some((v, source())),
+                "metadata-version" => metadata_version = Some(v),
+                "dynamic" => dynamic.push(v.to_lowercase()),
+                "license-expression" => license_expression = Some(v.trim().to_string()),
+                "license-file" => license_files.push(v.trim().to_string()),
+                "requires-python" => requires_python = Some(v.trim().to_string()),
+                "project-url" => project_urls.extend(ProjectUrl::parse(&v)),
+                "provides-extra" => {
+                    let extra = pep503(&v);
+                    if !extras.contains(&extra) {
+                        extras.push(extra);
                     }
                 }
-                ParsedLine::Dependency(k, v) => {
-                    dependencies.insert((k, v));
+                _ => (),
+            },
+            ParsedLine::Dependency(k, v) => {
+                if !dependencies.iter().any(|(n, d, _)| *n == k && *d == v) {
+                    dependencies.push((k, v, source()));
                 }
             }
         }
     }
 
     // validate and construnct all the neccesary objects
-    let validated_name = normalize_name(&name.ok_or("Can not parse package name from file")?, "-");
-    let validated_version = version.ok_or("Can not parse version name from file")?;
-    let dm = DistributionMeta::from_parsed_file(validated_version, dependencies)?;
+    let (name, name_source) = name.ok_or("Can not parse package name from file")?;
+    let (validated_version, version_source) =
+        version.ok_or("Can not parse version name from file")?;
+    let validated_name = pep503(&name);
+    let mut dm =
+        DistributionMeta::from_parsed_file(validated_version, dependencies, &extras, marker_env)?;
+    dm.declared_name = Some(name.trim().to_string());
+    dm.name_source = Some(name_source);
+    dm.version_source = Some(version_source);
+    dm.metadata_version = metadata_version;
+    dm.dynamic = dynamic;
+    dm.license_expression = license_expression;
+    dm.license_files = license_files;
+    dm.requires_python = requires_python;
+    dm.project_urls = project_urls;
 
-    Ok(((normalize_name(&validated_name, "-")), dm))
+    Ok((validated_name, dm))
 }
 
-const METADATA_FILE_NAME: &'static str = "METADATA";
+const METADATA_FILE_NAME: &str = "METADATA";
+const INSTALLER_FILE_NAME: &str = "INSTALLER";
+const PKG_INFO_FILE_NAME: &str = "PKG-INFO";
+const EGG_REQUIRES_FILE_NAME: &str = "requires.txt";
+const REQUESTED_FILE_NAME: &str = "REQUESTED";
 
-pub fn get_dep_dag_from_env(env_path: &PathBuf) -> Result<DependencyDag, &'static str> {
-    let mut dependency_dag: DependencyDag = HashMap::new();
+/// Convert requires.txt of an egg-info dir into Requires-Dist lines.
+/// Sections look like `[extra]`, `[extra:marker]` or `[:marker]`
+/// and apply to all requirements below them, extras of the sections
+/// are declared with Provides-Extra lines. Each line comes with the number
+/// of the requires.txt line it was made of.
+fn egg_requires_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut section_marker: Option<String> = None;
+    for (i, line) in content.lines().map(str::trim).enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let (extra, marker) = section.split_once(':').unwrap_or((section, ""));
+            if !extra.trim().is_empty() {
+                lines.push((i + 1, format!("Provides-Extra: {}", extra.trim())));
+            }
+            section_marker = match (extra.trim(), marker.trim()) {
+                ("", "") => None,
+                ("", marker) => Some(marker.to_string()),
+                (extra, "") => Some(format!("extra == \"{}\"", extra)),
+                (extra, marker) => Some(format!("extra == \"{}\" and ({})", extra, marker)),
+            };
+            continue;
+        }
+        match &section_marker {
+            Some(marker) => lines.push((i + 1, format!("Requires-Dist: {}; {}", line, marker))),
+            None => lines.push((i + 1, format!("Requires-Dist: {}", line))),
+        }
+    }
+    lines
+}
 
-    for dir in get_meta_dirs(env_path) {
-        // get metadata file
-        let meta_file_path = dir.path().join(METADATA_FILE_NAME);
-        if fs::exists(&meta_file_path).unwrap() {
-            // read only first part of the file, until the first stopper
-            let readline_iter = get_lnreader(&meta_file_path, |line| {
-                let r = line.as_ref().unwrap();
-                // TODO: think about valid delimiter
-                !(r == "Description-Content-Type")
-            })
-            .expect("Can not constuct reader for a file {meta_file_path:?}");
+/// Read the name of the tool which installed a distribution.
+/// File is optional, so any problem reading it means "unknown installer"
+fn read_installer(source: &dyn MetadataSource, meta_dir: &Path) -> Option<String> {
+    let content = source.read_file(meta_dir, INSTALLER_FILE_NAME).ok()?;
+    let installer = content.lines().next()?.trim();
+    if installer.is_empty() {
+        None
+    } else {
+        Some(installer.to_string())
+    }
+}
+
+/// Add parsed distribution to the dag, resolving name clashes with the merge strategy.
+/// Not selected copy is kept in the `shadowed` list of the selected one. Stale copies
+/// left over in the same install prefix never fail the `error` strategy, so the
+/// duplicate check reports them whatever the strategy. Venv copies always win
+/// over the ones inherited from the base interpreter, the same way python
+/// puts the venv first on sys.path
+pub(crate) fn insert_distribution(
+    dag: &mut DependencyDag,
+    name: DistributionName,
+    mut meta: DistributionMeta,
+    strategy: MergeStrategy,
+) -> Result<(), String> {
+    let Some(existing) = dag.get_mut(&name) else {
+        dag.insert(name, meta);
+        return Ok(());
+    };
 
-            let (k, v) = node_from_file_iter(readline_iter)?;
-            dependency_dag.insert(k, v);
+    let shadow = |meta: &DistributionMeta, inherited: bool| ShadowedCopy {
+        installed_version: meta.installed_version.clone(),
+        meta_dir: meta.meta_dir.clone(),
+        inherited,
+    };
+    let inherited = meta.inherited && !existing.inherited;
+    let inheriting = existing.inherited && !meta.inherited;
+    match strategy {
+        _ if inherited => existing.shadowed.push(shadow(&meta, true)),
+        _ if inheriting => {
+            meta.shadowed = std::mem::take(&mut existing.shadowed);
+            meta.shadowed.push(shadow(existing, true));
+            *existing = meta;
+        }
+        MergeStrategy::Error
+            if existing.installed_version != meta.installed_version
+                && leftover_reason(&existing.meta_dir, &meta.meta_dir).is_none() =>
+        {
+            return Err(format!(
+                "{} is installed twice with different versions: {} in {:?} and {} in {:?}",
+                name,
+                existing.installed_version,
+                existing.meta_dir,
+                meta.installed_version,
+                meta.meta_dir
+            ));
+        }
+        MergeStrategy::First | MergeStrategy::Error => existing.shadowed.push(shadow(&meta, false)),
+        MergeStrategy::Last => {
+            meta.shadowed = std::mem::take(&mut existing.shadowed);
+            meta.shadowed.push(shadow(existing, false));
+            *existing = meta;
         }
     }
-    Ok(dependency_dag)
+    Ok(())
+}
+
+fn meta_file_name(kind: MetaDirKind) -> &'static str {
+    match kind {
+        MetaDirKind::DistInfo => METADATA_FILE_NAME,
+        MetaDirKind::EggInfo => PKG_INFO_FILE_NAME,
+    }
+}
+
+/// Metadata lines of a dir: header of the metadata file, followed by
+/// requirements of requires.txt for egg-info dirs
+struct MetaDirLines {
+    meta_file: PathBuf,
+    // fields end at the first empty line, the description body is not read
+    header: String,
+    requires_file: PathBuf,
+    /// requirements converted to Requires-Dist lines, with their line numbers
+    egg_requires: Vec<(usize, String)>,
+}
+
+impl MetaDirLines {
+    fn read(source: &dyn MetadataSource, dir: &MetaDirEntry) -> io::Result<Self> {
+        let header = source.read_header(&dir.path, meta_file_name(dir.kind))?;
+        // egg-info keeps requirements in a separate file
+        let egg_requires = match dir.kind {
+            MetaDirKind::EggInfo => source
+                .read_file(&dir.path, EGG_REQUIRES_FILE_NAME)
+                .map(|content| egg_requires_lines(&content))
+                .unwrap_or_default(),
+            MetaDirKind::DistInfo => Vec::new(),
+        };
+        Ok(Self {
+            meta_file: dir.path.join(meta_file_name(dir.kind)),
+            header,
+            requires_file: dir.path.join(EGG_REQUIRES_FILE_NAME),
+            egg_requires,
+        })
+    }
+
+    /// Lines with their file and 1-based line number
+    fn iter(&self) -> impl Iterator<Item = (&Path, usize, &str)> {
+        let header = self
+            .header
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (self.meta_file.as_path(), i + 1, line));
+        let egg_requires = self
+            .egg_requires
+            .iter()
+            .map(|(line_number, line)| (self.requires_file.as_path(), *line_number, line.as_str()));
+        header.chain(egg_requires)
+    }
+}
+
+/// Line of Name, Version or Requires-Dist field as the parser understood it
+#[derive(Debug, PartialEq)]
+pub struct DumpedLine {
+    pub source: SourceLine,
+    pub line: String,
+    /// None if the grammar did not recognize the line
+    pub parsed: Option<ParsedLine>,
+    /// why the line was rejected, when the parser can tell
+    pub error: Option<String>,
+    /// declared extras which pull the requirement in
+    pub extras: Vec<String>,
+}
+
+/// Parse tracked fields of a metadata dir of the source line by line,
+/// keeping lines the grammar did not recognize
+pub fn dump_meta_dir(source: &dyn MetadataSource, meta_dir: &Path) -> io::Result<Vec<DumpedLine>> {
+    let file_name = meta_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let dir = MetaDirEntry {
+        path: meta_dir.to_path_buf(),
+        // EGG-INFO dir of a zipped egg is not classified by its name
+        kind: MetaDirKind::classify(&file_name).unwrap_or(MetaDirKind::EggInfo),
+        file_name,
+    };
+    let lines = MetaDirLines::read(source, &dir)?;
+
+    let extra_envs: Vec<(String, MarkerEnv)> = lines
+        .iter()
+        .filter_map(|(_, _, line)| match parse_line(line) {
+            Some(ParsedLine::Meta(key, extra)) if key == "provides-extra" => Some(pep503(&extra)),
+            _ => None,
+        })
+        .map(|extra| (extra.clone(), MarkerEnv::default().with_extras([&extra])))
+        .collect();
+    let mut dumped = Vec::new();
+    for (file, line_number, line) in lines.iter() {
+        let parsed = parse_line(line);
+        let extras = match &parsed {
+            Some(ParsedLine::Meta(key, _)) if key != "name" && key != "version" => continue,
+            Some(ParsedLine::Dependency(_, dependency_str)) => {
+                match split_dependency_str(dependency_str) {
+                    Ok((_, Some(marker))) if MarkerEnv::default().evaluate(marker) == Ok(false) => {
+                        extra_envs
+                            .iter()
+                            .filter(|(_, env)| env.evaluate(marker) == Ok(true))
+                            .map(|(extra, _)| extra.clone())
+                            .collect()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            Some(ParsedLine::Meta(..)) => Vec::new(),
+            None if !TRACKED_FIELDS
+                .iter()
+                .any(|key| field_value(line, key).is_some()) =>
+            {
+                continue
+            }
+            None => Vec::new(),
+        };
+        let error = match parsed {
+            None => requirement_error(line),
+            Some(_) => None,
+        };
+        dumped.push(DumpedLine {
+            source: SourceLine {
+                file: file.to_path_buf(),
+                line: line_number,
+            },
+            line: line.to_string(),
+            parsed,
+            error,
+            extras,
+        });
+    }
+    Ok(dumped)
+}
+
+/// Parse a single metadata dir, None if its metadata file can not be read.
+/// Errors name the metadata file which could not be parsed, or the
+/// misread line in strict mode
+pub(crate) fn parse_meta_dir(
+    source: &dyn MetadataSource,
+    dir: MetaDirEntry,
+    marker_env: Option<&MarkerEnv>,
+    strict: bool,
+    profile: &mut Profile,
+) -> Result<Option<(DistributionName, DistributionMeta)>, String> {
+    let start = Instant::now();
+    let Ok(lines) = MetaDirLines::read(source, &dir) else {
+        return Ok(None);
+    };
+    let (k, mut v) =
+        node_from_located_lines(lines.iter(), marker_env, strict).map_err(|err| match err {
+            ParseError::Invalid(reason) => format!("{}: {}", lines.meta_file.display(), reason),
+            ParseError::Misread(message) => message,
+        })?;
+    let meta_file = lines.meta_file;
+    v.installer = read_installer(source, &dir.path);
+    v.requested = source.read_file(&dir.path, REQUESTED_FILE_NAME).is_ok();
+    v.direct_url = read_direct_url(source, &dir.path);
+    let elapsed = start.elapsed();
+    profile.add_stage("parse", elapsed);
+    profile.add_file(meta_file, elapsed);
+    v.meta_dir = dir.path;
+    if v.has_dynamic_dependencies() {
+        warn(&trf(Msg::DynamicDependencies, &[&k]));
+    }
+    Ok(Some((k, v)))
+}
+
+/// Metadata dirs of a single scanned root, in the order of their paths
+pub(crate) fn scan_meta_dirs(
+    source: &dyn MetadataSource,
+    follow_egg_info: bool,
+    profile: &mut Profile,
+) -> Vec<MetaDirEntry> {
+    profile
+        .time("scan", || source.meta_dirs())
+        .into_iter()
+        .filter(|d| follow_egg_info || d.kind == MetaDirKind::DistInfo)
+        .collect()
+}
+
+/// Parse all distributions of a single scanned root,
+/// in the order of metadata dir paths. When the time budget is used up
+/// the distributions parsed so far are returned, with a warning
+pub(crate) fn scan_env(
+    source: &dyn MetadataSource,
+    marker_env: Option<&MarkerEnv>,
+    follow_egg_info: bool,
+    strict: bool,
+    profile: &mut Profile,
+) -> Result<Vec<(DistributionName, DistributionMeta)>, String> {
+    let mut distributions = Vec::new();
+    let dirs = scan_meta_dirs(source, follow_egg_info, profile);
+    let total = dirs.len();
+    events::emit("scan_started", json!({ "meta_dirs": total }));
+    for (parsed, dir) in dirs.into_iter().enumerate() {
+        if let Err(err) = deadline::check() {
+            warn(&trf(
+                Msg::ScanStopped,
+                &[&err, &parsed, &total, &dir.path.display()],
+            ));
+            break;
+        }
+        if let Some(distribution) = parse_meta_dir(source, dir, marker_env, strict, profile)? {
+            let (name, meta) = &distribution;
+            events::emit(
+                "package_parsed",
+                json!({
+                    "name": name,
+                    "version": meta.installed_version,
+                    "location": meta.meta_dir,
+                    "parsed": parsed + 1,
+                    "total": total,
+                }),
+            );
+            distributions.push(distribution);
+        }
+    }
+    Ok(distributions)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::normalize::test::{distribution_name, is_pep503, specifier_set, version};
+    use crate::render::{render_dag, RenderOptions};
+    use crate::source::SitePackagesDir;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn name_and_version_round_trip(name in distribution_name(), version in version()) {
+            prop_assert_eq!(
+                parse_line(&format!("Name: {name}")),
+                Some(ParsedLine::Meta("name".to_string(), name.clone()))
+            );
+            prop_assert_eq!(
+                parse_line(&format!("Version: {version}")),
+                Some(ParsedLine::Meta("version".to_string(), version.clone()))
+            );
+        }
+
+        #[test]
+        fn specifier_round_trip(
+            name in distribution_name(),
+            specifier in specifier_set(),
+            with_marker in any::<bool>(),
+        ) {
+            let marker = if with_marker { "; extra == \"test\"" } else { "" };
+            let line = format!("Requires-Dist: {name}{specifier}{marker}");
+
+            let Some(ParsedLine::Dependency(dep_name, dependency_str)) = parse_line(&line) else {
+                return Err(TestCaseError::fail(format!("not a dependency: {line}")));
+            };
+            prop_assert_eq!(pep503(&dep_name), pep503(&name));
+            let (parsed_specifier, parsed_marker) = split_dependency_str(&dependency_str).unwrap();
+            let canonical = canonical_specifier(&specifier);
+            prop_assert_eq!(canonical_specifier(parsed_specifier), canonical.clone());
+            prop_assert_eq!(canonical_specifier(&canonical), canonical.clone());
+            prop_assert_eq!(parsed_marker.is_some(), with_marker);
+        }
+
+        #[test]
+        fn rendered_specifier_round_trip(
+            name in distribution_name(),
+            specifier in specifier_set(),
+            installed in version(),
+        ) {
+            let dep = pep503(&name);
+            let app = if dep == "app" { "app2" } else { "app" };
+            let (app_name, app_meta) = node_from_file_iter(
+                [
+                    format!("Name: {app}"),
+                    String::from("Version: 1.0"),
+                    format!("Requires-Dist: {name}{specifier}"),
+                ],
+                None,
+            )
+            .unwrap();
+            let (dep_name, dep_meta) = node_from_file_iter(
+                [format!("Name: {name}"), format!("Version: {installed}")],
+                None,
+            )
+            .unwrap();
+            let dag = DependencyDag::from([(app_name.clone(), app_meta), (dep_name, dep_meta)]);
+
+            let mut out = Vec::new();
+            render_dag(&mut out, &dag, &app_name, None, 0, &RenderOptions::default()).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            // shown as declared, matched by the normalized name
+            let expected = format!(
+                "-{} [required: {}, installed: {}]",
+                name,
+                canonical_specifier(&specifier),
+                installed
+            );
+            prop_assert!(out.contains(&expected), "{} not in {}", expected, out);
+        }
+
+        #[test]
+        fn stored_names_are_normalized(
+            name in "[A-Za-z0-9]{1,8}([._-]{1,3}[A-Za-z0-9]{1,8}){0,3}",
+            dep in "[A-Za-z0-9]{1,8}([._-]{1,3}[A-Za-z0-9]{1,8}){0,3}",
+        ) {
+            let sample_meta = [
+                format!("Name: {name}"),
+                String::from("Version: 1.0"),
+                format!("Requires-Dist: {dep} >= 1.0"),
+            ];
+
+            let (distribution_name, distribution_meta) = node_from_file_iter(sample_meta, None).unwrap();
+
+            prop_assert!(is_pep503(&distribution_name));
+            prop_assert_eq!(distribution_meta.display_name(&distribution_name), &name);
+            prop_assert_eq!(distribution_meta.dependencies.len(), 1);
+            for d in &distribution_meta.dependencies {
+                prop_assert!(is_pep503(&d.name));
+            }
+        }
+    }
 
     #[test]
     fn distr_meta_from_iter_simple() {
@@ -196,11 +1386,11 @@ mod test {
         ];
 
         let (distribution_name, distribution_meta) =
-            node_from_file_iter(sample_meta.into_iter()).unwrap();
+            node_from_file_iter(sample_meta, None).unwrap();
 
         assert_eq!(distribution_name, "sample-package");
         assert_eq!(distribution_meta.installed_version, "0.0.1");
-        assert_eq!(distribution_meta.dependencies.is_empty(), false);
+        assert!(!distribution_meta.dependencies.is_empty());
         assert_eq!(distribution_meta.dependencies.len(), 1);
 
         let expected_dependency = RequiredDistribution::from_str("pyarrow", ">=10.0.1");
@@ -229,11 +1419,11 @@ mod test {
         ];
 
         let (distribution_name, distribution_meta) =
-            node_from_file_iter(sample_meta.into_iter()).unwrap();
+            node_from_file_iter(sample_meta, None).unwrap();
 
         assert_eq!(distribution_name, "sample-package");
         assert_eq!(distribution_meta.installed_version, "0.0.1");
-        assert_eq!(distribution_meta.dependencies.is_empty(), false);
+        assert!(!distribution_meta.dependencies.is_empty());
         assert_eq!(distribution_meta.dependencies.len(), 3);
 
         for (depname, depver) in [
@@ -266,7 +1456,7 @@ mod test {
         ];
 
         let (distribution_name, distribution_meta) =
-            node_from_file_iter(input_data.iter()).unwrap();
+            node_from_file_iter(input_data.iter(), None).unwrap();
 
         assert_eq!(distribution_name, "pythondistr");
         assert_eq!(distribution_meta.installed_version, "1.99.1241");
@@ -297,7 +1487,7 @@ mod test {
         ];
 
         let (distribution_name, distribution_meta) =
-            node_from_file_iter(input_data.iter()).unwrap();
+            node_from_file_iter(input_data.iter(), None).unwrap();
 
         assert_eq!(distribution_name, "pythondistr");
         assert_eq!(distribution_meta.installed_version, "1.99.1241");
@@ -321,6 +1511,606 @@ mod test {
         }
     }
 
+    #[test]
+    fn parse_dependency_named_like_meta_key() {
+        let input_data = [
+            "Name: pythonDistr",
+            "Version: 1.0",
+            "Requires-Dist: versioneer >= 0.28",
+            "Requires-Dist: nameparser >= 1.1",
+        ];
+
+        let (_, distribution_meta) = node_from_file_iter(input_data, None).unwrap();
+
+        assert_eq!(distribution_meta.installed_version, "1.0");
+        assert_eq!(distribution_meta.dependencies.len(), 2);
+    }
+
+    #[test]
+    fn scan_windows_line_endings() {
+        let site = crate::fixture::SitePackages::new("crlf")
+            .file(
+                "six-1.16.0.dist-info/METADATA",
+                "\u{feff}Metadata-Version: 2.1\r\nName: six\r\nVersion: 1.16.0\r\n",
+            )
+            .file(
+                "legacy-1.0.egg-info/PKG-INFO",
+                "Metadata-Version: 1.1\r\nName: legacy\r\nVersion: 1.0\r\n",
+            )
+            .file("legacy-1.0.egg-info/requires.txt", "\u{feff}six>=1.0\r\n");
+
+        let distributions = scan_env(
+            &SitePackagesDir(site.path().to_path_buf()),
+            None,
+            true,
+            false,
+            &mut Profile::default(),
+        )
+        .unwrap();
+        let mut found: Vec<(&str, &str)> = distributions
+            .iter()
+            .map(|(name, meta)| (name.as_str(), meta.installed_version.as_str()))
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![("legacy", "1.0"), ("six", "1.16.0")]);
+
+        let legacy = &distributions.iter().find(|(n, _)| n == "legacy").unwrap().1;
+        let deps: Vec<(&str, &str)> = legacy
+            .dependencies
+            .iter()
+            .map(|d| (d.name.as_str(), d.required_version.as_str()))
+            .collect();
+        assert_eq!(deps, vec![("six", ">=1.0")]);
+    }
+
+    #[test]
+    fn model_serializes_with_stable_field_names() {
+        let requirement =
+            |name: &str, specifier: &str, marker: Option<&str>| RequiredDistribution {
+                name: name.to_string(),
+                required_version: specifier.to_string(),
+                marker: marker.map(String::from),
+                source: Some(SourceLine {
+                    file: PathBuf::from("METADATA"),
+                    line: 4,
+                }),
+            };
+        let app = DistributionMeta {
+            installed_version: "1.0".to_string(),
+            dependencies: HashSet::from([
+                requirement("six", ">=1.5", None),
+                requirement("lib", ">=2.0", Some("python_version < \"3.11\"")),
+            ]),
+            meta_dir: PathBuf::from("site/app-1.0.dist-info"),
+            requested: true,
+            extras: BTreeMap::from([(
+                "test".to_string(),
+                HashSet::from([requirement("pytest", "", None)]),
+            )]),
+            name_source: Some(SourceLine {
+                file: PathBuf::from("METADATA"),
+                line: 2,
+            }),
+            ..Default::default()
+        };
+        let dag = DependencyDag::from([("app".to_string(), app)]);
+
+        let document = serde_json::to_value(&dag).unwrap();
+        assert_eq!(
+            document,
+            serde_json::json!({
+                "app": {
+                    "version": "1.0",
+                    "dependencies": [
+                        {"name": "lib", "specifier": ">=2.0", "marker": "python_version < \"3.11\""},
+                        {"name": "six", "specifier": ">=1.5"},
+                    ],
+                    "location": "site/app-1.0.dist-info",
+                    "requested": true,
+                    "extras": {"test": [{"name": "pytest", "specifier": ""}]},
+                }
+            })
+        );
+
+        let dag: DependencyDag = serde_json::from_value(document).unwrap();
+        let app = &dag["app"];
+        assert_eq!(app.installed_version, "1.0");
+        assert_eq!(app.dependencies.len(), 2);
+        assert!(app.requested);
+        assert_eq!(app.extras["test"].len(), 1);
+        assert_eq!(app.name_source, None);
+    }
+
+    #[test]
+    fn dump_keeps_tracked_lines_with_extras() {
+        let site = crate::fixture::SitePackages::new("dump-meta")
+            .egg_info(
+                "legacy",
+                "1.0",
+                "six>=1.0\n[test]\npytest>=7\n[:sys_platform == \"win32\"]\ncolorama>=0.4\n",
+            )
+            .dist_info("broken", "0.1", &["==1.0"]);
+        let source = SitePackagesDir(site.path().to_path_buf());
+        let meta_dir = site.path().join("legacy-1.0-py3.11.egg-info");
+
+        let dumped = dump_meta_dir(&source, &meta_dir).unwrap();
+        let summary: Vec<(usize, Option<&ParsedLine>, Vec<&str>)> = dumped
+            .iter()
+            .map(|d| {
+                let extras = d.extras.iter().map(String::as_str).collect();
+                (d.source.line, d.parsed.as_ref(), extras)
+            })
+            .collect();
+        let meta = |k: &str, v: &str| ParsedLine::Meta(k.to_string(), v.to_string());
+        let dependency = |k: &str, v: &str| ParsedLine::Dependency(k.to_string(), v.to_string());
+        assert_eq!(
+            summary,
+            vec![
+                (2, Some(&meta("name", "legacy")), vec![]),
+                (3, Some(&meta("version", "1.0")), vec![]),
+                (1, Some(&dependency("six", ">=1.0")), vec![]),
+                (
+                    3,
+                    Some(&dependency("pytest", ">=7; extra == \"test\"")),
+                    vec!["test"]
+                ),
+                (
+                    5,
+                    Some(&dependency("colorama", ">=0.4; sys_platform == \"win32\"")),
+                    vec![]
+                ),
+            ]
+        );
+        assert_eq!(dumped[2].source.file, meta_dir.join(EGG_REQUIRES_FILE_NAME));
+
+        let dumped = dump_meta_dir(&source, &site.path().join("broken-0.1.dist-info")).unwrap();
+        let misread = dumped.last().unwrap();
+        assert_eq!(misread.line, "Requires-Dist: ==1.0");
+        assert_eq!(misread.parsed, None);
+        #[cfg(feature = "pep508")]
+        assert!(misread.error.is_some());
+    }
+
+    #[test]
+    fn scan_records_source_lines() {
+        let site = crate::fixture::SitePackages::new("source-lines")
+            .dist_info("app", "1.0", &["lib>=2.0", "six>=1.0"])
+            .egg_info("legacy", "1.0", "# pinned\nsix>=1.0\n");
+
+        let distributions = scan_env(
+            &SitePackagesDir(site.path().to_path_buf()),
+            None,
+            true,
+            false,
+            &mut Profile::default(),
+        )
+        .unwrap();
+        let meta_of = |name: &str| &distributions.iter().find(|(n, _)| n == name).unwrap().1;
+
+        let app = meta_of("app");
+        let metadata = app.meta_dir.join(METADATA_FILE_NAME);
+        let line = |line| {
+            Some(SourceLine {
+                file: metadata.clone(),
+                line,
+            })
+        };
+        assert_eq!(app.name_source, line(2));
+        assert_eq!(app.version_source, line(3));
+        let mut deps: Vec<(&str, Option<SourceLine>)> = app
+            .dependencies
+            .iter()
+            .map(|d| (d.name.as_str(), d.source.clone()))
+            .collect();
+        deps.sort_by_key(|(name, _)| *name);
+        assert_eq!(deps, vec![("lib", line(4)), ("six", line(5))]);
+
+        let legacy = meta_of("legacy");
+        let six = legacy.dependencies.iter().next().unwrap();
+        assert_eq!(
+            six.source,
+            Some(SourceLine {
+                file: legacy.meta_dir.join(EGG_REQUIRES_FILE_NAME),
+                line: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn scan_error_names_metadata_file() {
+        let site = crate::fixture::SitePackages::new("source-error")
+            .file("broken-1.0.dist-info/METADATA", "Name: broken\n");
+
+        let result = scan_env(
+            &SitePackagesDir(site.path().to_path_buf()),
+            None,
+            false,
+            false,
+            &mut Profile::default(),
+        );
+        let error = result.unwrap_err();
+        assert!(error.starts_with(&site.path().display().to_string()));
+        assert!(error.ends_with("METADATA: Can not parse version name from file"));
+    }
+
+    #[test]
+    fn strict_scan_fails_on_misread_line() {
+        let site = crate::fixture::SitePackages::new("source-strict").file(
+            "app-1.0.dist-info/METADATA",
+            "Name: app\nVersion: 1.0\nRequires-Dist: lib (>=2.0\n",
+        );
+        let scan = |strict| {
+            scan_env(
+                &SitePackagesDir(site.path().to_path_buf()),
+                None,
+                false,
+                strict,
+                &mut Profile::default(),
+            )
+        };
+
+        assert!(scan(false).unwrap()[0].1.dependencies.is_empty());
+        let error = scan(true).unwrap_err();
+        let location = site.path().join("app-1.0.dist-info/METADATA:3");
+        assert!(error.starts_with(&location.display().to_string()));
+        assert!(error.contains("Requires-Dist: lib (>=2.0"));
+    }
+
+    #[test]
+    fn split_dependency_str_with_marker() {
+        assert_eq!(
+            split_dependency_str(">=8.3.2; extra == 'test'"),
+            Ok((">=8.3.2", Some("extra == 'test'")))
+        );
+        assert_eq!(
+            split_dependency_str("<3,>=1.21.1"),
+            Ok(("<3,>=1.21.1", None))
+        );
+    }
+
+    #[test]
+    fn requirements_of_extras() {
+        let input_data = [
+            "Name: requests",
+            "Version: 2.32.3",
+            "Provides-Extra: socks",
+            "Provides-Extra: use_chardet_on_py3",
+            "Requires-Dist: idna<4,>=2.5",
+            "Requires-Dist: PySocks!=1.5.7,>=1.5.6; extra == \"socks\"",
+            "Requires-Dist: chardet<6,>=3.0.2; extra == \"use-chardet-on-py3\"",
+        ];
+
+        let (_, distribution_meta) = node_from_file_iter(input_data, None).unwrap();
+        assert_eq!(distribution_meta.dependencies.len(), 3);
+        assert_eq!(
+            distribution_meta.extras.keys().collect::<Vec<_>>(),
+            ["socks", "use-chardet-on-py3"]
+        );
+        assert_eq!(
+            distribution_meta.extras["socks"],
+            HashSet::from([RequiredDistribution::from_str("pysocks", "!=1.5.7,>=1.5.6")])
+        );
+
+        let env = MarkerEnv::new();
+        let (_, distribution_meta) = node_from_file_iter(input_data, Some(&env)).unwrap();
+        assert_eq!(
+            distribution_meta.dependencies,
+            HashSet::from([RequiredDistribution::from_str("idna", "<4,>=2.5")])
+        );
+        assert_eq!(distribution_meta.extras["use-chardet-on-py3"].len(), 1);
+    }
+
+    #[test]
+    fn requirements_excluded_by_marker() {
+        let input_data = [
+            "Name: app",
+            "Version: 1.0",
+            "Provides-Extra: test",
+            "Requires-Dist: six>=1.0",
+            "Requires-Dist: pywin32>=300; sys_platform == \"win32\"",
+            "Requires-Dist: pytest>=7; extra == \"test\"",
+        ];
+        let env = MarkerEnv::new().with_values(HashMap::from([(
+            "sys_platform".to_string(),
+            "linux".to_string(),
+        )]));
+
+        let (_, distribution_meta) = node_from_file_iter(input_data, Some(&env)).unwrap();
+        let excluded: Vec<(&str, Option<&str>)> = distribution_meta
+            .excluded_by_marker
+            .iter()
+            .map(|d| (d.name.as_str(), d.marker.as_deref()))
+            .collect();
+        assert_eq!(excluded, [("pywin32", Some("sys_platform == \"win32\""))]);
+
+        let (_, distribution_meta) = node_from_file_iter(input_data, None).unwrap();
+        assert!(distribution_meta.excluded_by_marker.is_empty());
+    }
+
+    #[test]
+    fn constraints_of_distribution() {
+        let mut dag = DependencyDag::new();
+        for (name, specifier) in [
+            ("a", ">=1.5"),
+            ("b", "<2, >=1.5"),
+            ("c", ">=1.5"),
+            ("d", ""),
+        ] {
+            let mut meta = DistributionMeta::default();
+            meta.dependencies
+                .insert(RequiredDistribution::from_str("six", specifier));
+            dag.insert(name.to_string(), meta);
+        }
+
+        let constraints = get_constraints(&dag, &"six".to_string());
+        let specifiers: Vec<(&str, Vec<&str>)> = constraints
+            .specifiers
+            .iter()
+            .map(|(s, requirers)| (*s, requirers.iter().map(|r| r.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            specifiers,
+            vec![
+                ("", vec!["d"]),
+                ("<2,>=1.5", vec!["b"]),
+                (">=1.5", vec!["a", "c"])
+            ]
+        );
+        assert_eq!(constraints.intersection, "<2,>=1.5");
+    }
+
+    #[test]
+    fn root_strategies() {
+        let mut dag = DependencyDag::new();
+        for (name, requested, requires) in [
+            ("meta-a", true, "meta-b"),
+            ("meta-b", false, "meta-a"),
+            ("tool", false, ""),
+            ("app", true, "tool"),
+        ] {
+            let mut meta = DistributionMeta {
+                requested,
+                ..Default::default()
+            };
+            if !requires.is_empty() {
+                meta.dependencies
+                    .insert(RequiredDistribution::from_str(requires, ""));
+            }
+            dag.insert(name.to_string(), meta);
+        }
+
+        let roots = |strategy: &str| -> Vec<String> {
+            let strategy: RootStrategy = strategy.parse().unwrap();
+            strategy.select(&dag).into_iter().cloned().collect()
+        };
+        assert_eq!(roots("auto"), ["app"]);
+        assert_eq!(roots("requested"), ["app", "meta-a"]);
+        assert_eq!(roots("explicit=Meta_B, missing,tool"), ["meta-b", "tool"]);
+        assert!("explicit=".parse::<RootStrategy>().is_err());
+        assert!("top".parse::<RootStrategy>().is_err());
+
+        let custom =
+            RootStrategy::Custom(|dag| dag.keys().filter(|n| n.starts_with("meta")).collect());
+        assert_eq!(custom.select(&dag).len(), 2);
+    }
+
+    #[test]
+    fn evaluated_requirements_of_same_distribution_merged() {
+        let input_data = [
+            "Name: pandas",
+            "Version: 2.2.2",
+            "Requires-Dist: numpy>=1.22.4; python_version < \"3.11\"",
+            "Requires-Dist: numpy>=1.23.2; python_version >= \"3.11\"",
+            "Requires-Dist: numpy<3; sys_platform != \"emscripten\"",
+            "Requires-Dist: tzdata>=2022.7",
+        ];
+        let env = MarkerEnv::new().with_values(HashMap::from([
+            ("python_version".to_string(), "3.12".to_string()),
+            ("sys_platform".to_string(), "linux".to_string()),
+        ]));
+
+        let (_, distribution_meta) = node_from_file_iter(input_data, Some(&env)).unwrap();
+        let numpy: Vec<&RequiredDistribution> = distribution_meta
+            .dependencies
+            .iter()
+            .filter(|d| d.name == "numpy")
+            .collect();
+        assert_eq!(numpy.len(), 1);
+        assert_eq!(numpy[0].required_version, "<3,>=1.23.2");
+        assert_eq!(
+            numpy[0].marker.as_deref(),
+            Some("(python_version >= \"3.11\") and (sys_platform != \"emscripten\")")
+        );
+        assert_eq!(distribution_meta.dependencies.len(), 2);
+    }
+
+    #[test]
+    fn markers_of_repeated_requirement_joined() {
+        let input_data = [
+            "Name: black",
+            "Version: 24.4.2",
+            "Requires-Dist: tomli>=1.1.0; python_version < \"3.11\"",
+            "Requires-Dist: aiohttp>=3.7.4; extra == \"d\"",
+            "Requires-Dist: aiohttp>=3.7.4; extra == \"jupyter\"",
+            "Requires-Dist: click>=8.0.0",
+            "Requires-Dist: click>=8.0.0; os_name == \"nt\"",
+        ];
+
+        let (_, distribution_meta) = node_from_file_iter(input_data, None).unwrap();
+        let marker = |name: &str| {
+            let dep = distribution_meta
+                .dependencies
+                .iter()
+                .find(|d| d.name == name)
+                .unwrap();
+            dep.marker.clone()
+        };
+        assert_eq!(distribution_meta.dependencies.len(), 3);
+        assert_eq!(
+            marker("tomli").as_deref(),
+            Some("python_version < \"3.11\"")
+        );
+        assert_eq!(
+            marker("aiohttp").as_deref(),
+            Some("(extra == \"d\") or (extra == \"jupyter\")")
+        );
+        assert_eq!(marker("click"), None);
+    }
+
+    #[test]
+    fn parse_newer_metadata_fields() {
+        let input_data = [
+            "Metadata-Version: 2.4",
+            "Name: pythonDistr",
+            "Version: 1.0",
+            "Dynamic: Requires-Dist",
+            "Dynamic: Classifier",
+            "License-Expression: MIT OR Apache-2.0",
+            "License-File: LICENSE",
+            "License-File: licenses/NOTICE.txt",
+            "Project-URL: Homepage, https://example.org",
+            "Project-URL: Release Notes, https://example.org/changes",
+            "Project-URL: Source, https://git.example.org/distr",
+            "Project-URL: no label",
+        ];
+
+        let (_, distribution_meta) = node_from_file_iter(input_data, None).unwrap();
+
+        assert_eq!(distribution_meta.metadata_version.as_deref(), Some("2.4"));
+        assert_eq!(
+            distribution_meta.dynamic,
+            vec!["requires-dist", "classifier"]
+        );
+        assert!(distribution_meta.has_dynamic_dependencies());
+        assert_eq!(
+            distribution_meta.license_expression.as_deref(),
+            Some("MIT OR Apache-2.0")
+        );
+        assert_eq!(
+            distribution_meta.license_files,
+            vec!["LICENSE", "licenses/NOTICE.txt"]
+        );
+        assert_eq!(distribution_meta.project_urls.len(), 3);
+        assert_eq!(
+            distribution_meta.project_url(LinkKind::Changelog),
+            Some("https://example.org/changes")
+        );
+        assert_eq!(
+            distribution_meta.project_url(LinkKind::Repository),
+            Some("https://git.example.org/distr")
+        );
+        assert_eq!(distribution_meta.project_url(LinkKind::Documentation), None);
+    }
+
+    fn copy(version: &str, meta_dir: &str) -> DistributionMeta {
+        DistributionMeta {
+            installed_version: version.to_string(),
+            meta_dir: PathBuf::from(meta_dir),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_strategies() {
+        let name = String::from("numpy");
+
+        let mut dag = DependencyDag::new();
+        insert_distribution(
+            &mut dag,
+            name.clone(),
+            copy("1.0", "/a"),
+            MergeStrategy::First,
+        )
+        .unwrap();
+        insert_distribution(
+            &mut dag,
+            name.clone(),
+            copy("2.0", "/b"),
+            MergeStrategy::First,
+        )
+        .unwrap();
+        assert_eq!(dag[&name].installed_version, "1.0");
+        assert_eq!(
+            dag[&name].shadowed,
+            vec![ShadowedCopy {
+                installed_version: String::from("2.0"),
+                meta_dir: PathBuf::from("/b"),
+                inherited: false,
+            }]
+        );
+
+        let mut dag = DependencyDag::new();
+        insert_distribution(
+            &mut dag,
+            name.clone(),
+            copy("1.0", "/a"),
+            MergeStrategy::Last,
+        )
+        .unwrap();
+        insert_distribution(
+            &mut dag,
+            name.clone(),
+            copy("2.0", "/b"),
+            MergeStrategy::Last,
+        )
+        .unwrap();
+        insert_distribution(
+            &mut dag,
+            name.clone(),
+            copy("3.0", "/c"),
+            MergeStrategy::Last,
+        )
+        .unwrap();
+        assert_eq!(dag[&name].installed_version, "3.0");
+        assert_eq!(dag[&name].shadowed.len(), 2);
+
+        let mut dag = DependencyDag::new();
+        insert_distribution(
+            &mut dag,
+            name.clone(),
+            copy("1.0", "/a/numpy-1.0.dist-info"),
+            MergeStrategy::Error,
+        )
+        .unwrap();
+        insert_distribution(
+            &mut dag,
+            name.clone(),
+            copy("1.0", "/b/numpy-1.0.dist-info"),
+            MergeStrategy::Error,
+        )
+        .unwrap();
+        assert!(insert_distribution(
+            &mut dag,
+            name.clone(),
+            copy("2.0", "/c/numpy-2.0.dist-info"),
+            MergeStrategy::Error
+        )
+        .is_err());
+
+        // the venv copy wins over the inherited one whatever the strategy
+        for strategy in [
+            MergeStrategy::First,
+            MergeStrategy::Last,
+            MergeStrategy::Error,
+        ] {
+            let base = || DistributionMeta {
+                inherited: true,
+                ..copy("1.0", "/base/numpy-1.0.dist-info")
+            };
+            let mut dag = DependencyDag::new();
+            insert_distribution(&mut dag, name.clone(), copy("2.0", "/venv"), strategy).unwrap();
+            insert_distribution(&mut dag, name.clone(), base(), strategy).unwrap();
+            assert_eq!(dag[&name].installed_version, "2.0");
+            assert!(!dag[&name].has_conflicts());
+
+            let mut dag = DependencyDag::new();
+            insert_distribution(&mut dag, name.clone(), base(), strategy).unwrap();
+            insert_distribution(&mut dag, name.clone(), copy("2.0", "/venv"), strategy).unwrap();
+            assert_eq!(dag[&name].installed_version, "2.0");
+            assert!(dag[&name].shadowed[0].inherited);
+        }
+    }
+
     #[test]
     fn distr_meta_no_version_fail() {
         let sample_meta = [
@@ -329,9 +2119,12 @@ mod test {
             String::from("Developed by me"),
         ];
 
-        let result = node_from_file_iter(sample_meta.into_iter());
+        let result = node_from_file_iter(sample_meta, None);
         assert!(result.is_err());
-        assert_eq!(result.err(), Some("Can not parse version name from file"));
+        assert_eq!(
+            result.err(),
+            Some(ParseError::Invalid("Can not parse version name from file"))
+        );
     }
 
     #[test]
@@ -341,9 +2134,12 @@ mod test {
             String::from("Developed by me"),
         ];
 
-        let result = node_from_file_iter(sample_meta.into_iter());
+        let result = node_from_file_iter(sample_meta, None);
         assert!(result.is_err());
-        assert_eq!(result.err(), Some("Can not parse package name from file"));
+        assert_eq!(
+            result.err(),
+            Some(ParseError::Invalid("Can not parse package name from file"))
+        );
     }
 
     #[test]
@@ -400,7 +2196,7 @@ mod test {
 
         for (input_data, expected_data) in tests_cases.iter() {
             let (distribution_name, distribution_meta) =
-                node_from_file_iter(input_data.iter()).unwrap();
+                node_from_file_iter(input_data.iter(), None).unwrap();
 
             assert_eq!(
                 distribution_name, expected_data[0],
@@ -566,8 +2362,15 @@ mod test {
         ];
 
         for (input_data, expected_data) in tests_cases.iter() {
+            // PEP 440 forbids local versions in ordered comparisons
+            if cfg!(feature = "pep508")
+                && expected_data[3].starts_with('<')
+                && expected_data[3].contains('+')
+            {
+                continue;
+            }
             let (distribution_name, distribution_meta) =
-                node_from_file_iter(input_data.iter()).unwrap();
+                node_from_file_iter(input_data.iter(), None).unwrap();
 
             assert_eq!(
                 distribution_name, expected_data[0],
@@ -0,0 +1,47 @@
+//! Time budget of the whole run, see `--timeout`. Subprocesses and network
+//! queries are limited to the time left, scans check it between metadata dirs
+//! and stop with a warning naming how far they got
+
+use std::io;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+static DEADLINE: OnceLock<(Instant, Duration)> = OnceLock::new();
+
+/// Limit the rest of the run to the given time, only the first call counts
+pub fn set_timeout(timeout: Duration) {
+    let _ = DEADLINE.set((Instant::now(), timeout));
+}
+
+/// Time budget of the run, None if unlimited
+pub fn timeout() -> Option<Duration> {
+    DEADLINE.get().map(|(_, timeout)| *timeout)
+}
+
+/// Time left, None if unlimited
+pub fn remaining() -> Option<Duration> {
+    DEADLINE
+        .get()
+        .map(|(start, timeout)| timeout.saturating_sub(start.elapsed()))
+}
+
+/// The time budget is used up
+pub fn expired() -> bool {
+    remaining().is_some_and(|left| left.is_zero())
+}
+
+/// The given timeout of a single operation, cut to the time left
+pub fn limit(timeout: Duration) -> Duration {
+    remaining().map_or(timeout, |left| left.min(timeout))
+}
+
+/// Error once the time budget is used up
+pub fn check() -> io::Result<()> {
+    match timeout() {
+        Some(timeout) if expired() => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("time limit of {}s exceeded", timeout.as_secs()),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,30 @@
+//! Structured progress events for GUI wrappers, see `--events-file`. Every event
+//! is a single json line `{"event": ..., ...}` written to a side channel,
+//! so the output on stdout and diagnostics on stderr stay untouched
+
+use serde_json::{json, Value};
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+static SINK: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Write events of the rest of the run to the sink, only the first call counts
+pub fn set_sink(sink: Box<dyn Write + Send>) {
+    let _ = SINK.set(Mutex::new(sink));
+}
+
+/// Write an event with the given fields, if events were requested.
+/// Every event is flushed right away, failed writes are ignored,
+/// so a wrapper which stopped listening never breaks the run
+pub fn emit(event: &str, fields: Value) {
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    let mut line = json!({ "event": event });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    if let Ok(mut sink) = sink.lock() {
+        let _ = writeln!(sink, "{}", line).and_then(|_| sink.flush());
+    }
+}
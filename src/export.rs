@@ -0,0 +1,177 @@
+//! Export of the env into a SQLite database, see `export --sqlite`.
+//! Tables are prefixed with `rdeptree_`, so the export can live next to
+//! the tables of the user (e.g. an asset inventory) and be joined with them
+
+use crate::dag::DependencyDag;
+#[cfg(feature = "sqlite")]
+use crate::origin::Origin;
+use crate::policy::Violation;
+use std::path::Path;
+
+/// Tables of the export, recreated on every export into the same file.
+/// Other tables of the database are left alone
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "\
+DROP TABLE IF EXISTS rdeptree_findings;
+DROP TABLE IF EXISTS rdeptree_edges;
+DROP TABLE IF EXISTS rdeptree_packages;
+CREATE TABLE rdeptree_packages (
+    name TEXT PRIMARY KEY,
+    version TEXT NOT NULL,
+    installer TEXT,
+    origin TEXT NOT NULL,
+    requested INTEGER NOT NULL,
+    location TEXT NOT NULL
+);
+CREATE TABLE rdeptree_edges (
+    requirer TEXT NOT NULL REFERENCES rdeptree_packages(name),
+    dependency TEXT NOT NULL,
+    specifier TEXT NOT NULL,
+    marker TEXT,
+    installed INTEGER NOT NULL
+);
+CREATE TABLE rdeptree_findings (
+    rule TEXT NOT NULL,
+    package TEXT,
+    message TEXT NOT NULL
+);
+";
+
+/// Write packages sorted by name, their direct requirements and the given
+/// findings into the database file, creating it if needed. Everything is
+/// written in a single transaction, so a failed export leaves the previous one
+#[cfg(feature = "sqlite")]
+pub fn write_sqlite(
+    path: &Path,
+    dag: &DependencyDag,
+    findings: &[Violation],
+) -> Result<(), String> {
+    use rusqlite::{params, Connection};
+
+    let mut connection = Connection::open(path).map_err(|err| err.to_string())?;
+    let transaction = connection.transaction().map_err(|err| err.to_string())?;
+    transaction
+        .execute_batch(SCHEMA)
+        .map_err(|err| err.to_string())?;
+    {
+        let mut packages = transaction
+            .prepare("INSERT INTO rdeptree_packages VALUES (?1, ?2, ?3, ?4, ?5, ?6)")
+            .map_err(|err| err.to_string())?;
+        let mut edges = transaction
+            .prepare("INSERT INTO rdeptree_edges VALUES (?1, ?2, ?3, ?4, ?5)")
+            .map_err(|err| err.to_string())?;
+        let mut rows = transaction
+            .prepare("INSERT INTO rdeptree_findings VALUES (?1, ?2, ?3)")
+            .map_err(|err| err.to_string())?;
+
+        let mut names: Vec<_> = dag.keys().collect();
+        names.sort();
+        for name in &names {
+            let meta = &dag[*name];
+            packages
+                .execute(params![
+                    name,
+                    meta.installed_version,
+                    meta.installer,
+                    Origin::of(meta).to_string(),
+                    meta.requested,
+                    meta.meta_dir.display().to_string(),
+                ])
+                .map_err(|err| err.to_string())?;
+        }
+        for name in &names {
+            for dependency in dag[*name].sorted_dependencies() {
+                edges
+                    .execute(params![
+                        name,
+                        dependency.name,
+                        dependency.required_version,
+                        dependency.marker,
+                        dag.contains_key(&dependency.name),
+                    ])
+                    .map_err(|err| err.to_string())?;
+            }
+        }
+        for finding in findings {
+            rows.execute(params![finding.rule, finding.package, finding.message])
+                .map_err(|err| err.to_string())?;
+        }
+    }
+    transaction.commit().map_err(|err| err.to_string())
+}
+
+/// Builds without the `sqlite` feature can't write databases
+#[cfg(not(feature = "sqlite"))]
+pub fn write_sqlite(
+    _path: &Path,
+    _dag: &DependencyDag,
+    _findings: &[Violation],
+) -> Result<(), String> {
+    Err(String::from(
+        "writing SQLite databases needs rdeptree built with the sqlite feature",
+    ))
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod test {
+    use super::*;
+    use crate::dag::{DistributionMeta, RequiredDistribution};
+    use crate::fixture::SitePackages;
+    use rusqlite::Connection;
+
+    #[test]
+    fn packages_edges_and_findings_written() {
+        let mut dag = DependencyDag::new();
+        dag.insert(
+            String::from("app"),
+            DistributionMeta {
+                installed_version: String::from("1.0"),
+                installer: Some(String::from("pip")),
+                dependencies: [RequiredDistribution {
+                    name: String::from("o-brien"),
+                    required_version: String::from(">=2"),
+                    marker: Some(String::from("os_name == 'posix'")),
+                    source: None,
+                }]
+                .into(),
+                ..Default::default()
+            },
+        );
+        let findings = [Violation {
+            rule: String::from("missing"),
+            package: Some(String::from("app")),
+            message: String::from("o-brien is not installed"),
+        }];
+        let dir = SitePackages::new("sqlite-export");
+        let db = dir.path().join("env.db");
+        Connection::open(&db)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE packages (name TEXT); INSERT INTO packages VALUES ('own');",
+            )
+            .unwrap();
+
+        write_sqlite(&db, &dag, &findings).unwrap();
+        write_sqlite(&db, &dag, &findings).unwrap();
+        let connection = Connection::open(&db).unwrap();
+        let query = |sql: &str| -> String {
+            connection
+                .query_row(sql, [], |row| row.get::<_, String>(0))
+                .unwrap()
+        };
+        assert_eq!(
+            query("SELECT name || ' ' || version || ' ' || installer FROM rdeptree_packages"),
+            "app 1.0 pip"
+        );
+        assert_eq!(
+            query("SELECT dependency || ' ' || marker || ' ' || installed FROM rdeptree_edges"),
+            "o-brien os_name == 'posix' 0"
+        );
+        assert_eq!(
+            query("SELECT rule || ': ' || message FROM rdeptree_findings"),
+            "missing: o-brien is not installed"
+        );
+        // tables of the user are kept
+        assert_eq!(query("SELECT name FROM packages"), "own");
+    }
+}
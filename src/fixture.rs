@@ -0,0 +1,87 @@
+//! Synthetic site-packages trees for tests.
+//! Depends on std only, so integration tests include it with `#[path]`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static FIXTURE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Temporary site-packages dir, removed when dropped
+///
+/// ```ignore
+/// let site = SitePackages::new("tree")
+///     .dist_info("requests", "2.31.0", &["idna<4,>=2.5"])
+///     .dist_info("idna", "3.6", &[]);
+/// ```
+pub struct SitePackages {
+    root: PathBuf,
+}
+
+impl SitePackages {
+    /// Create empty dir, `name` only helps to find leftovers of failed tests
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!(
+            "rdeptree-fixture-{}-{}-{}",
+            name,
+            std::process::id(),
+            FIXTURE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write arbitrary file, creating parent dirs
+    pub fn file(self, path: &str, content: &str) -> Self {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        self
+    }
+
+    /// Add `<name>-<version>.dist-info` with METADATA listing given
+    /// Requires-Dist values, e.g. `idna<4,>=2.5` or `pytest>=7; extra == "test"`
+    pub fn dist_info(self, name: &str, version: &str, requires: &[&str]) -> Self {
+        let mut metadata = format!(
+            "Metadata-Version: 2.1\nName: {}\nVersion: {}\n",
+            name, version
+        );
+        for requirement in requires {
+            metadata.push_str(&format!("Requires-Dist: {}\n", requirement));
+        }
+        let path = format!("{}/METADATA", Self::dist_info_dir(name, version));
+        self.file(&path, &metadata)
+    }
+
+    /// Add a file next to METADATA of already added dist-info, e.g. INSTALLER or RECORD
+    pub fn dist_info_file(self, name: &str, version: &str, file: &str, content: &str) -> Self {
+        let path = format!("{}/{}", Self::dist_info_dir(name, version), file);
+        self.file(&path, content)
+    }
+
+    /// Add legacy `<name>-<version>.egg-info` with PKG-INFO and requires.txt
+    pub fn egg_info(self, name: &str, version: &str, requires_txt: &str) -> Self {
+        let dir = format!("{}-{}-py3.11.egg-info", name.replace('-', "_"), version);
+        let pkg_info = format!(
+            "Metadata-Version: 1.1\nName: {}\nVersion: {}\n",
+            name, version
+        );
+        self.file(&format!("{}/PKG-INFO", dir), &pkg_info)
+            .file(&format!("{}/requires.txt", dir), requires_txt)
+    }
+
+    fn dist_info_dir(name: &str, version: &str) -> String {
+        format!("{}-{}.dist-info", name.replace('-', "_"), version)
+    }
+}
+
+impl Drop for SitePackages {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
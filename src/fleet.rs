@@ -0,0 +1,268 @@
+//! Audit of every virtual env found under a directory tree,
+//! for hosts running many service environments side by side
+
+use crate::builder::DagBuilder;
+use crate::deadline;
+use crate::locator::{
+    get_site_packages_loc, inherited_site_packages, layout_site_packages, venv_interpreter,
+};
+use crate::policy::{check_duplicates, check_extras, Violation};
+use crate::render::RenderOptions;
+use crate::renderer::Summary;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+const PYVENV_CFG: &str = "pyvenv.cfg";
+
+/// Virtual envs under the root dir, i.e. dirs with pyvenv.cfg, sorted by path.
+/// Envs are not looked into and symlinked dirs are not followed,
+/// unreadable dirs are skipped. The search stops when the run is out of time
+pub fn find_venvs(root: &Path) -> Vec<PathBuf> {
+    let mut venvs = Vec::new();
+    let mut to_visit = vec![root.to_path_buf()];
+    while let Some(dir) = to_visit.pop() {
+        if deadline::expired() {
+            break;
+        }
+        if dir.join(PYVENV_CFG).is_file() {
+            venvs.push(dir);
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        to_visit.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                .map(|entry| entry.path()),
+        );
+    }
+    venvs.sort();
+    venvs
+}
+
+/// Health of a single virtual env
+#[derive(Debug)]
+pub struct VenvAudit {
+    pub venv: PathBuf,
+    /// totals of all installed packages, error if the env could not be parsed
+    pub summary: Result<Summary, String>,
+    pub violations: Vec<Violation>,
+}
+
+impl VenvAudit {
+    /// Env was parsed and has no conflicts, missing requirements or violations
+    pub fn healthy(&self) -> bool {
+        match &self.summary {
+            Ok(summary) => {
+                summary.conflicts == 0 && summary.missing == 0 && self.violations.is_empty()
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// How site-packages dirs of a virtual env are found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Discovery {
+    /// by the directory layout, no interpreter is run
+    Layout,
+    /// by asking the env interpreter, the layout is used when it can not be run
+    Interpreter,
+}
+
+/// site-packages dirs of a virtual env
+pub fn venv_site_packages(venv: &Path, discovery: Discovery) -> Vec<PathBuf> {
+    if discovery == Discovery::Interpreter {
+        if let Ok(site_packages) = get_site_packages_loc(&venv_interpreter(venv)) {
+            return vec![site_packages];
+        }
+    }
+    layout_site_packages(venv)
+}
+
+/// Parse the env with the given builder, which carries the
+/// parsing options, and check it for conflicts
+pub fn audit_venv(venv: &Path, builder: DagBuilder, discovery: Discovery) -> VenvAudit {
+    let paths = venv_site_packages(venv, discovery);
+    let dag = if paths.is_empty() {
+        Err(String::from("no site-packages dir found"))
+    } else {
+        builder
+            .paths(paths)
+            .inherited_paths(inherited_site_packages(venv))
+            .build()
+    };
+    match dag {
+        Ok(dag) => VenvAudit {
+            venv: venv.to_path_buf(),
+            summary: Ok(Summary::of(&dag, &RenderOptions::default())),
+            violations: check_duplicates(&dag)
+                .into_iter()
+                .chain(check_extras(&dag))
+                .collect(),
+        },
+        Err(err) => VenvAudit {
+            venv: venv.to_path_buf(),
+            summary: Err(err),
+            violations: Vec::new(),
+        },
+    }
+}
+
+/// Threads auditing envs when not given, interpreter startups
+/// mostly wait on the disk, so one per core is plenty
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(4, |n| n.get())
+}
+
+/// Audit envs on up to `jobs` threads, so slow interpreter startups of many
+/// envs overlap. Audits are returned in the order of envs
+pub fn audit_venvs(
+    venvs: &[PathBuf],
+    builder: &DagBuilder,
+    discovery: Discovery,
+    jobs: usize,
+) -> Vec<VenvAudit> {
+    let next = AtomicUsize::new(0);
+    let mut audits: Vec<(usize, VenvAudit)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, venvs.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut audits = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(venv) = venvs.get(index) else {
+                            break;
+                        };
+                        audits.push((index, audit_venv(venv, builder.clone(), discovery)));
+                    }
+                    audits
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("audit thread panicked"))
+            .collect()
+    });
+    audits.sort_by_key(|(index, _)| *index);
+    audits.into_iter().map(|(_, audit)| audit).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::SitePackages;
+
+    #[test]
+    fn venvs_found_by_pyvenv_cfg() {
+        let root = SitePackages::new("fleet-find")
+            .file("svc-a/pyvenv.cfg", "version = 3.11.4\n")
+            .file("svc-a/lib/nested/pyvenv.cfg", "version = 3.11.4\n")
+            .file("team/svc-b/.venv/pyvenv.cfg", "version = 3.12.1\n")
+            .file("team/notes.txt", "");
+
+        let venvs: Vec<PathBuf> = find_venvs(root.path())
+            .iter()
+            .map(|venv| venv.strip_prefix(root.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            venvs,
+            vec![PathBuf::from("svc-a"), PathBuf::from("team/svc-b/.venv")]
+        );
+    }
+
+    #[test]
+    fn audit_reports_missing_and_unparsed_envs() {
+        let root = SitePackages::new("fleet-audit")
+            .file("ok/pyvenv.cfg", "")
+            .file(
+                "ok/lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA",
+                "Name: six\nVersion: 1.16.0\n",
+            )
+            .file("broken/pyvenv.cfg", "")
+            .file(
+                "broken/lib/python3.11/site-packages/app-1.0.dist-info/METADATA",
+                "Name: app\nVersion: 1.0\nRequires-Dist: lib>=2.0\n",
+            )
+            .file("empty/pyvenv.cfg", "");
+
+        let audit = audit_venv(
+            &root.path().join("ok"),
+            DagBuilder::new(),
+            Discovery::Layout,
+        );
+        assert_eq!(audit.summary.as_ref().unwrap().packages, 1);
+        assert!(audit.healthy());
+
+        let audit = audit_venv(
+            &root.path().join("broken"),
+            DagBuilder::new(),
+            Discovery::Layout,
+        );
+        assert_eq!(audit.summary.as_ref().unwrap().missing, 1);
+        assert!(!audit.healthy());
+
+        let audit = audit_venv(
+            &root.path().join("empty"),
+            DagBuilder::new(),
+            Discovery::Layout,
+        );
+        assert!(audit.summary.is_err());
+        assert!(!audit.healthy());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn audits_probe_interpreters_concurrently() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut root = SitePackages::new("fleet-probe");
+        for i in 0..5 {
+            root = root.file(&format!("svc-{i}/pyvenv.cfg"), "").file(
+                &format!("svc-{i}/lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA"),
+                "Name: six\nVersion: 1.16.0\n",
+            );
+        }
+        // interpreter reporting a site-packages dir outside of the layout
+        let custom = root.path().join("svc-3/custom");
+        let root = root
+            .file(
+                "svc-3/bin/python3",
+                &format!("#!/bin/sh\necho {}\n", custom.display()),
+            )
+            .file(
+                "svc-3/custom/app-1.0.dist-info/METADATA",
+                "Name: app\nVersion: 1.0\n",
+            )
+            .file(
+                "svc-3/custom/lib-2.0.dist-info/METADATA",
+                "Name: lib\nVersion: 2.0\n",
+            );
+        let interpreter = root.path().join("svc-3/bin/python3");
+        fs::set_permissions(&interpreter, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let venvs = find_venvs(root.path());
+        let packages = |discovery| -> Vec<(String, usize)> {
+            audit_venvs(&venvs, &DagBuilder::new(), discovery, 3)
+                .iter()
+                .map(|audit| {
+                    let venv = audit.venv.strip_prefix(root.path()).unwrap();
+                    let packages = audit.summary.as_ref().unwrap().packages;
+                    (venv.display().to_string(), packages)
+                })
+                .collect()
+        };
+        let expected = |probed| {
+            (0..5)
+                .map(|i| (format!("svc-{i}"), if i == 3 { probed } else { 1 }))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(packages(Discovery::Layout), expected(1));
+        assert_eq!(packages(Discovery::Interpreter), expected(2));
+    }
+}
@@ -0,0 +1,615 @@
+use crate::dag::{
+    get_requirers, DependencyDag, DistributionMeta, DistributionName, EdgeKind,
+    RequiredDistribution,
+};
+use crate::normalize::pep503;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+
+/// Standard packaging tooling present in almost every environment,
+/// which users usually don't want to see in the output
+pub const DEFAULT_TOOLING: [&str; 5] =
+    ["pip", "setuptools", "wheel", "pkg-resources", "distribute"];
+
+/// Remove given distributions from the dag, top level or not, then every
+/// dependency of a removed distribution which no distribution left in the dag
+/// requires, repeating for dependencies of those. Names not in the dag are
+/// ignored. Returns names of all removed distributions.
+pub fn prune(
+    dag: &mut DependencyDag,
+    names: &HashSet<DistributionName>,
+) -> HashSet<DistributionName> {
+    let mut removed: HashSet<DistributionName> = HashSet::new();
+    let mut to_remove: Vec<DistributionName> = names
+        .iter()
+        .filter(|n| dag.contains_key(*n))
+        .cloned()
+        .collect();
+
+    while let Some(name) = to_remove.pop() {
+        let Some(meta) = dag.remove(&name) else {
+            continue;
+        };
+        removed.insert(name);
+
+        // dependencies of removed node which are not required by anyone else
+        for dep in meta.dependencies {
+            if removed.contains(&dep.name) || !dag.contains_key(&dep.name) {
+                continue;
+            }
+            let still_required = dag
+                .values()
+                .flat_map(|v| &v.dependencies)
+                .any(|d| d.name == dep.name);
+            if !still_required {
+                to_remove.push(dep.name);
+            }
+        }
+    }
+    removed
+}
+
+/// Selection of edges by kind: `runtime`, `extra`, `extra:<name>`,
+/// `conditional` or `conditional:<text>`, matching markers which contain the text
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeKindFilter {
+    Runtime,
+    Extra(Option<String>),
+    Conditional(Option<String>),
+}
+
+impl EdgeKindFilter {
+    pub fn matches(&self, kind: &EdgeKind) -> bool {
+        match (self, kind) {
+            (Self::Runtime, EdgeKind::Runtime) => true,
+            (Self::Extra(None), EdgeKind::Extra(_)) => true,
+            (Self::Extra(Some(name)), EdgeKind::Extra(extras)) => extras.contains(name),
+            (Self::Conditional(None), EdgeKind::Conditional(_)) => true,
+            (Self::Conditional(Some(text)), EdgeKind::Conditional(marker)) => {
+                marker.contains(text.as_str())
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for EdgeKindFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, argument) = match s.split_once(':') {
+            Some((kind, argument)) => (kind, Some(argument.trim())),
+            None => (s, None),
+        };
+        match (kind.trim(), argument) {
+            ("runtime", None) => Ok(Self::Runtime),
+            ("extra", argument) => Ok(Self::Extra(argument.map(pep503))),
+            ("conditional", argument) => Ok(Self::Conditional(argument.map(String::from))),
+            _ => Err(format!(
+                "unknown edge kind '{}', expected runtime, extra[:<name>] or conditional[:<text>]",
+                s
+            )),
+        }
+    }
+}
+
+/// Keep only edges of the given kinds, in the dependencies and in the requirements
+/// of extras alike, so every view of the dag agrees. Distributions required only
+/// through dropped edges are pruned, see [`prune`].
+/// Returns names of all removed distributions.
+pub fn retain_edge_kinds(
+    dag: &mut DependencyDag,
+    filters: &[EdgeKindFilter],
+) -> HashSet<DistributionName> {
+    let required = |dag: &DependencyDag| -> HashSet<DistributionName> {
+        dag.values()
+            .flat_map(|meta| &meta.dependencies)
+            .map(|dep| dep.name.clone())
+            .collect()
+    };
+    let required_before = required(dag);
+    for meta in dag.values_mut() {
+        meta.dependencies
+            .retain(|dep| filters.iter().any(|f| f.matches(&dep.kind())));
+        for (extra, requirements) in meta.extras.iter_mut() {
+            let kind = EdgeKind::Extra(vec![extra.clone()]);
+            if !filters.iter().any(|f| f.matches(&kind)) {
+                requirements.clear();
+            }
+        }
+    }
+    let required_after = required(dag);
+    let orphaned: HashSet<DistributionName> = required_before
+        .difference(&required_after)
+        .cloned()
+        .collect();
+    prune(dag, &orphaned)
+}
+
+/// Stable digest of the dag, computed over sorted normalized
+/// `name==version` nodes and `name -> dependency specifier` edges.
+/// Two environments with the same packages and requirements always
+/// produce the same fingerprint, regardless of scan order or location.
+pub fn fingerprint(dag: &DependencyDag) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for (name, meta) in dag {
+        lines.push(format!("{}=={}", name, meta.installed_version));
+        for dep in &meta.dependencies {
+            lines.push(format!("{} -> {} {}", name, dep.name, dep.required_version));
+        }
+    }
+    lines.sort();
+
+    let mut hasher = Sha256::new();
+    for line in lines {
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+    }
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("sha256:{}", digest)
+}
+
+/// Which edges a traversal follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// from requirers to their dependencies
+    Dependencies,
+    /// from dependencies to the distributions requiring them
+    Requirers,
+}
+
+/// Edges of a distribution in the given direction, each with the requirement
+/// it stands for, sorted by name and specifier
+fn edges<'a>(
+    dag: &'a DependencyDag,
+    name: &DistributionName,
+    direction: Direction,
+) -> Vec<(&'a DistributionName, &'a RequiredDistribution)> {
+    match direction {
+        Direction::Dependencies => {
+            let Some(meta) = dag.get(name) else {
+                return Vec::new();
+            };
+            let mut edges: Vec<_> = meta.dependencies.iter().map(|d| (&d.name, d)).collect();
+            edges.sort_by_key(|(_, d)| (&d.name, &d.required_version));
+            edges
+        }
+        Direction::Requirers => get_requirers(dag, name),
+    }
+}
+
+/// Node reached by a traversal
+#[derive(Debug, PartialEq)]
+pub struct Visit<'a> {
+    pub name: &'a DistributionName,
+    /// None if the distribution is required but not installed
+    pub meta: Option<&'a DistributionMeta>,
+    /// requirement of the edge the node was reached by, None for start nodes
+    pub requirement: Option<&'a RequiredDistribution>,
+    /// number of edges from the start node
+    pub depth: usize,
+    /// the node already appears on the path from the start node,
+    /// its edges are not followed
+    pub cycle: bool,
+}
+
+/// Iterative depth first traversal yielding nodes in preorder, i.e. the order
+/// trees are printed in. A node is visited once per path leading to it, paths
+/// stop at dependency cycles and at `max_depth`
+pub struct DepthFirst<'a> {
+    dag: &'a DependencyDag,
+    direction: Direction,
+    max_depth: Option<usize>,
+    stack: Vec<(
+        &'a DistributionName,
+        Option<&'a RequiredDistribution>,
+        usize,
+    )>,
+    /// nodes from the start node to the parent of the next visited one
+    path: Vec<&'a DistributionName>,
+}
+
+impl<'a> DepthFirst<'a> {
+    pub fn new(dag: &'a DependencyDag, start: &'a DistributionName, direction: Direction) -> Self {
+        Self {
+            dag,
+            direction,
+            max_depth: None,
+            stack: vec![(start, None, 0)],
+            path: Vec::new(),
+        }
+    }
+
+    /// Don't follow edges of nodes at this depth, unlimited by default
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+    type Item = Visit<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (name, requirement, depth) = self.stack.pop()?;
+        self.path.truncate(depth);
+        let cycle = self.path.contains(&name);
+        let exhausted = self.max_depth.is_some_and(|max_depth| depth >= max_depth);
+        if !cycle && !exhausted {
+            self.path.push(name);
+            let edges = edges(self.dag, name, self.direction);
+            self.stack.extend(
+                edges
+                    .into_iter()
+                    .rev()
+                    .map(|(next, requirement)| (next, Some(requirement), depth + 1)),
+            );
+        }
+        Some(Visit {
+            name,
+            meta: self.dag.get(name),
+            requirement,
+            depth,
+            cycle,
+        })
+    }
+}
+
+/// Breadth first traversal from the start nodes, every reachable node is
+/// yielded once together with its distance from the nearest start node.
+/// Required but not installed distributions are yielded too
+pub fn breadth_first<'a>(
+    dag: &'a DependencyDag,
+    starts: impl IntoIterator<Item = &'a DistributionName>,
+    direction: Direction,
+) -> Vec<(&'a DistributionName, usize)> {
+    let mut seen: HashSet<&DistributionName> = HashSet::new();
+    let mut queue: VecDeque<(&DistributionName, usize)> = VecDeque::new();
+    for start in starts {
+        if seen.insert(start) {
+            queue.push_back((start, 0));
+        }
+    }
+    let mut visited = Vec::new();
+    while let Some((name, depth)) = queue.pop_front() {
+        visited.push((name, depth));
+        for (next, _) in edges(dag, name, direction) {
+            if seen.insert(next) {
+                queue.push_back((next, depth + 1));
+            }
+        }
+    }
+    visited
+}
+
+/// Installed transitive dependencies each root requires exclusively, i.e. no
+/// other root requires them, so they would go away together with the root.
+/// Roots come in the given order, their dependencies sorted by name
+pub fn exclusive_dependencies<'a>(
+    dag: &'a DependencyDag,
+    roots: &[&'a DistributionName],
+) -> Vec<(&'a DistributionName, Vec<&'a DistributionName>)> {
+    // index of the only root reaching a distribution, None when several do
+    let mut reached_by: HashMap<&DistributionName, Option<usize>> = HashMap::new();
+    for (idx, root) in roots.iter().enumerate() {
+        for (name, _) in breadth_first(dag, [*root], Direction::Dependencies) {
+            reached_by
+                .entry(name)
+                .and_modify(|only| {
+                    if *only != Some(idx) {
+                        *only = None;
+                    }
+                })
+                .or_insert(Some(idx));
+        }
+    }
+    let mut exclusive: Vec<Vec<&DistributionName>> = vec![Vec::new(); roots.len()];
+    for (name, only) in reached_by {
+        if let Some(idx) = only {
+            if !roots.contains(&name) && dag.contains_key(name) {
+                exclusive[idx].push(name);
+            }
+        }
+    }
+    roots
+        .iter()
+        .zip(exclusive)
+        .map(|(root, mut names)| {
+            names.sort();
+            (*root, names)
+        })
+        .collect()
+}
+
+/// Everything the start node transitively requires, itself included, in
+/// topological order: every distribution comes after all of its dependencies,
+/// as they have to be installed. Edges closing a cycle are ignored, ties are
+/// ordered by name. Required but not installed distributions are yielded too
+pub fn dependency_closure<'a>(
+    dag: &'a DependencyDag,
+    start: &'a DistributionName,
+) -> Vec<&'a DistributionName> {
+    let mut seen: HashSet<&DistributionName> = HashSet::from([start]);
+    let mut order = Vec::new();
+    // postorder traversal, a node is emitted when all its edges are explored
+    let mut stack = vec![(
+        start,
+        edges(dag, start, Direction::Dependencies).into_iter(),
+    )];
+    while let Some((name, edges_left)) = stack.last_mut() {
+        match edges_left.next() {
+            Some((next, _)) => {
+                if seen.insert(next) {
+                    let next_edges = edges(dag, next, Direction::Dependencies).into_iter();
+                    stack.push((next, next_edges));
+                }
+            }
+            None => {
+                order.push(*name);
+                stack.pop();
+            }
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::{DistributionMeta, RequiredDistribution};
+
+    fn node(deps: &[&str]) -> DistributionMeta {
+        DistributionMeta {
+            installed_version: String::from("1.0"),
+            dependencies: deps
+                .iter()
+                .map(|d| RequiredDistribution {
+                    name: d.to_string(),
+                    required_version: String::new(),
+                    marker: None,
+                    source: None,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn edges_retained_by_kind() {
+        let marked = |name: &str, marker: &str| RequiredDistribution {
+            name: name.to_string(),
+            required_version: String::new(),
+            marker: Some(marker.to_string()),
+            source: None,
+        };
+        let dag = || -> DependencyDag {
+            let mut app = node(&["lib"]);
+            app.dependencies.extend([
+                marked("pytest", r#"extra == "test""#),
+                marked("pywin32", r#"sys_platform == "win32""#),
+            ]);
+            app.extras.insert(
+                String::from("test"),
+                HashSet::from([marked("pytest", r#"extra == "test""#)]),
+            );
+            [
+                ("app", app),
+                ("lib", node(&[])),
+                ("pytest", node(&["pluggy"])),
+                ("pluggy", node(&[])),
+                ("pywin32", node(&[])),
+                ("tool", node(&[])),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect()
+        };
+
+        let names = |filters: &str| {
+            let filters: Vec<EdgeKindFilter> =
+                filters.split(',').map(|f| f.parse().unwrap()).collect();
+            let mut dag = dag();
+            retain_edge_kinds(&mut dag, &filters);
+            let mut names: Vec<String> = dag.keys().cloned().collect();
+            names.sort();
+            let extras = dag["app"].extras["test"].len();
+            (names, extras)
+        };
+        assert_eq!(
+            names("runtime"),
+            (["app", "lib", "tool"].map(String::from).to_vec(), 0)
+        );
+        assert_eq!(
+            names("runtime,extra:Test"),
+            (
+                ["app", "lib", "pluggy", "pytest", "tool"]
+                    .map(String::from)
+                    .to_vec(),
+                1
+            )
+        );
+        assert_eq!(
+            names("runtime,conditional:win32"),
+            (
+                ["app", "lib", "pywin32", "tool"].map(String::from).to_vec(),
+                0
+            )
+        );
+        assert!("optional".parse::<EdgeKindFilter>().is_err());
+        assert!("runtime:x".parse::<EdgeKindFilter>().is_err());
+    }
+
+    #[test]
+    fn fingerprint_is_stable() {
+        let dag_a: DependencyDag = [("app", node(&["six", "numpy"])), ("six", node(&[]))]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        let dag_b: DependencyDag = [("six", node(&[])), ("app", node(&["numpy", "six"]))]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+
+        assert_eq!(fingerprint(&dag_a), fingerprint(&dag_b));
+        assert!(fingerprint(&dag_a).starts_with("sha256:"));
+        assert_eq!(fingerprint(&dag_a).len(), "sha256:".len() + 64);
+
+        let mut dag_c = dag_b;
+        dag_c.get_mut("six").unwrap().installed_version = String::from("1.1");
+        assert_ne!(fingerprint(&dag_a), fingerprint(&dag_c));
+    }
+
+    #[test]
+    fn depth_first_stops_at_cycles_and_depth() {
+        let dag: DependencyDag = [
+            ("app", node(&["lib", "six"])),
+            ("lib", node(&["six", "app"])),
+            ("six", node(&["missing"])),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        let app = String::from("app");
+
+        let visits: Vec<(&str, usize, bool)> = DepthFirst::new(&dag, &app, Direction::Dependencies)
+            .map(|v| (v.name.as_str(), v.depth, v.cycle))
+            .collect();
+        assert_eq!(
+            visits,
+            [
+                ("app", 0, false),
+                ("lib", 1, false),
+                ("app", 2, true),
+                ("six", 2, false),
+                ("missing", 3, false),
+                ("six", 1, false),
+                ("missing", 2, false),
+            ]
+        );
+
+        let visits: Vec<&str> = DepthFirst::new(&dag, &app, Direction::Dependencies)
+            .max_depth(Some(1))
+            .map(|v| v.name.as_str())
+            .collect();
+        assert_eq!(visits, ["app", "lib", "six"]);
+
+        let six = String::from("six");
+        let visits: Vec<(&str, usize, bool)> = DepthFirst::new(&dag, &six, Direction::Requirers)
+            .map(|v| (v.name.as_str(), v.depth, v.cycle))
+            .collect();
+        assert_eq!(
+            visits,
+            [
+                ("six", 0, false),
+                ("app", 1, false),
+                ("lib", 2, false),
+                ("app", 3, true),
+                ("lib", 1, false),
+                ("app", 2, false),
+                ("lib", 3, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn breadth_first_visits_nodes_once() {
+        let dag: DependencyDag = [
+            ("app", node(&["lib", "six"])),
+            ("lib", node(&["six", "app"])),
+            ("six", node(&["missing"])),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        let app = String::from("app");
+
+        let visits: Vec<(&str, usize)> = breadth_first(&dag, [&app], Direction::Dependencies)
+            .into_iter()
+            .map(|(name, depth)| (name.as_str(), depth))
+            .collect();
+        assert_eq!(visits, [("app", 0), ("lib", 1), ("six", 1), ("missing", 2)]);
+    }
+
+    #[test]
+    fn closure_lists_dependencies_first() {
+        let dag: DependencyDag = [
+            ("app", node(&["web", "lib"])),
+            ("web", node(&["lib", "six"])),
+            ("lib", node(&["six", "app"])),
+            ("six", node(&["missing"])),
+            ("tool", node(&["six"])),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+        let web = String::from("web");
+        let closure: Vec<&str> = dependency_closure(&dag, &web)
+            .into_iter()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(closure, ["app", "missing", "six", "lib", "web"]);
+
+        let six = String::from("six");
+        assert_eq!(dependency_closure(&dag, &six), ["missing", "six"]);
+    }
+
+    #[test]
+    fn prune_removes_exclusive_dependencies() {
+        let mut dag: DependencyDag = [
+            ("pip", node(&[])),
+            ("setuptools", node(&["jaraco-text"])),
+            ("jaraco-text", node(&["more-itertools"])),
+            ("more-itertools", node(&[])),
+            ("app", node(&["packaging"])),
+            ("wheel", node(&["packaging"])),
+            ("packaging", node(&[])),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+        let names = DEFAULT_TOOLING.iter().map(|n| n.to_string()).collect();
+        let removed = prune(&mut dag, &names);
+
+        let mut remaining: Vec<&str> = dag.keys().map(|k| k.as_str()).collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["app", "packaging"]);
+        assert_eq!(removed.len(), 5);
+        assert!(removed.contains("more-itertools"));
+    }
+
+    #[test]
+    fn exclusive_dependencies_of_roots() {
+        let dag: DependencyDag = [
+            ("app", node(&["requests", "click"])),
+            ("tool", node(&["click", "rich"])),
+            ("requests", node(&["urllib3", "idna"])),
+            ("urllib3", node(&[])),
+            ("idna", node(&[])),
+            ("click", node(&[])),
+            ("rich", node(&["missing"])),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        let (app, tool) = (String::from("app"), String::from("tool"));
+
+        let exclusive: Vec<(&str, Vec<&str>)> = exclusive_dependencies(&dag, &[&app, &tool])
+            .into_iter()
+            .map(|(root, names)| (root.as_str(), names.iter().map(|n| n.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            exclusive,
+            vec![
+                ("app", vec!["idna", "requests", "urllib3"]),
+                ("tool", vec!["rich"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Maintenance heuristics of installed packages, based on
+//! the release history published on the index
+
+use crate::dag::DistributionName;
+use crate::index::Release;
+use crate::pep::Version;
+use std::cmp::Reverse;
+
+const SECS_PER_DAY: u64 = 86400;
+
+/// Points lost per year since the newest release, and the max of them
+const AGE_PENALTY_PER_YEAR: u64 = 10;
+const MAX_AGE_PENALTY: u64 = 50;
+/// Points lost when the installed version is yanked
+const YANKED_PENALTY: u64 = 30;
+/// Points lost when there were no or just a single release in the window
+const NO_RECENT_RELEASES_PENALTY: u64 = 20;
+const SINGLE_RECENT_RELEASE_PENALTY: u64 = 10;
+
+/// Maintenance signals of a single package and the score combining them,
+/// from 100 for an actively maintained package down to 0
+#[derive(Debug, PartialEq)]
+pub struct PackageHealth {
+    pub name: DistributionName,
+    pub installed_version: String,
+    /// days since the newest release, None if upload times are unknown
+    pub last_release_days: Option<u64>,
+    /// releases uploaded within the window
+    pub recent_releases: usize,
+    /// the installed version is yanked on the index
+    pub installed_yanked: bool,
+    pub score: u64,
+}
+
+/// Order of the health report
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HealthOrder {
+    /// lowest score first
+    #[default]
+    Score,
+    Name,
+    /// the longest without a release first
+    Age,
+    /// the fewest recent releases first
+    Releases,
+}
+
+/// Versions are the same if they are equal strings or equal PEP 440 versions
+fn same_version(lhs: &str, rhs: &str) -> bool {
+    lhs == rhs
+        || matches!(
+            (lhs.parse::<Version>(), rhs.parse::<Version>()),
+            (Ok(lhs), Ok(rhs)) if lhs == rhs
+        )
+}
+
+/// Score a package by its releases, as of `now` (unix timestamp).
+/// Releases uploaded within `window_days` before `now` are recent
+pub fn assess(
+    name: &DistributionName,
+    installed_version: &str,
+    releases: &[Release],
+    now: u64,
+    window_days: u64,
+) -> PackageHealth {
+    let days_ago = |uploaded: u64| now.saturating_sub(uploaded) / SECS_PER_DAY;
+    let last_release_days = releases
+        .iter()
+        .filter_map(|r| r.uploaded)
+        .max()
+        .map(days_ago);
+    let recent_releases = releases
+        .iter()
+        .filter_map(|r| r.uploaded)
+        .filter(|uploaded| days_ago(*uploaded) <= window_days)
+        .count();
+    let installed_yanked = releases
+        .iter()
+        .any(|r| r.yanked && same_version(&r.version, installed_version));
+
+    let mut penalty = last_release_days.map_or(0, |days| {
+        (days * AGE_PENALTY_PER_YEAR / 365).min(MAX_AGE_PENALTY)
+    });
+    if installed_yanked {
+        penalty += YANKED_PENALTY;
+    }
+    penalty += match recent_releases {
+        0 => NO_RECENT_RELEASES_PENALTY,
+        1 => SINGLE_RECENT_RELEASE_PENALTY,
+        _ => 0,
+    };
+
+    PackageHealth {
+        name: name.clone(),
+        installed_version: installed_version.to_string(),
+        last_release_days,
+        recent_releases,
+        installed_yanked,
+        score: 100u64.saturating_sub(penalty),
+    }
+}
+
+/// Sort the report, ties are broken by name
+pub fn sort_health(report: &mut [PackageHealth], order: HealthOrder) {
+    match order {
+        HealthOrder::Score => report.sort_by(|a, b| (a.score, &a.name).cmp(&(b.score, &b.name))),
+        HealthOrder::Name => report.sort_by(|a, b| a.name.cmp(&b.name)),
+        // unknown ages go last
+        HealthOrder::Age => report.sort_by_key(|h| {
+            (
+                h.last_release_days.is_none(),
+                Reverse(h.last_release_days),
+                h.name.clone(),
+            )
+        }),
+        HealthOrder::Releases => {
+            report.sort_by(|a, b| (a.recent_releases, &a.name).cmp(&(b.recent_releases, &b.name)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn release(version: &str, days_ago: u64, yanked: bool) -> Release {
+        Release {
+            version: version.to_string(),
+            uploaded: Some(NOW - days_ago * SECS_PER_DAY),
+            yanked,
+        }
+    }
+
+    #[test]
+    fn active_and_abandoned_packages() {
+        let active = assess(
+            &"requests".to_string(),
+            "2.31.0",
+            &[
+                release("2.31.0", 200, false),
+                release("2.32.0", 100, false),
+                release("2.32.1", 10, false),
+            ],
+            NOW,
+            365,
+        );
+        assert_eq!(active.last_release_days, Some(10));
+        assert_eq!(active.recent_releases, 3);
+        assert_eq!(active.score, 100);
+
+        let abandoned = assess(
+            &"old".to_string(),
+            "1.0",
+            &[release("1.0.0", 3000, true), release("1.1", 2000, false)],
+            NOW,
+            365,
+        );
+        assert!(abandoned.installed_yanked);
+        assert_eq!(abandoned.recent_releases, 0);
+        assert_eq!(abandoned.score, 0);
+
+        let unknown = assess(&"private".to_string(), "1.0", &[], NOW, 365);
+        assert_eq!(unknown.last_release_days, None);
+        assert_eq!(unknown.score, 80);
+    }
+
+    #[test]
+    fn sort_orders() {
+        let mut report = vec![
+            assess(&"b".to_string(), "1", &[release("1", 10, false)], NOW, 365),
+            assess(&"a".to_string(), "1", &[], NOW, 365),
+            assess(&"c".to_string(), "1", &[release("1", 900, false)], NOW, 365),
+        ];
+        let names = |report: &[PackageHealth]| -> Vec<String> {
+            report.iter().map(|h| h.name.clone()).collect()
+        };
+
+        sort_health(&mut report, HealthOrder::Score);
+        assert_eq!(names(&report), vec!["c", "a", "b"]);
+        sort_health(&mut report, HealthOrder::Age);
+        assert_eq!(names(&report), vec!["c", "b", "a"]);
+        sort_health(&mut report, HealthOrder::Name);
+        assert_eq!(names(&report), vec!["a", "b", "c"]);
+    }
+}
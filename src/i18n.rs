@@ -0,0 +1,604 @@
+use crate::events;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+/// Languages of user facing diagnostics.
+/// Machine readable outputs (snapshots, fingerprints, info fields) are never translated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Ru,
+}
+
+impl Lang {
+    /// Parse language code or POSIX locale name such as `ru`, `ru_RU.UTF-8` or `en_US`
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let code = locale
+            .split(['_', '.', '@', '-'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match code.as_str() {
+            "en" | "c" | "posix" => Some(Lang::En),
+            "ru" => Some(Lang::Ru),
+            _ => None,
+        }
+    }
+
+    /// Detect language from the environment, following the gettext lookup order
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|val| !val.is_empty())
+            .and_then(|val| Lang::from_locale(&val))
+            .unwrap_or_default()
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Select language of diagnostics, only the first call has an effect
+pub fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+pub fn lang() -> Lang {
+    *LANG.get().unwrap_or(&Lang::En)
+}
+
+/// How diagnostics are printed to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LogFormat {
+    /// `WARNING: message` lines for humans
+    #[default]
+    Text,
+    /// one `{"level": "warning", "message": "..."}` object per line
+    Json,
+}
+
+static LOG_FORMAT: OnceLock<LogFormat> = OnceLock::new();
+
+/// Select format of diagnostics, only the first call has an effect
+pub fn set_log_format(format: LogFormat) {
+    let _ = LOG_FORMAT.set(format);
+}
+
+pub fn log_format() -> LogFormat {
+    LOG_FORMAT.get().copied().unwrap_or_default()
+}
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppress warnings and informational messages, errors are still printed
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Print diagnostics useful for reporting misread metadata
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Keys of the message catalog, `{}` in messages are filled in order by `trf`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Msg {
+    Error,
+    Warning,
+    PackageNotInstalled,
+    DidYouMean,
+    UsingClosestMatch,
+    DynamicDependencies,
+    InterpreterNotFound,
+    SitePackagesNotFound,
+    PathDoesNotExist,
+    InterpreterUnavailable,
+    MarkerValuesUnknown,
+    ParsingFailed,
+    RecordUnreadable,
+    HistoryStoreUnknown,
+    HistoryStoreUnreadable,
+    HistoryStoreUnwritable,
+    HistoryIndexOutOfRange,
+    SnapshotUnreadable,
+    SnapshotUnwritable,
+    StdinUnreadable,
+    OutputUnwritable,
+    LineNotRecognized,
+    NoChanges,
+    RequirementsChanged,
+    NoViolations,
+    CheckPluginFailed,
+    RequirementsUnreadable,
+    RequirementsUnwritable,
+    RemoteTreeFailed,
+    NoUpgradePlan,
+    ReleaseDateUnknown,
+    ReleasesUnknown,
+    RequiredByLevelsUp,
+    RequiresLevelsDown,
+    RequestedMarkersMissing,
+    ProjectVenvUsed,
+    PythonVersionUnknown,
+    IgnoreFileUnreadable,
+    ExtraAdditionalPackages,
+    NoExtrasDeclared,
+    DiffPackagePathsRequired,
+    InterpreterShimResolved,
+    MetadataLineMisread,
+    MetadataLineRejected,
+    MetadataFieldSource,
+    NoVenvsFound,
+    LatestVersionUnknown,
+    NoRequirers,
+    ConstraintsSatisfied,
+    ConstraintsViolated,
+    ConstraintsUnchecked,
+    RequirementExcludedByMarker,
+    MetadataUnreadable,
+    ClosureIncomplete,
+    UpdateCheckFailed,
+    UpToDate,
+    UpdateAvailable,
+    NoReleaseBinary,
+    UpdateFailed,
+    Updated,
+    TimeLimitExceeded,
+    BadgeUnwritable,
+    NetworkDisabled,
+    EventsUnwritable,
+    PackageDatabaseNotFound,
+    OsPackageQueryFailed,
+    NoPythonVersions,
+    PythonVersionNotInstalled,
+    WrapperVenvIgnored,
+    NoCachedWheels,
+    CachedWheelUnreadable,
+    PackageNotCached,
+    ReadRetried,
+    DatabaseUnwritable,
+    GitUnavailable,
+    ScanStopped,
+}
+
+fn catalog(lang: Lang, msg: Msg) -> &'static str {
+    match (lang, msg) {
+        (Lang::En, Msg::Error) => "ERROR",
+        (Lang::En, Msg::Warning) => "WARNING",
+        (Lang::En, Msg::PackageNotInstalled) => "package '{}' is not installed",
+        (Lang::En, Msg::DidYouMean) => ", did you mean: {}?",
+        (Lang::En, Msg::UsingClosestMatch) => "Package '{}' not found, using closest match '{}'",
+        (Lang::En, Msg::DynamicDependencies) => {
+            "{} declares Requires-Dist as Dynamic, its dependencies may be incomplete"
+        }
+        (Lang::En, Msg::InterpreterNotFound) => {
+            "Can not locate python interpreter location due to an error:\n{}"
+        }
+        (Lang::En, Msg::SitePackagesNotFound) => {
+            "Can not locate python site-packages location due to an error:\n{}"
+        }
+        (Lang::En, Msg::PathDoesNotExist) => "Path must point to an existing entity",
+        (Lang::En, Msg::InterpreterUnavailable) => {
+            "Python interpreter is not usable ({}), site-packages are taken from VIRTUAL_ENV"
+        }
+        (Lang::En, Msg::MarkerValuesUnknown) => {
+            "Python interpreter is not run, environment markers are evaluated for extras only"
+        }
+        (Lang::En, Msg::ParsingFailed) => "Problem parsing installed distributions: {}",
+        (Lang::En, Msg::RecordUnreadable) => "Can not read RECORD of {}: {}",
+        (Lang::En, Msg::HistoryStoreUnknown) => {
+            "Can not determine history store location, please pass --store"
+        }
+        (Lang::En, Msg::HistoryStoreUnreadable) => "Can not read history store {}: {}",
+        (Lang::En, Msg::HistoryStoreUnwritable) => "Can not write history store {}: {}",
+        (Lang::En, Msg::HistoryIndexOutOfRange) => {
+            "History has {} records, can not compare {} and {}"
+        }
+        (Lang::En, Msg::SnapshotUnreadable) => "Can not read snapshot {}: {}",
+        (Lang::En, Msg::SnapshotUnwritable) => "Can not save snapshot {}: {}",
+        (Lang::En, Msg::StdinUnreadable) => "Can not read line from stdin: {}",
+        (Lang::En, Msg::OutputUnwritable) => "Can not write output: {}",
+        (Lang::En, Msg::LineNotRecognized) => "Line is not recognized by the grammar",
+        (Lang::En, Msg::NoChanges) => "No changes",
+        (Lang::En, Msg::RequirementsChanged) => "requirements changed",
+        (Lang::En, Msg::NoViolations) => "No policy violations found",
+        (Lang::En, Msg::CheckPluginFailed) => "Check plugin {} failed: {}",
+        (Lang::En, Msg::RequirementsUnreadable) => "Can not read requirements file {}: {}",
+        (Lang::En, Msg::RequirementsUnwritable) => "Can not write requirements file {}: {}",
+        (Lang::En, Msg::RemoteTreeFailed) => "Can not resolve dependency tree on the index: {}",
+        (Lang::En, Msg::NoUpgradePlan) => "No conflict-free upgrade of {} found: {}",
+        (Lang::En, Msg::ReleaseDateUnknown) => "Can not get release date of {}: {}",
+        (Lang::En, Msg::ReleasesUnknown) => "Can not get releases of {}: {}",
+        (Lang::En, Msg::RequiredByLevelsUp) => "# required by ({} levels up)",
+        (Lang::En, Msg::RequiresLevelsDown) => "# requires ({} levels down)",
+        (Lang::En, Msg::IgnoreFileUnreadable) => "Can not read ignore file {}: {}",
+        (Lang::En, Msg::ExtraAdditionalPackages) => "# {}[{}] pulls in {} additional packages",
+        (Lang::En, Msg::NoExtrasDeclared) => "{} declares no extras",
+        (Lang::En, Msg::InterpreterShimResolved) => {
+            "Python on PATH {} is a wrapper, using interpreter {}"
+        }
+        (Lang::En, Msg::MetadataLineMisread) => "{}: line is not understood: {}",
+        (Lang::En, Msg::MetadataLineRejected) => "{}: line is not understood: {}: {}",
+        (Lang::En, Msg::MetadataFieldSource) => "{} of {} is read from {}",
+        (Lang::En, Msg::NoVenvsFound) => "No virtual envs found under {}",
+        (Lang::En, Msg::LatestVersionUnknown) => "Can not get the latest release of {}: {}",
+        (Lang::En, Msg::RequirementExcludedByMarker) => {
+            "{} required by {} is left out, marker is false in this environment: {}"
+        }
+        (Lang::En, Msg::MetadataUnreadable) => "Can not read metadata of {}: {}",
+        (Lang::En, Msg::ClosureIncomplete) => {
+            "{} is required in the closure of {} but not installed"
+        }
+        (Lang::En, Msg::UpdateCheckFailed) => "Can not check for updates: {}",
+        (Lang::En, Msg::UpToDate) => "rdeptree {} is up to date",
+        (Lang::En, Msg::UpdateAvailable) => {
+            "rdeptree {} is available, installed {}; run `rdeptree self update`"
+        }
+        (Lang::En, Msg::NoReleaseBinary) => "Release {} has no {} binary with its .sha256",
+        (Lang::En, Msg::UpdateFailed) => "Can not update rdeptree: {}",
+        (Lang::En, Msg::Updated) => "rdeptree is updated to {}",
+        (Lang::En, Msg::TimeLimitExceeded) => {
+            "Time limit of {}s exceeded, the results are incomplete"
+        }
+        (Lang::En, Msg::BadgeUnwritable) => "Can not write badge {}: {}",
+        (Lang::En, Msg::NetworkDisabled) => "{} needs the network, which is disabled by --offline",
+        (Lang::En, Msg::EventsUnwritable) => "Can not open events file {}: {}",
+        (Lang::En, Msg::PackageDatabaseNotFound) => {
+            "Neither dpkg nor rpm database found, OS packages are not shown"
+        }
+        (Lang::En, Msg::OsPackageQueryFailed) => "Can not query OS package owners: {}",
+        (Lang::En, Msg::NoPythonVersions) => "No python site-packages found under {}",
+        (Lang::En, Msg::PythonVersionNotInstalled) => {
+            "Python {} is not installed under {}, see `rdeptree python-versions`"
+        }
+        (Lang::En, Msg::WrapperVenvIgnored) => "Ignoring env {} of the wrapper running rdeptree",
+        (Lang::En, Msg::NoCachedWheels) => "No cached wheels found under {}",
+        (Lang::En, Msg::CachedWheelUnreadable) => "Skipping cached wheel {}: {}",
+        (Lang::En, Msg::PackageNotCached) => "{} is not in the cache",
+        (Lang::En, Msg::ReadRetried) => "{}: read failed ({}), retrying in {} ms",
+        (Lang::En, Msg::DatabaseUnwritable) => "Can not write database {}: {}",
+        (Lang::En, Msg::GitUnavailable) => {
+            "Source drift of packages installed from existing dirs is not checked: {}"
+        }
+        (Lang::En, Msg::ScanStopped) => {
+            "Scan stopped, {} after parsing {} of {} metadata dirs, next one is {}"
+        }
+        (Lang::En, Msg::NoRequirers) => "{} is not required by any installed package",
+        (Lang::En, Msg::ConstraintsSatisfied) => "installed {} is inside the intersection",
+        (Lang::En, Msg::ConstraintsViolated) => "installed {} is outside the intersection",
+        (Lang::En, Msg::ConstraintsUnchecked) => {
+            "installed {} can not be checked against the intersection"
+        }
+        (Lang::En, Msg::DiffPackagePathsRequired) => {
+            "diff-package compares exactly two environments, {} --path given"
+        }
+        (Lang::En, Msg::PythonVersionUnknown) => {
+            "Requires-Python is not checked, target python version is unknown: {}"
+        }
+        (Lang::En, Msg::ProjectVenvUsed) => "No active environment, using project virtual env {}",
+        (Lang::En, Msg::RequestedMarkersMissing) => {
+            "No package has a REQUESTED marker, the installer does not record explicitly installed packages"
+        }
+
+        (Lang::Ru, Msg::Error) => "ОШИБКА",
+        (Lang::Ru, Msg::Warning) => "ПРЕДУПРЕЖДЕНИЕ",
+        (Lang::Ru, Msg::PackageNotInstalled) => "пакет '{}' не установлен",
+        (Lang::Ru, Msg::DidYouMean) => ", возможно, имелся в виду: {}?",
+        (Lang::Ru, Msg::UsingClosestMatch) => {
+            "Пакет '{}' не найден, используется ближайшее совпадение '{}'"
+        }
+        (Lang::Ru, Msg::DynamicDependencies) => {
+            "{} объявляет Requires-Dist как Dynamic, список зависимостей может быть неполным"
+        }
+        (Lang::Ru, Msg::InterpreterNotFound) => {
+            "Не удалось найти интерпретатор python из-за ошибки:\n{}"
+        }
+        (Lang::Ru, Msg::SitePackagesNotFound) => {
+            "Не удалось найти каталог site-packages из-за ошибки:\n{}"
+        }
+        (Lang::Ru, Msg::PathDoesNotExist) => "Путь должен указывать на существующий объект",
+        (Lang::Ru, Msg::InterpreterUnavailable) => {
+            "Интерпретатор python недоступен ({}), site-packages взяты из VIRTUAL_ENV"
+        }
+        (Lang::Ru, Msg::MarkerValuesUnknown) => {
+            "Интерпретатор python не запускается, маркеры окружения вычисляются только для extras"
+        }
+        (Lang::Ru, Msg::ParsingFailed) => "Ошибка разбора установленных пакетов: {}",
+        (Lang::Ru, Msg::RecordUnreadable) => "Не удалось прочитать RECORD пакета {}: {}",
+        (Lang::Ru, Msg::HistoryStoreUnknown) => {
+            "Не удалось определить расположение истории, укажите --store"
+        }
+        (Lang::Ru, Msg::HistoryStoreUnreadable) => "Не удалось прочитать историю {}: {}",
+        (Lang::Ru, Msg::HistoryStoreUnwritable) => "Не удалось записать историю {}: {}",
+        (Lang::Ru, Msg::HistoryIndexOutOfRange) => {
+            "В истории {} записей, невозможно сравнить {} и {}"
+        }
+        (Lang::Ru, Msg::SnapshotUnreadable) => "Не удалось прочитать снимок {}: {}",
+        (Lang::Ru, Msg::SnapshotUnwritable) => "Не удалось сохранить снимок {}: {}",
+        (Lang::Ru, Msg::StdinUnreadable) => "Не удалось прочитать строку из stdin: {}",
+        (Lang::Ru, Msg::OutputUnwritable) => "Не удалось записать вывод: {}",
+        (Lang::Ru, Msg::LineNotRecognized) => "Строка не распознана грамматикой",
+        (Lang::Ru, Msg::NoChanges) => "Изменений нет",
+        (Lang::Ru, Msg::RequirementsChanged) => "изменились зависимости",
+        (Lang::Ru, Msg::NoViolations) => "Нарушений политик не найдено",
+        (Lang::Ru, Msg::CheckPluginFailed) => "Плагин проверки {} завершился с ошибкой: {}",
+        (Lang::Ru, Msg::RequirementsUnreadable) => "Не удалось прочитать файл зависимостей {}: {}",
+        (Lang::Ru, Msg::RequirementsUnwritable) => "Не удалось записать файл зависимостей {}: {}",
+        (Lang::Ru, Msg::RemoteTreeFailed) => {
+            "Не удалось построить дерево зависимостей по индексу: {}"
+        }
+        (Lang::Ru, Msg::NoUpgradePlan) => "Не найдено обновление {} без конфликтов: {}",
+        (Lang::Ru, Msg::ReleaseDateUnknown) => "Не удалось получить дату выпуска {}: {}",
+        (Lang::Ru, Msg::ReleasesUnknown) => "Не удалось получить список выпусков {}: {}",
+        (Lang::Ru, Msg::RequiredByLevelsUp) => "# требуется пакетами ({} уровней вверх)",
+        (Lang::Ru, Msg::RequiresLevelsDown) => "# зависимости ({} уровней вниз)",
+        (Lang::Ru, Msg::IgnoreFileUnreadable) => "Не удалось прочитать файл исключений {}: {}",
+        (Lang::Ru, Msg::ExtraAdditionalPackages) => "# {}[{}] добавляет пакетов: {}",
+        (Lang::Ru, Msg::NoExtrasDeclared) => "{} не объявляет дополнительных зависимостей (extras)",
+        (Lang::Ru, Msg::InterpreterShimResolved) => {
+            "Python в PATH {} является обёрткой, используется интерпретатор {}"
+        }
+        (Lang::Ru, Msg::MetadataLineMisread) => "{}: строка не распознана: {}",
+        (Lang::Ru, Msg::MetadataLineRejected) => "{}: строка не распознана: {}: {}",
+        (Lang::Ru, Msg::MetadataFieldSource) => "{} пакета {} прочитано из {}",
+        (Lang::Ru, Msg::NoVenvsFound) => "В {} не найдено виртуальных окружений",
+        (Lang::Ru, Msg::LatestVersionUnknown) => "Не удалось получить последний выпуск {}: {}",
+        (Lang::Ru, Msg::RequirementExcludedByMarker) => {
+            "{}, требуемый {}, пропущен, маркер ложен в этом окружении: {}"
+        }
+        (Lang::Ru, Msg::MetadataUnreadable) => "Не удалось прочитать метаданные {}: {}",
+        (Lang::Ru, Msg::ClosureIncomplete) => "{} требуется в замыкании {}, но не установлен",
+        (Lang::Ru, Msg::UpdateCheckFailed) => "Не удалось проверить обновления: {}",
+        (Lang::Ru, Msg::UpToDate) => "rdeptree {} не требует обновления",
+        (Lang::Ru, Msg::UpdateAvailable) => {
+            "Доступен rdeptree {}, установлен {}; выполните `rdeptree self update`"
+        }
+        (Lang::Ru, Msg::NoReleaseBinary) => "В выпуске {} нет сборки {} с её .sha256",
+        (Lang::Ru, Msg::UpdateFailed) => "Не удалось обновить rdeptree: {}",
+        (Lang::Ru, Msg::Updated) => "rdeptree обновлён до {}",
+        (Lang::Ru, Msg::TimeLimitExceeded) => {
+            "Превышен лимит времени {} с, результаты неполные"
+        }
+        (Lang::Ru, Msg::BadgeUnwritable) => "Не удалось записать значок {}: {}",
+        (Lang::Ru, Msg::NetworkDisabled) => "{} требует доступа к сети, отключённого флагом --offline",
+        (Lang::Ru, Msg::EventsUnwritable) => "Не удалось открыть файл событий {}: {}",
+        (Lang::Ru, Msg::PackageDatabaseNotFound) => {
+            "Не найдена база пакетов dpkg или rpm, пакеты ОС не показаны"
+        }
+        (Lang::Ru, Msg::OsPackageQueryFailed) => "Не удалось запросить владельцев в пакетах ОС: {}",
+        (Lang::Ru, Msg::NoPythonVersions) => "В {} не найдено ни одного site-packages python",
+        (Lang::Ru, Msg::PythonVersionNotInstalled) => {
+            "Python {} не установлен в {}, см. `rdeptree python-versions`"
+        }
+        (Lang::Ru, Msg::WrapperVenvIgnored) => {
+            "Окружение {} обёртки, запустившей rdeptree, пропущено"
+        }
+        (Lang::Ru, Msg::NoCachedWheels) => "В {} не найдено закэшированных wheel",
+        (Lang::Ru, Msg::CachedWheelUnreadable) => "Пропущен закэшированный wheel {}: {}",
+        (Lang::Ru, Msg::PackageNotCached) => "{} нет в кэше",
+        (Lang::Ru, Msg::ReadRetried) => "{}: ошибка чтения ({}), повтор через {} мс",
+        (Lang::Ru, Msg::DatabaseUnwritable) => "Не удалось записать базу данных {}: {}",
+        (Lang::Ru, Msg::GitUnavailable) => {
+            "Расхождение с исходниками пакетов из существующих папок не проверено: {}"
+        }
+        (Lang::Ru, Msg::ScanStopped) => {
+            "Сканирование остановлено, {} после разбора {} из {} папок метаданных, следующая {}"
+        }
+        (Lang::Ru, Msg::NoRequirers) => "{} не требуется ни одним установленным пакетом",
+        (Lang::Ru, Msg::ConstraintsSatisfied) => "установленная {} входит в пересечение",
+        (Lang::Ru, Msg::ConstraintsViolated) => "установленная {} не входит в пересечение",
+        (Lang::Ru, Msg::ConstraintsUnchecked) => {
+            "установленную {} не удалось сверить с пересечением"
+        }
+        (Lang::Ru, Msg::DiffPackagePathsRequired) => {
+            "diff-package сравнивает ровно два окружения, указано --path: {}"
+        }
+        (Lang::Ru, Msg::PythonVersionUnknown) => {
+            "Requires-Python не проверяется, целевая версия python неизвестна: {}"
+        }
+        (Lang::Ru, Msg::ProjectVenvUsed) => {
+            "Нет активного окружения, используется виртуальное окружение проекта {}"
+        }
+        (Lang::Ru, Msg::RequestedMarkersMissing) => {
+            "Ни у одного пакета нет файла REQUESTED, установщик не отмечает явно установленные пакеты"
+        }
+    }
+}
+
+/// Print a diagnostic to stderr in the selected log format,
+/// `label` prefixes text messages
+fn emit(level: &str, label: Option<Msg>, message: &str) {
+    match log_format() {
+        LogFormat::Text => match label {
+            Some(label) => eprintln!("{}: {}", tr(label), message),
+            None => eprintln!("{}", message),
+        },
+        LogFormat::Json => eprintln!(
+            "{}",
+            serde_json::json!({"level": level, "message": message})
+        ),
+    }
+}
+
+/// Print translated error to stderr
+pub fn error(message: &str) {
+    events::emit("error", serde_json::json!({ "message": message }));
+    emit("error", Some(Msg::Error), message);
+}
+
+/// Print translated warning to stderr, unless quiet
+pub fn warn(message: &str) {
+    events::emit("warning", serde_json::json!({ "message": message }));
+    if !quiet() {
+        emit("warning", Some(Msg::Warning), message);
+    }
+}
+
+/// Print informational message to stderr, unless quiet
+pub fn info(message: &str) {
+    if !quiet() {
+        emit("info", None, message);
+    }
+}
+
+/// Print diagnostic message to stderr, only in verbose mode and unless quiet
+pub fn debug(message: &str) {
+    if verbose() && !quiet() {
+        emit("debug", None, message);
+    }
+}
+
+/// Translate message without parameters into the selected language
+pub fn tr(msg: Msg) -> &'static str {
+    catalog(lang(), msg)
+}
+
+/// Translate message and fill its `{}` placeholders with given args in order
+pub fn trf(msg: Msg, args: &[&dyn Display]) -> String {
+    fill(tr(msg), args)
+}
+
+fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut args_iter = args.iter();
+    let mut parts = template.split("{}");
+
+    if let Some(first) = parts.next() {
+        result.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args_iter.next() {
+            result.push_str(&arg.to_string());
+        }
+        result.push_str(part);
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn locale_parsing() {
+        assert_eq!(Lang::from_locale("ru_RU.UTF-8"), Some(Lang::Ru));
+        assert_eq!(Lang::from_locale("en_US"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("C"), Some(Lang::En));
+        assert_eq!(Lang::from_locale("de_DE"), None);
+    }
+
+    #[test]
+    fn fill_placeholders() {
+        assert_eq!(
+            fill("Can not read RECORD of {}: {}", &[&"six", &"io error"]),
+            "Can not read RECORD of six: io error"
+        );
+        assert_eq!(
+            fill("# requires ({} levels down)", &[&2]),
+            "# requires (2 levels down)"
+        );
+        assert_eq!(fill("No changes", &[]), "No changes");
+    }
+
+    #[test]
+    fn catalogs_have_same_placeholders() {
+        let all = [
+            Msg::Error,
+            Msg::Warning,
+            Msg::PackageNotInstalled,
+            Msg::DidYouMean,
+            Msg::UsingClosestMatch,
+            Msg::DynamicDependencies,
+            Msg::InterpreterNotFound,
+            Msg::SitePackagesNotFound,
+            Msg::PathDoesNotExist,
+            Msg::InterpreterUnavailable,
+            Msg::MarkerValuesUnknown,
+            Msg::ParsingFailed,
+            Msg::RecordUnreadable,
+            Msg::HistoryStoreUnknown,
+            Msg::HistoryStoreUnreadable,
+            Msg::HistoryStoreUnwritable,
+            Msg::HistoryIndexOutOfRange,
+            Msg::SnapshotUnreadable,
+            Msg::SnapshotUnwritable,
+            Msg::StdinUnreadable,
+            Msg::OutputUnwritable,
+            Msg::LineNotRecognized,
+            Msg::NoChanges,
+            Msg::RequirementsChanged,
+            Msg::NoViolations,
+            Msg::CheckPluginFailed,
+            Msg::RequirementsUnreadable,
+            Msg::RequirementsUnwritable,
+            Msg::RemoteTreeFailed,
+            Msg::NoUpgradePlan,
+            Msg::ReleaseDateUnknown,
+            Msg::ReleasesUnknown,
+            Msg::RequiredByLevelsUp,
+            Msg::RequiresLevelsDown,
+            Msg::RequestedMarkersMissing,
+            Msg::ProjectVenvUsed,
+            Msg::PythonVersionUnknown,
+            Msg::IgnoreFileUnreadable,
+            Msg::ExtraAdditionalPackages,
+            Msg::NoExtrasDeclared,
+            Msg::DiffPackagePathsRequired,
+            Msg::InterpreterShimResolved,
+            Msg::MetadataLineMisread,
+            Msg::MetadataLineRejected,
+            Msg::MetadataFieldSource,
+            Msg::NoVenvsFound,
+            Msg::LatestVersionUnknown,
+            Msg::NoRequirers,
+            Msg::ConstraintsSatisfied,
+            Msg::ConstraintsViolated,
+            Msg::ConstraintsUnchecked,
+            Msg::RequirementExcludedByMarker,
+            Msg::MetadataUnreadable,
+            Msg::ClosureIncomplete,
+            Msg::UpdateCheckFailed,
+            Msg::UpToDate,
+            Msg::UpdateAvailable,
+            Msg::NoReleaseBinary,
+            Msg::UpdateFailed,
+            Msg::Updated,
+            Msg::TimeLimitExceeded,
+            Msg::BadgeUnwritable,
+            Msg::NetworkDisabled,
+            Msg::EventsUnwritable,
+            Msg::PackageDatabaseNotFound,
+            Msg::OsPackageQueryFailed,
+            Msg::NoPythonVersions,
+            Msg::PythonVersionNotInstalled,
+            Msg::WrapperVenvIgnored,
+            Msg::NoCachedWheels,
+            Msg::CachedWheelUnreadable,
+            Msg::PackageNotCached,
+            Msg::ReadRetried,
+            Msg::DatabaseUnwritable,
+            Msg::GitUnavailable,
+            Msg::ScanStopped,
+        ];
+        for msg in all {
+            assert_eq!(
+                catalog(Lang::En, msg).matches("{}").count(),
+                catalog(Lang::Ru, msg).matches("{}").count(),
+                "placeholders differ for {:?}",
+                msg
+            );
+        }
+    }
+}
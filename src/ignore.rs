@@ -0,0 +1,114 @@
+//! Packages hidden from rendering and checks by glob patterns, read from
+//! a project-local `.rdeptreeignore` file and given with `--exclude`
+
+use crate::dag::{DependencyDag, DistributionName};
+use crate::normalize::pep503;
+use crate::utils::{project_dirs, read_text};
+use regex::Regex;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const IGNORE_FILE_NAME: &str = ".rdeptreeignore";
+
+/// Glob pattern matched against normalized package names,
+/// `*` matches any run of characters and `?` a single one
+#[derive(Debug)]
+struct Pattern {
+    regex: Regex,
+    /// `!pattern` brings back packages ignored by earlier patterns
+    negated: bool,
+}
+
+impl Pattern {
+    fn new(pattern: &str) -> Self {
+        let (negated, glob) = match pattern.strip_prefix('!') {
+            Some(glob) => (true, glob),
+            None => (false, pattern),
+        };
+        let regex = regex::escape(&pep503(glob.trim()))
+            .replace(r"\*", ".*")
+            .replace(r"\?", ".");
+        Self {
+            regex: Regex::new(&format!("^{}$", regex)).expect("escaped glob is a valid regex"),
+            negated,
+        }
+    }
+}
+
+/// Ordered ignore patterns, the last pattern matching a package decides,
+/// the same way as in .gitignore
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<Pattern>,
+}
+
+impl IgnoreRules {
+    /// One pattern per line, blank lines and `#` comments are skipped
+    pub fn parse(content: &str) -> Self {
+        let mut rules = Self::default();
+        for line in content.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') {
+                rules.add(line);
+            }
+        }
+        rules
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self::parse(&read_text(path)?))
+    }
+
+    /// Nearest ignore file in the start dir or its ancestors up to the git root
+    pub fn find(start: &Path) -> Option<PathBuf> {
+        project_dirs(start)
+            .into_iter()
+            .map(|dir| dir.join(IGNORE_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Append a pattern, it takes precedence over all patterns added before
+    pub fn add(&mut self, pattern: &str) {
+        self.patterns.push(Pattern::new(pattern));
+    }
+
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| p.regex.is_match(name))
+            .is_some_and(|p| !p.negated)
+    }
+
+    /// Names of ignored packages of the dag
+    pub fn ignored(&self, dag: &DependencyDag) -> HashSet<DistributionName> {
+        dag.keys()
+            .filter(|name| self.is_ignored(name))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn last_matching_pattern_wins() {
+        let mut rules = IgnoreRules::parse(
+            "# vendored shims\n\
+             internal_*\n\
+             \n\
+             !internal-api\n\
+             legacy-?\n",
+        );
+        assert!(rules.is_ignored("internal-shim"));
+        assert!(!rules.is_ignored("internal-api"));
+        assert!(rules.is_ignored("legacy-1"));
+        assert!(!rules.is_ignored("legacy-10"));
+        assert!(!rules.is_ignored("six"));
+
+        rules.add("Internal.API");
+        assert!(rules.is_ignored("internal-api"));
+    }
+}
@@ -0,0 +1,410 @@
+//! Dependency trees of packages which are not installed, resolved
+//! against the metadata published on a package index
+
+use crate::marker::MarkerEnv;
+use crate::network;
+use crate::pep::{Requirement, Version};
+use crate::utils::parse_utc_timestamp;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+/// PyPI JSON API, see https://docs.pypi.org/api/json/
+pub const DEFAULT_INDEX_URL: &str = "https://pypi.org/pypi";
+
+/// Source of published versions and their requirements
+pub trait PackageIndex {
+    /// All published versions of a distribution, in any order
+    fn versions(&self, name: &str) -> Result<Vec<String>, String>;
+
+    /// Requires-Dist values of a published version
+    fn requires_dist(&self, name: &str, version: &str) -> Result<Vec<String>, String>;
+}
+
+#[derive(Deserialize)]
+struct ReleaseFile {
+    #[serde(default)]
+    yanked: bool,
+    upload_time_iso_8601: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProjectPage {
+    releases: BTreeMap<String, Vec<ReleaseFile>>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseInfo {
+    requires_dist: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseUrl {
+    upload_time_iso_8601: String,
+}
+
+#[derive(Deserialize)]
+struct ReleasePage {
+    info: ReleaseInfo,
+    #[serde(default)]
+    urls: Vec<ReleaseUrl>,
+}
+
+/// Published version of a distribution, as listed on its project page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Release {
+    pub version: String,
+    /// upload time of the earliest file, unix timestamp
+    pub uploaded: Option<u64>,
+    /// all files of the release are yanked
+    pub yanked: bool,
+}
+
+/// Longest time a single index query may take
+const QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Index serving the PyPI JSON API, queried with `curl`
+/// the same way the interpreter is queried with `python`
+pub struct JsonApiIndex {
+    url: String,
+}
+
+impl JsonApiIndex {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, String> {
+        let url = format!("{}/{}/json", self.url, path);
+        let body = network::curl(&url, &[], QUERY_TIMEOUT)?;
+        serde_json::from_slice(&body)
+            .map_err(|err| format!("Unable to parse response of {}: {}", url, err))
+    }
+}
+
+impl JsonApiIndex {
+    /// All releases having files, yanked ones included
+    pub fn releases(&self, name: &str) -> Result<Vec<Release>, String> {
+        let page: ProjectPage = self.get(name)?;
+        Ok(page
+            .releases
+            .into_iter()
+            .filter(|(_, files)| !files.is_empty())
+            .map(|(version, files)| Release {
+                uploaded: files
+                    .iter()
+                    .filter_map(|f| f.upload_time_iso_8601.as_deref())
+                    .filter_map(parse_utc_timestamp)
+                    .min(),
+                yanked: files.iter().all(|f| f.yanked),
+                version,
+            })
+            .collect())
+    }
+
+    /// Upload time of the earliest file of a published version as unix timestamp,
+    /// None if the version has no files
+    pub fn upload_time(&self, name: &str, version: &str) -> Result<Option<u64>, String> {
+        let page: ReleasePage = self.get(&format!("{}/{}", name, version))?;
+        Ok(page
+            .urls
+            .iter()
+            .filter_map(|u| parse_utc_timestamp(&u.upload_time_iso_8601))
+            .min())
+    }
+}
+
+impl PackageIndex for JsonApiIndex {
+    fn versions(&self, name: &str) -> Result<Vec<String>, String> {
+        Ok(self
+            .releases(name)?
+            .into_iter()
+            // releases without files can't be installed, yanked ones shouldn't be
+            .filter(|r| !r.yanked)
+            .map(|r| r.version)
+            .collect())
+    }
+
+    fn requires_dist(&self, name: &str, version: &str) -> Result<Vec<String>, String> {
+        let page: ReleasePage = self.get(&format!("{}/{}", name, version))?;
+        Ok(page.info.requires_dist.unwrap_or_default())
+    }
+}
+
+/// Newest published final release, as spelled on the index. Pre-releases
+/// count only when nothing else is published, None if no version is valid
+pub fn latest_version(index: &dyn PackageIndex, name: &str) -> Result<Option<String>, String> {
+    let versions: Vec<(Version, String)> = index
+        .versions(name)?
+        .into_iter()
+        .filter_map(|v| Some((v.parse().ok()?, v)))
+        .collect();
+    let newest = |final_only: bool| {
+        versions
+            .iter()
+            .filter(|(v, _)| !final_only || !v.is_prerelease())
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, spelling)| spelling.clone())
+    };
+    Ok(newest(true).or_else(|| newest(false)))
+}
+
+/// Node of a resolved tree. Version is None when nothing
+/// published satisfies the requirement
+#[derive(Debug, PartialEq)]
+pub struct RemoteNode {
+    pub name: String,
+    pub required_version: String,
+    pub version: Option<String>,
+    /// the distribution already appears above in the same branch
+    pub cycle: bool,
+    pub dependencies: Vec<RemoteNode>,
+}
+
+/// Resolves requirements to the newest matching versions,
+/// every index query is made only once
+pub struct RemoteResolver<'a> {
+    index: &'a dyn PackageIndex,
+    marker_env: MarkerEnv,
+    max_depth: Option<usize>,
+    /// parsed versions together with their spelling on the index
+    versions: RefCell<HashMap<String, Vec<(Version, String)>>>,
+    requirements: RefCell<HashMap<(String, String), Vec<Requirement>>>,
+}
+
+impl<'a> RemoteResolver<'a> {
+    /// Markers are evaluated against the given target env,
+    /// markers with unknown variables are considered true
+    pub fn new(
+        index: &'a dyn PackageIndex,
+        marker_env: MarkerEnv,
+        max_depth: Option<usize>,
+    ) -> Self {
+        Self {
+            index,
+            marker_env,
+            max_depth,
+            versions: RefCell::default(),
+            requirements: RefCell::default(),
+        }
+    }
+
+    pub fn resolve(&self, requirement: &Requirement) -> Result<RemoteNode, String> {
+        self.resolve_node(requirement, &mut Vec::new())
+    }
+
+    /// Published versions in ascending order, together with their spelling on the index
+    pub(crate) fn published(&self, name: &str) -> Result<Vec<(Version, String)>, String> {
+        if let Some(versions) = self.versions.borrow().get(name) {
+            return Ok(versions.clone());
+        }
+        let mut versions: Vec<(Version, String)> = self
+            .index
+            .versions(name)?
+            .into_iter()
+            .filter_map(|v| Some((v.parse().ok()?, v)))
+            .collect();
+        versions.sort();
+        self.versions
+            .borrow_mut()
+            .insert(name.to_string(), versions.clone());
+        Ok(versions)
+    }
+
+    /// Newest published version satisfying the requirement, as spelled on the index
+    fn best_version(&self, requirement: &Requirement) -> Result<Option<String>, String> {
+        Ok(self
+            .published(&requirement.normalized_name())?
+            .into_iter()
+            .rev()
+            .find(|(v, _)| requirement.specifier.contains(v))
+            .map(|(_, spelling)| spelling))
+    }
+
+    /// Requirements of a published version which hold in the target env
+    pub(crate) fn dependencies(
+        &self,
+        name: &str,
+        version: &str,
+        extras: &[String],
+    ) -> Result<Vec<Requirement>, String> {
+        let key = (name.to_string(), version.to_string());
+        if !self.requirements.borrow().contains_key(&key) {
+            // invalid requirements are dropped the same way as for installed packages
+            let requirements = self
+                .index
+                .requires_dist(name, version)?
+                .iter()
+                .filter_map(|r| r.parse().ok())
+                .collect();
+            self.requirements
+                .borrow_mut()
+                .insert(key.clone(), requirements);
+        }
+
+        let marker_env = self.marker_env.clone().with_extras(extras);
+        Ok(self.requirements.borrow()[&key]
+            .iter()
+            .filter(|r| match &r.marker {
+                Some(marker) => marker_env.evaluate(&marker.to_string()) != Ok(false),
+                None => true,
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn resolve_node(
+        &self,
+        requirement: &Requirement,
+        branch: &mut Vec<String>,
+    ) -> Result<RemoteNode, String> {
+        let name = requirement.normalized_name();
+        let mut node = RemoteNode {
+            name: name.clone(),
+            required_version: requirement.specifier.to_string(),
+            version: self.best_version(requirement)?,
+            cycle: branch.contains(&name),
+            dependencies: Vec::new(),
+        };
+        let depth_exhausted = self.max_depth.is_some_and(|max| branch.len() >= max);
+        let Some(version) = node
+            .version
+            .clone()
+            .filter(|_| !node.cycle && !depth_exhausted)
+        else {
+            return Ok(node);
+        };
+
+        let mut dependencies = self.dependencies(&name, &version, &requirement.extras)?;
+        dependencies.sort_by_key(|r| r.normalized_name());
+        branch.push(name);
+        for dependency in &dependencies {
+            node.dependencies
+                .push(self.resolve_node(dependency, branch)?);
+        }
+        branch.pop();
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::normalize::pep503;
+
+    /// Index of (name, version, requires_dist) entries
+    pub struct StaticIndex(pub Vec<(&'static str, &'static str, Vec<&'static str>)>);
+
+    impl PackageIndex for StaticIndex {
+        fn versions(&self, name: &str) -> Result<Vec<String>, String> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|(n, _, _)| pep503(n) == name)
+                .map(|(_, v, _)| v.to_string())
+                .collect())
+        }
+
+        fn requires_dist(&self, name: &str, version: &str) -> Result<Vec<String>, String> {
+            self.0
+                .iter()
+                .find(|(n, v, _)| pep503(n) == name && *v == version)
+                .map(|(_, _, requires)| requires.iter().map(|r| r.to_string()).collect())
+                .ok_or(format!("{}=={} is not published", name, version))
+        }
+    }
+
+    fn index() -> StaticIndex {
+        StaticIndex(vec![
+            (
+                "requests",
+                "2.31.0",
+                vec![
+                    "idna<4,>=2.5",
+                    "PySocks!=1.5.7,>=1.5.6; extra == \"socks\"",
+                    "win-inet-pton; sys_platform == \"win32\" and extra == \"socks\"",
+                ],
+            ),
+            ("requests", "2.32.0", vec!["idna<4,>=2.5", "loop>=1"]),
+            ("idna", "3.6", vec![]),
+            ("idna", "3.7rc1", vec![]),
+            ("idna", "4.0", vec![]),
+            ("PySocks", "1.7.1", vec![]),
+            ("loop", "1.0", vec!["requests>=2"]),
+        ])
+    }
+
+    fn names(node: &RemoteNode) -> Vec<(&str, Option<&str>)> {
+        node.dependencies
+            .iter()
+            .map(|d| (d.name.as_str(), d.version.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn latest_final_release() {
+        let index = index();
+        assert_eq!(
+            latest_version(&index, "idna"),
+            Ok(Some(String::from("4.0")))
+        );
+        let pre_only = StaticIndex(vec![("idna", "3.7rc1", vec![])]);
+        assert_eq!(
+            latest_version(&pre_only, "idna"),
+            Ok(Some(String::from("3.7rc1")))
+        );
+        assert_eq!(latest_version(&index, "unknown"), Ok(None));
+    }
+
+    #[test]
+    fn resolve_pinned_version() {
+        let index = index();
+        let env = MarkerEnv::new().with_values(HashMap::from([(
+            "sys_platform".to_string(),
+            "linux".to_string(),
+        )]));
+        let resolver = RemoteResolver::new(&index, env, None);
+
+        let tree = resolver
+            .resolve(&"requests[socks]==2.31.0".parse().unwrap())
+            .unwrap();
+        assert_eq!(tree.version.as_deref(), Some("2.31.0"));
+        assert_eq!(
+            names(&tree),
+            vec![("idna", Some("3.6")), ("pysocks", Some("1.7.1"))]
+        );
+
+        let tree = resolver
+            .resolve(&"requests==2.31.0".parse().unwrap())
+            .unwrap();
+        assert_eq!(names(&tree), vec![("idna", Some("3.6"))]);
+
+        let tree = resolver.resolve(&"requests>=3".parse().unwrap()).unwrap();
+        assert_eq!(tree.version, None);
+    }
+
+    #[test]
+    fn resolve_cycles_and_depth() {
+        let index = index();
+        let tree = RemoteResolver::new(&index, MarkerEnv::new(), None)
+            .resolve(&"requests".parse().unwrap())
+            .unwrap();
+        assert_eq!(tree.version.as_deref(), Some("2.32.0"));
+        let requests_again = &tree.dependencies[1].dependencies[0];
+        assert_eq!(requests_again.name, "requests");
+        assert!(requests_again.cycle);
+        assert!(requests_again.dependencies.is_empty());
+
+        let tree = RemoteResolver::new(&index, MarkerEnv::new(), Some(1))
+            .resolve(&"requests".parse().unwrap())
+            .unwrap();
+        assert_eq!(
+            names(&tree),
+            vec![("idna", Some("3.6")), ("loop", Some("1.0"))]
+        );
+        assert!(tree.dependencies[1].dependencies.is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+//! Dependency dag parsed on demand: metadata dirs are only listed up front
+//! and parsed when a distribution or its subtree is first queried, for
+//! interactive consumers where latency to the first result matters
+
+use crate::dag::{
+    insert_distribution, parse_meta_dir, scan_meta_dirs, DependencyDag, DistributionMeta,
+    DistributionName, MergeStrategy,
+};
+use crate::marker::MarkerEnv;
+use crate::normalize::pep503;
+use crate::profile::Profile;
+use crate::source::MetadataSource;
+use crate::utils::MetaDirEntry;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Distribution name encoded in a metadata dir name, e.g. `Flask_Login`
+/// of `Flask_Login-0.6.3.dist-info`, of `Flask_Login-0.6.3-py3.11.egg` or
+/// of `Flask_Login.egg-info` left without version by develop installs
+fn dir_distribution_name(dir: &MetaDirEntry) -> Option<DistributionName> {
+    let stem = [".dist-info", ".egg-info", ".egg"]
+        .iter()
+        .find_map(|suffix| dir.file_name.strip_suffix(suffix))
+        .unwrap_or(&dir.file_name);
+    let name = stem.split_once('-').map_or(stem, |(name, _)| name);
+    if name.is_empty() {
+        None
+    } else {
+        Some(pep503(name))
+    }
+}
+
+/// Created with `DagBuilder::build_lazy`
+///
+/// ```no_run
+/// use rdeptree::builder::DagBuilder;
+///
+/// let mut env = DagBuilder::new()
+///     .paths(["/venv/lib/python3.12/site-packages"])
+///     .build_lazy()
+///     .unwrap();
+/// // only `requests` and what it requires is parsed
+/// let subtree = env.expand("requests").unwrap();
+/// ```
+pub struct LazyDag {
+    /// scanned roots, with distributions of the second ones marked as inherited
+    sources: Vec<(Box<dyn MetadataSource>, bool)>,
+    /// metadata dirs not parsed yet, keyed by the name in the dir name,
+    /// in the order the builder would parse them
+    pending: HashMap<DistributionName, Vec<(usize, MetaDirEntry)>>,
+    dag: DependencyDag,
+    marker_env: Option<MarkerEnv>,
+    merge_strategy: MergeStrategy,
+    strict: bool,
+}
+
+impl LazyDag {
+    /// List metadata dirs of all sources, dirs with names not following
+    /// the `<name>-<version>` convention are parsed right away
+    pub(crate) fn new(
+        sources: Vec<(Box<dyn MetadataSource>, bool)>,
+        marker_env: Option<MarkerEnv>,
+        merge_strategy: MergeStrategy,
+        follow_egg_info: bool,
+        strict: bool,
+    ) -> Result<Self, String> {
+        let mut lazy_dag = Self {
+            sources,
+            pending: HashMap::new(),
+            dag: DependencyDag::new(),
+            marker_env,
+            merge_strategy,
+            strict,
+        };
+        let mut profile = Profile::default();
+        for source_idx in 0..lazy_dag.sources.len() {
+            let source = lazy_dag.sources[source_idx].0.as_ref();
+            for dir in scan_meta_dirs(source, follow_egg_info, &mut profile) {
+                match dir_distribution_name(&dir) {
+                    Some(name) => lazy_dag
+                        .pending
+                        .entry(name)
+                        .or_default()
+                        .push((source_idx, dir)),
+                    None => lazy_dag.parse(source_idx, dir)?,
+                }
+            }
+        }
+        Ok(lazy_dag)
+    }
+
+    fn parse(&mut self, source_idx: usize, dir: MetaDirEntry) -> Result<(), String> {
+        let (source, inherited) = &self.sources[source_idx];
+        let parsed = parse_meta_dir(
+            source.as_ref(),
+            dir,
+            self.marker_env.as_ref(),
+            self.strict,
+            &mut Profile::default(),
+        )?;
+        if let Some((name, mut meta)) = parsed {
+            meta.inherited = *inherited;
+            insert_distribution(&mut self.dag, name, meta, self.merge_strategy)?;
+        }
+        Ok(())
+    }
+
+    /// Parse all copies of a distribution, unless it is parsed already
+    fn load(&mut self, name: &DistributionName) -> Result<(), String> {
+        for (source_idx, dir) in self.pending.remove(name).unwrap_or_default() {
+            self.parse(source_idx, dir)?;
+        }
+        Ok(())
+    }
+
+    /// Names of all installed distributions, sorted, nothing is parsed
+    pub fn names(&self) -> Vec<&DistributionName> {
+        let names: BTreeSet<&DistributionName> =
+            self.pending.keys().chain(self.dag.keys()).collect();
+        names.into_iter().collect()
+    }
+
+    /// Metadata of a single distribution, None if it is not installed
+    pub fn get(&mut self, name: &str) -> Result<Option<&DistributionMeta>, String> {
+        let name = pep503(name);
+        self.load(&name)?;
+        Ok(self.dag.get(&name))
+    }
+
+    /// Parse a distribution and everything it requires, directly or transitively.
+    /// Returns the dag parsed so far, ready to be rendered from the distribution
+    pub fn expand(&mut self, name: &str) -> Result<&DependencyDag, String> {
+        let mut seen: HashSet<DistributionName> = HashSet::new();
+        let mut to_visit = vec![pep503(name)];
+        while let Some(name) = to_visit.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            self.load(&name)?;
+            if let Some(meta) = self.dag.get(&name) {
+                to_visit.extend(meta.dependencies.iter().map(|d| d.name.clone()));
+            }
+        }
+        Ok(&self.dag)
+    }
+
+    /// Distributions parsed so far
+    pub fn dag(&self) -> &DependencyDag {
+        &self.dag
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::dir_distribution_name;
+    use crate::builder::DagBuilder;
+    use crate::fixture::SitePackages;
+    use crate::utils::{MetaDirEntry, MetaDirKind};
+
+    #[test]
+    fn name_from_dir_name() {
+        let name = |file_name: &str, kind| {
+            dir_distribution_name(&MetaDirEntry {
+                path: file_name.into(),
+                file_name: file_name.to_string(),
+                kind,
+            })
+        };
+        let dist_info = MetaDirKind::DistInfo;
+        let egg_info = MetaDirKind::EggInfo;
+        assert_eq!(
+            name("Flask_Login-0.6.3.dist-info", dist_info).unwrap(),
+            "flask-login"
+        );
+        assert_eq!(
+            name("Flask_Login-0.6.3-py3.11.egg", egg_info).unwrap(),
+            "flask-login"
+        );
+        assert_eq!(
+            name("Flask_Login.egg-info", egg_info).unwrap(),
+            "flask-login"
+        );
+        assert_eq!(name(".egg-info", egg_info), None);
+    }
+
+    #[test]
+    fn subtree_parsed_on_demand() {
+        let site = SitePackages::new("lazy")
+            .dist_info("app", "1.0", &["lib>=2.0"])
+            .dist_info("lib", "2.1", &["six<2,>=1.5"])
+            .dist_info("six", "1.16.0", &[])
+            .dist_info("tool", "0.3", &[])
+            .egg_info("Legacy_Pkg", "1.0", "six>=1.0\n");
+
+        let mut env = DagBuilder::new()
+            .paths([site.path()])
+            .follow_egg_info(true)
+            .build_lazy()
+            .unwrap();
+        assert_eq!(env.names(), ["app", "legacy-pkg", "lib", "six", "tool"]);
+        assert!(env.dag().is_empty());
+
+        assert_eq!(env.get("Six").unwrap().unwrap().installed_version, "1.16.0");
+        assert_eq!(env.dag().len(), 1);
+
+        let mut expanded: Vec<&String> = env.expand("app").unwrap().keys().collect();
+        expanded.sort();
+        assert_eq!(expanded, ["app", "lib", "six"]);
+        assert!(env.get("missing").unwrap().is_none());
+
+        let legacy = env.get("legacy-pkg").unwrap().unwrap();
+        assert_eq!(legacy.dependencies.len(), 1);
+    }
+}
@@ -0,0 +1,46 @@
+pub mod badge;
+pub mod builder;
+pub mod cache;
+pub mod dag;
+pub mod deadline;
+pub mod events;
+pub mod export;
+#[cfg(test)]
+pub mod fixture;
+pub mod fleet;
+pub mod graph;
+#[cfg(feature = "network")]
+pub mod health;
+pub mod i18n;
+pub mod ignore;
+#[cfg(feature = "network")]
+pub mod index;
+pub mod lazy;
+pub mod locator;
+pub mod lookup;
+pub mod marker;
+pub mod network;
+pub mod normalize;
+pub mod origin;
+pub mod parser;
+pub mod pep;
+pub mod permissions;
+#[cfg(feature = "network")]
+pub mod plan;
+pub mod plugin;
+pub mod policy;
+pub mod profile;
+pub mod record;
+pub mod render;
+pub mod renderer;
+pub mod requirements;
+pub mod scanner;
+pub mod server;
+pub mod snapshot;
+pub mod source;
+pub mod syspkg;
+pub mod table;
+#[cfg(feature = "network")]
+pub mod update;
+pub mod utils;
+pub mod wheelcache;
@@ -1,8 +1,35 @@
-use std::ffi::OsStr;
-use std::path::PathBuf;
-use std::process::{Command, Output};
+use crate::deadline;
+use crate::i18n::{info, trf, Msg};
+use crate::utils::{project_dirs, read_text};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
 use std::{env, str};
 
+/// Project-local virtual env dirs, in order of preference
+const PROJECT_VENV_DIRS: [&str; 2] = [".venv", "venv"];
+
+/// Longest time a locator or git command may run, a hung interpreter
+/// (e.g. waiting on a network mount) is killed after it
+pub(crate) const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Variables which make the interpreter run user code on startup or
+/// change its sys.path, so it could report site paths of another env
+const PYTHON_ENV_VARS_REMOVED: [&str; 2] = ["PYTHONSTARTUP", "PYTHONPATH"];
+
+/// Interpreter locations inside a virtual env, unix layout first
+const VENV_INTERPRETERS: [&[&str]; 3] = [
+    &["bin", "python3"],
+    &["bin", "python"],
+    &["Scripts", "python.exe"],
+];
+
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 fn get_which_command() -> &'static str {
     "which"
@@ -13,15 +40,176 @@ fn get_which_command() -> &'static str {
     "where"
 }
 
+/// Path printed by a command, kept byte for byte so non-UTF-8 paths survive
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn path_from_output(line: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(line))
+}
+
+/// Path printed by a command. Python prints UTF-8 as PYTHONIOENCODING is set
+/// for it, `where` output in other code pages is decoded lossily
+#[cfg(target_os = "windows")]
+fn path_from_output(line: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(line).into_owned())
+}
+
+/// Paths printed one per line, e.g. by `where python` which lists every match
+/// on PATH. CRLF endings and blank lines are dropped, spaces inside paths kept
+fn output_paths(stdout: &[u8]) -> Vec<PathBuf> {
+    stdout
+        .split(|b| *b == b'\n')
+        .map(|line| line.trim_ascii())
+        .filter(|line| !line.is_empty())
+        .map(path_from_output)
+        .collect()
+}
+
 // The way to break a build if OS is not supported by this module
 #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
 compile_error!("Unsuported OS! Current build is supported by: [linux, macos, windows].");
 
+/// Read a child stream to the end on a separate thread, so a child filling
+/// one pipe never blocks while the other one is waited on
+fn drain<R: Read + Send + 'static>(stream: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut stream) = stream {
+            let _ = stream.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Run the command with closed stdin, capturing both streams.
+/// The child is killed when it runs longer than the timeout
+/// or than the time left of the run
+pub(crate) fn run_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    wait_with_timeout(child, timeout).map_err(|err| match err.kind() {
+        io::ErrorKind::TimedOut => io::Error::new(err.kind(), format!("{:?} {}", command, err)),
+        _ => err,
+    })
+}
+
+/// Wait for a spawned child, capturing its piped streams. The child is killed
+/// when it runs longer than the timeout or than the time left of the run
+pub(crate) fn wait_with_timeout(mut child: Child, timeout: Duration) -> io::Result<Output> {
+    let timeout = deadline::limit(timeout);
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("did not finish in {:?}", timeout),
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
 fn execute_command<T>(cmd: T, args: &[&str]) -> Result<Output, std::io::Error>
 where
     T: AsRef<OsStr>,
 {
-    Command::new(cmd).args(args).output()
+    let mut command = Command::new(cmd);
+    command.args(args);
+    if let (Some(venv), Some(path)) = (IGNORED_WRAPPER.get(), env::var_os("PATH")) {
+        command.env("PATH", path_without_venv(&path, venv));
+    }
+    run_with_timeout(&mut command, COMMAND_TIMEOUT)
+}
+
+/// Virtual env of a pipx/uvx wrapper rdeptree runs from, skipped while locating
+/// the python env, see `--ignore-wrapper`
+static IGNORED_WRAPPER: OnceLock<PathBuf> = OnceLock::new();
+
+/// Virtual env the given executable is installed in, i.e. the env pipx or uvx
+/// created for rdeptree, which puts its interpreter first on PATH
+pub fn wrapper_venv(exe: &Path) -> Option<PathBuf> {
+    let venv = exe.parent()?.parent()?;
+    venv.join("pyvenv.cfg")
+        .is_file()
+        .then(|| venv.to_path_buf())
+}
+
+/// Skip the wrapper env while locating the python env, for the rest of the run:
+/// its interpreter on PATH and VIRTUAL_ENV pointing to it are ignored
+pub fn ignore_wrapper(venv: PathBuf) {
+    let _ = IGNORED_WRAPPER.set(venv);
+}
+
+/// Interpreter given with `--python`, used instead of the located one
+static INTERPRETER: OnceLock<PathBuf> = OnceLock::new();
+
+/// Locate the python env of the given interpreter for the rest of the run:
+/// the active venv and the `python3` on PATH are not looked at
+pub fn use_interpreter(interpreter: PathBuf) {
+    let _ = INTERPRETER.set(interpreter);
+}
+
+fn is_inside(path: &Path, venv: &Path) -> bool {
+    path.starts_with(venv)
+        || matches!(
+            (path.canonicalize(), venv.canonicalize()),
+            (Ok(path), Ok(venv)) if path.starts_with(&venv)
+        )
+}
+
+/// PATH value with the dirs inside the venv left out
+fn path_without_venv(path: &OsStr, venv: &Path) -> OsString {
+    let dirs: Vec<PathBuf> = env::split_paths(path)
+        .filter(|dir| !is_inside(dir, venv))
+        .collect();
+    env::join_paths(dirs).unwrap_or_else(|_| path.to_os_string())
+}
+
+/// Interpreter invocation with startup hooks and sys.path overrides of
+/// the caller's environment stripped
+fn python_command(interpreter_path: &Path, script: &str) -> Command {
+    let mut command = Command::new(interpreter_path);
+    command.args(["-c", script]);
+    for var in PYTHON_ENV_VARS_REMOVED {
+        command.env_remove(var);
+    }
+    // paths are printed in UTF-8 regardless of the console code page
+    command.env("PYTHONIOENCODING", "utf-8");
+    command
+}
+
+fn execute_python(interpreter_path: &Path, script: &str) -> Result<Output, std::io::Error> {
+    run_with_timeout(
+        &mut python_command(interpreter_path, script),
+        COMMAND_TIMEOUT,
+    )
+}
+
+/// Exit status and both streams of a failed command, for diagnostics
+pub(crate) fn describe_failure(output: &Output) -> String {
+    format!(
+        "{}, stdout: {:?}, stderr: {:?}",
+        output.status,
+        String::from_utf8_lossy(&output.stdout).trim(),
+        String::from_utf8_lossy(&output.stderr).trim()
+    )
 }
 
 fn run_python_locator_cmd(command: &str) -> Result<Option<Vec<u8>>, std::io::Error> {
@@ -34,10 +222,10 @@ fn run_python_locator_cmd(command: &str) -> Result<Option<Vec<u8>>, std::io::Err
         match alt_result.status.success() {
             true => Some(alt_result.stdout),
             false => {
-                eprintln!(
-                    "Command <which(where) python(3)> returned: {:?}",
-                    String::from_utf8(alt_result.stderr).unwrap()
-                );
+                info(&format!(
+                    "Command <which(where) python(3)> returned: {}",
+                    describe_failure(&alt_result)
+                ));
                 None
             }
         }
@@ -48,61 +236,258 @@ fn run_python_locator_cmd(command: &str) -> Result<Option<Vec<u8>>, std::io::Err
 
 /// function responsible for identifying the
 /// location of current python interpreter
-/// Run child sub-proccess using which/where command
-///
-/// TODO: work out scenario with 2+ paths. Is it possible?
+/// Run child sub-proccess using which/where command,
+/// the first of the listed interpreters which exists is taken
 fn get_python_interpreter_location() -> Result<PathBuf, &'static str> {
     let init_command = get_which_command();
-    let cmd_result = run_python_locator_cmd(init_command).expect(
-        "Unable to locate python interpreter, something went wrong invoking search command",
-    );
+    let cmd_result = run_python_locator_cmd(init_command).map_err(|_| {
+        "Unable to locate python interpreter, something went wrong invoking search command"
+    })?;
 
     if cmd_result.is_none() {
         return Err("Unable to locate python interpreter, command returned nothing");
     }
 
-    let s = String::from_utf8(cmd_result.unwrap())
-        .expect("Unable to convert <which(where) python(3)> subcommand result to String");
+    let candidates = output_paths(&cmd_result.unwrap());
+    let interpreter = candidates
+        .iter()
+        .find(|path| path.exists())
+        .or(candidates.first())
+        .ok_or("Unable to locate python interpreter, command returned nothing")?;
+
+    Ok(resolve_real_interpreter(interpreter))
+}
+
+/// Script printing the path of the binary actually running the interpreter
+const REAL_INTERPRETER_SCRIPT: &str = "import sys,site;print(sys.executable)";
+
+/// Interpreter behind a candidate found on PATH, which may be a shell wrapper
+/// forwarding to another binary. The candidate is kept when it can not be
+/// run or does not report an existing executable
+fn resolve_real_interpreter(candidate: &Path) -> PathBuf {
+    let reported = execute_python(candidate, REAL_INTERPRETER_SCRIPT)
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| output_paths(&output.stdout).into_iter().next())
+        .filter(|path| path.is_file());
+    match reported {
+        Some(real) if real.canonicalize().ok() != candidate.canonicalize().ok() => {
+            info(&trf(
+                Msg::InterpreterShimResolved,
+                &[&candidate.display(), &real.display()],
+            ));
+            real
+        }
+        _ => candidate.to_path_buf(),
+    }
+}
 
-    Ok(PathBuf::from(s.trim()))
+/// Interpreter of a virtual env, `bin/python3` if none of the known layouts is found
+pub(crate) fn venv_interpreter(venv: &Path) -> PathBuf {
+    let candidates = VENV_INTERPRETERS.map(|parts| parts.iter().collect::<PathBuf>());
+    candidates
+        .iter()
+        .map(|candidate| venv.join(candidate))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| venv.join(&candidates[0]))
 }
 
 fn check_venv_env_var() -> Option<String> {
-    if let Ok(e) = env::var("VIRTUAL_ENV") {
-        Some(e)
-    } else {
-        None
+    env::var("VIRTUAL_ENV").ok().filter(|venv| {
+        !IGNORED_WRAPPER
+            .get()
+            .is_some_and(|w| is_inside(Path::new(venv), w))
+    })
+}
+
+/// Virtual env of the project the search starts in: `.venv` or `venv` dir
+/// with pyvenv.cfg in the start dir or its ancestors up to the git root.
+/// Outside of a git repository only the start dir is looked into
+pub fn find_project_venv(start: &Path) -> Option<PathBuf> {
+    project_dirs(start).into_iter().find_map(|dir| {
+        PROJECT_VENV_DIRS
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|venv| venv.join("pyvenv.cfg").is_file())
+    })
+}
+
+/// Active virtual env, or the project one when no env is active.
+/// The project venv is looked up once, with a notice when it is used.
+/// With `--python` it is the venv of that interpreter, if any
+fn venv_dir() -> Option<PathBuf> {
+    static PROJECT_VENV: OnceLock<Option<PathBuf>> = OnceLock::new();
+    if let Some(interpreter) = INTERPRETER.get() {
+        return wrapper_venv(interpreter);
     }
+    if let Some(venv) = check_venv_env_var() {
+        return Some(PathBuf::from(venv));
+    }
+    PROJECT_VENV
+        .get_or_init(|| {
+            let venv = find_project_venv(&env::current_dir().ok()?)?;
+            info(&trf(Msg::ProjectVenvUsed, &[&venv.display()]));
+            Some(venv)
+        })
+        .clone()
 }
 
-pub fn get_python_interpreter_loc() -> Result<PathBuf, &'static str> {
-    let interpreter_path = match check_venv_env_var() {
-        Some(venv_env_val) => {
-            let mut pb = PathBuf::from(venv_env_val);
-            // TODO: expand find python3 logic
-            pb.extend(["bin", "python3"].iter());
-            pb
+/// site-packages dirs of an install prefix, found by its layout: `lib/python3.X/site-packages`
+/// on unix (`dist-packages` on Debian based systems) and `Lib/site-packages` on windows
+pub(crate) fn layout_site_packages(prefix: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = ["lib", "lib64"]
+        .iter()
+        .filter_map(|lib| prefix.join(lib).read_dir().ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("python"))
+        .flat_map(|entry| ["site-packages", "dist-packages"].map(|dir| entry.path().join(dir)))
+        .chain([prefix.join("Lib").join("site-packages")])
+        .filter(|path| path.is_dir())
+        .collect();
+    paths.sort();
+    // lib64 is usually a symlink to lib
+    paths.dedup_by(|a, b| a.canonicalize().ok() == b.canonicalize().ok());
+    paths
+}
+
+/// Python of a single minor version installed under a prefix
+#[derive(Debug, PartialEq)]
+pub struct PythonTree {
+    /// minor version, e.g. `3.11`
+    pub version: String,
+    pub site_packages: Vec<PathBuf>,
+    /// `bin/python3.11` of the prefix, None when missing
+    pub interpreter: Option<PathBuf>,
+}
+
+/// `(3, 11)` of a `python3.11` lib dir name
+fn tree_version(dir_name: &str) -> Option<(u32, u32)> {
+    let (major, minor) = dir_name.strip_prefix("python")?.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Python minor versions installed under a prefix, e.g. `/usr` holding
+/// both `lib/python3.10` and `lib/python3.12`, oldest first. Debian's
+/// `lib/python3/dist-packages` is shared by all of them, so it's listed
+/// for each version which has its own site-packages or interpreter
+pub fn python_trees(prefix: &Path) -> Vec<PythonTree> {
+    let shared = prefix.join("lib").join("python3").join("dist-packages");
+    let shared = shared.is_dir().then_some(shared);
+    let mut versions: BTreeMap<(u32, u32), Vec<PathBuf>> = BTreeMap::new();
+    for entry in ["lib", "lib64"]
+        .iter()
+        .filter_map(|lib| prefix.join(lib).read_dir().ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+    {
+        let Some(version) = tree_version(&entry.file_name().to_string_lossy()) else {
+            continue;
+        };
+        let dirs = versions.entry(version).or_default();
+        for dir in ["site-packages", "dist-packages"].map(|dir| entry.path().join(dir)) {
+            let canonical = dir.canonicalize().ok();
+            // lib64 is usually a symlink to lib
+            if dir.is_dir() && !dirs.iter().any(|d| d.canonicalize().ok() == canonical) {
+                dirs.push(dir);
+            }
         }
-        None => get_python_interpreter_location()?,
+    }
+    versions
+        .into_iter()
+        .filter_map(|((major, minor), mut site_packages)| {
+            let version = format!("{}.{}", major, minor);
+            let interpreter = prefix.join("bin").join(format!("python{}", version));
+            let interpreter = interpreter.is_file().then_some(interpreter);
+            if site_packages.is_empty() && interpreter.is_none() {
+                return None;
+            }
+            site_packages.sort();
+            site_packages.extend(shared.clone());
+            Some(PythonTree {
+                version,
+                site_packages,
+                interpreter,
+            })
+        })
+        .collect()
+}
+
+/// Install prefix of an interpreter in the unix layout, `/usr` of `/usr/bin/python3`
+pub fn interpreter_prefix(interpreter_path: &Path) -> Option<&Path> {
+    let bin = interpreter_path.parent()?;
+    (bin.file_name()? == "bin").then_some(bin.parent()?)
+}
+
+/// site-packages dirs of the active (or project) virtual env, found by the venv layout
+/// without running its interpreter
+pub fn get_venv_site_packages() -> Vec<PathBuf> {
+    match venv_dir() {
+        Some(venv) => layout_site_packages(&venv),
+        None => Vec::new(),
+    }
+}
+
+/// site-packages dirs of the base interpreter a virtual env was created from,
+/// when pyvenv.cfg says `include-system-site-packages = true`. The base prefix
+/// is taken from the `home` key, which is the dir of the base interpreter
+pub fn inherited_site_packages(venv: &Path) -> Vec<PathBuf> {
+    let Ok(cfg) = read_text(venv.join("pyvenv.cfg")) else {
+        return Vec::new();
+    };
+    let values: HashMap<String, &str> = cfg
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_lowercase(), value.trim()))
+        .collect();
+    let include = values.get("include-system-site-packages");
+    let (Some(home), true) = (
+        values.get("home"),
+        include.is_some_and(|v| v.eq_ignore_ascii_case("true")),
+    ) else {
+        return Vec::new();
+    };
+    // unix homes are bin dirs of the prefix, windows ones are the prefix itself
+    let home = Path::new(home);
+    let prefix = match home.file_name() {
+        Some(name) if name == "bin" => home.parent().unwrap_or(home),
+        _ => home,
+    };
+    layout_site_packages(prefix)
+}
+
+/// site-packages dirs inherited by the active (or project) virtual env
+pub fn get_inherited_site_packages() -> Vec<PathBuf> {
+    match venv_dir() {
+        Some(venv) => inherited_site_packages(&venv),
+        None => Vec::new(),
+    }
+}
+
+pub fn get_python_interpreter_loc() -> Result<PathBuf, &'static str> {
+    let interpreter_path = match (INTERPRETER.get(), venv_dir()) {
+        (Some(interpreter), _) => interpreter.clone(),
+        (None, Some(venv)) => venv_interpreter(&venv),
+        (None, None) => get_python_interpreter_location()?,
     };
 
     if interpreter_path.exists() {
         Ok(interpreter_path)
     } else {
-        eprintln!("Found python interpreter path: {:?}", interpreter_path);
+        info(&format!(
+            "Found python interpreter path: {:?}",
+            interpreter_path
+        ));
         Err("Found python interpreter path does not exists")
     }
 }
 
 /// function responsible for identifying the
 /// location of python site-packages dir
-pub fn get_site_packages_loc(interpreter_path: &PathBuf) -> Result<PathBuf, &'static str> {
-    let command_result_wrapped = execute_command(
-        interpreter_path.as_os_str(),
-        &[
-            "-c",
-            r#"import site; print('\n'.join(site.getsitepackages()))"#,
-        ],
+pub fn get_site_packages_loc(interpreter_path: &Path) -> Result<PathBuf, &'static str> {
+    let command_result_wrapped = execute_python(
+        interpreter_path,
+        r#"import site; print('\n'.join(site.getsitepackages()))"#,
     );
 
     let command_result = match command_result_wrapped {
@@ -110,28 +495,370 @@ pub fn get_site_packages_loc(interpreter_path: &PathBuf) -> Result<PathBuf, &'st
             if val.status.success() {
                 val.stdout
             } else {
-                eprintln!(
-                    "Command <find python site-packages> returned: {:?}",
-                    String::from_utf8(val.stderr).unwrap()
-                );
+                info(&format!(
+                    "Command <find python site-packages> returned: {}",
+                    describe_failure(&val)
+                ));
                 return Err("Python find site-packages subcommand was unsuccessful");
             }
         }
         Err(e) => {
-            eprintln!("{:?}", e);
+            info(&format!("{:?}", e));
             return Err("Unable to run `site.getsitepackages()` function in python interpreter to locate site-packages");
         }
     };
 
-    let site_packages_path =
-        String::from_utf8(command_result).expect("Unable to convert subcommand result to String");
-
-    let pb = PathBuf::from(site_packages_path.trim());
+    // venvs with system site-packages list the base prefix dirs after their own,
+    // those are scanned as inherited
+    let pb = output_paths(&command_result)
+        .into_iter()
+        .next()
+        .unwrap_or_default();
 
     if pb.exists() {
         Ok(pb)
     } else {
-        eprintln!("Found python site-packages path: {:?}", interpreter_path);
-        Err("Found python site-packages path {:?} does not exists")
+        info(&format!("Found python site-packages path: {:?}", pb));
+        Err("Found python site-packages path does not exists")
+    }
+}
+
+/// Script printing values of PEP 508 environment markers as json
+const MARKER_VALUES_SCRIPT: &str = r#"
+import json, os, platform, sys
+v = sys.implementation.version
+iver = '{0.major}.{0.minor}.{0.micro}'.format(v)
+if v.releaselevel != 'final':
+    iver += v.releaselevel[0] + str(v.serial)
+print(json.dumps({
+    'implementation_name': sys.implementation.name,
+    'implementation_version': iver,
+    'os_name': os.name,
+    'platform_machine': platform.machine(),
+    'platform_python_implementation': platform.python_implementation(),
+    'platform_release': platform.release(),
+    'platform_system': platform.system(),
+    'platform_version': platform.version(),
+    'python_full_version': platform.python_version(),
+    'python_version': '.'.join(platform.python_version_tuple()[:2]),
+    'sys_platform': sys.platform,
+}))
+"#;
+
+/// function responsible for getting values of environment
+/// markers (python_version, sys_platform, ...) from the interpreter
+pub fn get_marker_values(interpreter_path: &Path) -> Result<HashMap<String, String>, &'static str> {
+    let output = execute_python(interpreter_path, MARKER_VALUES_SCRIPT).map_err(|e| {
+        info(&format!("{:?}", e));
+        "Unable to run python interpreter to get environment marker values"
+    })?;
+    if !output.status.success() {
+        info(&format!(
+            "Command <get environment marker values> returned: {}",
+            describe_failure(&output)
+        ));
+        return Err("Python environment markers subcommand was unsuccessful");
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|_| "Unable to parse environment marker values returned by python")
+}
+
+/// Script printing site dirs of the interpreter as json
+const SITE_DIRS_SCRIPT: &str = r#"
+import json, site
+print(json.dumps({
+    'site_packages': site.getsitepackages(),
+    'user_site': site.getusersitepackages(),
+    'user_site_enabled': site.ENABLE_USER_SITE is True,
+}))
+"#;
+
+/// Dirs the interpreter imports installed packages from
+#[derive(Debug, Default, Deserialize)]
+pub struct SiteDirs {
+    /// global site-packages (dist-packages) dirs, the one of the env first
+    pub site_packages: Vec<PathBuf>,
+    pub user_site: PathBuf,
+    /// false in virtual envs and with `python -s`
+    pub user_site_enabled: bool,
+}
+
+/// function responsible for getting site dirs of the interpreter
+pub fn get_site_dirs(interpreter_path: &Path) -> Result<SiteDirs, &'static str> {
+    let output = execute_python(interpreter_path, SITE_DIRS_SCRIPT).map_err(|e| {
+        info(&format!("{:?}", e));
+        "Unable to run python interpreter to get its site dirs"
+    })?;
+    if !output.status.success() {
+        info(&format!(
+            "Command <get site dirs> returned: {}",
+            describe_failure(&output)
+        ));
+        return Err("Python site dirs subcommand was unsuccessful");
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|_| "Unable to parse site dirs returned by python")
+}
+
+/// Dir python may import packages from and whether rdeptree scans it
+#[derive(Debug, PartialEq)]
+pub struct ScanPath {
+    pub path: PathBuf,
+    pub scanned: bool,
+    /// where the dir comes from and why it is (not) scanned
+    pub reason: &'static str,
+    pub exists: bool,
+}
+
+/// Every dir rdeptree scans, in scan order with the reason it is scanned,
+/// followed by the other dirs python imports from: site-packages dirs and
+/// the user site of the interpreter and `PYTHONPATH` entries
+pub fn scan_paths(
+    scanned: &[(PathBuf, &'static str)],
+    explicit: bool,
+    site_dirs: Option<&SiteDirs>,
+    python_path: &[PathBuf],
+) -> Vec<ScanPath> {
+    let mut paths: Vec<ScanPath> = Vec::new();
+    let mut push = |path: &Path, scanned: bool, reason: &'static str| {
+        if !paths.iter().any(|p| p.path == path) {
+            paths.push(ScanPath {
+                path: path.to_path_buf(),
+                scanned,
+                reason,
+                exists: path.is_dir(),
+            });
+        }
+    };
+
+    scanned
+        .iter()
+        .for_each(|(path, reason)| push(path, true, reason));
+    if let Some(site_dirs) = site_dirs {
+        let other_reason = match explicit {
+            false => "further site-packages of the interpreter, not scanned",
+            true => "site-packages of the interpreter, not scanned as --path is given",
+        };
+        site_dirs
+            .site_packages
+            .iter()
+            .for_each(|path| push(path, false, other_reason));
+        let user_reason = match site_dirs.user_site_enabled {
+            true => "user site-packages, not scanned",
+            false => "user site-packages, disabled in the interpreter",
+        };
+        push(&site_dirs.user_site, false, user_reason);
+    }
+    python_path
+        .iter()
+        .for_each(|path| push(path, false, "PYTHONPATH entry, add it with --path"));
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::SitePackages;
+
+    #[test]
+    fn python_command_strips_startup_env() {
+        let command = python_command(Path::new("python3"), "pass");
+        let removed: Vec<&OsStr> = command
+            .get_envs()
+            .filter(|(_, value)| value.is_none())
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(removed, ["PYTHONPATH", "PYTHONSTARTUP"]);
+    }
+
+    #[test]
+    fn output_paths_of_where() {
+        let stdout = "C:\\Program Files\\Python312\\python.exe\r\n\
+                      \\\\fileserver\\tools\\Python\\python.exe\r\n\
+                      \r\n\
+                      C:\\Users\\Jürgen\\AppData\\Local\\Programs\\Python\\python.exe\r\n";
+        assert_eq!(
+            output_paths(stdout.as_bytes()),
+            [
+                PathBuf::from(r"C:\Program Files\Python312\python.exe"),
+                PathBuf::from(r"\\fileserver\tools\Python\python.exe"),
+                PathBuf::from(r"C:\Users\Jürgen\AppData\Local\Programs\Python\python.exe"),
+            ]
+        );
+        assert!(output_paths(b"\n").is_empty());
+    }
+
+    #[test]
+    fn venv_interpreter_of_windows_layout() {
+        let venv = env::temp_dir().join(format!("rdeptree-venv-{}", std::process::id()));
+        assert_eq!(venv_interpreter(&venv), venv.join("bin").join("python3"));
+
+        std::fs::create_dir_all(venv.join("Scripts")).unwrap();
+        std::fs::write(venv.join("Scripts").join("python.exe"), b"").unwrap();
+        assert_eq!(
+            venv_interpreter(&venv),
+            venv.join("Scripts").join("python.exe")
+        );
+        std::fs::remove_dir_all(&venv).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shim_resolved_to_real_interpreter() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("rdeptree-shim-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let real = dir.join("python3.12");
+        std::fs::write(&real, b"").unwrap();
+        let shim = dir.join("python3");
+        std::fs::write(&shim, format!("#!/bin/sh\necho {}\n", real.display())).unwrap();
+        std::fs::set_permissions(&shim, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(resolve_real_interpreter(&shim), real);
+        // not runnable candidates are kept
+        assert_eq!(resolve_real_interpreter(&real), real);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_streams_captured_and_hung_command_killed() {
+        let output = run_with_timeout(
+            Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]),
+            COMMAND_TIMEOUT,
+        )
+        .unwrap();
+        assert!(!output.status.success());
+        assert!(describe_failure(&output).ends_with(r#"stdout: "out", stderr: "err""#));
+
+        let started = Instant::now();
+        let err = run_with_timeout(Command::new("sleep").arg("10"), Duration::from_millis(100))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn scan_paths_flag_skipped_dirs() {
+        let site = SitePackages::new("scan-paths");
+        let site_dirs = SiteDirs {
+            site_packages: vec![
+                site.path().to_path_buf(),
+                PathBuf::from("/nowhere/dist-packages"),
+            ],
+            user_site: PathBuf::from("/home/u/.local/site-packages"),
+            user_site_enabled: false,
+        };
+        let inherited = [PathBuf::from("/nowhere/base/site-packages")];
+        let python_path = [PathBuf::from("/src"), site.path().to_path_buf()];
+
+        let scanned = [
+            (
+                site.path().to_path_buf(),
+                "site-packages of the interpreter",
+            ),
+            (
+                inherited[0].clone(),
+                "system site-packages inherited by the venv",
+            ),
+        ];
+        let paths = scan_paths(&scanned, false, Some(&site_dirs), &python_path);
+        let summary: Vec<(&Path, bool, bool)> = paths
+            .iter()
+            .map(|p| (p.path.as_path(), p.scanned, p.exists))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (site.path(), true, true),
+                (inherited[0].as_path(), true, false),
+                (Path::new("/nowhere/dist-packages"), false, false),
+                (site_dirs.user_site.as_path(), false, false),
+                (Path::new("/src"), false, false),
+            ]
+        );
+        assert_eq!(
+            paths[3].reason,
+            "user site-packages, disabled in the interpreter"
+        );
+
+        let explicit = [(PathBuf::from("/given"), "given with --path")];
+        let paths = scan_paths(&explicit, true, Some(&site_dirs), &[]);
+        let scanned: Vec<&Path> = paths
+            .iter()
+            .filter(|p| p.scanned)
+            .map(|p| p.path.as_path())
+            .collect();
+        assert_eq!(scanned, vec![Path::new("/given")]);
+        assert_eq!(
+            paths[1].reason,
+            "site-packages of the interpreter, not scanned as --path is given"
+        );
+    }
+
+    #[test]
+    fn python_trees_listed_by_minor_version() {
+        let prefix = SitePackages::new("python-trees");
+        let prefix = prefix.path();
+        for dir in [
+            "lib/python3.12/site-packages",
+            "lib/python3.9/dist-packages",
+            "lib/python3.9/site-packages",
+            "lib/python3/dist-packages",
+            "lib/python3.11",
+            "lib/python3.13",
+        ] {
+            std::fs::create_dir_all(prefix.join(dir)).unwrap();
+        }
+        std::fs::create_dir_all(prefix.join("bin")).unwrap();
+        std::fs::write(prefix.join("bin/python3.12"), b"").unwrap();
+        // Debian's python3.13 keeps only its stdlib in lib/python3.13
+        std::fs::write(prefix.join("bin/python3.13"), b"").unwrap();
+
+        let trees = python_trees(prefix);
+        let versions: Vec<&str> = trees.iter().map(|t| t.version.as_str()).collect();
+        assert_eq!(versions, vec!["3.9", "3.12", "3.13"]);
+        assert_eq!(
+            trees[0].site_packages,
+            vec![
+                prefix.join("lib/python3.9/dist-packages"),
+                prefix.join("lib/python3.9/site-packages"),
+                prefix.join("lib/python3/dist-packages"),
+            ]
+        );
+        assert_eq!(
+            trees[1].site_packages,
+            vec![
+                prefix.join("lib/python3.12/site-packages"),
+                prefix.join("lib/python3/dist-packages"),
+            ]
+        );
+        assert_eq!(
+            trees[2].site_packages,
+            vec![prefix.join("lib/python3/dist-packages")]
+        );
+        assert_eq!(trees[0].interpreter, None);
+        assert_eq!(trees[1].interpreter, Some(prefix.join("bin/python3.12")));
+        assert_eq!(
+            interpreter_prefix(&prefix.join("bin/python3.12")),
+            Some(prefix)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn wrapper_venv_left_out_of_path() {
+        let venv = SitePackages::new("wrapper-venv").file("pyvenv.cfg", "home = /usr/bin\n");
+        let venv = venv.path();
+        std::fs::create_dir_all(venv.join("bin")).unwrap();
+        assert_eq!(
+            wrapper_venv(&venv.join("bin/rdeptree")),
+            Some(venv.to_path_buf())
+        );
+        assert_eq!(wrapper_venv(Path::new("/usr/bin/rdeptree")), None);
+
+        let path = env::join_paths([venv.join("bin"), PathBuf::from("/usr/bin")]).unwrap();
+        assert_eq!(path_without_venv(&path, venv), OsString::from("/usr/bin"));
     }
 }
@@ -0,0 +1,162 @@
+use crate::dag::{DependencyDag, DistributionName};
+use crate::i18n::{info, trf, Msg};
+use crate::normalize::pep503;
+use std::fmt;
+
+/// Max amount of suggestions printed for an unknown package name
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum LookupError {
+    NotFound {
+        query: String,
+        suggestions: Vec<DistributionName>,
+    },
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::NotFound { query, suggestions } => {
+                f.write_str(&trf(Msg::PackageNotInstalled, &[query]))?;
+                if !suggestions.is_empty() {
+                    f.write_str(&trf(Msg::DidYouMean, &[&suggestions.join(", ")]))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Classic dynamic programming edit distance, computed over chars
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur_row = vec![i + 1; b_chars.len() + 1];
+        for (j, cb) in b_chars.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur_row[j + 1] = (prev_row[j] + cost)
+                .min(prev_row[j + 1] + 1)
+                .min(cur_row[j] + 1);
+        }
+        prev_row = cur_row;
+    }
+    prev_row[b_chars.len()]
+}
+
+/// Installed names which are close enough to the query, best matches first
+fn closest_names<'a>(dag: &'a DependencyDag, normalized_query: &str) -> Vec<&'a DistributionName> {
+    // allow roughly one typo per three chars, but at least two
+    let max_distance = (normalized_query.chars().count() / 3).max(2);
+
+    let mut candidates: Vec<(usize, &DistributionName)> = dag
+        .keys()
+        .map(|name| (levenshtein(normalized_query, name), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Resolve user supplied package name to the key used in the dag.
+/// Names are compared after PEP 503 normalization, so `Pillow`, `pillow`
+/// and `PILLOW` are all the same. When nothing matches and `fuzzy` is set,
+/// the closest installed name is used instead.
+pub fn find_distribution<'a>(
+    dag: &'a DependencyDag,
+    query: &str,
+    fuzzy: bool,
+) -> Result<&'a DistributionName, LookupError> {
+    let normalized_query = pep503(query);
+    if let Some((name, _)) = dag.get_key_value(&normalized_query) {
+        return Ok(name);
+    }
+
+    let suggestions = closest_names(dag, &normalized_query);
+    match suggestions.first() {
+        Some(best) if fuzzy => {
+            info(&trf(Msg::UsingClosestMatch, &[&query, best]));
+            Ok(best)
+        }
+        _ => Err(LookupError::NotFound {
+            query: query.to_string(),
+            suggestions: suggestions.into_iter().cloned().collect(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::DistributionMeta;
+
+    fn sample_dag() -> DependencyDag {
+        ["pillow", "numpy", "pandas", "requests", "typing-extensions"]
+            .into_iter()
+            .map(|name| {
+                (
+                    name.to_string(),
+                    DistributionMeta {
+                        installed_version: String::from("1.0"),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn levenshtein_distances() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("numpy", "numpy"), 0);
+        assert_eq!(levenshtein("nunpy", "numpy"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn find_distribution_case_insensitive() {
+        let dag = sample_dag();
+        for query in ["Pillow", "PILLOW", "pillow"] {
+            assert_eq!(find_distribution(&dag, query, false).unwrap(), "pillow");
+        }
+        assert_eq!(
+            find_distribution(&dag, "Typing_Extensions", false).unwrap(),
+            "typing-extensions"
+        );
+    }
+
+    #[test]
+    fn find_distribution_suggestions() {
+        let dag = sample_dag();
+        let err = find_distribution(&dag, "reqeusts", false).unwrap_err();
+        assert_eq!(
+            err,
+            LookupError::NotFound {
+                query: String::from("reqeusts"),
+                suggestions: vec![String::from("requests")],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "package 'reqeusts' is not installed, did you mean: requests?"
+        );
+
+        let err = find_distribution(&dag, "django", false).unwrap_err();
+        assert_eq!(err.to_string(), "package 'django' is not installed");
+    }
+
+    #[test]
+    fn find_distribution_fuzzy() {
+        let dag = sample_dag();
+        assert_eq!(find_distribution(&dag, "nunpy", true).unwrap(), "numpy");
+        assert!(find_distribution(&dag, "django", true).is_err());
+    }
+}
@@ -1,91 +1,1183 @@
-mod dag;
-mod locator;
-mod parser;
-mod render;
-mod utils;
-
-use dag::get_dep_dag_from_env;
-use locator::{get_python_interpreter_loc, get_site_packages_loc};
-use render::render_dag;
-use std::{collections::HashSet, env, process};
-
-/// This function is devoted to parsing and processing of input params
-/// This fn will be replaced in future by more convenient framework functionality
-fn check_input_params() -> Result<(), &'static str> {
-    let args: Vec<String> = env::args().skip(1).collect();
-
-    if args.is_empty() {
-        Ok(())
+mod cli;
+
+#[cfg(feature = "network")]
+use cli::SelfAction;
+use cli::{Cli, Commands, DiffFormat, GroupBy, HistoryAction, SnapshotAction};
+use rdeptree::badge::{count_outdated, Badge, Outdated};
+use rdeptree::builder::DagBuilder;
+use rdeptree::cache::ResultCache;
+use rdeptree::dag::{
+    dump_meta_dir, get_orphans, get_requirers, parse_line, DependencyDag, DistributionName,
+    RequiredDistribution, RootStrategy,
+};
+use rdeptree::deadline;
+use rdeptree::events;
+use rdeptree::export::write_sqlite;
+use rdeptree::fleet::{audit_venvs, default_jobs, find_venvs, Discovery, VenvAudit};
+use rdeptree::graph::{dependency_closure, fingerprint, prune, retain_edge_kinds, DEFAULT_TOOLING};
+#[cfg(feature = "network")]
+use rdeptree::health::{assess, sort_health};
+use rdeptree::i18n::{
+    debug, error, info, set_lang, set_log_format, set_quiet, set_verbose, tr, trf, warn, Lang, Msg,
+};
+use rdeptree::ignore::IgnoreRules;
+#[cfg(feature = "network")]
+use rdeptree::index::{latest_version, JsonApiIndex, RemoteResolver};
+use rdeptree::locator::{
+    get_inherited_site_packages, get_marker_values, get_python_interpreter_loc, get_site_dirs,
+    get_site_packages_loc, get_venv_site_packages, ignore_wrapper, interpreter_prefix,
+    python_trees, scan_paths, use_interpreter, wrapper_venv, PythonTree,
+};
+use rdeptree::lookup::find_distribution;
+#[cfg(feature = "network")]
+use rdeptree::marker::MarkerEnv;
+use rdeptree::network;
+use rdeptree::normalize::pep503;
+#[cfg(feature = "network")]
+use rdeptree::origin::Origin;
+use rdeptree::origin::SourceDriftCheck;
+use rdeptree::permissions::PermissionsCheck;
+#[cfg(feature = "network")]
+use rdeptree::plan::UpgradePlanner;
+use rdeptree::plugin::ExternalCheck;
+use rdeptree::policy::{
+    check_duplicates, check_extras, check_requires_python, check_sizes, next_python_minor,
+    CheckRegistry, SizePolicy,
+};
+use rdeptree::profile::Profile;
+use rdeptree::record::{distribution_size, record_fingerprint, verify_distribution};
+use rdeptree::render::{
+    render_cache, render_cached_requirements, render_constraints, render_extras, render_fleet,
+    render_focus, render_history, render_info, render_list, render_meta_dump, render_package_diff,
+    render_parsed_line, render_profile, render_python_trees, render_requirement_edits,
+    render_reverse_dag, render_scan_paths, render_snapshot_diff, render_snapshot_diff_dot,
+    render_vendor_list, render_verify_report, render_violations, ReleaseAges, RenderOptions,
+};
+#[cfg(feature = "network")]
+use rdeptree::render::{render_health, render_remote_tree, render_upgrade_plan};
+use rdeptree::renderer::{RendererRegistry, Summary};
+use rdeptree::requirements::{Edit, RequirementsFile};
+use rdeptree::server::LookupServer;
+use rdeptree::snapshot::{
+    append_history, default_history_path, diff_package, diff_snapshots, read_history, Snapshot,
+};
+use rdeptree::source::open_source_of;
+use rdeptree::syspkg::{system_packages, PackageManager};
+#[cfg(feature = "network")]
+use rdeptree::update::{
+    is_newer, latest_release, platform_asset_name, replace_executable, CURRENT_VERSION,
+};
+use rdeptree::wheelcache::{scan_cache, CachedDistribution};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+#[cfg(feature = "network")]
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{io, process};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Amount of the slowest metadata files listed by `--profile`
+const PROFILE_SLOWEST_FILES: usize = 10;
+
+/// Exit code of runs cut short by `--timeout`, the one of coreutils `timeout`
+const TIMED_OUT_EXIT_CODE: i32 = 124;
+
+/// Exit when the time budget of the run is used up,
+/// whatever was found is expected to be printed already
+fn exit_if_timed_out() {
+    if let Some(timeout) = deadline::timeout().filter(|_| deadline::expired()) {
+        warn(&trf(Msg::TimeLimitExceeded, &[&timeout.as_secs()]));
+        process::exit(TIMED_OUT_EXIT_CODE);
+    }
+}
+
+/// Print translated error to stderr and exit with non-zero code
+fn fail(message: &str) -> ! {
+    error(message);
+    process::exit(1);
+}
+
+/// Exit with an error if output could not be written.
+/// Closed pipe (e.g. `rdeptree | head`) means the reader has
+/// everything it wanted, so it is not an error
+fn check_output(result: io::Result<()>) {
+    match result {
+        Ok(()) => (),
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+        Err(err) => fail(&trf(Msg::OutputUnwritable, &[&err])),
+    }
+}
+
+/// Resolve user supplied package name or exit with a helpful message
+fn resolve_package<'a>(dag: &'a DependencyDag, name: &str, fuzzy: bool) -> &'a DistributionName {
+    find_distribution(dag, name, fuzzy).unwrap_or_else(|err| fail(&err.to_string()))
+}
+
+/// Print where Name, Version and every requirement of a package were read from,
+/// see `--verbose`
+fn report_field_sources(dag: &DependencyDag, name: &DistributionName) {
+    let Some(meta) = dag.get(name) else {
+        return;
+    };
+    let fields = [
+        ("Name", &meta.name_source),
+        ("Version", &meta.version_source),
+    ];
+    for (field, source) in fields {
+        if let Some(source) = source {
+            debug(&trf(
+                Msg::MetadataFieldSource,
+                &[&field, name, &source.to_string()],
+            ));
+        }
+    }
+    for dependency in meta.sorted_dependencies() {
+        if let Some(source) = &dependency.source {
+            let field = format!("Requires-Dist {}", dependency.name);
+            debug(&trf(
+                Msg::MetadataFieldSource,
+                &[&field, name, &source.to_string()],
+            ));
+        }
+    }
+}
+
+/// Explain with `--verbose` why required packages are absent: their
+/// requirements can never apply in the evaluated env
+fn report_excluded_by_marker(dag: &DependencyDag) {
+    let mut excluded: Vec<(&DistributionName, &RequiredDistribution)> = dag
+        .iter()
+        .flat_map(|(name, meta)| meta.excluded_by_marker.iter().map(move |d| (name, d)))
+        .collect();
+    excluded.sort_by(|a, b| (&a.1.name, a.0).cmp(&(&b.1.name, b.0)));
+    for (requirer, requirement) in excluded {
+        let marker = requirement.marker.as_deref().unwrap_or_default();
+        debug(&trf(
+            Msg::RequirementExcludedByMarker,
+            &[&requirement.name, requirer, &marker],
+        ));
+    }
+}
+
+/// Location of the history store, either given by user or the default one
+fn history_store(store: &Option<PathBuf>) -> PathBuf {
+    store
+        .clone()
+        .or_else(default_history_path)
+        .unwrap_or_else(|| fail(tr(Msg::HistoryStoreUnknown)))
+}
+
+fn load_snapshot(path: &Path) -> Snapshot {
+    Snapshot::load(path)
+        .unwrap_or_else(|err| fail(&trf(Msg::SnapshotUnreadable, &[&path.display(), &err])))
+}
+
+/// Print changes between two snapshots in the selected format
+fn render_diff(out: &mut dyn Write, old: &Snapshot, new: &Snapshot, format: DiffFormat) {
+    check_output(match format {
+        DiffFormat::Text => render_snapshot_diff(out, &diff_snapshots(old, new)),
+        DiffFormat::Dot => render_snapshot_diff_dot(out, old, new),
+    });
+}
+
+/// Builder with parsing options given on the command line, without paths
+fn env_builder(cli: &Cli) -> DagBuilder {
+    DagBuilder::new()
+        .merge_strategy(cli.merge_strategy.into())
+        .include_extras(&cli.extra)
+        .evaluate_markers(cli.evaluate_markers)
+        .follow_egg_info(cli.follow_egg_info)
+        .strict(cli.strict)
+}
+
+/// Parse a single environment given with `--path` the same way as the located one
+fn build_env_dag(cli: &Cli, path: &Path) -> DependencyDag {
+    env_builder(cli)
+        .paths([path])
+        .build()
+        .unwrap_or_else(|err| fail(&trf(Msg::ParsingFailed, &[&err])))
+}
+
+/// Send progress events to the file or descriptor given, see `--events-file`
+fn open_events(cli: &Cli) {
+    #[cfg(unix)]
+    let path = cli
+        .events_fd
+        .map(|fd| PathBuf::from(format!("/dev/fd/{}", fd)))
+        .or_else(|| cli.events_file.clone());
+    #[cfg(not(unix))]
+    let path = cli.events_file.clone();
+    let Some(path) = path else {
+        return;
+    };
+    // descriptors are opened without truncating what the wrapper set up
+    let opened = if path.starts_with("/dev/fd") {
+        OpenOptions::new().append(true).open(&path)
     } else {
-        Err("Please just invoker rdeptree with no args")
+        File::create(&path)
+    };
+    match opened {
+        Ok(file) => events::set_sink(Box::new(file)),
+        Err(err) => fail(&trf(Msg::EventsUnwritable, &[&path.display(), &err])),
+    }
+}
+
+/// Command or flag of the run which queries the network, see `--offline`
+fn network_use(cli: &Cli) -> Option<&'static str> {
+    match &cli.command {
+        #[cfg(feature = "network")]
+        Some(Commands::SelfManage { .. }) => return Some("self"),
+        #[cfg(feature = "network")]
+        Some(Commands::RemoteTree { .. }) => return Some("remote-tree"),
+        #[cfg(feature = "network")]
+        Some(Commands::PlanUpgrade { .. }) => return Some("plan-upgrade"),
+        #[cfg(feature = "network")]
+        Some(Commands::Health { .. }) => return Some("health"),
+        _ => (),
+    }
+    #[cfg(feature = "network")]
+    if cli.show_age {
+        return Some("--show-age");
+    }
+    #[cfg(feature = "network")]
+    if cli.show_latest {
+        return Some("--show-latest");
     }
+    None
+}
+
+/// Run commands which don't need any python env.
+/// Returns false if there is no command or it requires env to be located first
+fn run_offline_command(cli: &Cli, out: &mut dyn Write) -> bool {
+    let Some(command) = &cli.command else {
+        return false;
+    };
+    match command {
+        // debugging helper for the grammar
+        Commands::ParseLine => {
+            let mut line = String::new();
+            if let Err(err) = io::stdin().read_line(&mut line) {
+                fail(&trf(Msg::StdinUnreadable, &[&err]));
+            }
+            match parse_line(line.trim_end_matches(['\r', '\n'])) {
+                Some(parsed_line) => check_output(render_parsed_line(out, &parsed_line)),
+                None => {
+                    fail(tr(Msg::LineNotRecognized));
+                }
+            }
+        }
+        Commands::Paths => {
+            let (interpreter_loc, paths, inherited_paths) =
+                locate_roots(cli, &mut Profile::default());
+            let reason = match (cli.path.is_empty(), &cli.python_version, &interpreter_loc) {
+                (false, _, _) => "given with --path",
+                (true, Some(_), _) => "site-packages of the chosen --python-version",
+                (true, None, Some(_)) => "site-packages of the interpreter",
+                (true, None, None) => "venv layout, the interpreter can not be run",
+            };
+            // the same roots in the same order as the builder scans them
+            let scanned: Vec<(PathBuf, &'static str)> = paths
+                .iter()
+                .map(|path| (path.clone(), reason))
+                .chain(
+                    inherited_paths
+                        .into_iter()
+                        .map(|path| (path, "system site-packages inherited by the venv")),
+                )
+                .collect();
+            // skipped dirs of the interpreter are listed even when --path is given
+            let site_dirs = match interpreter_loc {
+                Some(interpreter_loc) => Ok(interpreter_loc),
+                None if !cli.path.is_empty() => get_python_interpreter_loc(),
+                None => Err("the interpreter can not be run"),
+            }
+            .and_then(|interpreter_loc| get_site_dirs(&interpreter_loc))
+            .map_err(info)
+            .ok();
+            let python_path: Vec<PathBuf> = env::var_os("PYTHONPATH")
+                .map(|value| {
+                    env::split_paths(&value)
+                        .filter(|path| !path.as_os_str().is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let paths = scan_paths(
+                &scanned,
+                !cli.path.is_empty(),
+                site_dirs.as_ref(),
+                &python_path,
+            );
+            check_output(render_scan_paths(out, &paths));
+        }
+        Commands::CacheScan { dirs, requires } => {
+            let cached = scan_cache(dirs);
+            if cached.is_empty() {
+                let dirs: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+                fail(&trf(Msg::NoCachedWheels, &[&dirs.join(", ")]));
+            }
+            match requires {
+                Some(package) => {
+                    let name = pep503(package);
+                    let versions: Vec<&CachedDistribution> =
+                        cached.iter().filter(|c| c.name == name).collect();
+                    if versions.is_empty() {
+                        fail(&trf(Msg::PackageNotCached, &[package]));
+                    }
+                    check_output(render_cached_requirements(out, &versions));
+                }
+                None => check_output(render_cache(out, &cached)),
+            }
+        }
+        Commands::PythonVersions => {
+            let (prefix, trees) = located_python_trees();
+            if trees.is_empty() {
+                fail(&trf(Msg::NoPythonVersions, &[&prefix.display()]));
+            }
+            check_output(render_python_trees(out, &trees));
+        }
+        Commands::Snapshot {
+            action: SnapshotAction::Diff { old, new, format },
+        } => {
+            render_diff(out, &load_snapshot(old), &load_snapshot(new), *format);
+        }
+        Commands::DiffPackage { package } => {
+            let [old_path, new_path] = cli.path.as_slice() else {
+                fail(&trf(Msg::DiffPackagePathsRequired, &[&cli.path.len()]));
+            };
+            let old = Snapshot::from_dag(
+                &build_env_dag(cli, old_path),
+                std::slice::from_ref(old_path),
+            );
+            let new = Snapshot::from_dag(
+                &build_env_dag(cli, new_path),
+                std::slice::from_ref(new_path),
+            );
+            let name = pep503(package);
+            if !old.packages.contains_key(&name) && !new.packages.contains_key(&name) {
+                fail(&trf(Msg::PackageNotInstalled, &[package]));
+            }
+            check_output(render_package_diff(
+                out,
+                &name,
+                &diff_package(&old, &new, &name),
+            ));
+        }
+        Commands::ScanAll {
+            root,
+            probe_interpreters,
+            jobs,
+        } => {
+            let venvs = find_venvs(root);
+            if venvs.is_empty() {
+                fail(&trf(Msg::NoVenvsFound, &[&root.display()]));
+            }
+            let discovery = if *probe_interpreters {
+                Discovery::Interpreter
+            } else {
+                Discovery::Layout
+            };
+            let jobs = jobs.map_or_else(default_jobs, usize::from);
+            let audits = audit_venvs(&venvs, &env_builder(cli), discovery, jobs);
+            check_output(render_fleet(out, root, &audits));
+            if !audits.iter().all(VenvAudit::healthy) {
+                check_output(out.flush());
+                process::exit(1);
+            }
+        }
+        Commands::History {
+            store,
+            action: HistoryAction::List,
+        } => {
+            let store = history_store(store);
+            match read_history(&store) {
+                Ok(snapshots) => check_output(render_history(out, &snapshots)),
+                Err(err) => fail(&trf(Msg::HistoryStoreUnreadable, &[&store.display(), &err])),
+            }
+        }
+        Commands::History {
+            store,
+            action: HistoryAction::Diff { from, to, format },
+        } => {
+            let store = history_store(store);
+            let snapshots = read_history(&store).unwrap_or_else(|err| {
+                fail(&trf(Msg::HistoryStoreUnreadable, &[&store.display(), &err]))
+            });
+            let to = to.unwrap_or(snapshots.len().saturating_sub(1));
+            let from = from.unwrap_or(to.saturating_sub(1));
+            match (snapshots.get(from), snapshots.get(to)) {
+                (Some(old), Some(new)) => render_diff(out, old, new, *format),
+                _ => fail(&trf(
+                    Msg::HistoryIndexOutOfRange,
+                    &[&snapshots.len(), &from, &to],
+                )),
+            }
+        }
+        #[cfg(feature = "network")]
+        Commands::SelfManage {
+            release_url,
+            action,
+        } => {
+            let release = latest_release(release_url)
+                .unwrap_or_else(|err| fail(&trf(Msg::UpdateCheckFailed, &[&err])));
+            let latest = release.version();
+            if !is_newer(CURRENT_VERSION, latest) {
+                check_output(writeln!(out, "{}", trf(Msg::UpToDate, &[&CURRENT_VERSION])));
+                return true;
+            }
+            match action {
+                SelfAction::CheckUpdate => check_output(writeln!(
+                    out,
+                    "{}",
+                    trf(Msg::UpdateAvailable, &[&latest, &CURRENT_VERSION])
+                )),
+                SelfAction::Update => {
+                    let Some((asset, checksum)) = release
+                        .platform_asset()
+                        .and_then(|asset| Some((asset, release.checksum_asset(asset)?)))
+                    else {
+                        fail(&trf(
+                            Msg::NoReleaseBinary,
+                            &[&latest, &platform_asset_name()],
+                        ));
+                    };
+                    let replaced =
+                        env::current_exe()
+                            .map_err(|err| err.to_string())
+                            .and_then(|exe| {
+                                replace_executable(
+                                    &exe,
+                                    &asset.browser_download_url,
+                                    &checksum.browser_download_url,
+                                )
+                            });
+                    if let Err(err) = replaced {
+                        fail(&trf(Msg::UpdateFailed, &[&err]));
+                    }
+                    check_output(writeln!(out, "{}", trf(Msg::Updated, &[&latest])));
+                }
+            }
+        }
+        #[cfg(feature = "network")]
+        Commands::RemoteTree {
+            requirement,
+            max_depth,
+            marker_value,
+        } => {
+            let index = JsonApiIndex::new(&cli.index_url);
+            let marker_env = MarkerEnv::new().with_values(marker_value.iter().cloned().collect());
+            match RemoteResolver::new(&index, marker_env, *max_depth).resolve(requirement) {
+                Ok(tree) => check_output(render_remote_tree(out, &tree, 0)),
+                Err(err) => fail(&trf(Msg::RemoteTreeFailed, &[&err])),
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Install prefix of the located interpreter with python minor versions under it
+fn located_python_trees() -> (PathBuf, Vec<PythonTree>) {
+    let interpreter_loc = get_python_interpreter_loc()
+        .unwrap_or_else(|err| fail(&trf(Msg::InterpreterNotFound, &[&err])));
+    let prefix = interpreter_prefix(&interpreter_loc)
+        .unwrap_or(&interpreter_loc)
+        .to_path_buf();
+    let trees = python_trees(&prefix);
+    (prefix, trees)
+}
+
+/// Interpreter and site-packages dirs of the python minor version chosen
+/// with `--python-version`, among the ones under the located prefix
+fn locate_python_version(version: &str) -> (Option<PathBuf>, Vec<PathBuf>) {
+    let (prefix, trees) = located_python_trees();
+    match trees.into_iter().find(|tree| tree.version == version) {
+        Some(tree) => (tree.interpreter, tree.site_packages),
+        None => fail(&trf(
+            Msg::PythonVersionNotInstalled,
+            &[&version, &prefix.display()],
+        )),
+    }
+}
+
+/// OS packages owning the distributions, with `--show-os-package`
+fn os_packages(cli: &Cli, dag: &DependencyDag) -> Option<HashMap<DistributionName, String>> {
+    if !cli.show_os_package {
+        return None;
+    }
+    let Some(manager) = PackageManager::detect() else {
+        warn(tr(Msg::PackageDatabaseNotFound));
+        return None;
+    };
+    system_packages(manager, dag)
+        .map_err(|err| warn(&trf(Msg::OsPackageQueryFailed, &[&err])))
+        .ok()
+}
+
+/// Locate interpreter and site-packages dir of current python env.
+/// When the interpreter can not be run (e.g. minimal containers without
+/// python on PATH), site-packages are taken from the VIRTUAL_ENV layout
+/// and features which need the interpreter are disabled
+fn locate_env() -> (Option<PathBuf>, Vec<PathBuf>) {
+    let located = get_python_interpreter_loc()
+        .map_err(|err| (Msg::InterpreterNotFound, err))
+        .and_then(|interpreter_loc| {
+            let path = get_site_packages_loc(&interpreter_loc)
+                .map_err(|err| (Msg::SitePackagesNotFound, err))?;
+            Ok((interpreter_loc, path))
+        });
+
+    match located {
+        Ok((interpreter_loc, path)) => {
+            // TODO: put this into locator
+            if !path.exists() {
+                warn(tr(Msg::PathDoesNotExist));
+            }
+            (Some(interpreter_loc), vec![path])
+        }
+        Err((msg, err)) => {
+            let paths = get_venv_site_packages();
+            if paths.is_empty() {
+                fail(&trf(msg, &[&err]));
+            }
+            warn(&trf(Msg::InterpreterUnavailable, &[&err]));
+            (None, paths)
+        }
+    }
+}
+
+/// Interpreter, site-packages dirs and the inherited system site-packages dirs
+/// to scan: dirs given with `--path`, the chosen `--python-version` or the located env
+fn locate_roots(cli: &Cli, profile: &mut Profile) -> (Option<PathBuf>, Vec<PathBuf>, Vec<PathBuf>) {
+    if !cli.path.is_empty() {
+        return (None, cli.path.clone(), Vec::new());
+    }
+    let (interpreter_loc, paths) = match &cli.python_version {
+        Some(version) => profile.time("locate", || locate_python_version(version)),
+        None => profile.time("locate", locate_env),
+    };
+    (interpreter_loc, paths, get_inherited_site_packages())
+}
+
+/// Patterns of the project ignore file followed by `--exclude` ones
+fn ignore_rules(cli: &Cli) -> IgnoreRules {
+    let file = match cli.no_ignore_file {
+        true => None,
+        false => std::env::current_dir()
+            .ok()
+            .and_then(|dir| IgnoreRules::find(&dir)),
+    };
+    let mut rules = match file {
+        Some(path) => IgnoreRules::load(&path).unwrap_or_else(|err| {
+            warn(&trf(Msg::IgnoreFileUnreadable, &[&path.display(), &err]));
+            IgnoreRules::default()
+        }),
+        None => IgnoreRules::default(),
+    };
+    for pattern in &cli.exclude {
+        rules.add(pattern);
+    }
+    rules
+}
+
+/// Python version checked by `check --python-upgrade`: the given one,
+/// or the next minor version of the env interpreter
+fn target_python(
+    given: Option<&str>,
+    interpreter_loc: Option<&Path>,
+) -> Result<String, &'static str> {
+    if let Some(python) = given {
+        return Ok(python.to_string());
+    }
+    let interpreter_loc = interpreter_loc.ok_or("python interpreter is not located")?;
+    let values = get_marker_values(interpreter_loc)?;
+    values
+        .get("python_version")
+        .and_then(|version| next_python_minor(version))
+        .ok_or("python version is not reported by the interpreter")
+}
+
+/// Cache of expensive lookups, see `--cache-dir`
+fn result_cache(cli: &Cli) -> ResultCache {
+    match &cli.cache_dir {
+        Some(dir) => ResultCache::new(dir.clone(), cli.cache_ttl.saturating_mul(SECONDS_PER_DAY))
+            .refresh(cli.refresh),
+        None => ResultCache::disabled(),
+    }
+}
+
+/// Query the index for upload times of installed versions, see `--show-age`
+#[cfg(feature = "network")]
+fn release_ages(cli: &Cli, dag: &DependencyDag, cache: &ResultCache) -> Option<ReleaseAges> {
+    if !cli.show_age {
+        return None;
+    }
+    let index = JsonApiIndex::new(&cli.index_url);
+    let mut released = HashMap::new();
+    for name in index_packages(dag) {
+        if deadline::expired() {
+            break;
+        }
+        let version = &dag[name].installed_version;
+        let key = format!("{} {}=={}", cli.index_url, name, version);
+        match cache.get_or_try_insert_with("upload-time", &key, || index.upload_time(name, version))
+        {
+            Ok(Some(time)) => {
+                released.insert(name.clone(), time);
+            }
+            Ok(None) => (),
+            Err(err) => warn(&trf(Msg::ReleaseDateUnknown, &[name, &err])),
+        }
+    }
+    Some(ReleaseAges {
+        released,
+        now: unix_now(),
+        max_age_days: cli.max_age,
+    })
+}
+
+/// Query the index for the newest release of installed packages, see `--show-latest`.
+/// Also tells whether every lookup was answered
+#[cfg(feature = "network")]
+fn latest_versions(
+    cli: &Cli,
+    dag: &DependencyDag,
+    cache: &ResultCache,
+) -> (HashMap<DistributionName, String>, bool) {
+    let mut latest = HashMap::new();
+    // freshness is what a badge reports, when the index can be queried
+    let badge = matches!(cli.command, Some(Commands::Badge { .. })) && !cli.offline;
+    if !cli.show_latest && !badge {
+        return (latest, true);
+    }
+    let index = JsonApiIndex::new(&cli.index_url);
+    let mut complete = true;
+    for name in index_packages(dag) {
+        if deadline::expired() {
+            complete = false;
+            break;
+        }
+        let key = format!("{} {}", cli.index_url, name);
+        match cache.get_or_try_insert_with("latest-version", &key, || latest_version(&index, name))
+        {
+            Ok(Some(version)) => {
+                latest.insert(name.clone(), version);
+            }
+            Ok(None) => (),
+            Err(err) => {
+                complete = false;
+                warn(&trf(Msg::LatestVersionUnknown, &[name, &err]));
+            }
+        }
+    }
+    (latest, complete)
+}
+
+#[cfg(feature = "network")]
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Installed packages which were installed from the index, sorted by name
+#[cfg(feature = "network")]
+fn index_packages(dag: &DependencyDag) -> Vec<&DistributionName> {
+    let mut names: Vec<&DistributionName> = dag
+        .iter()
+        // vcs checkouts, local paths and direct urls have no release on the index
+        .filter(|(_, meta)| Origin::of(meta) == Origin::Index)
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(not(feature = "network"))]
+fn release_ages(_cli: &Cli, _dag: &DependencyDag, _cache: &ResultCache) -> Option<ReleaseAges> {
+    None
+}
+
+#[cfg(not(feature = "network"))]
+fn latest_versions(
+    _cli: &Cli,
+    _dag: &DependencyDag,
+    _cache: &ResultCache,
+) -> (HashMap<DistributionName, String>, bool) {
+    (HashMap::new(), true)
 }
 
 fn main() {
     // step 1: get and validate input params
-    if let Err(e) = check_input_params() {
-        eprintln!("Incorrect input params: {:?}", e);
-        std::process::exit(1);
+    let cli = Cli::parse_with_env();
+    set_lang(cli.lang.unwrap_or_else(Lang::from_env));
+    set_quiet(cli.quiet);
+    set_verbose(cli.verbose);
+    set_log_format(cli.log_format.into());
+    if let Some(timeout) = cli.timeout {
+        deadline::set_timeout(Duration::from_secs(timeout));
+    }
+    network::set_offline(cli.offline);
+    open_events(&cli);
+    if cli.ignore_wrapper {
+        if let Some(venv) = env::current_exe().ok().and_then(|exe| wrapper_venv(&exe)) {
+            info(&trf(Msg::WrapperVenvIgnored, &[&venv.display()]));
+            ignore_wrapper(venv);
+        }
+    }
+    if let Some(interpreter) = &cli.python {
+        use_interpreter(interpreter.clone());
+    }
+    if let Some(usage) = network_use(&cli).filter(|_| cli.offline) {
+        fail(&trf(Msg::NetworkDisabled, &[&usage]));
+    }
+
+    let mut out = BufWriter::new(io::stdout().lock());
+    if run_offline_command(&cli, &mut out) {
+        check_output(out.flush());
+        exit_if_timed_out();
+        return;
     }
 
     // step 2: locate current python env and
     // get location of <site-packages> dir
-    let interpreter_loc = get_python_interpreter_loc().unwrap_or_else(|err| {
-        eprintln!(
-            "ERROR: Can not locate python interpreter location due to an error:\n{:?}",
-            err
-        );
-        std::process::exit(1);
-    });
+    let mut profile = Profile::default();
+    let (interpreter_loc, paths, inherited_paths) = locate_roots(&cli, &mut profile);
 
-    let path = get_site_packages_loc(&interpreter_loc).unwrap_or_else(|err| {
-        eprintln!(
-            "ERROR: Can not locate python site-packages location due to an error:\n{:?}",
-            err
-        );
-        std::process::exit(1);
+    // step 3: parse metadata to dag
+    // Parse base information
+    let mut builder = env_builder(&cli)
+        .paths(&paths)
+        .inherited_paths(&inherited_paths);
+    // without interpreter only extras are evaluated
+    match (cli.evaluate_markers, &interpreter_loc) {
+        (true, Some(interpreter_loc)) => {
+            match profile.time("locate", || get_marker_values(interpreter_loc)) {
+                Ok(values) => builder = builder.marker_values(values),
+                Err(err) => warn(err),
+            }
+        }
+        (true, None) => warn(tr(Msg::MarkerValuesUnknown)),
+        (false, _) => (),
+    }
+    let mut dag = builder.build_profiled(&mut profile).unwrap_or_else(|err| {
+        error(&trf(Msg::ParsingFailed, &[&err]));
+        exit_if_timed_out();
+        process::exit(1)
     });
 
-    // TODO: put this into locator
-    if !path.exists() {
-        eprintln!("Path must point to an existing entity");
+    let start = Instant::now();
+    if !cli.edge_kinds.is_empty() {
+        retain_edge_kinds(&mut dag, &cli.edge_kinds);
     }
 
-    // step 3: parse metadata to dag
-    // Parse base information
-    let dag = get_dep_dag_from_env(&path).unwrap_or_else(|err| {
-        eprintln!("Problem parsing installed distributions: {err}");
-        process::exit(1);
-    });
+    // --keep wins over --exclude, which wins over the ignore file
+    let mut excluded: HashSet<DistributionName> = ignore_rules(&cli).ignored(&dag);
+    if cli.exclude_defaults {
+        excluded.extend(DEFAULT_TOOLING.iter().map(|n| n.to_string()));
+    }
+    let kept: HashSet<String> = cli.keep.iter().map(|k| pep503(k)).collect();
+    excluded.retain(|n| !kept.contains(n));
+    if !excluded.is_empty() {
+        prune(&mut dag, &excluded);
+    }
+    if !cli.edge_kinds.is_empty() || !excluded.is_empty() {
+        profile.add_stage("graph", start.elapsed());
+    }
 
-    let non_empty_dependenices_names: HashSet<&String> = dag
-        .values()
-        .into_iter()
-        .filter_map(|v| {
-            if !v.dependencies.is_empty() {
-                Some(&v.dependencies)
-            } else {
-                None
+    report_excluded_by_marker(&dag);
+    let cache = result_cache(&cli);
+
+    if matches!(cli.roots, RootStrategy::Requested) && !dag.values().any(|meta| meta.requested) {
+        warn(tr(Msg::RequestedMarkersMissing));
+    }
+    let (latest_versions, latest_complete) = latest_versions(&cli, &dag, &cache);
+    let render_opts = RenderOptions {
+        show_installer: cli.show_installer,
+        show_requirers: cli.show_requirers,
+        show_requested: cli.show_requested,
+        show_requires_python: cli.show_requires_python,
+        color_origin: cli.group_by == Some(GroupBy::Origin) && cli.color.enabled(),
+        max_depth: None,
+        packages: cli
+            .package
+            .iter()
+            .map(|p| resolve_package(&dag, p, cli.fuzzy).clone())
+            .collect(),
+        roots: match &cli.roots {
+            RootStrategy::Explicit(names) => RootStrategy::Explicit(
+                names
+                    .iter()
+                    .map(|n| resolve_package(&dag, n, cli.fuzzy).clone())
+                    .collect(),
+            ),
+            roots => roots.clone(),
+        },
+        group_by_origin: cli.group_by == Some(GroupBy::Origin),
+        group_by_prefix: cli.group_by == Some(GroupBy::Prefix),
+        os_packages: os_packages(&cli, &dag),
+        release_ages: release_ages(&cli, &dag, &cache),
+        latest_versions,
+        summary: !cli.no_summary,
+        json_summary: cli.json_summary,
+        root_cost: cli.root_cost,
+    };
+    // step 4: run requested command
+    let succeeded = profile.time("render", || match &cli.command {
+        Some(Commands::Why { package }) => {
+            let name = resolve_package(&dag, package, cli.fuzzy);
+            check_output(render_reverse_dag(
+                &mut out,
+                &dag,
+                name,
+                None,
+                0,
+                &render_opts,
+            ));
+            true
+        }
+        Some(Commands::Focus { package, up, down }) => {
+            let name = resolve_package(&dag, package, cli.fuzzy);
+            check_output(render_focus(&mut out, &dag, name, *up, *down, &render_opts));
+            true
+        }
+        Some(Commands::Verify { package }) => {
+            let mut names: Vec<&DistributionName> = match package {
+                Some(p) => vec![resolve_package(&dag, p, cli.fuzzy)],
+                None => dag.keys().collect(),
+            };
+            names.sort();
+
+            let mut problems_found = false;
+            for name in names {
+                if deadline::expired() {
+                    break;
+                }
+                let meta = &dag[name];
+                // the location tells apart copies of the same version in different envs,
+                // mtimes and sizes of the files make any change to them miss the cache
+                let verified = record_fingerprint(&meta.meta_dir).and_then(|fingerprint| {
+                    let key = format!(
+                        "{}=={} {} {}",
+                        name,
+                        meta.installed_version,
+                        meta.meta_dir.display(),
+                        fingerprint
+                    );
+                    cache.get_or_try_insert_with("record", &key, || {
+                        verify_distribution(&meta.meta_dir)
+                    })
+                });
+                match verified {
+                    Ok(problems) => {
+                        problems_found |= !problems.is_empty();
+                        check_output(render_verify_report(&mut out, name, &problems));
+                    }
+                    Err(err) => warn(&trf(Msg::RecordUnreadable, &[name, &err])),
+                }
+            }
+            !problems_found
+        }
+        Some(Commands::Check {
+            max_env_size,
+            max_package_size,
+            requirements,
+            suggest,
+            write,
+            plugin,
+            python_upgrade,
+            source_drift,
+            permissions,
+        }) => {
+            let size_policy = SizePolicy {
+                max_env_size: *max_env_size,
+                max_package_size: *max_package_size,
+            };
+
+            let mut sizes: BTreeMap<DistributionName, u64> = BTreeMap::new();
+            if size_policy.max_env_size.is_some() || size_policy.max_package_size.is_some() {
+                for (name, meta) in &dag {
+                    match distribution_size(&meta.meta_dir) {
+                        Ok(size) => {
+                            sizes.insert(name.clone(), size);
+                        }
+                        Err(err) => warn(&trf(Msg::RecordUnreadable, &[name, &err])),
+                    }
+                }
+            }
+
+            let mut violations = check_sizes(&sizes, &size_policy);
+            violations.extend(check_duplicates(&dag));
+            violations.extend(check_extras(&dag));
+            if let Some(python) = python_upgrade {
+                match target_python(python.as_deref(), interpreter_loc.as_deref()) {
+                    Ok(python) => violations.extend(check_requires_python(&dag, &python)),
+                    Err(err) => warn(&trf(Msg::PythonVersionUnknown, &[&err])),
+                }
+            }
+
+            let mut checks = CheckRegistry::default();
+            if *source_drift {
+                checks.register(Box::new(SourceDriftCheck));
+            }
+            if *permissions {
+                checks.register(Box::new(PermissionsCheck));
+            }
+            for path in plugin {
+                checks.register(Box::new(ExternalCheck::new(path)));
+            }
+            let (plugin_violations, failures) = checks.run(&dag);
+            violations.extend(plugin_violations);
+            for (name, err) in &failures {
+                warn(&trf(Msg::CheckPluginFailed, &[name, err]));
+            }
+
+            let mut edits = Vec::new();
+            if let Some(path) = requirements {
+                let file = RequirementsFile::load(path).unwrap_or_else(|err| {
+                    fail(&trf(Msg::RequirementsUnreadable, &[&path.display(), &err]))
+                });
+                edits = file.drift(&dag);
+                if *write && !edits.is_empty() {
+                    if let Err(err) = file.save(&edits) {
+                        fail(&trf(Msg::RequirementsUnwritable, &[&path.display(), &err]));
+                    }
+                } else {
+                    violations.extend(edits.iter().map(Edit::to_violation));
+                }
             }
-        })
-        .flatten()
-        .map(|v| &v.name)
-        .collect();
 
-    let top_level_distributions: Vec<&String> = dag
-        .keys()
-        .into_iter()
-        .filter_map(|k| {
-            if !non_empty_dependenices_names.contains(k) {
-                Some(k)
+            check_output(render_violations(&mut out, &violations));
+            if let (true, Some(path)) = (suggest, requirements) {
+                check_output(render_requirement_edits(&mut out, path, &edits));
+            }
+            let passed = violations.is_empty() && failures.is_empty();
+            events::emit(
+                "check_finished",
+                serde_json::json!({
+                    "violations": violations.len(),
+                    "failed_checks": failures.len(),
+                    "passed": passed,
+                }),
+            );
+            passed
+        }
+        Some(Commands::Fingerprint) => {
+            check_output(writeln!(out, "{}", fingerprint(&dag)));
+            true
+        }
+        Some(Commands::Snapshot {
+            action: SnapshotAction::Save { file },
+        }) => {
+            if let Err(err) = Snapshot::from_dag(&dag, &paths).save(file) {
+                fail(&trf(Msg::SnapshotUnwritable, &[&file.display(), &err]));
+            }
+            true
+        }
+        Some(Commands::VendorList { package }) => {
+            let name = resolve_package(&dag, package, cli.fuzzy);
+            let (installed, missing): (Vec<_>, Vec<_>) = dependency_closure(&dag, name)
+                .into_iter()
+                .partition(|name| dag.contains_key(*name));
+            check_output(render_vendor_list(&mut out, &dag, &installed));
+            for missing_name in &missing {
+                warn(&trf(Msg::ClosureIncomplete, &[missing_name, name]));
+            }
+            missing.is_empty()
+        }
+        Some(Commands::Extract { package, output }) => {
+            let name = resolve_package(&dag, package, cli.fuzzy);
+            let snapshot = Snapshot::from_dag(&dag, &paths).extract(name);
+            if let Err(err) = snapshot.save(output) {
+                fail(&trf(Msg::SnapshotUnwritable, &[&output.display(), &err]));
+            }
+            true
+        }
+        Some(Commands::Export { sqlite }) => {
+            let mut findings = check_duplicates(&dag);
+            findings.extend(check_extras(&dag));
+            if let Err(err) = write_sqlite(sqlite, &dag, &findings) {
+                fail(&trf(Msg::DatabaseUnwritable, &[&sqlite.display(), &err]));
+            }
+            true
+        }
+        Some(Commands::History {
+            store,
+            action: HistoryAction::Record,
+        }) => {
+            let store = history_store(store);
+            if let Err(err) = append_history(&store, &Snapshot::from_dag(&dag, &paths)) {
+                fail(&trf(Msg::HistoryStoreUnwritable, &[&store.display(), &err]));
+            }
+            true
+        }
+        Some(
+            Commands::ParseLine
+            | Commands::Paths
+            | Commands::PythonVersions
+            | Commands::CacheScan { .. }
+            | Commands::Snapshot { .. }
+            | Commands::History { .. }
+            | Commands::DiffPackage { .. }
+            | Commands::ScanAll { .. },
+        ) => {
+            unreachable!("handled before env lookup")
+        }
+        #[cfg(feature = "network")]
+        Some(Commands::SelfManage { .. }) => unreachable!("handled before env lookup"),
+        #[cfg(feature = "network")]
+        Some(Commands::RemoteTree { .. }) => unreachable!("handled before env lookup"),
+        #[cfg(feature = "network")]
+        Some(Commands::PlanUpgrade {
+            package,
+            with_requirers,
+        }) => {
+            let name = resolve_package(&dag, package, cli.fuzzy);
+            let index = JsonApiIndex::new(&cli.index_url);
+            let marker_values = interpreter_loc
+                .as_ref()
+                .and_then(|interpreter_loc| get_marker_values(interpreter_loc).ok())
+                .unwrap_or_default();
+            let resolver =
+                RemoteResolver::new(&index, MarkerEnv::new().with_values(marker_values), None);
+            match UpgradePlanner::new(&resolver, &dag, *with_requirers).plan(name) {
+                Ok(pins) => check_output(render_upgrade_plan(&mut out, &pins)),
+                Err(err) => fail(&trf(Msg::NoUpgradePlan, &[name, &err])),
+            }
+            true
+        }
+        #[cfg(feature = "network")]
+        Some(Commands::Health { months, sort }) => {
+            let index = JsonApiIndex::new(&cli.index_url);
+            let now = unix_now();
+            let mut report = Vec::new();
+            for name in index_packages(&dag) {
+                if deadline::expired() {
+                    break;
+                }
+                let key = format!("{} {}", cli.index_url, name);
+                match cache.get_or_try_insert_with("releases", &key, || index.releases(name)) {
+                    Ok(releases) => report.push(assess(
+                        name,
+                        &dag[name].installed_version,
+                        &releases,
+                        now,
+                        months * 30,
+                    )),
+                    Err(err) => warn(&trf(Msg::ReleasesUnknown, &[name, &err])),
+                }
+            }
+            sort_health(&mut report, (*sort).into());
+            check_output(render_health(&mut out, &report, *months));
+            true
+        }
+        Some(Commands::Orphans) => {
+            // without markers every top level package would look like an orphan
+            if !dag.values().any(|meta| meta.requested) {
+                warn(tr(Msg::RequestedMarkersMissing));
+                return true;
+            }
+            check_output(render_list(&mut out, &dag, &get_orphans(&dag)));
+            true
+        }
+        Some(Commands::List { filter }) => {
+            let mut names: Vec<&DistributionName> = dag.keys().collect();
+            if let Some(filter) = filter {
+                names.retain(|name| filter.matches(&dag[*name].installed_version));
+            }
+            names.sort();
+            check_output(render_list(&mut out, &dag, &names));
+            true
+        }
+        Some(Commands::Extras { package }) => {
+            let name = resolve_package(&dag, package, cli.fuzzy);
+            if dag[name].extras.is_empty() {
+                info(&trf(Msg::NoExtrasDeclared, &[name]));
+            }
+            check_output(render_extras(&mut out, &dag, name, &render_opts));
+            true
+        }
+        Some(Commands::Constraints { package }) => {
+            let name = pep503(package);
+            let name = if dag.contains_key(&name) || !get_requirers(&dag, &name).is_empty() {
+                &name
             } else {
-                None
+                resolve_package(&dag, package, cli.fuzzy)
+            };
+            check_output(render_constraints(&mut out, &dag, name));
+            true
+        }
+        Some(Commands::DumpMeta { package }) => {
+            let name = resolve_package(&dag, package, cli.fuzzy);
+            let meta_dir = &dag[name].meta_dir;
+            let roots: Vec<&Path> = paths
+                .iter()
+                .chain(&inherited_paths)
+                .map(|p| p.as_path())
+                .collect();
+            let dumped = open_source_of(&roots, meta_dir)
+                .and_then(|source| dump_meta_dir(source.as_ref(), meta_dir));
+            match dumped {
+                Ok(lines) => check_output(render_meta_dump(&mut out, meta_dir, &lines)),
+                Err(err) => fail(&trf(Msg::MetadataUnreadable, &[&meta_dir.display(), &err])),
             }
-        })
-        .collect();
+            true
+        }
+        Some(Commands::Info { package }) => {
+            let name = resolve_package(&dag, package, cli.fuzzy);
+            check_output(render_info(&mut out, &dag, name));
+            report_field_sources(&dag, name);
+            true
+        }
+        Some(Commands::Badge { out: file }) => {
+            let outdated = if !cfg!(feature = "network") || cli.offline {
+                Outdated::NotChecked
+            } else if !latest_complete {
+                Outdated::Unknown
+            } else {
+                Outdated::Count(count_outdated(&dag, &render_opts.latest_versions))
+            };
+            let badge = Badge::of(&Summary::of(&dag, &render_opts), outdated);
+            match file {
+                Some(file) => {
+                    let written = File::create(file).and_then(|mut f| badge.render_svg(&mut f));
+                    if let Err(err) = written {
+                        fail(&trf(Msg::BadgeUnwritable, &[&file.display(), &err]));
+                    }
+                }
+                None => check_output(badge.render_svg(&mut out)),
+            }
+            true
+        }
+        Some(Commands::Lookup { .. }) => {
+            check_output(LookupServer::new(&dag).serve(io::stdin().lock(), &mut out));
+            true
+        }
+        None => {
+            // step 5: print results
+            let registry = RendererRegistry::default();
+            let renderer = registry
+                .get(&cli.format)
+                .expect("format is validated by the cli parser");
+            // plain text like pipdeptree's, but silenced by --quiet
+            for warning in renderer.warnings(&dag, &render_opts) {
+                info(&warning);
+            }
+            check_output(renderer.render(&dag, &render_opts, &mut out));
+            true
+        }
+    });
+
+    check_output(out.flush());
 
-    // step 5: print results
-    for tlp in top_level_distributions {
-        render_dag(&dag, tlp, None, 0);
+    if cli.profile {
+        render_profile(&profile, PROFILE_SLOWEST_FILES);
+    }
+    exit_if_timed_out();
+    if !succeeded {
+        process::exit(1);
     }
 }
@@ -0,0 +1,431 @@
+use crate::normalize::pep503;
+use crate::parser::{DepParser, Rule};
+use pest::iterators::Pair;
+use pest::Parser;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+type MarkerResult = Result<bool, &'static str>;
+
+/// Results of already evaluated markers keyed by the raw marker string.
+/// The same few python_version clauses repeat across thousands of packages,
+/// so big envs evaluate every distinct marker only once
+#[derive(Debug, Default)]
+struct MarkerCache(Mutex<HashMap<String, MarkerResult>>);
+
+impl MarkerCache {
+    fn get_or_insert_with(&self, marker: &str, f: impl FnOnce() -> MarkerResult) -> MarkerResult {
+        if let Some(result) = self.0.lock().unwrap().get(marker) {
+            return *result;
+        }
+        let result = f();
+        self.0.lock().unwrap().insert(marker.to_string(), result);
+        result
+    }
+}
+
+/// Cached results are valid only for the env they were computed in,
+/// so copies start empty
+impl Clone for MarkerCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for MarkerCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// Values of environment markers (python_version, sys_platform, ...) and
+/// requested extras, used to evaluate markers of Requires-Dist lines.
+/// Comparisons with variables missing from the env are considered true,
+/// so an incomplete env never hides dependencies.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MarkerEnv {
+    values: HashMap<String, String>,
+    extras: HashSet<String>,
+    cache: MarkerCache,
+}
+
+impl MarkerEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_values(mut self, values: HashMap<String, String>) -> Self {
+        self.values.extend(values);
+        self.cache = MarkerCache::default();
+        self
+    }
+
+    pub fn with_extras<I, S>(mut self, extras: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.extras
+            .extend(extras.into_iter().map(|e| pep503(e.as_ref())));
+        self.cache = MarkerCache::default();
+        self
+    }
+
+    /// Evaluate raw marker string, e.g. `python_version < "3.11" and extra == "test"`
+    pub fn evaluate(&self, marker: &str) -> MarkerResult {
+        self.cache
+            .get_or_insert_with(marker, || self.evaluate_uncached(marker))
+    }
+
+    fn evaluate_uncached(&self, marker: &str) -> MarkerResult {
+        let marker_or = DepParser::parse(Rule::marker, marker)
+            .map_err(|_| "Failed to parse environment marker")?
+            .next()
+            .unwrap()
+            .into_inner()
+            .next()
+            .unwrap();
+        Ok(self.evaluate_or(marker_or))
+    }
+
+    fn evaluate_or(&self, pair: Pair<Rule>) -> bool {
+        pair.into_inner()
+            .any(|marker_and| self.evaluate_and(marker_and))
+    }
+
+    fn evaluate_and(&self, pair: Pair<Rule>) -> bool {
+        pair.into_inner().all(|atom| self.evaluate_atom(atom))
+    }
+
+    fn evaluate_atom(&self, pair: Pair<Rule>) -> bool {
+        let mut inner = pair.into_inner();
+        let first = inner.next().unwrap();
+        if first.as_rule() == Rule::marker_or {
+            return self.evaluate_or(first);
+        }
+        let op = inner.next().unwrap();
+        let second = inner.next().unwrap();
+        // normalize whitespace of `not  in`
+        let op: String = op.as_str().split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let (var, literal) = if first.as_rule() == Rule::env_var {
+            (first.as_str(), second.as_str())
+        } else {
+            (second.as_str(), first.as_str())
+        };
+        let literal = &literal[1..literal.len() - 1];
+
+        if var == "extra" {
+            let requested = self.extras.contains(&pep503(literal));
+            return match op.as_str() {
+                "==" => requested,
+                "!=" => !requested,
+                _ => true,
+            };
+        }
+        let Some(value) = self.values.get(var) else {
+            return true;
+        };
+
+        if first.as_rule() == Rule::env_var {
+            compare(value, &op, literal)
+        } else {
+            compare(literal, &op, value)
+        }
+    }
+}
+
+/// Parsed environment marker, for exports to tools doing their own
+/// environment-specific filtering. Groups of a single clause are flattened
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum MarkerExpr {
+    Or {
+        or: Vec<MarkerExpr>,
+    },
+    And {
+        and: Vec<MarkerExpr>,
+    },
+    /// `variable op "value"`, reversed when written as `"value" op variable`
+    Compare {
+        variable: String,
+        op: String,
+        value: String,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        reversed: bool,
+    },
+}
+
+impl MarkerExpr {
+    /// Parse raw marker string, e.g. `python_version < "3.11" and extra == "test"`
+    pub fn parse(marker: &str) -> Result<Self, &'static str> {
+        let marker_or = DepParser::parse(Rule::marker, marker)
+            .map_err(|_| "Failed to parse environment marker")?
+            .next()
+            .unwrap()
+            .into_inner()
+            .next()
+            .unwrap();
+        Ok(Self::from_or(marker_or))
+    }
+
+    /// Extras the marker compares with `extra == "<name>"`, normalized and sorted
+    pub fn extras(&self) -> Vec<String> {
+        let mut extras = Vec::new();
+        self.collect_extras(&mut extras);
+        extras.sort();
+        extras.dedup();
+        extras
+    }
+
+    fn collect_extras(&self, extras: &mut Vec<String>) {
+        match self {
+            Self::Or { or: clauses } | Self::And { and: clauses } => {
+                clauses.iter().for_each(|c| c.collect_extras(extras))
+            }
+            Self::Compare {
+                variable,
+                op,
+                value,
+                ..
+            } => {
+                if variable == "extra" && op == "==" {
+                    extras.push(pep503(value));
+                }
+            }
+        }
+    }
+
+    fn from_or(pair: Pair<Rule>) -> Self {
+        let mut clauses: Vec<Self> = pair.into_inner().map(Self::from_and).collect();
+        match clauses.len() {
+            1 => clauses.remove(0),
+            _ => Self::Or { or: clauses },
+        }
+    }
+
+    fn from_and(pair: Pair<Rule>) -> Self {
+        let mut clauses: Vec<Self> = pair.into_inner().map(Self::from_atom).collect();
+        match clauses.len() {
+            1 => clauses.remove(0),
+            _ => Self::And { and: clauses },
+        }
+    }
+
+    fn from_atom(pair: Pair<Rule>) -> Self {
+        let mut inner = pair.into_inner();
+        let first = inner.next().unwrap();
+        if first.as_rule() == Rule::marker_or {
+            return Self::from_or(first);
+        }
+        let op = inner.next().unwrap();
+        let second = inner.next().unwrap();
+        let reversed = first.as_rule() != Rule::env_var;
+        let (variable, literal) = match reversed {
+            false => (first.as_str(), second.as_str()),
+            true => (second.as_str(), first.as_str()),
+        };
+        Self::Compare {
+            variable: variable.to_string(),
+            // normalize whitespace of `not  in`
+            op: op.as_str().split_whitespace().collect::<Vec<_>>().join(" "),
+            value: literal[1..literal.len() - 1].to_string(),
+            reversed,
+        }
+    }
+}
+
+/// Release numbers of a plain dotted version, None for anything else
+pub(crate) fn release_numbers(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|n| n.parse().ok()).collect()
+}
+
+/// Compare releases as if the shorter one was padded with zeros
+pub(crate) fn compare_releases(lhs: &[u64], rhs: &[u64]) -> Ordering {
+    let len = lhs.len().max(rhs.len());
+    let padded = |numbers: &[u64]| {
+        let mut numbers = numbers.to_vec();
+        numbers.resize(len, 0);
+        numbers
+    };
+    padded(lhs).cmp(&padded(rhs))
+}
+
+/// Whether a plain dotted version satisfies a specifier, e.g. python version
+/// and Requires-Python `>=3.8, !=3.9.*, <4`. None if the version or some
+/// clause is not understood
+pub fn release_allowed(specifier: &str, version: &str) -> Option<bool> {
+    const OPERATORS: [&str; 8] = ["===", "==", "!=", "~=", "<=", ">=", "<", ">"];
+    let release = release_numbers(version)?;
+    let mut allowed = true;
+    for clause in specifier.split(',').map(str::trim) {
+        if clause.is_empty() {
+            continue;
+        }
+        let op = OPERATORS.iter().find(|op| clause.starts_with(**op))?;
+        let rhs = clause[op.len()..].trim();
+        allowed &= match (*op, rhs.strip_suffix(".*")) {
+            // ==3.8.* matches every 3.8 release
+            ("==" | "!=", Some(prefix)) => {
+                let prefix = release_numbers(prefix)?;
+                let matches = release.len() >= prefix.len() && release[..prefix.len()] == prefix;
+                matches == (*op == "==")
+            }
+            _ => {
+                release_numbers(rhs)?;
+                compare(version, op, rhs)
+            }
+        };
+    }
+    Some(allowed)
+}
+
+/// Compare marker operands, as versions if both of them look like versions
+fn compare(lhs: &str, op: &str, rhs: &str) -> bool {
+    match op {
+        "in" => return rhs.contains(lhs),
+        "not in" => return !rhs.contains(lhs),
+        "===" => return lhs == rhs,
+        _ => (),
+    }
+
+    let ordering = match (release_numbers(lhs), release_numbers(rhs)) {
+        (Some(lhs_numbers), Some(rhs_numbers)) => {
+            if op == "~=" {
+                // ~=3.10.2 means >=3.10.2, ==3.10.*
+                let prefix = &rhs_numbers[..rhs_numbers.len().saturating_sub(1).max(1)];
+                let lhs_prefix = &lhs_numbers[..prefix.len().min(lhs_numbers.len())];
+                return compare_releases(&lhs_numbers, &rhs_numbers) != Ordering::Less
+                    && compare_releases(lhs_prefix, prefix) == Ordering::Equal;
+            }
+            compare_releases(&lhs_numbers, &rhs_numbers)
+        }
+        _ => lhs.cmp(rhs),
+    };
+
+    match op {
+        "==" => ordering == Ordering::Equal,
+        "!=" => ordering != Ordering::Equal,
+        "<" => ordering == Ordering::Less,
+        "<=" => ordering != Ordering::Greater,
+        ">" => ordering == Ordering::Greater,
+        ">=" => ordering != Ordering::Less,
+        _ => ordering == Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn env() -> MarkerEnv {
+        MarkerEnv::new()
+            .with_values(HashMap::from([
+                ("python_version".to_string(), "3.11".to_string()),
+                ("python_full_version".to_string(), "3.11.4".to_string()),
+                ("sys_platform".to_string(), "linux".to_string()),
+                ("os_name".to_string(), "posix".to_string()),
+            ]))
+            .with_extras(["Socks"])
+    }
+
+    #[test]
+    fn evaluate_markers() {
+        let cases = [
+            ("python_version < \"3.11\"", false),
+            ("python_version >= '3.8'", true),
+            ("python_version == \"3.11.0\"", true),
+            ("python_full_version ~= '3.11.2'", true),
+            ("python_full_version ~= '3.10.0'", false),
+            ("python_version > \"3.9\"", true),
+            ("sys_platform == 'win32'", false),
+            ("'linux' in sys_platform", true),
+            ("os_name not in 'nt'", true),
+            ("extra == 'test'", false),
+            ("extra == \"socks\"", true),
+            ("extra != 'test'", true),
+            ("extra == 'test' or python_version >= '3.8'", true),
+            ("extra == 'socks' and sys_platform == 'win32'", false),
+            (
+                "(sys_platform == 'win32' or os_name == 'posix') and extra == 'socks'",
+                true,
+            ),
+            // unknown values never hide dependencies
+            ("platform_machine == 'arm64'", true),
+        ];
+        let env = env();
+        for (marker, expected) in cases {
+            assert_eq!(env.evaluate(marker), Ok(expected), "{}", marker);
+        }
+    }
+
+    #[test]
+    fn evaluation_is_cached_per_env() {
+        let env = env();
+        for _ in 0..3 {
+            assert_eq!(env.evaluate("extra == 'test'"), Ok(false));
+            assert!(env.evaluate("python_version <").is_err());
+        }
+        let cached = |env: &MarkerEnv| env.cache.0.lock().unwrap().len();
+        assert_eq!(cached(&env), 2);
+        assert_eq!(cached(&env.clone()), 0);
+
+        let env = env.with_extras(["test"]);
+        assert_eq!(cached(&env), 0);
+        assert_eq!(env.evaluate("extra == 'test'"), Ok(true));
+    }
+
+    #[test]
+    fn release_specifiers() {
+        let cases = [
+            (">=3.8", "3.13", Some(true)),
+            (">=3.8, <3.13", "3.13", Some(false)),
+            ("!=3.12.*, >=3.9", "3.12", Some(false)),
+            ("==3.*", "3.13", Some(true)),
+            ("~=3.10", "3.13", Some(true)),
+            ("<=3.12", "3.12", Some(true)),
+            ("", "3.13", Some(true)),
+            (">=3.8-dev", "3.13", None),
+        ];
+        for (requires_python, python, expected) in cases {
+            assert_eq!(
+                release_allowed(requires_python, python),
+                expected,
+                "{} {}",
+                requires_python,
+                python
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_invalid_marker() {
+        assert!(env().evaluate("python_version <").is_err());
+        assert!(env().evaluate("unknown == '1'").is_err());
+    }
+
+    #[test]
+    fn parse_marker_structure() {
+        let parsed = MarkerExpr::parse(
+            r#"extra == "test" and ('linux' in sys_platform or python_version not  in "3.8")"#,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::json!({"and": [
+                {"variable": "extra", "op": "==", "value": "test"},
+                {"or": [
+                    {"variable": "sys_platform", "op": "in", "value": "linux", "reversed": true},
+                    {"variable": "python_version", "op": "not in", "value": "3.8"},
+                ]},
+            ]})
+        );
+        assert_eq!(parsed.extras(), ["test"]);
+        let joined =
+            MarkerExpr::parse(r#"(extra == "Socks") or (extra == 'test' and os_name != "nt")"#);
+        assert_eq!(joined.unwrap().extras(), ["socks", "test"]);
+        assert!(MarkerExpr::parse("python_version <").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+//! Switch disabling every network access, see `--offline`, and the curl
+//! runner index and release queries go through, so nothing leaves the host
+//! when set. Builds without the `network` feature have no runner and are
+//! always offline
+
+#[cfg(feature = "network")]
+use crate::deadline;
+#[cfg(feature = "network")]
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Disable network access for the rest of the run
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Network access is disabled, by `--offline` or at build time
+pub fn offline() -> bool {
+    !cfg!(feature = "network") || OFFLINE.load(Ordering::Relaxed)
+}
+
+/// Error when network access is disabled
+pub fn check(url: &str) -> Result<(), String> {
+    if !cfg!(feature = "network") {
+        return Err(format!(
+            "Query {} was not sent: rdeptree is built without the network feature",
+            url
+        ));
+    }
+    if offline() {
+        return Err(format!(
+            "Query {} was not sent: network access is disabled by --offline",
+            url
+        ));
+    }
+    Ok(())
+}
+
+/// Run curl with the given args, returning its stdout. The query may take
+/// up to the timeout, but no longer than what is left of the time budget
+#[cfg(feature = "network")]
+pub fn curl(url: &str, args: &[&str], timeout: Duration) -> Result<Vec<u8>, String> {
+    check(url)?;
+    deadline::check().map_err(|err| format!("Query {} was not sent: {}", url, err))?;
+    let max_time = deadline::limit(timeout).as_secs_f64().to_string();
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", &max_time])
+        .args(args)
+        .arg(url)
+        .output()
+        .map_err(|err| format!("Unable to run curl to query {}: {}", url, err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Query {} was unsuccessful: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Builds without the `network` feature don't run curl at all
+#[cfg(not(feature = "network"))]
+pub fn curl(url: &str, _args: &[&str], _timeout: Duration) -> Result<Vec<u8>, String> {
+    Err(check(url).expect_err("always offline without the network feature"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn queries_refused_when_offline() {
+        assert_eq!(check("https://pypi.org/pypi/six/json"), Ok(()));
+        set_offline(true);
+        assert!(check("https://pypi.org/pypi/six/json")
+            .unwrap_err()
+            .contains("disabled by --offline"));
+        set_offline(false);
+    }
+
+    #[cfg(not(feature = "network"))]
+    #[test]
+    fn queries_refused_without_network_feature() {
+        assert!(offline());
+        assert!(check("https://pypi.org/pypi/six/json")
+            .unwrap_err()
+            .contains("without the network feature"));
+    }
+}
@@ -0,0 +1,153 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// from https://packaging.python.org/en/latest/specifications/name-normalization/#name-normalization
+const PEP503_NORMALIZE_REGEX: &str = r"[-_.]+";
+
+static PEP503_NORMALIZE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(PEP503_NORMALIZE_REGEX).unwrap());
+
+/// Normalize distribution name according to PEP 503:
+/// runs of "-", "_" and "." are collapsed into a single "-"
+/// and the result is lowercased.
+/// All names stored in the dag must pass through this function,
+/// otherwise edges between distributions may be silently lost.
+pub fn pep503(name: &str) -> String {
+    PEP503_NORMALIZE_RE.replace_all(name, "-").to_lowercase()
+}
+
+/// Canonical form of a version specifier set: whitespace is removed and
+/// comma separated clauses are sorted, so `>= 1.0,<2` and `<2, >=1.0`
+/// both become `<2,>=1.0`
+pub fn canonical_specifier(specifier: &str) -> String {
+    let mut clauses: Vec<String> = specifier
+        .split(',')
+        .map(|c| c.split_whitespace().collect::<String>())
+        .filter(|c| !c.is_empty())
+        .collect();
+    clauses.sort();
+    clauses.dedup();
+    clauses.join(",")
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Check whether a name is already in its PEP 503 normalized form
+    pub fn is_pep503(name: &str) -> bool {
+        pep503(name) == name
+    }
+
+    /// Valid distribution names as written by authors, i.e. not normalized
+    pub fn distribution_name() -> impl Strategy<Value = String> {
+        "[A-Za-z0-9]([A-Za-z0-9._-]{0,20}[A-Za-z0-9])?"
+    }
+
+    /// PEP 440 versions in normalized form, e.g. `1!2.0rc1.post3.dev4+ubuntu.5`.
+    /// Local label is generated only if `with_local` is set
+    pub fn version_with(with_local: bool, min_release: usize) -> impl Strategy<Value = String> {
+        let local = if with_local {
+            prop::option::of("([a-z][a-z0-9]{0,4}|[1-9][0-9]{0,3})(\\.([a-z][a-z0-9]{0,4}|[1-9][0-9]{0,3})){0,2}").boxed()
+        } else {
+            Just(None).boxed()
+        };
+        (
+            prop::option::of(1u32..5),
+            prop::collection::vec(0u32..100, min_release..min_release + 3),
+            prop::option::of((prop::sample::select(vec!["a", "b", "rc"]), 0u32..20)),
+            prop::option::of(0u32..20),
+            prop::option::of(0u32..20),
+            local,
+        )
+            .prop_map(|(epoch, release, pre, post, dev, local)| {
+                let mut version = String::new();
+                if let Some(epoch) = epoch {
+                    version.push_str(&format!("{}!", epoch));
+                }
+                let release: Vec<String> = release.iter().map(|n| n.to_string()).collect();
+                version.push_str(&release.join("."));
+                if let Some((kind, n)) = pre {
+                    version.push_str(&format!("{}{}", kind, n));
+                }
+                if let Some(n) = post {
+                    version.push_str(&format!(".post{}", n));
+                }
+                if let Some(n) = dev {
+                    version.push_str(&format!(".dev{}", n));
+                }
+                if let Some(local) = local {
+                    version.push_str(&format!("+{}", local));
+                }
+                version
+            })
+    }
+
+    pub fn version() -> impl Strategy<Value = String> {
+        version_with(true, 1)
+    }
+
+    /// Single version clause, valid for strict PEP 440 parsing as well:
+    /// local versions only with equality and at least two release numbers for `~=`
+    pub fn specifier() -> impl Strategy<Value = String> {
+        prop_oneof![
+            (
+                prop::sample::select(vec![">=", "<=", "<", ">", "==="]),
+                version_with(false, 1)
+            ),
+            (Just("~="), version_with(false, 2)),
+            (prop::sample::select(vec!["==", "!="]), version()),
+        ]
+        .prop_map(|(op, version)| format!("{}{}", op, version))
+    }
+
+    /// Comma separated version clauses
+    pub fn specifier_set() -> impl Strategy<Value = String> {
+        prop::collection::vec(specifier(), 1..4).prop_map(|clauses| clauses.join(","))
+    }
+
+    #[test]
+    fn pep503_examples() {
+        for (input, expected) in [
+            ("friendly-bard", "friendly-bard"),
+            ("Friendly-Bard", "friendly-bard"),
+            ("FRIENDLY-BARD", "friendly-bard"),
+            ("friendly.bard", "friendly-bard"),
+            ("friendly_bard", "friendly-bard"),
+            ("friendly--bard", "friendly-bard"),
+            ("FrIeNdLy-._.-bArD", "friendly-bard"),
+        ] {
+            assert_eq!(pep503(input), expected);
+        }
+    }
+
+    #[test]
+    fn canonical_specifier_examples() {
+        for (input, expected) in [
+            (">= 1.0,<2", "<2,>=1.0"),
+            ("<2, >=1.0", "<2,>=1.0"),
+            ("== 1.0.1", "==1.0.1"),
+            ("<3,>=1.21.1", "<3,>=1.21.1"),
+            (">=1.0, >=1.0", ">=1.0"),
+            ("", ""),
+        ] {
+            assert_eq!(canonical_specifier(input), expected);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn pep503_is_idempotent(name in "[A-Za-z0-9][A-Za-z0-9._-]{0,30}") {
+            let once = pep503(&name);
+            prop_assert_eq!(pep503(&once), once.clone());
+            prop_assert!(is_pep503(&once));
+        }
+
+        #[test]
+        fn pep503_ignores_case_and_separators(name in "[A-Za-z0-9]{1,10}([._-]+[A-Za-z0-9]{1,10}){0,3}") {
+            let alt = name.to_uppercase().replace(['.', '_'], "-");
+            prop_assert_eq!(pep503(&name), pep503(&alt));
+        }
+    }
+}
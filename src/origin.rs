@@ -0,0 +1,469 @@
+use crate::dag::{DependencyDag, DistributionMeta};
+use crate::i18n::{trf, warn, Msg};
+use crate::locator::{run_with_timeout, COMMAND_TIMEOUT};
+use crate::policy::{Check, Violation};
+use crate::source::MetadataSource;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const DIRECT_URL_FILE_NAME: &str = "direct_url.json";
+
+/// Content of direct_url.json, see
+/// https://packaging.python.org/en/latest/specifications/direct-url-data-structure/
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct DirectUrl {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vcs_info: Option<VcsInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir_info: Option<DirInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_info: Option<ArchiveInfo>,
+    /// path of the project inside the repository or archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subdirectory: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct VcsInfo {
+    pub vcs: String,
+    pub commit_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_revision: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct DirInfo {
+    #[serde(default)]
+    pub editable: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ArchiveInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+impl DirectUrl {
+    /// Requirement line installing the same reference again: a PEP 440
+    /// direct reference pinned to the installed commit or archive hash,
+    /// editable installs of local dirs are `-e` lines
+    pub fn requirement_line(&self, name: &str) -> String {
+        let mut url = match &self.vcs_info {
+            Some(vcs_info) if self.url.starts_with(&format!("{}+", vcs_info.vcs)) => {
+                format!("{}@{}", self.url, vcs_info.commit_id)
+            }
+            Some(vcs_info) => format!("{}+{}@{}", vcs_info.vcs, self.url, vcs_info.commit_id),
+            None => self.url.clone(),
+        };
+
+        let mut fragment = Vec::new();
+        if let Some(hash) = self.archive_info.as_ref().and_then(|a| a.hash.as_ref()) {
+            fragment.push(hash.clone());
+        }
+        if let Some(subdirectory) = &self.subdirectory {
+            fragment.push(format!("subdirectory={}", subdirectory));
+        }
+        if !fragment.is_empty() && !url.contains('#') {
+            url.push('#');
+            url.push_str(&fragment.join("&"));
+        }
+
+        if self.dir_info.as_ref().is_some_and(|d| d.editable) {
+            format!("-e {}", url)
+        } else {
+            format!("{} @ {}", name, url)
+        }
+    }
+}
+
+/// Requirement line installing the same distribution again,
+/// `name==version` unless it was installed from a direct reference
+pub fn requirement_line(name: &str, meta: &DistributionMeta) -> String {
+    match &meta.direct_url {
+        Some(direct_url) => direct_url.requirement_line(name),
+        None => format!("{}=={}", name, meta.installed_version),
+    }
+}
+
+/// Read direct_url.json of a dist-info dir, the file exists only
+/// for distributions installed from a direct reference
+pub fn read_direct_url(source: &dyn MetadataSource, meta_dir: &Path) -> Option<DirectUrl> {
+    let content = source.read_file(meta_dir, DIRECT_URL_FILE_NAME).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Decode `%XX` escapes of a url path, malformed escapes are kept as is
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Local dir the distribution was built from: the path of a `file://` url
+/// of a local path install, or of a vcs install cloned from a local repository.
+/// None for remote references
+pub fn source_dir(direct_url: &DirectUrl) -> Option<PathBuf> {
+    let mut url = direct_url.url.as_str();
+    if let Some(vcs_info) = &direct_url.vcs_info {
+        url = url
+            .strip_prefix(vcs_info.vcs.as_str())
+            .and_then(|u| u.strip_prefix('+'))
+            .unwrap_or(url);
+    }
+    let path = percent_decode(url.strip_prefix("file://")?);
+    // file:///C:/project on windows
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => &path[1..],
+        _ => &path,
+    };
+    let mut dir = PathBuf::from(path);
+    if let Some(subdirectory) = &direct_url.subdirectory {
+        dir.push(subdirectory);
+    }
+    Some(dir)
+}
+
+/// How the source dir of a local install differs from what was installed
+#[derive(Debug, PartialEq)]
+pub enum SourceDrift {
+    /// source dir does not exist anymore
+    Gone,
+    /// HEAD of the repository is not the recorded commit
+    Diverged { recorded: String, current: String },
+    /// the project dir has uncommitted changes
+    Dirty,
+}
+
+/// Output of a git command run in the dir, None if git reports an error,
+/// e.g. the dir is not in a repository
+fn git(dir: &Path, args: &[&str]) -> Result<Option<String>, String> {
+    let output = run_with_timeout(
+        Command::new("git").arg("-C").arg(dir).args(args),
+        COMMAND_TIMEOUT,
+    )
+    .map_err(|err| format!("can not run git: {}", err))?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(
+        String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    ))
+}
+
+/// Compare the source dir of a local install with the recorded commit, if any.
+/// Dirs outside of git repositories can not drift, remote references are not checked
+pub fn source_drift(direct_url: &DirectUrl) -> Result<Vec<SourceDrift>, String> {
+    let Some(dir) = source_dir(direct_url) else {
+        return Ok(Vec::new());
+    };
+    if !dir.is_dir() {
+        return Ok(vec![SourceDrift::Gone]);
+    }
+    let Some(head) = git(&dir, &["rev-parse", "HEAD"])? else {
+        return Ok(Vec::new());
+    };
+    let mut drift = Vec::new();
+    if let Some(vcs_info) = direct_url.vcs_info.as_ref().filter(|v| v.vcs == "git") {
+        if vcs_info.commit_id != head {
+            drift.push(SourceDrift::Diverged {
+                recorded: vcs_info.commit_id.clone(),
+                current: head,
+            });
+        }
+    }
+    let status = git(&dir, &["status", "--porcelain", "--", "."])?;
+    if status.is_some_and(|status| !status.is_empty()) {
+        drift.push(SourceDrift::Dirty);
+    }
+    Ok(drift)
+}
+
+/// Flag local path and vcs installs whose source dir changed since installation:
+/// removed, checked out at another commit or modified. Runs git for every such install,
+/// when git can not be run only removed dirs are reported, with a single warning
+pub struct SourceDriftCheck;
+
+impl Check for SourceDriftCheck {
+    fn name(&self) -> &str {
+        "source-drift"
+    }
+
+    fn check(&self, dag: &DependencyDag) -> Result<Vec<Violation>, String> {
+        let mut installs: Vec<(&String, &DirectUrl)> = dag
+            .iter()
+            .filter_map(|(name, meta)| Some((name, meta.direct_url.as_ref()?)))
+            .collect();
+        installs.sort_by_key(|(name, _)| *name);
+
+        let mut violations = Vec::new();
+        let mut git_failed = false;
+        for (name, direct_url) in installs {
+            let Some(dir) = source_dir(direct_url) else {
+                continue;
+            };
+            let drifts = match source_drift(direct_url) {
+                Ok(drifts) => drifts,
+                Err(err) => {
+                    if !git_failed {
+                        warn(&trf(Msg::GitUnavailable, &[&err]));
+                        git_failed = true;
+                    }
+                    continue;
+                }
+            };
+            for drift in drifts {
+                let message = match drift {
+                    SourceDrift::Gone => {
+                        format!(
+                            "{} was installed from {}, which no longer exists",
+                            name,
+                            dir.display()
+                        )
+                    }
+                    SourceDrift::Diverged { recorded, current } => format!(
+                        "{} was installed from commit {}, {} is at {}",
+                        name,
+                        recorded,
+                        dir.display(),
+                        current
+                    ),
+                    SourceDrift::Dirty => {
+                        format!("{} source {} has uncommitted changes", name, dir.display())
+                    }
+                };
+                violations.push(Violation {
+                    rule: String::from("source-drift"),
+                    package: Some(name.clone()),
+                    message,
+                });
+            }
+        }
+        Ok(violations)
+    }
+}
+
+/// Where an installed distribution came from
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Origin {
+    Index,
+    Conda,
+    Vcs,
+    LocalPath,
+    Url,
+}
+
+impl Origin {
+    pub fn of(meta: &DistributionMeta) -> Self {
+        match &meta.direct_url {
+            Some(direct_url) if direct_url.vcs_info.is_some() => Origin::Vcs,
+            Some(direct_url) if direct_url.url.starts_with("file://") => Origin::LocalPath,
+            Some(_) => Origin::Url,
+            None if meta.installer.as_deref() == Some("conda") => Origin::Conda,
+            None => Origin::Index,
+        }
+    }
+
+    /// ANSI color code used to paint distributions of this origin
+    pub fn color(&self) -> &'static str {
+        match self {
+            Origin::Index => "0",
+            Origin::Conda => "32",
+            Origin::Vcs => "35",
+            Origin::LocalPath => "33",
+            Origin::Url => "36",
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Origin::Index => "index",
+            Origin::Conda => "conda",
+            Origin::Vcs => "vcs",
+            Origin::LocalPath => "local path",
+            Origin::Url => "direct url",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn meta_with(direct_url: Option<&str>, installer: Option<&str>) -> DistributionMeta {
+        DistributionMeta {
+            direct_url: direct_url.map(|d| serde_json::from_str(d).unwrap()),
+            installer: installer.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn origin_detection() {
+        let cases = [
+            (None, Some("pip"), Origin::Index),
+            (None, Some("conda"), Origin::Conda),
+            (
+                Some(
+                    r#"{"url": "https://github.com/org/repo.git", "vcs_info": {"vcs": "git", "commit_id": "7e5a"}}"#,
+                ),
+                Some("pip"),
+                Origin::Vcs,
+            ),
+            (
+                Some(r#"{"url": "file:///home/user/project", "dir_info": {"editable": true}}"#),
+                Some("pip"),
+                Origin::LocalPath,
+            ),
+            (
+                Some(
+                    r#"{"url": "https://example.com/pkg-1.0.tar.gz", "archive_info": {"hash": "sha256=ab"}}"#,
+                ),
+                Some("uv"),
+                Origin::Url,
+            ),
+        ];
+
+        for (direct_url, installer, expected) in cases {
+            assert_eq!(Origin::of(&meta_with(direct_url, installer)), expected);
+        }
+    }
+
+    fn git_repo(dir: &Path) {
+        for args in [
+            &["init", "-q"][..],
+            &["add", "."],
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-qm",
+                "init",
+            ],
+        ] {
+            assert!(Command::new("git")
+                .arg("-C")
+                .arg(dir)
+                .args(args)
+                .status()
+                .unwrap()
+                .success());
+        }
+    }
+
+    #[test]
+    fn source_dirs_of_local_references() {
+        let cases = [
+            (
+                r#"{"url": "file:///home/user/my%20project", "dir_info": {}}"#,
+                Some("/home/user/my project"),
+            ),
+            (
+                r#"{"url": "git+file:///srv/mono", "vcs_info": {"vcs": "git", "commit_id": "7e5a"}, "subdirectory": "libs/pkg"}"#,
+                Some("/srv/mono/libs/pkg"),
+            ),
+            (
+                r#"{"url": "https://github.com/org/repo.git", "vcs_info": {"vcs": "git", "commit_id": "7e5a"}}"#,
+                None,
+            ),
+        ];
+        for (direct_url, expected) in cases {
+            let direct_url: DirectUrl = serde_json::from_str(direct_url).unwrap();
+            assert_eq!(source_dir(&direct_url), expected.map(PathBuf::from));
+        }
+    }
+
+    #[test]
+    fn drift_of_local_git_sources() {
+        let site = crate::fixture::SitePackages::new("source-drift").file("project/setup.py", "");
+        let project = site.path().join("project");
+        git_repo(&project);
+        let head = git(&project, &["rev-parse", "HEAD"]).unwrap().unwrap();
+        let direct_url = |commit: &str| DirectUrl {
+            url: format!("file://{}", project.display()),
+            vcs_info: Some(VcsInfo {
+                vcs: String::from("git"),
+                commit_id: commit.to_string(),
+                requested_revision: None,
+            }),
+            dir_info: None,
+            archive_info: None,
+            subdirectory: None,
+        };
+
+        assert_eq!(source_drift(&direct_url(&head)), Ok(vec![]));
+
+        std::fs::write(project.join("setup.py"), "changed").unwrap();
+        assert_eq!(
+            source_drift(&direct_url("0ld")),
+            Ok(vec![
+                SourceDrift::Diverged {
+                    recorded: String::from("0ld"),
+                    current: head,
+                },
+                SourceDrift::Dirty
+            ])
+        );
+
+        let gone = DirectUrl {
+            url: format!("file://{}", site.path().join("gone").display()),
+            ..direct_url("0ld")
+        };
+        assert_eq!(source_drift(&gone), Ok(vec![SourceDrift::Gone]));
+    }
+
+    #[test]
+    fn direct_reference_lines() {
+        let cases = [
+            (
+                r#"{"url": "https://github.com/org/repo.git", "vcs_info": {"vcs": "git", "commit_id": "7e5a", "requested_revision": "main"}}"#,
+                "pkg @ git+https://github.com/org/repo.git@7e5a",
+            ),
+            (
+                r#"{"url": "https://github.com/org/mono.git", "vcs_info": {"vcs": "git", "commit_id": "7e5a"}, "subdirectory": "libs/pkg"}"#,
+                "pkg @ git+https://github.com/org/mono.git@7e5a#subdirectory=libs/pkg",
+            ),
+            (
+                r#"{"url": "https://example.com/pkg-1.0.tar.gz", "archive_info": {"hash": "sha256=ab"}}"#,
+                "pkg @ https://example.com/pkg-1.0.tar.gz#sha256=ab",
+            ),
+            (
+                r#"{"url": "file:///home/user/project", "dir_info": {"editable": true}}"#,
+                "-e file:///home/user/project",
+            ),
+            (
+                r#"{"url": "file:///home/user/project", "dir_info": {}}"#,
+                "pkg @ file:///home/user/project",
+            ),
+        ];
+
+        for (direct_url, expected) in cases {
+            let direct_url: DirectUrl = serde_json::from_str(direct_url).unwrap();
+            assert_eq!(direct_url.requirement_line("pkg"), expected);
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use pest::Parser;
 use pest_derive::Parser;
 
 #[derive(Parser)]
@@ -8,6 +7,7 @@ pub struct DepParser;
 #[cfg(test)]
 mod test {
     use super::*;
+    use pest::Parser;
 
     // from https://stackoverflow.com/questions/34662713/how-can-i-create-parameterized-tests-in-rust
     macro_rules! parse_name_tests {
@@ -124,5 +124,103 @@ mod test {
         test_parse_required_distr_extra_sql_test: ("Requires-Dist: SQLAlchemy>=2.0.0; extra == \"sql-other\"", "SQLAlchemy", ">=2.0.0; extra == \"sql-other\""),
         test_parse_required_distr_python_version: ("Requires-Dist: numpy>=1.22.4; python_version < \"3.11\"", "numpy", ">=1.22.4; python_version < \"3.11\""),
         test_parse_required_distr_extra_package: ("Requires-Dist: pyarrow>=10.0.1; extra == \"pyarrow\"", "pyarrow", ">=10.0.1; extra == \"pyarrow\""),
+        test_parse_required_distr_three_versions: ("Requires-Dist: foo>=1.0,<2.0,!=1.5", "foo", ">=1.0,<2.0,!=1.5"),
+        test_parse_required_distr_spaced_versions: ("Requires-Dist: foo >= 1.0 , < 2.0", "foo", ">= 1.0 , < 2.0"),
+        test_parse_required_distr_epoch_local_marker: ("Requires-Dist: foo>=1!2.0.post1.dev3+local.1; python_version >= \"3.8\"", "foo", ">=1!2.0.post1.dev3+local.1; python_version >= \"3.8\""),
+        test_parse_required_distr_marker_and: ("Requires-Dist: foo<=1.0rc1; extra == \"test\" and python_version < \"3.11\"", "foo", "<=1.0rc1; extra == \"test\" and python_version < \"3.11\""),
+        test_parse_required_distr_marker_parens: ("Requires-Dist: foo>1; (os_name == 'nt' or sys_platform == 'cygwin') and extra == 'x'", "foo", ">1; (os_name == 'nt' or sys_platform == 'cygwin') and extra == 'x'"),
+        test_parse_required_distr_marker_in: ("Requires-Dist: foo~=1.4.5a4; 'linux' in sys_platform", "foo", "~=1.4.5a4; 'linux' in sys_platform"),
+        test_parse_required_distr_marker_not_in: ("Requires-Dist: foo===1.0+abc.7; sys_platform not in 'win32'", "foo", "===1.0+abc.7; sys_platform not in 'win32'"),
+    }
+
+    /// Version examples from the PEP 440 appendix, every one of them
+    /// must survive in a requirement with any operator and marker
+    mod pep440 {
+        use super::*;
+
+        const APPENDIX_VERSIONS: [&str; 22] = [
+            "1.0.dev456",
+            "1.0a1",
+            "1.0a2.dev456",
+            "1.0a12.dev456",
+            "1.0a12",
+            "1.0b1.dev456",
+            "1.0b2",
+            "1.0b2.post345.dev456",
+            "1.0b2.post345",
+            "1.0rc1.dev456",
+            "1.0rc1",
+            "1.0",
+            "1.0+abc.5",
+            "1.0+abc.7",
+            "1.0+5",
+            "1.0.post456.dev34",
+            "1.0.post456",
+            "1.0.15",
+            "1.1.dev1",
+            "1!1.0",
+            "1!2.0.post1.dev3+local.1",
+            "2012.15",
+        ];
+
+        const OPERATORS: [&str; 8] = [">=", "<=", "!=", "===", "==", "~=", ">", "<"];
+
+        const MARKERS: [&str; 3] = [
+            "",
+            "; extra == 'test'",
+            "; python_version >= \"3.8\" and platform_python_implementation != 'PyPy'",
+        ];
+
+        #[test]
+        fn appendix_versions_in_requirements() {
+            for version in APPENDIX_VERSIONS {
+                for op in OPERATORS {
+                    for marker in MARKERS {
+                        let dependency_str = format!("{}{},<3{}", op, version, marker);
+                        let line = format!("Requires-Dist: foo{}", dependency_str);
+                        let parsed = DepParser::parse(Rule::required_distribution_row, &line)
+                            .unwrap_or_else(|_| panic!("Row dropped: {}", line))
+                            .next()
+                            .unwrap()
+                            .into_inner()
+                            .find(|p| p.as_rule() == Rule::dependency_str)
+                            .unwrap();
+                        assert_eq!(parsed.as_str(), dependency_str);
+                    }
+                }
+            }
+        }
+    }
+
+    macro_rules! parse_newer_meta_fields_tests {
+        ($($name:ident: $value:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let (row_rule, key_rule, value_rule, input, expected_key, expected_value) = $value;
+                let result = DepParser::parse(row_rule, input)
+                    .expect("Unable to parse metadata string:\n")
+                    .next()
+                    .unwrap();
+                for pair in result.into_inner() {
+                    if pair.as_rule() == key_rule {
+                        assert_eq!(pair.as_str(), expected_key);
+                    } else if pair.as_rule() == value_rule {
+                        assert_eq!(pair.as_str(), expected_value);
+                    } else if pair.as_rule() != Rule::EOI {
+                        panic!("Unknown rule to parse: <{:?}>", pair.as_rule());
+                    }
+                }
+            }
+        )*
+        }
+    }
+
+    parse_newer_meta_fields_tests! {
+        test_parse_metadata_version: (Rule::metadata_version_row, Rule::metadata_version_kw, Rule::distribution_version, "Metadata-Version: 2.4", "Metadata-Version", "2.4"),
+        test_parse_dynamic: (Rule::dynamic_row, Rule::dynamic_kw, Rule::field_name, "Dynamic: Requires-Dist", "Dynamic", "Requires-Dist"),
+        test_parse_license_expression: (Rule::license_expression_row, Rule::license_expression_kw, Rule::free_text, "License-Expression: MIT OR Apache-2.0", "License-Expression", "MIT OR Apache-2.0"),
+        test_parse_license_file: (Rule::license_file_row, Rule::license_file_kw, Rule::free_text, "License-File: LICENSES/BSD 3.txt", "License-File", "LICENSES/BSD 3.txt"),
+        test_parse_project_url: (Rule::project_url_row, Rule::project_url_kw, Rule::free_text, "Project-URL: Release notes, https://x.org/changes", "Project-URL", "Release notes, https://x.org/changes"),
     }
 }
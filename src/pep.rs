@@ -0,0 +1,1233 @@
+//! Strict PEP 508 requirement and PEP 440 version parsing. Versions and
+//! specifiers (e.g. of `list --where`) are always parsed here, requirements
+//! only when the `pep508` feature replaces the lightweight pest grammar with it
+
+use crate::normalize::pep503;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Problem found in the input, `position` is a byte offset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Simple cursor over the parsed string
+struct Cursor<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Same as `eat`, but ignoring ASCII case
+    fn eat_ignore_case(&mut self, token: &str) -> bool {
+        let rest = self.rest();
+        if rest.len() >= token.len() && rest[..token.len()].eq_ignore_ascii_case(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn skip_ws(&mut self) -> bool {
+        !self.eat_while(|c| c == ' ' || c == '\t').is_empty()
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, ParseError> {
+        Err(ParseError {
+            message: message.to_string(),
+            position: self.pos,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PreRelease {
+    Alpha,
+    Beta,
+    Rc,
+}
+
+impl fmt::Display for PreRelease {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PreRelease::Alpha => "a",
+            PreRelease::Beta => "b",
+            PreRelease::Rc => "rc",
+        })
+    }
+}
+
+/// Segment of a local version label, numbers sort after strings
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LocalSegment {
+    Alpha(String),
+    Number(u64),
+}
+
+impl fmt::Display for LocalSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LocalSegment::Alpha(s) => f.write_str(s),
+            LocalSegment::Number(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+/// PEP 440 version, displayed in its normalized form
+#[derive(Debug, Clone, Default)]
+pub struct Version {
+    pub epoch: u64,
+    pub release: Vec<u64>,
+    pub pre: Option<(PreRelease, u64)>,
+    pub post: Option<u64>,
+    pub dev: Option<u64>,
+    pub local: Vec<LocalSegment>,
+}
+
+const PRE_RELEASE_SPELLINGS: [(&str, PreRelease); 8] = [
+    ("alpha", PreRelease::Alpha),
+    ("a", PreRelease::Alpha),
+    ("beta", PreRelease::Beta),
+    ("b", PreRelease::Beta),
+    ("preview", PreRelease::Rc),
+    ("pre", PreRelease::Rc),
+    ("rc", PreRelease::Rc),
+    ("c", PreRelease::Rc),
+];
+
+const POST_RELEASE_SPELLINGS: [&str; 3] = ["post", "rev", "r"];
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '.' | '-' | '_')
+}
+
+fn parse_number(cursor: &mut Cursor) -> Option<u64> {
+    let digits = cursor.eat_while(|c| c.is_ascii_digit());
+    if digits.is_empty() {
+        None
+    } else {
+        digits.parse().ok()
+    }
+}
+
+/// Parse `[sep] keyword [sep] [number]` part of a version,
+/// cursor is left untouched if the keyword is not found
+fn parse_labeled_number(cursor: &mut Cursor, spellings: &[&str]) -> Option<(usize, u64)> {
+    let start = cursor.pos;
+    cursor.eat_while(is_separator);
+    for (idx, spelling) in spellings.iter().enumerate() {
+        if cursor.eat_ignore_case(spelling) {
+            let before_number = cursor.pos;
+            cursor.eat_while(is_separator);
+            let number = parse_number(cursor).unwrap_or_else(|| {
+                cursor.pos = before_number;
+                0
+            });
+            return Some((idx, number));
+        }
+    }
+    cursor.pos = start;
+    None
+}
+
+impl Version {
+    fn parse_from(cursor: &mut Cursor) -> Result<Self, ParseError> {
+        let mut version = Version::default();
+        cursor.eat_ignore_case("v");
+
+        let Some(first) = parse_number(cursor) else {
+            return cursor.error("Expected version number");
+        };
+        if cursor.eat("!") {
+            let Some(release) = parse_number(cursor) else {
+                return cursor.error("Expected release number");
+            };
+            version.epoch = first;
+            version.release.push(release);
+        } else {
+            version.release.push(first);
+        }
+        loop {
+            let start = cursor.pos;
+            if !cursor.eat(".") {
+                break;
+            }
+            match parse_number(cursor) {
+                Some(n) => version.release.push(n),
+                None => {
+                    cursor.pos = start;
+                    break;
+                }
+            }
+        }
+
+        let pre_spellings: Vec<&str> = PRE_RELEASE_SPELLINGS.iter().map(|(s, _)| *s).collect();
+        if let Some((idx, n)) = parse_labeled_number(cursor, &pre_spellings) {
+            version.pre = Some((PRE_RELEASE_SPELLINGS[idx].1, n));
+        }
+
+        if let Some((_, n)) = parse_labeled_number(cursor, &POST_RELEASE_SPELLINGS) {
+            version.post = Some(n);
+        } else {
+            // implicit post release, e.g. 1.0-1
+            let start = cursor.pos;
+            if cursor.eat("-") {
+                match parse_number(cursor) {
+                    Some(n) => version.post = Some(n),
+                    None => cursor.pos = start,
+                }
+            }
+        }
+
+        if let Some((_, n)) = parse_labeled_number(cursor, &["dev"]) {
+            version.dev = Some(n);
+        }
+
+        if cursor.eat("+") {
+            loop {
+                let segment = cursor.eat_while(|c| c.is_ascii_alphanumeric());
+                if segment.is_empty() {
+                    return cursor.error("Expected local version segment");
+                }
+                version.local.push(match segment.parse() {
+                    Ok(n) => LocalSegment::Number(n),
+                    Err(_) => LocalSegment::Alpha(segment.to_ascii_lowercase()),
+                });
+                let start = cursor.pos;
+                if cursor.eat_while(is_separator).len() != 1 {
+                    cursor.pos = start;
+                    break;
+                }
+            }
+        }
+        Ok(version)
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some() || self.dev.is_some()
+    }
+
+    /// Release numbers without trailing zeros, 1.0 and 1.0.0 are the same version
+    fn trimmed_release(&self) -> &[u64] {
+        let len = self
+            .release
+            .iter()
+            .rposition(|n| *n != 0)
+            .map_or(0, |idx| idx + 1);
+        &self.release[..len]
+    }
+}
+
+impl FromStr for Version {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cursor = Cursor::new(s.trim());
+        let version = Version::parse_from(&mut cursor)?;
+        if !cursor.at_end() {
+            return cursor.error("Unexpected trailing characters in version");
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.epoch != 0 {
+            write!(f, "{}!", self.epoch)?;
+        }
+        let release: Vec<String> = self.release.iter().map(|n| n.to_string()).collect();
+        f.write_str(&release.join("."))?;
+        if let Some((kind, n)) = self.pre {
+            write!(f, "{}{}", kind, n)?;
+        }
+        if let Some(n) = self.post {
+            write!(f, ".post{}", n)?;
+        }
+        if let Some(n) = self.dev {
+            write!(f, ".dev{}", n)?;
+        }
+        if !self.local.is_empty() {
+            let local: Vec<String> = self.local.iter().map(|s| s.to_string()).collect();
+            write!(f, "+{}", local.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+/// Helper to place missing version parts before or after present ones
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Bound<T> {
+    Min,
+    Value(T),
+    Max,
+}
+
+/// Epoch, release, pre, post, dev and local parts in comparison order
+type SortKey<'a> = (
+    u64,
+    &'a [u64],
+    Bound<(PreRelease, u64)>,
+    Bound<u64>,
+    Bound<u64>,
+    &'a [LocalSegment],
+);
+
+impl Version {
+    fn sort_key(&self) -> SortKey<'_> {
+        // dev release of a final version goes before its pre releases
+        let pre = match (self.pre, self.post, self.dev) {
+            (None, None, Some(_)) => Bound::Min,
+            (None, _, _) => Bound::Max,
+            (Some(pre), _, _) => Bound::Value(pre),
+        };
+        let post = self.post.map_or(Bound::Min, Bound::Value);
+        let dev = self.dev.map_or(Bound::Max, Bound::Value);
+        (
+            self.epoch,
+            self.trimmed_release(),
+            pre,
+            post,
+            dev,
+            &self.local,
+        )
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    Arbitrary,
+}
+
+/// Longer operators first, so that `==` is not taken for a prefix of `===`
+const OPERATORS: [(&str, Operator); 8] = [
+    ("===", Operator::Arbitrary),
+    ("~=", Operator::Compatible),
+    ("==", Operator::Equal),
+    ("!=", Operator::NotEqual),
+    ("<=", Operator::LessEqual),
+    (">=", Operator::GreaterEqual),
+    ("<", Operator::Less),
+    (">", Operator::Greater),
+];
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (token, _) = OPERATORS.iter().find(|(_, op)| op == self).unwrap();
+        f.write_str(token)
+    }
+}
+
+/// Single version clause, e.g. `>=1.0` or `==2.*`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Specifier {
+    pub operator: Operator,
+    /// normalized version, may end with `.*` for `==` and `!=`,
+    /// kept verbatim for `===`
+    pub version: String,
+}
+
+impl Specifier {
+    fn parse_from(cursor: &mut Cursor) -> Result<Self, ParseError> {
+        let Some(operator) = OPERATORS
+            .iter()
+            .find(|(token, _)| cursor.eat(token))
+            .map(|(_, op)| *op)
+        else {
+            return cursor.error("Expected version operator");
+        };
+        cursor.skip_ws();
+
+        let start = cursor.pos;
+        let raw = cursor.eat_while(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '*' | '+' | '!')
+        });
+        if raw.is_empty() {
+            return cursor.error("Expected version");
+        }
+        if operator == Operator::Arbitrary {
+            return Ok(Specifier {
+                operator,
+                version: raw.to_string(),
+            });
+        }
+
+        let (raw_version, wildcard) = match raw.strip_suffix(".*") {
+            Some(prefix) => (prefix, true),
+            None => (raw, false),
+        };
+        let version: Version = raw_version.parse().map_err(|err: ParseError| ParseError {
+            message: err.message,
+            position: start + err.position,
+        })?;
+
+        let position = start;
+        let fail = |message: &str| {
+            Err(ParseError {
+                message: message.to_string(),
+                position,
+            })
+        };
+        let equality = matches!(operator, Operator::Equal | Operator::NotEqual);
+        if wildcard && !equality {
+            return fail("Wildcard is allowed only with == and !=");
+        }
+        if wildcard && (version.dev.is_some() || !version.local.is_empty()) {
+            return fail("Wildcard can not follow dev or local version");
+        }
+        if !version.local.is_empty() && !equality {
+            return fail("Local version is allowed only with == and !=");
+        }
+        if operator == Operator::Compatible && version.release.len() < 2 {
+            return fail("~= requires at least two release segments");
+        }
+
+        Ok(Specifier {
+            operator,
+            version: if wildcard {
+                format!("{}.*", version)
+            } else {
+                version.to_string()
+            },
+        })
+    }
+}
+
+impl Specifier {
+    /// Whether the version satisfies the clause, see
+    /// https://packaging.python.org/en/latest/specifications/version-specifiers/
+    pub fn contains(&self, version: &Version) -> bool {
+        if self.operator == Operator::Arbitrary {
+            return self.version.eq_ignore_ascii_case(&version.to_string());
+        }
+        let (raw, wildcard) = match self.version.strip_suffix(".*") {
+            Some(prefix) => (prefix, true),
+            None => (self.version.as_str(), false),
+        };
+        let Ok(spec) = raw.parse::<Version>() else {
+            return false;
+        };
+        // local labels matter only when the clause has one
+        let candidate = if spec.local.is_empty() {
+            version.public()
+        } else {
+            version.clone()
+        };
+
+        match self.operator {
+            Operator::Equal if wildcard => candidate.has_prefix(&spec),
+            Operator::NotEqual if wildcard => !candidate.has_prefix(&spec),
+            Operator::Equal => candidate == spec,
+            Operator::NotEqual => candidate != spec,
+            Operator::LessEqual => candidate <= spec,
+            Operator::GreaterEqual => candidate >= spec,
+            // <V excludes pre releases of V itself, unless V is a pre release
+            Operator::Less => {
+                candidate < spec
+                    && (spec.is_prerelease()
+                        || !candidate.is_prerelease()
+                        || !candidate.same_release(&spec))
+            }
+            // >V excludes post releases of V itself, unless V is a post release
+            Operator::Greater => {
+                candidate > spec
+                    && (spec.post.is_some()
+                        || candidate.post.is_none()
+                        || !candidate.same_release(&spec)
+                        || candidate.pre != spec.pre)
+            }
+            Operator::Compatible => {
+                let prefix = Version {
+                    epoch: spec.epoch,
+                    release: spec.release[..spec.release.len() - 1].to_vec(),
+                    ..Default::default()
+                };
+                candidate >= spec && candidate.has_prefix(&prefix)
+            }
+            Operator::Arbitrary => unreachable!("handled above"),
+        }
+    }
+}
+
+impl Version {
+    /// The same version without local label
+    fn public(&self) -> Version {
+        Version {
+            local: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    fn same_release(&self, other: &Version) -> bool {
+        self.epoch == other.epoch && self.trimmed_release() == other.trimmed_release()
+    }
+
+    /// Prefix match used by `==V.*`, release of the candidate
+    /// is padded with zeros to the length of the prefix
+    fn has_prefix(&self, prefix: &Version) -> bool {
+        if self.epoch != prefix.epoch {
+            return false;
+        }
+        let mut release = self.release.clone();
+        release.resize(release.len().max(prefix.release.len()), 0);
+        if release[..prefix.release.len()] != prefix.release[..] {
+            return false;
+        }
+        // prefixes like 1.0rc1.* or 1.0.post1.* fix the following parts too
+        if prefix.pre.is_some() && (self.pre != prefix.pre || release.len() != prefix.release.len())
+        {
+            return false;
+        }
+        prefix.post.is_none() || (self.pre == prefix.pre && self.post == prefix.post)
+    }
+}
+
+impl fmt::Display for Specifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.operator, self.version)
+    }
+}
+
+/// Comma separated version clauses, empty set allows any version
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpecifierSet(pub Vec<Specifier>);
+
+impl SpecifierSet {
+    fn parse_from(cursor: &mut Cursor) -> Result<Self, ParseError> {
+        let mut specifiers = vec![Specifier::parse_from(cursor)?];
+        loop {
+            let start = cursor.pos;
+            cursor.skip_ws();
+            if !cursor.eat(",") {
+                cursor.pos = start;
+                break;
+            }
+            cursor.skip_ws();
+            specifiers.push(Specifier::parse_from(cursor)?);
+        }
+        Ok(SpecifierSet(specifiers))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether the version satisfies all clauses. Pre releases are
+    /// accepted only if some clause explicitly mentions a pre release
+    pub fn contains(&self, version: &Version) -> bool {
+        let prereleases = self.0.iter().any(|s| {
+            s.version
+                .trim_end_matches(".*")
+                .parse::<Version>()
+                .is_ok_and(|v| v.is_prerelease())
+        });
+        (prereleases || !version.is_prerelease()) && self.0.iter().all(|s| s.contains(version))
+    }
+}
+
+impl FromStr for SpecifierSet {
+    type Err = ParseError;
+
+    /// Parse comma separated clauses, empty string is an empty set
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cursor = Cursor::new(s.trim());
+        if cursor.at_end() {
+            return Ok(SpecifierSet::default());
+        }
+        let specifiers = SpecifierSet::parse_from(&mut cursor)?;
+        if !cursor.at_end() {
+            return cursor.error("Unexpected trailing characters in version specifier");
+        }
+        Ok(specifiers)
+    }
+}
+
+impl fmt::Display for SpecifierSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let specifiers: Vec<String> = self.0.iter().map(|s| s.to_string()).collect();
+        f.write_str(&specifiers.join(","))
+    }
+}
+
+/// Selection of installed versions, e.g. `version<2024` or `>=1,<2`.
+/// Unlike requirements, pre releases are matched by plain clauses too
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionFilter(pub SpecifierSet);
+
+impl VersionFilter {
+    /// Unparsable versions never match
+    pub fn matches(&self, version: &str) -> bool {
+        version
+            .parse::<Version>()
+            .is_ok_and(|v| self.0 .0.iter().all(|s| s.contains(&v)))
+    }
+}
+
+impl FromStr for VersionFilter {
+    type Err = ParseError;
+
+    /// Specifier set, optionally prefixed with `version`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let specifiers = s.strip_prefix("version").unwrap_or(s);
+        Ok(VersionFilter(specifiers.parse()?))
+    }
+}
+
+/// Environment markers known to PEP 508 and its successors
+pub const MARKER_VARIABLES: [&str; 14] = [
+    "python_version",
+    "python_full_version",
+    "os_name",
+    "sys_platform",
+    "platform_release",
+    "platform_system",
+    "platform_version",
+    "platform_machine",
+    "platform_python_implementation",
+    "implementation_name",
+    "implementation_version",
+    "extra",
+    "extras",
+    "dependency_groups",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerOperator {
+    Version(Operator),
+    In,
+    NotIn,
+}
+
+impl fmt::Display for MarkerOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarkerOperator::Version(op) => write!(f, "{}", op),
+            MarkerOperator::In => f.write_str("in"),
+            MarkerOperator::NotIn => f.write_str("not in"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkerValue {
+    Variable(String),
+    Literal(String),
+}
+
+impl fmt::Display for MarkerValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarkerValue::Variable(name) => f.write_str(name),
+            MarkerValue::Literal(value) if value.contains('"') => write!(f, "'{}'", value),
+            MarkerValue::Literal(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+/// Environment marker expression, `and` binds tighter than `or`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Marker {
+    Compare(MarkerValue, MarkerOperator, MarkerValue),
+    And(Vec<Marker>),
+    Or(Vec<Marker>),
+}
+
+impl Marker {
+    fn parse_or(cursor: &mut Cursor) -> Result<Self, ParseError> {
+        let mut items = vec![Marker::parse_and(cursor)?];
+        while eat_keyword(cursor, "or") {
+            items.push(Marker::parse_and(cursor)?);
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            Marker::Or(items)
+        })
+    }
+
+    fn parse_and(cursor: &mut Cursor) -> Result<Self, ParseError> {
+        let mut items = vec![Marker::parse_atom(cursor)?];
+        while eat_keyword(cursor, "and") {
+            items.push(Marker::parse_atom(cursor)?);
+        }
+        Ok(if items.len() == 1 {
+            items.pop().unwrap()
+        } else {
+            Marker::And(items)
+        })
+    }
+
+    fn parse_atom(cursor: &mut Cursor) -> Result<Self, ParseError> {
+        cursor.skip_ws();
+        if cursor.eat("(") {
+            let marker = Marker::parse_or(cursor)?;
+            cursor.skip_ws();
+            if !cursor.eat(")") {
+                return cursor.error("Expected closing parenthesis");
+            }
+            return Ok(marker);
+        }
+
+        let lhs = parse_marker_value(cursor)?;
+        cursor.skip_ws();
+        let op = if let Some((_, op)) = OPERATORS.iter().find(|(token, _)| cursor.eat(token)) {
+            MarkerOperator::Version(*op)
+        } else if eat_keyword(cursor, "in") {
+            MarkerOperator::In
+        } else if eat_keyword(cursor, "not") && eat_keyword(cursor, "in") {
+            MarkerOperator::NotIn
+        } else {
+            return cursor.error("Expected marker operator");
+        };
+        cursor.skip_ws();
+        let rhs = parse_marker_value(cursor)?;
+        Ok(Marker::Compare(lhs, op, rhs))
+    }
+
+    fn fmt_nested(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Marker::Or(_) => write!(f, "({})", self),
+            _ => write!(f, "{}", self),
+        }
+    }
+}
+
+impl FromStr for Marker {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cursor = Cursor::new(s);
+        let marker = Marker::parse_or(&mut cursor)?;
+        cursor.skip_ws();
+        if !cursor.at_end() {
+            return cursor.error("Unexpected trailing characters in marker");
+        }
+        Ok(marker)
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Marker::Compare(lhs, op, rhs) => write!(f, "{} {} {}", lhs, op, rhs),
+            Marker::And(items) => {
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(" and ")?;
+                    }
+                    item.fmt_nested(f)?;
+                }
+                Ok(())
+            }
+            Marker::Or(items) => {
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(" or ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Eat a whole word surrounded by optional whitespace
+fn eat_keyword(cursor: &mut Cursor, keyword: &str) -> bool {
+    let start = cursor.pos;
+    cursor.skip_ws();
+    if cursor.eat(keyword)
+        && !cursor
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        true
+    } else {
+        cursor.pos = start;
+        false
+    }
+}
+
+fn parse_marker_value(cursor: &mut Cursor) -> Result<MarkerValue, ParseError> {
+    cursor.skip_ws();
+    if let Some(quote) = cursor.peek().filter(|c| *c == '"' || *c == '\'') {
+        cursor.eat(&quote.to_string());
+        let value = cursor.eat_while(|c| c != quote);
+        if !cursor.eat(&quote.to_string()) {
+            return cursor.error("Unterminated string in marker");
+        }
+        return Ok(MarkerValue::Literal(value.to_string()));
+    }
+
+    let start = cursor.pos;
+    let name = cursor.eat_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    if MARKER_VARIABLES.contains(&name) {
+        Ok(MarkerValue::Variable(name.to_string()))
+    } else {
+        cursor.pos = start;
+        cursor.error("Expected marker variable or quoted string")
+    }
+}
+
+/// Single PEP 508 dependency specification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    /// name as written, see `normalized_name`
+    pub name: String,
+    pub extras: Vec<String>,
+    pub specifier: SpecifierSet,
+    pub url: Option<String>,
+    pub marker: Option<Marker>,
+}
+
+fn parse_identifier(cursor: &mut Cursor, what: &str) -> Result<String, ParseError> {
+    let start = cursor.pos;
+    let ident = cursor.eat_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    let valid_edges = ident.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && ident.ends_with(|c: char| c.is_ascii_alphanumeric());
+    if !valid_edges {
+        cursor.pos = start;
+        return cursor.error(&format!("Expected {}", what));
+    }
+    Ok(ident.to_string())
+}
+
+impl Requirement {
+    pub fn normalized_name(&self) -> String {
+        pep503(&self.name)
+    }
+
+    /// Version specifier and marker in the form used by `ParsedLine::Dependency`
+    pub fn dependency_str(&self) -> String {
+        match &self.marker {
+            Some(marker) => format!("{}; {}", self.specifier, marker),
+            None => self.specifier.to_string(),
+        }
+    }
+
+    fn parse_extras(cursor: &mut Cursor) -> Result<Vec<String>, ParseError> {
+        let mut extras = Vec::new();
+        cursor.skip_ws();
+        if !cursor.eat("[") {
+            return Ok(extras);
+        }
+        cursor.skip_ws();
+        if cursor.eat("]") {
+            return Ok(extras);
+        }
+        loop {
+            cursor.skip_ws();
+            extras.push(parse_identifier(cursor, "extra name")?);
+            cursor.skip_ws();
+            if cursor.eat("]") {
+                return Ok(extras);
+            }
+            if !cursor.eat(",") {
+                return cursor.error("Expected comma or closing bracket");
+            }
+        }
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cursor = Cursor::new(s.trim());
+        let name = parse_identifier(&mut cursor, "distribution name")?;
+        let extras = Requirement::parse_extras(&mut cursor)?;
+        cursor.skip_ws();
+
+        let mut url = None;
+        let mut specifier = SpecifierSet::default();
+        if cursor.eat("@") {
+            cursor.skip_ws();
+            let uri = cursor.eat_while(|c| !c.is_whitespace());
+            if uri.is_empty() {
+                return cursor.error("Expected URL");
+            }
+            url = Some(uri.to_string());
+            // marker after URL must be separated by whitespace, ';' may be part of it
+            if !cursor.skip_ws() && !cursor.at_end() {
+                return cursor.error("Expected whitespace after URL");
+            }
+        } else if cursor.eat("(") {
+            cursor.skip_ws();
+            specifier = SpecifierSet::parse_from(&mut cursor)?;
+            cursor.skip_ws();
+            if !cursor.eat(")") {
+                return cursor.error("Expected closing parenthesis");
+            }
+        } else if cursor.peek().is_some_and(|c| "<>=!~".contains(c)) {
+            specifier = SpecifierSet::parse_from(&mut cursor)?;
+        }
+
+        cursor.skip_ws();
+        let mut marker = None;
+        if cursor.eat(";") {
+            marker = Some(Marker::parse_or(&mut cursor)?);
+            cursor.skip_ws();
+        }
+        if !cursor.at_end() {
+            return cursor.error("Unexpected trailing characters in requirement");
+        }
+
+        Ok(Requirement {
+            name,
+            extras,
+            specifier,
+            url,
+            marker,
+        })
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if !self.extras.is_empty() {
+            write!(f, "[{}]", self.extras.join(","))?;
+        }
+        if let Some(url) = &self.url {
+            write!(f, " @ {}", url)?;
+            if self.marker.is_some() {
+                f.write_str(" ")?;
+            }
+        } else {
+            write!(f, "{}", self.specifier)?;
+        }
+        if let Some(marker) = &self.marker {
+            write!(f, "; {}", marker)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::normalize::test::{distribution_name, specifier_set, version};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn normalized_version_round_trip(version in version()) {
+            let parsed: Version = version.parse().unwrap();
+            prop_assert_eq!(parsed.to_string(), version);
+        }
+
+        #[test]
+        fn requirement_round_trip(
+            name in distribution_name(),
+            specifier in specifier_set(),
+            extras in prop::collection::vec(distribution_name(), 0..3),
+        ) {
+            let extras = if extras.is_empty() { String::new() } else { format!("[{}]", extras.join(",")) };
+            let line = format!("{name}{extras}{specifier}; python_version >= \"3.8\"");
+            let requirement: Requirement = line.parse().unwrap();
+            prop_assert_eq!(&requirement.name, &name);
+            let reparsed: Requirement = requirement.to_string().parse().unwrap();
+            prop_assert_eq!(reparsed, requirement);
+        }
+    }
+
+    #[test]
+    fn version_filter() {
+        let filter: VersionFilter = "version<1".parse().unwrap();
+        assert!(filter.matches("0.9.2"));
+        assert!(filter.matches("0.5rc1"));
+        assert!(!filter.matches("1.0"));
+        assert!(!filter.matches("not a version"));
+
+        let filter: VersionFilter = " >=2, !=2.1.*".parse().unwrap();
+        assert!(filter.matches("2.0"));
+        assert!(!filter.matches("2.1.3"));
+        assert!("version~2".parse::<VersionFilter>().is_err());
+    }
+
+    /// Examples from the PEP 508 text and the `packaging` test suite
+    const VALID_REQUIREMENTS: [(&str, &str); 17] = [
+        ("A", "A"),
+        ("A.B-C_D", "A.B-C_D"),
+        ("aa", "aa"),
+        ("name", "name"),
+        ("name<=1", "name<=1"),
+        ("name>=3,<2", "name>=3,<2"),
+        ("name (>=3, <2)", "name>=3,<2"),
+        ("name@http://foo.com", "name @ http://foo.com"),
+        (
+            "name [fred,bar] @ http://foo.com ; python_version=='2.7'",
+            "name[fred,bar] @ http://foo.com ; python_version == \"2.7\"",
+        ),
+        (
+            "name[quux, strange];python_version<'2.7' and platform_version=='2'",
+            "name[quux,strange]; python_version < \"2.7\" and platform_version == \"2\"",
+        ),
+        (
+            "name; os_name=='a' or os_name=='b'",
+            "name; os_name == \"a\" or os_name == \"b\"",
+        ),
+        (
+            "name; os_name=='a' and os_name=='b' or os_name=='c'",
+            "name; os_name == \"a\" and os_name == \"b\" or os_name == \"c\"",
+        ),
+        (
+            "name; os_name=='a' and (os_name=='b' or os_name=='c')",
+            "name; os_name == \"a\" and (os_name == \"b\" or os_name == \"c\")",
+        ),
+        (
+            "name; (os_name=='a' or os_name=='b') and os_name=='c'",
+            "name; (os_name == \"a\" or os_name == \"b\") and os_name == \"c\"",
+        ),
+        (
+            "name; 'linux' in sys_platform",
+            "name; \"linux\" in sys_platform",
+        ),
+        (
+            "foo>=1!2.0.post1.dev3,==1.0.*,===weird-1; extra not in 'a'",
+            "foo>=1!2.0.post1.dev3,==1.0.*,===weird-1; extra not in \"a\"",
+        ),
+        ("Foo_Bar ~= 1.4.5A4", "Foo_Bar~=1.4.5a4"),
+    ];
+
+    const INVALID_REQUIREMENTS: [&str; 14] = [
+        "",
+        "-name",
+        "name-",
+        "name[",
+        "name[bar",
+        "name[bar,]",
+        "name>=",
+        "name==1.0 garbage",
+        "name;",
+        "name; os_name",
+        "name; unknown_var == 'x'",
+        "name @ ",
+        "name~=1",
+        "name>=1.0+local",
+    ];
+
+    #[test]
+    fn valid_requirements() {
+        for (input, expected) in VALID_REQUIREMENTS {
+            let requirement: Requirement = input
+                .parse()
+                .unwrap_or_else(|err| panic!("{:?} not parsed: {}", input, err));
+            assert_eq!(requirement.to_string(), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn invalid_requirements() {
+        for input in INVALID_REQUIREMENTS {
+            assert!(input.parse::<Requirement>().is_err(), "{:?} parsed", input);
+        }
+    }
+
+    #[test]
+    fn requirement_parts() {
+        let requirement: Requirement =
+            "Requests[Security] >=2.8.1, ==2.8.* ; python_version < \"2.7\""
+                .parse()
+                .unwrap();
+        assert_eq!(requirement.normalized_name(), "requests");
+        assert_eq!(requirement.extras, vec!["Security"]);
+        assert_eq!(
+            requirement.dependency_str(),
+            ">=2.8.1,==2.8.*; python_version < \"2.7\""
+        );
+        assert_eq!(
+            requirement.marker,
+            Some(Marker::Compare(
+                MarkerValue::Variable("python_version".to_string()),
+                MarkerOperator::Version(Operator::Less),
+                MarkerValue::Literal("2.7".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn version_normalization() {
+        let cases = [
+            ("1.0ALPHA1", "1.0a1"),
+            ("1.0-beta.2", "1.0b2"),
+            ("1.0c1", "1.0rc1"),
+            ("1.0-preview_3", "1.0rc3"),
+            ("1.0a", "1.0a0"),
+            ("1.0-1", "1.0.post1"),
+            ("1.0-r4", "1.0.post4"),
+            ("1.0.rev", "1.0.post0"),
+            ("1.0.DEV", "1.0.dev0"),
+            ("v1.0", "1.0"),
+            ("0!1.0", "1.0"),
+            ("01.002", "1.2"),
+            ("1.0+Ubuntu-1", "1.0+ubuntu.1"),
+            ("1.0+abc_5", "1.0+abc.5"),
+        ];
+        for (input, expected) in cases {
+            let version: Version = input.parse().unwrap();
+            assert_eq!(version.to_string(), expected, "{:?}", input);
+        }
+
+        for invalid in ["", "french toast", "1.0+", "1..0", "1.0-", "1.0+a..b", "1!"] {
+            assert!(invalid.parse::<Version>().is_err(), "{:?} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn version_ordering() {
+        // PEP 440 appendix, in increasing order
+        let ordered = [
+            "1.0.dev456",
+            "1.0a1",
+            "1.0a2.dev456",
+            "1.0a12.dev456",
+            "1.0a12",
+            "1.0b1.dev456",
+            "1.0b2",
+            "1.0b2.post345.dev456",
+            "1.0b2.post345",
+            "1.0rc1.dev456",
+            "1.0rc1",
+            "1.0",
+            "1.0+abc.5",
+            "1.0+abc.7",
+            "1.0+5",
+            "1.0.post456.dev34",
+            "1.0.post456",
+            "1.0.15",
+            "1.1.dev1",
+            "1!0.1",
+        ];
+        let versions: Vec<Version> = ordered.iter().map(|v| v.parse().unwrap()).collect();
+        for pair in versions.windows(2) {
+            assert!(pair[0] < pair[1], "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!("1.0".parse::<Version>(), "1.0.0".parse::<Version>());
+        assert!("1.0rc1".parse::<Version>().unwrap().is_prerelease());
+    }
+
+    #[test]
+    fn specifier_matching() {
+        let cases = [
+            (">=1.0,<2", "1.5", true),
+            (">=1.0,<2", "2.0", false),
+            (">=1.0,<2", "1.5rc1", false),
+            (">=1.0rc1", "1.0rc2", true),
+            ("<2.0", "2.0rc1", false),
+            ("<2.0rc2", "2.0rc1", true),
+            (">1.0", "1.0.post1", false),
+            (">1.0.post1", "1.0.post2", true),
+            (">1.0", "1.1", true),
+            ("==1.4.*", "1.4.5", true),
+            ("==1.4.*", "1.40", false),
+            ("==1.4.*", "1.4", true),
+            ("!=1.4.*", "1.5", true),
+            ("==1.0", "1.0.0", true),
+            ("==1.0", "1.0+local.1", true),
+            ("==1.0+local.1", "1.0", false),
+            ("~=2.2", "2.9", true),
+            ("~=2.2", "3.0", false),
+            ("~=1.4.5", "1.4.9", true),
+            ("~=1.4.5", "1.5.0", false),
+            ("===1.0.0", "1.0.0", true),
+            ("===1.0.0", "1.0", false),
+            ("<=1!1.0", "2.0", true),
+        ];
+        for (specifier, version, expected) in cases {
+            let requirement: Requirement = format!("name{}", specifier).parse().unwrap();
+            let version: Version = version.parse().unwrap();
+            assert_eq!(
+                requirement.specifier.contains(&version),
+                expected,
+                "{} in {}",
+                version,
+                specifier
+            );
+        }
+    }
+
+    #[test]
+    fn marker_errors_have_position() {
+        let err = "name; os_name == 'a' and"
+            .parse::<Requirement>()
+            .unwrap_err();
+        assert_eq!(err.position, 24);
+    }
+}
@@ -0,0 +1,168 @@
+//! Permissions and ownership audit of installed packages, see `check --permissions`.
+//! Anyone able to write into site-packages can change the code every
+//! user of the env imports, so such paths are flagged on shared hosts
+
+use crate::dag::{DependencyDag, DistributionName};
+use crate::policy::{Check, Violation};
+use crate::record::read_record;
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Path with `.` and `..` components resolved without touching the filesystem,
+/// so `site-packages/../../../bin/tool` of a RECORD entry names the script itself
+fn lexically_normalized(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `/..` is `/`
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Paths of an installed distribution: its metadata dir and the files
+/// listed in RECORD together with their parent dirs inside site-packages.
+/// Files installed outside of it, e.g. scripts in `bin`, are checked
+/// without their parent dirs
+#[cfg(unix)]
+fn installed_paths(meta_dir: &Path) -> BTreeSet<PathBuf> {
+    let site_packages = lexically_normalized(meta_dir.parent().unwrap_or(meta_dir));
+    let mut paths = BTreeSet::from([meta_dir.to_path_buf()]);
+    // egg-info dirs have no RECORD, only the metadata dir is checked
+    for entry in read_record(meta_dir).unwrap_or_default() {
+        let path = lexically_normalized(&site_packages.join(&entry.path));
+        let mut dir = path.parent();
+        while let Some(parent) =
+            dir.filter(|d| d.starts_with(&site_packages) && *d != site_packages)
+        {
+            paths.insert(parent.to_path_buf());
+            dir = parent.parent();
+        }
+        paths.insert(path);
+    }
+    paths
+}
+
+/// Problem found with a single path
+#[derive(Debug, PartialEq, Eq)]
+pub enum PermissionIssue {
+    /// any user may modify the file or add files to the dir,
+    /// dirs with the sticky bit (like `/tmp`) are not flagged
+    WorldWritable,
+    /// owned by neither the owner of site-packages nor root
+    UnexpectedOwner { uid: u32 },
+}
+
+/// Issues of paths installed by a distribution, in the order of paths.
+/// Missing paths are skipped, `verify` reports them
+#[cfg(unix)]
+pub fn permission_issues(meta_dir: &Path) -> Vec<(PathBuf, PermissionIssue)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let site_packages = meta_dir.parent().unwrap_or(meta_dir);
+    let expected_owner = site_packages.metadata().ok().map(|m| m.uid());
+    let mut issues = Vec::new();
+    for path in installed_paths(meta_dir) {
+        // symlinks are always 0777, what they point to is checked on its own
+        let Ok(metadata) = path.symlink_metadata() else {
+            continue;
+        };
+        if metadata.file_type().is_symlink() {
+            continue;
+        }
+        let sticky = metadata.is_dir() && metadata.mode() & 0o1000 != 0;
+        if metadata.mode() & 0o002 != 0 && !sticky {
+            issues.push((path.clone(), PermissionIssue::WorldWritable));
+        }
+        let uid = metadata.uid();
+        if uid != 0 && expected_owner.is_some_and(|owner| owner != uid) {
+            issues.push((path, PermissionIssue::UnexpectedOwner { uid }));
+        }
+    }
+    issues
+}
+
+/// Flag packages with world-writable paths or paths owned by other users
+/// than the owner of site-packages and root. Each package is reported once
+/// per kind of issue, naming the first offending path
+pub struct PermissionsCheck;
+
+#[cfg(unix)]
+impl PermissionsCheck {
+    fn violations(name: &DistributionName, meta_dir: &Path) -> Vec<Violation> {
+        let issues = permission_issues(meta_dir);
+        let writable: Vec<&PathBuf> = issues
+            .iter()
+            .filter(|(_, issue)| *issue == PermissionIssue::WorldWritable)
+            .map(|(path, _)| path)
+            .collect();
+        let foreign: Vec<(&PathBuf, u32)> = issues
+            .iter()
+            .filter_map(|(path, issue)| match issue {
+                PermissionIssue::UnexpectedOwner { uid } => Some((path, *uid)),
+                PermissionIssue::WorldWritable => None,
+            })
+            .collect();
+
+        let mut violations = Vec::new();
+        if let Some(first) = writable.first() {
+            violations.push(Violation {
+                rule: String::from("world-writable"),
+                package: Some(name.clone()),
+                message: format!(
+                    "{} has {} world-writable paths, e.g. {}",
+                    name,
+                    writable.len(),
+                    first.display()
+                ),
+            });
+        }
+        if let Some((first, uid)) = foreign.first() {
+            violations.push(Violation {
+                rule: String::from("unexpected-owner"),
+                package: Some(name.clone()),
+                message: format!(
+                    "{} has {} paths owned by other users, e.g. {} (uid {})",
+                    name,
+                    foreign.len(),
+                    first.display(),
+                    uid
+                ),
+            });
+        }
+        violations
+    }
+}
+
+impl Check for PermissionsCheck {
+    fn name(&self) -> &str {
+        "permissions"
+    }
+
+    #[cfg(unix)]
+    fn check(&self, dag: &DependencyDag) -> Result<Vec<Violation>, String> {
+        let mut packages: Vec<(&DistributionName, &Path)> = dag
+            .iter()
+            .map(|(name, meta)| (name, meta.meta_dir.as_path()))
+            .collect();
+        packages.sort();
+        Ok(packages
+            .into_iter()
+            .flat_map(|(name, meta_dir)| Self::violations(name, meta_dir))
+            .collect())
+    }
+
+    #[cfg(not(unix))]
+    fn check(&self, _dag: &DependencyDag) -> Result<Vec<Violation>, String> {
+        Err(String::from("permissions are only checked on unix"))
+    }
+}
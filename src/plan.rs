@@ -0,0 +1,324 @@
+//! Targeted upgrade of a single installed package, checked against
+//! requirements of installed packages and the metadata published on the index
+
+use crate::dag::{get_requirers, DependencyDag, DistributionName};
+use crate::index::RemoteResolver;
+use crate::pep::{Requirement, SpecifierSet, Version};
+use std::collections::BTreeMap;
+
+/// Version pinned by an upgrade plan
+#[derive(Debug, PartialEq)]
+pub struct Pin {
+    pub name: DistributionName,
+    pub version: String,
+    /// None for packages which are not installed yet
+    pub installed: Option<String>,
+}
+
+/// Outcome of a single candidate: pins or the reason it was rejected
+type Attempt = Result<BTreeMap<DistributionName, String>, String>;
+
+/// Searches published versions of a package, newest first, for one which can be
+/// installed without breaking requirements of installed packages.
+///
+/// This is not a full resolver: unsatisfied dependencies of the candidate are
+/// pinned to their newest matching versions, but requirements of those new
+/// pins are not followed
+pub struct UpgradePlanner<'a> {
+    resolver: &'a RemoteResolver<'a>,
+    dag: &'a DependencyDag,
+    with_requirers: bool,
+}
+
+/// Whether the version satisfies a canonical specifier stored in the dag,
+/// unparsable specifiers and versions never match
+fn satisfies(specifier: &str, version: &str) -> bool {
+    match (
+        specifier.parse::<SpecifierSet>(),
+        version.parse::<Version>(),
+    ) {
+        (Ok(specifier), Ok(version)) => specifier.contains(&version),
+        _ => false,
+    }
+}
+
+impl<'a> UpgradePlanner<'a> {
+    /// With `with_requirers`, installed packages whose requirements exclude
+    /// a candidate may be upgraded as well
+    pub fn new(
+        resolver: &'a RemoteResolver<'a>,
+        dag: &'a DependencyDag,
+        with_requirers: bool,
+    ) -> Self {
+        Self {
+            resolver,
+            dag,
+            with_requirers,
+        }
+    }
+
+    /// Pins of the newest conflict-free upgrade. Err explains why the
+    /// newest candidate was rejected, or reports a failed index query
+    pub fn plan(&self, package: &DistributionName) -> Result<Vec<Pin>, String> {
+        let installed = &self.dag[package].installed_version;
+        let installed_version: Option<Version> = installed.parse().ok();
+        let candidates: Vec<(Version, String)> = self
+            .resolver
+            .published(package)?
+            .into_iter()
+            .rev()
+            .filter(|(v, _)| !v.is_prerelease())
+            .filter(|(v, _)| installed_version.as_ref().is_none_or(|i| v > i))
+            .collect();
+
+        let mut newest_rejection = None;
+        for (_, candidate) in &candidates {
+            match self.try_candidate(package, candidate)? {
+                Ok(pins) => return Ok(self.to_pins(pins)),
+                Err(reason) => {
+                    newest_rejection.get_or_insert(reason);
+                }
+            }
+        }
+        Err(newest_rejection.unwrap_or(format!(
+            "no version of {} newer than {} is published",
+            package, installed
+        )))
+    }
+
+    fn to_pins(&self, pins: BTreeMap<DistributionName, String>) -> Vec<Pin> {
+        pins.into_iter()
+            .map(|(name, version)| Pin {
+                installed: self.dag.get(&name).map(|m| m.installed_version.clone()),
+                name,
+                version,
+            })
+            .collect()
+    }
+
+    /// Version of a package after the plan is applied
+    fn effective_version<'p>(
+        &'p self,
+        pins: &'p BTreeMap<DistributionName, String>,
+        name: &str,
+    ) -> Option<&'p str> {
+        pins.get(name)
+            .or_else(|| self.dag.get(name).map(|m| &m.installed_version))
+            .map(String::as_str)
+    }
+
+    /// Whether all installed requirers of a package, except the pinned
+    /// ones, accept given version of it
+    fn accepted_by_requirers(
+        &self,
+        pins: &BTreeMap<DistributionName, String>,
+        name: &DistributionName,
+        version: &str,
+    ) -> bool {
+        get_requirers(self.dag, name)
+            .into_iter()
+            .all(|(requirer, requirement)| {
+                pins.contains_key(requirer) || satisfies(&requirement.required_version, version)
+            })
+    }
+
+    fn try_candidate(
+        &self,
+        package: &DistributionName,
+        candidate: &str,
+    ) -> Result<Attempt, String> {
+        let mut pins = BTreeMap::from([(package.clone(), candidate.to_string())]);
+
+        for (requirer, requirement) in get_requirers(self.dag, package) {
+            if satisfies(&requirement.required_version, candidate) {
+                continue;
+            }
+            let upgrade = match self.with_requirers {
+                true => self.upgrade_requirer(&pins, requirer, package, candidate)?,
+                false => None,
+            };
+            match upgrade {
+                Some(version) => {
+                    pins.insert(requirer.clone(), version);
+                }
+                None => {
+                    return Ok(Err(format!(
+                        "{} {} is excluded by {} ({}{})",
+                        package, candidate, requirer, package, requirement.required_version
+                    )))
+                }
+            }
+        }
+
+        for dependency in self.resolver.dependencies(package, candidate, &[])? {
+            if let Err(reason) = self.pin_dependency(&mut pins, &dependency)? {
+                return Ok(Err(format!("{} {}: {}", package, candidate, reason)));
+            }
+        }
+        Ok(Ok(pins))
+    }
+
+    /// Make sure a requirement holds after the plan, pinning the newest
+    /// matching version of the required package if needed
+    fn pin_dependency(
+        &self,
+        pins: &mut BTreeMap<DistributionName, String>,
+        dependency: &Requirement,
+    ) -> Result<Result<(), String>, String> {
+        let name = dependency.normalized_name();
+        let current = self.effective_version(pins, &name);
+        if current.is_some_and(|v| v.parse().is_ok_and(|v| dependency.specifier.contains(&v))) {
+            return Ok(Ok(()));
+        }
+        if pins.contains_key(&name) {
+            return Ok(Err(format!("requires {}", dependency)));
+        }
+        for (version, spelling) in self.resolver.published(&name)?.into_iter().rev() {
+            if dependency.specifier.contains(&version)
+                && self.accepted_by_requirers(pins, &name, &spelling)
+            {
+                pins.insert(name, spelling);
+                return Ok(Ok(()));
+            }
+        }
+        Ok(Err(format!(
+            "no published version of {} satisfies {} and its requirers",
+            name, dependency
+        )))
+    }
+
+    /// Newest version of a requirer accepting the candidate, whose other
+    /// requirements hold for the installed packages
+    fn upgrade_requirer(
+        &self,
+        pins: &BTreeMap<DistributionName, String>,
+        requirer: &DistributionName,
+        package: &DistributionName,
+        candidate: &str,
+    ) -> Result<Option<String>, String> {
+        let installed: Option<Version> = self.dag[requirer].installed_version.parse().ok();
+        let candidate_version: Version = candidate.parse().map_err(|err| format!("{}", err))?;
+
+        for (version, spelling) in self.resolver.published(requirer)?.into_iter().rev() {
+            if version.is_prerelease() || installed.as_ref().is_some_and(|i| &version <= i) {
+                continue;
+            }
+            if !self.accepted_by_requirers(pins, requirer, &spelling) {
+                continue;
+            }
+            let dependencies = self.resolver.dependencies(requirer, &spelling, &[])?;
+            let compatible = dependencies.iter().all(|d| {
+                let name = d.normalized_name();
+                if &name == package {
+                    return d.specifier.contains(&candidate_version);
+                }
+                self.effective_version(pins, &name)
+                    .is_some_and(|v| v.parse().is_ok_and(|v| d.specifier.contains(&v)))
+            });
+            if compatible {
+                return Ok(Some(spelling));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::{DistributionMeta, RequiredDistribution};
+    use crate::index::test::StaticIndex;
+    use crate::marker::MarkerEnv;
+
+    fn meta(version: &str, deps: &[(&str, &str)]) -> DistributionMeta {
+        DistributionMeta {
+            installed_version: version.to_string(),
+            dependencies: deps
+                .iter()
+                .map(|(name, spec)| RequiredDistribution {
+                    name: name.to_string(),
+                    required_version: spec.to_string(),
+                    marker: None,
+                    source: None,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn dag() -> DependencyDag {
+        DependencyDag::from([
+            ("app".to_string(), meta("1.0", &[("requests", "<2.32,>=2")])),
+            (
+                "requests".to_string(),
+                meta("2.28.0", &[("urllib3", "<2,>=1.21")]),
+            ),
+            ("urllib3".to_string(), meta("1.26.5", &[])),
+        ])
+    }
+
+    fn index() -> StaticIndex {
+        StaticIndex(vec![
+            ("requests", "2.28.0", vec!["urllib3<2,>=1.21"]),
+            ("requests", "2.31.0", vec!["urllib3<3,>=1.21"]),
+            ("requests", "2.32.0", vec!["urllib3<3,>=2"]),
+            ("requests", "3.0.0a1", vec![]),
+            ("urllib3", "1.26.5", vec![]),
+            ("urllib3", "2.2.1", vec![]),
+            ("app", "1.0", vec!["requests<2.32,>=2"]),
+            ("app", "1.1", vec!["requests>=2"]),
+        ])
+    }
+
+    #[test]
+    fn plan_respects_requirers() {
+        let (dag, index) = (dag(), index());
+        let resolver = RemoteResolver::new(&index, MarkerEnv::new(), None);
+        let pins = UpgradePlanner::new(&resolver, &dag, false)
+            .plan(&"requests".to_string())
+            .unwrap();
+        assert_eq!(
+            pins,
+            vec![Pin {
+                name: "requests".to_string(),
+                version: "2.31.0".to_string(),
+                installed: Some("2.28.0".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn plan_with_requirers() {
+        let (dag, index) = (dag(), index());
+        let resolver = RemoteResolver::new(&index, MarkerEnv::new(), None);
+        let pins = UpgradePlanner::new(&resolver, &dag, true)
+            .plan(&"requests".to_string())
+            .unwrap();
+        let pins: Vec<(&str, &str)> = pins
+            .iter()
+            .map(|p| (p.name.as_str(), p.version.as_str()))
+            .collect();
+        assert_eq!(
+            pins,
+            vec![("app", "1.1"), ("requests", "2.32.0"), ("urllib3", "2.2.1")]
+        );
+    }
+
+    #[test]
+    fn no_plan() {
+        let mut dag = dag();
+        dag.insert(
+            "pinned".to_string(),
+            meta("1.0", &[("requests", "==2.28.0")]),
+        );
+        let index = index();
+        let resolver = RemoteResolver::new(&index, MarkerEnv::new(), None);
+        let err = UpgradePlanner::new(&resolver, &dag, false)
+            .plan(&"requests".to_string())
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "requests 2.32.0 is excluded by app (requests<2.32,>=2)"
+        );
+    }
+}
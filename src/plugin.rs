@@ -0,0 +1,163 @@
+use crate::dag::DependencyDag;
+use crate::locator::wait_with_timeout;
+use crate::normalize::pep503;
+use crate::origin::Origin;
+use crate::policy::{Check, Violation};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+#[derive(Serialize)]
+struct PluginDependency<'a> {
+    name: &'a str,
+    required_version: &'a str,
+}
+
+#[derive(Serialize)]
+struct PluginPackage<'a> {
+    name: &'a str,
+    installed_version: &'a str,
+    installer: Option<&'a str>,
+    /// one of index, conda, vcs, local path, url
+    origin: String,
+    /// direct reference the package was installed from
+    url: Option<&'a str>,
+    dependencies: Vec<PluginDependency<'a>>,
+}
+
+/// Json document passed to plugins on stdin: packages sorted by name
+/// with their direct requirements
+fn plugin_input(dag: &DependencyDag) -> Result<Vec<u8>, String> {
+    let mut packages: Vec<PluginPackage> = dag
+        .iter()
+        .map(|(name, meta)| {
+            let dependencies: Vec<PluginDependency> = meta
+                .sorted_dependencies()
+                .into_iter()
+                .map(|d| PluginDependency {
+                    name: &d.name,
+                    required_version: &d.required_version,
+                })
+                .collect();
+            PluginPackage {
+                name,
+                installed_version: &meta.installed_version,
+                installer: meta.installer.as_deref(),
+                origin: Origin::of(meta).to_string(),
+                url: meta.direct_url.as_ref().map(|u| u.url.as_str()),
+                dependencies,
+            }
+        })
+        .collect();
+    packages.sort_by_key(|p| p.name);
+    serde_json::to_vec(&packages).map_err(|err| err.to_string())
+}
+
+/// Check implemented by an external executable.
+///
+/// The executable gets the json list of installed packages on stdin and
+/// prints a json list of violations to stdout, e.g.
+/// `[{"rule": "internal-index", "package": "acme-utils", "message": "..."}]`.
+/// Non-zero exit code means the plugin itself failed.
+pub struct ExternalCheck {
+    path: PathBuf,
+    name: String,
+}
+
+impl ExternalCheck {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            name: path.display().to_string(),
+        }
+    }
+}
+
+impl Check for ExternalCheck {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self, dag: &DependencyDag) -> Result<Vec<Violation>, String> {
+        let input = plugin_input(dag)?;
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| err.to_string())?;
+
+        // write from a separate thread, so a plugin printing
+        // before reading the whole input can't deadlock
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+        // plugins are limited only by the time left of the run
+        let output = wait_with_timeout(child, Duration::MAX).map_err(|err| err.to_string())?;
+        // plugins are free to ignore the input
+        let _ = writer.join();
+
+        if !output.status.success() {
+            return Err(format!(
+                "{}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let mut violations: Vec<Violation> = serde_json::from_slice(&output.stdout)
+            .map_err(|err| format!("invalid output: {}", err))?;
+        for violation in &mut violations {
+            violation.package = violation.package.as_deref().map(pep503);
+        }
+        Ok(violations)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use crate::dag::DistributionMeta;
+    use crate::fixture::SitePackages;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn plugin(site: &SitePackages, name: &str, script: &str) -> ExternalCheck {
+        let path = site.path().join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        ExternalCheck::new(&path)
+    }
+
+    #[test]
+    fn external_check() {
+        let site = SitePackages::new("plugin");
+        let dag = DependencyDag::from([(
+            String::from("six"),
+            DistributionMeta {
+                installed_version: String::from("1.16.0"),
+                ..Default::default()
+            },
+        )]);
+
+        let check = plugin(
+            &site,
+            "no-six.sh",
+            r#"grep -q '"name":"six","installed_version":"1.16.0"' || exit 3
+echo '[{"rule": "no-six", "package": "Six", "message": "six is not allowed"}]'"#,
+        );
+        assert_eq!(
+            check.check(&dag),
+            Ok(vec![Violation {
+                rule: String::from("no-six"),
+                package: Some(String::from("six")),
+                message: String::from("six is not allowed"),
+            }])
+        );
+
+        let check = plugin(&site, "broken.sh", "echo broken >&2; exit 2");
+        assert!(check.check(&dag).unwrap_err().ends_with("broken"));
+        let check = plugin(&site, "not-json.sh", "echo not json");
+        assert!(check.check(&dag).is_err());
+    }
+}
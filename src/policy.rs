@@ -0,0 +1,610 @@
+use crate::dag::{DependencyDag, DistributionName, RequiredDistribution};
+use crate::marker::{release_allowed, MarkerEnv};
+use crate::utils::{format_size, specifiers_disjoint};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::Path;
+
+/// Single policy rule violation found by `rdeptree check`
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct Violation {
+    /// short machine friendly rule id, e.g. "max-package-size"
+    pub rule: String,
+    /// offending package, None for env-wide rules
+    pub package: Option<DistributionName>,
+    pub message: String,
+}
+
+/// Installed size limits, in bytes
+#[derive(Debug, Default)]
+pub struct SizePolicy {
+    pub max_env_size: Option<u64>,
+    pub max_package_size: Option<u64>,
+}
+
+/// Check installed sizes of distributions against size policy
+pub fn check_sizes(sizes: &BTreeMap<DistributionName, u64>, policy: &SizePolicy) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if let Some(limit) = policy.max_package_size {
+        for (name, size) in sizes {
+            if *size > limit {
+                violations.push(Violation {
+                    rule: String::from("max-package-size"),
+                    package: Some(name.clone()),
+                    message: format!(
+                        "{} takes {}, limit is {}",
+                        name,
+                        format_size(*size),
+                        format_size(limit)
+                    ),
+                });
+            }
+        }
+    }
+
+    if let Some(limit) = policy.max_env_size {
+        let total: u64 = sizes.values().sum();
+        if total > limit {
+            violations.push(Violation {
+                rule: String::from("max-env-size"),
+                package: None,
+                message: format!(
+                    "environment takes {}, limit is {}",
+                    format_size(total),
+                    format_size(limit)
+                ),
+            });
+        }
+    }
+    violations
+}
+
+/// Install prefix, lib dir and python dir of a site-packages dir,
+/// e.g. `/venv/lib64/python3.11/site-packages` is (/venv, lib64, python3.11)
+fn site_layout(site_dir: &Path) -> Option<(&Path, &OsStr, &str)> {
+    let python = site_dir.parent()?;
+    let python_name = python.file_name()?.to_str()?;
+    if !python_name.starts_with("python") {
+        return None;
+    }
+    let lib = python.parent()?;
+    Some((lib.parent()?, lib.file_name()?, python_name))
+}
+
+/// Why a shadowed copy next to the selected one is most likely a leftover,
+/// None for copies in unrelated roots, e.g. layers scanned on purpose
+pub(crate) fn leftover_reason(selected: &Path, shadowed: &Path) -> Option<String> {
+    let selected_site = selected.parent()?;
+    let shadowed_site = shadowed.parent()?;
+    if selected_site == shadowed_site {
+        return Some(String::from("in the same dir"));
+    }
+    // e.g. lib64 symlinked to lib, scanned twice
+    if let (Ok(selected), Ok(shadowed)) = (selected.canonicalize(), shadowed.canonicalize()) {
+        if selected == shadowed {
+            return None;
+        }
+    }
+
+    let (selected_prefix, selected_lib, selected_python) = site_layout(selected_site)?;
+    let (shadowed_prefix, shadowed_lib, shadowed_python) = site_layout(shadowed_site)?;
+    if selected_prefix != shadowed_prefix {
+        None
+    } else if selected_python != shadowed_python {
+        Some(format!("left over from {}", shadowed_python))
+    } else if selected_lib != shadowed_lib {
+        Some(String::from("in both purelib and platlib"))
+    } else {
+        None
+    }
+}
+
+/// Find copies of distributions shadowed by another copy in the same
+/// install prefix: next to each other, split between purelib and platlib
+/// dirs, or left in site-packages of another python minor version. Copies
+/// inherited from the base interpreter are not duplicates
+pub fn check_duplicates(dag: &DependencyDag) -> Vec<Violation> {
+    let mut names: Vec<&DistributionName> = dag.keys().collect();
+    names.sort();
+
+    let mut violations = Vec::new();
+    for name in names {
+        let meta = &dag[name];
+        for copy in meta.shadowed.iter().filter(|copy| !copy.inherited) {
+            let Some(reason) = leftover_reason(&meta.meta_dir, &copy.meta_dir) else {
+                continue;
+            };
+            violations.push(Violation {
+                rule: String::from("duplicate-install"),
+                package: Some(name.clone()),
+                message: format!(
+                    "{} {} in {} is {} with {} in {}, remove the stale copy",
+                    name,
+                    copy.installed_version,
+                    copy.meta_dir.display(),
+                    reason,
+                    meta.installed_version,
+                    meta.meta_dir.display()
+                ),
+            });
+        }
+    }
+    violations
+}
+
+/// Find requirers asking for an extra which the installed version of the dependency
+/// does not declare, e.g. one requirer needs `pkg[grpc]` while another pins `pkg`
+/// to a version from before the extra was added, and requested extras conflicting
+/// with other requirements. Missing dependencies are not checked
+pub fn check_extras(dag: &DependencyDag) -> Vec<Violation> {
+    let mut names: Vec<&DistributionName> = dag.keys().collect();
+    names.sort();
+
+    let mut violations = Vec::new();
+    for requirer in &names {
+        for (name, extras) in &dag[*requirer].requested_extras {
+            let Some(meta) = dag.get(name) else {
+                continue;
+            };
+            for extra in extras.iter().filter(|e| !meta.extras.contains_key(*e)) {
+                let mut message = format!(
+                    "{} requires {}[{}], but the installed {} {} does not provide extra {}",
+                    requirer, name, extra, name, meta.installed_version, extra
+                );
+                // requirers which may have held the dependency back
+                let constraints: Vec<String> = names
+                    .iter()
+                    .filter(|other| *other != requirer)
+                    .flat_map(|other| {
+                        dag[*other]
+                            .sorted_dependencies()
+                            .into_iter()
+                            .filter(|d| d.name == *name && !d.required_version.is_empty())
+                            .map(move |d| format!("{} ({})", other, d.required_version))
+                    })
+                    .collect();
+                if !constraints.is_empty() {
+                    message.push_str(&format!(
+                        ", its version is constrained by {}",
+                        constraints.join(", ")
+                    ));
+                }
+                violations.push(Violation {
+                    rule: String::from("missing-extra"),
+                    package: Some(name.clone()),
+                    message,
+                });
+            }
+        }
+    }
+    violations.extend(check_extra_conflicts(dag, &names));
+    violations
+}
+
+/// Requirement placed on a dependency, by a requirer directly or by an extra
+/// of another distribution the requirer asks for
+struct Demand<'a> {
+    requirer: &'a DistributionName,
+    /// distribution and extra the requirement comes from
+    extra: Option<(&'a DistributionName, &'a str)>,
+    requirement: &'a RequiredDistribution,
+}
+
+impl Demand<'_> {
+    fn describe(&self) -> String {
+        let required = format!(
+            "{}{}",
+            self.requirement.name, self.requirement.required_version
+        );
+        match self.extra {
+            None => format!("{} requires {}", self.requirer, required),
+            Some((provider, extra)) => format!(
+                "{} requires {}[{}], which requires {}",
+                self.requirer, provider, extra, required
+            ),
+        }
+    }
+}
+
+/// Find requested extras pulling in a dependency with a version range no
+/// version satisfies together with another requirement of it, e.g. one requirer
+/// needs `pkg[grpc]` whose extra requires `protobuf>=4` while another requirer
+/// pins `protobuf<4`. Requirements whose markers can't hold with the requested
+/// extras, e.g. of other extras, don't conflict
+fn check_extra_conflicts(dag: &DependencyDag, names: &[&DistributionName]) -> Vec<Violation> {
+    let mut demands: BTreeMap<&DistributionName, Vec<Demand>> = BTreeMap::new();
+    for requirer in names {
+        let meta = &dag[*requirer];
+        for requirement in meta.sorted_dependencies() {
+            demands.entry(&requirement.name).or_default().push(Demand {
+                requirer,
+                extra: None,
+                requirement,
+            });
+        }
+        for (name, extras) in &meta.requested_extras {
+            let Some(provider) = dag.get(name) else {
+                continue;
+            };
+            for extra in extras {
+                let mut requirements: Vec<&RequiredDistribution> =
+                    provider.extras.get(extra).into_iter().flatten().collect();
+                requirements.sort_by_key(|d| (&d.name, &d.required_version));
+                for requirement in requirements {
+                    demands.entry(&requirement.name).or_default().push(Demand {
+                        requirer,
+                        extra: Some((name, extra)),
+                        requirement,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut violations = Vec::new();
+    for (name, demands) in demands {
+        for (idx, first) in demands.iter().enumerate() {
+            for second in &demands[idx + 1..] {
+                // plain requirements not satisfied together show up as conflicts in the tree
+                if first.extra.is_none() && second.extra.is_none() {
+                    continue;
+                }
+                let env = MarkerEnv::default().with_extras(
+                    [first.extra, second.extra]
+                        .into_iter()
+                        .flatten()
+                        .map(|(_, extra)| extra),
+                );
+                let excluded = [first, second].iter().any(|demand| {
+                    demand
+                        .requirement
+                        .marker
+                        .as_deref()
+                        .is_some_and(|marker| env.evaluate(marker) == Ok(false))
+                });
+                if excluded
+                    || !specifiers_disjoint(
+                        &first.requirement.required_version,
+                        &second.requirement.required_version,
+                    )
+                {
+                    continue;
+                }
+                violations.push(Violation {
+                    rule: String::from("conflicting-extras"),
+                    package: Some(name.clone()),
+                    message: format!(
+                        "{}, while {}: no version of {} satisfies both",
+                        first.describe(),
+                        second.describe(),
+                        name
+                    ),
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// Next minor version of python, e.g. 3.12 for 3.11 or 3.11.4
+pub fn next_python_minor(python_version: &str) -> Option<String> {
+    let mut numbers = python_version.split('.');
+    let major: u64 = numbers.next()?.parse().ok()?;
+    let minor: u64 = numbers.next()?.parse().ok()?;
+    Some(format!("{}.{}", major, minor + 1))
+}
+
+/// Find distributions whose Requires-Python excludes the given python version,
+/// e.g. the next minor one before upgrading the interpreter. Specifiers which
+/// are not understood are skipped
+pub fn check_requires_python(dag: &DependencyDag, python_version: &str) -> Vec<Violation> {
+    let mut names: Vec<&DistributionName> = dag.keys().collect();
+    names.sort();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let meta = &dag[name];
+            let requires_python = meta.requires_python.as_deref()?;
+            if release_allowed(requires_python, python_version)? {
+                return None;
+            }
+            Some(Violation {
+                rule: String::from("requires-python"),
+                package: Some(name.clone()),
+                message: format!(
+                    "{} {} requires python {}, which excludes {}",
+                    name, meta.installed_version, requires_python, python_version
+                ),
+            })
+        })
+        .collect()
+}
+
+/// Custom rule run by `rdeptree check` next to the builtin ones,
+/// e.g. "internal packages must come from our index"
+pub trait Check {
+    /// name used in diagnostics, registering a check with
+    /// the same name replaces the previous one
+    fn name(&self) -> &str;
+
+    /// Err means the check itself failed, not that the env violates it
+    fn check(&self, dag: &DependencyDag) -> Result<Vec<Violation>, String>;
+}
+
+/// Set of custom checks, empty by default
+#[derive(Default)]
+pub struct CheckRegistry {
+    checks: Vec<Box<dyn Check>>,
+}
+
+impl CheckRegistry {
+    /// Add a check, replacing already registered one with the same name
+    pub fn register(&mut self, check: Box<dyn Check>) {
+        self.checks.retain(|c| c.name() != check.name());
+        self.checks.push(check);
+    }
+
+    /// Run all checks in registration order, failed checks are
+    /// reported by name and don't prevent running the rest
+    pub fn run(&self, dag: &DependencyDag) -> (Vec<Violation>, Vec<(&str, String)>) {
+        let mut violations = Vec::new();
+        let mut failures = Vec::new();
+        for check in &self.checks {
+            match check.check(dag) {
+                Ok(found) => violations.extend(found),
+                Err(err) => failures.push((check.name(), err)),
+            }
+        }
+        (violations, failures)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::DagBuilder;
+    use crate::dag::{DistributionMeta, ShadowedCopy};
+    use crate::fixture::SitePackages;
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    #[test]
+    fn size_violations() {
+        let sizes: BTreeMap<DistributionName, u64> = [
+            (String::from("torch"), 2_000_000_000),
+            (String::from("six"), 40_000),
+            (String::from("numpy"), 60_000_000),
+        ]
+        .into_iter()
+        .collect();
+
+        let policy = SizePolicy {
+            max_env_size: Some(500_000_000),
+            max_package_size: Some(100_000_000),
+        };
+        let violations = check_sizes(&sizes, &policy);
+
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].rule, "max-package-size");
+        assert_eq!(violations[0].package.as_deref(), Some("torch"));
+        assert_eq!(
+            violations[0].message,
+            "torch takes 2.0 GB, limit is 100.0 MB"
+        );
+        assert_eq!(violations[1].rule, "max-env-size");
+        assert_eq!(violations[1].package, None);
+
+        assert!(check_sizes(&sizes, &SizePolicy::default()).is_empty());
+    }
+
+    #[test]
+    fn duplicate_violations() {
+        let meta = |version: &str, meta_dir: &str, shadowed: &[(&str, &str)]| DistributionMeta {
+            installed_version: version.to_string(),
+            meta_dir: PathBuf::from(meta_dir),
+            shadowed: shadowed
+                .iter()
+                .map(|(version, meta_dir)| ShadowedCopy {
+                    installed_version: version.to_string(),
+                    meta_dir: PathBuf::from(meta_dir),
+                    inherited: false,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let dag = DependencyDag::from([
+            (
+                String::from("six"),
+                meta(
+                    "1.16.0",
+                    "/venv/lib/python3.11/site-packages/six-1.16.0.dist-info",
+                    &[
+                        (
+                            "1.15.0",
+                            "/venv/lib/python3.10/site-packages/six-1.15.0.dist-info",
+                        ),
+                        (
+                            "1.16.0",
+                            "/layer/lib/python3.11/site-packages/six-1.16.0.dist-info",
+                        ),
+                    ],
+                ),
+            ),
+            (
+                String::from("numpy"),
+                meta(
+                    "1.26.0",
+                    "/venv/lib/python3.11/site-packages/numpy-1.26.0.dist-info",
+                    &[
+                        (
+                            "1.26.0",
+                            "/venv/lib64/python3.11/site-packages/numpy-1.26.0.dist-info",
+                        ),
+                        (
+                            "1.25.0",
+                            "/venv/lib/python3.11/site-packages/numpy-1.25.0.dist-info",
+                        ),
+                    ],
+                ),
+            ),
+        ]);
+
+        let violations = check_duplicates(&dag);
+        let messages: Vec<&str> = violations.iter().map(|v| v.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "numpy 1.26.0 in /venv/lib64/python3.11/site-packages/numpy-1.26.0.dist-info \
+                 is in both purelib and platlib with 1.26.0 in \
+                 /venv/lib/python3.11/site-packages/numpy-1.26.0.dist-info, remove the stale copy",
+                "numpy 1.25.0 in /venv/lib/python3.11/site-packages/numpy-1.25.0.dist-info \
+                 is in the same dir with 1.26.0 in \
+                 /venv/lib/python3.11/site-packages/numpy-1.26.0.dist-info, remove the stale copy",
+                "six 1.15.0 in /venv/lib/python3.10/site-packages/six-1.15.0.dist-info \
+                 is left over from python3.10 with 1.16.0 in \
+                 /venv/lib/python3.11/site-packages/six-1.16.0.dist-info, remove the stale copy",
+            ]
+        );
+        assert_eq!(violations[0].rule, "duplicate-install");
+    }
+
+    struct Forbidden(&'static str);
+
+    impl Check for Forbidden {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn check(&self, dag: &DependencyDag) -> Result<Vec<Violation>, String> {
+            if self.0.is_empty() {
+                return Err(String::from("nothing is forbidden"));
+            }
+            Ok(dag
+                .keys()
+                .filter(|name| name.as_str() == self.0)
+                .map(|name| Violation {
+                    rule: String::from("forbidden"),
+                    package: Some(name.clone()),
+                    message: format!("{} is forbidden", name),
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn requires_python_violations() {
+        let meta = |requires_python: Option<&str>| DistributionMeta {
+            installed_version: String::from("1.0"),
+            requires_python: requires_python.map(String::from),
+            ..Default::default()
+        };
+        let dag = DependencyDag::from([
+            (String::from("old"), meta(Some(">=3.7, <3.13"))),
+            (String::from("modern"), meta(Some(">=3.9"))),
+            (String::from("odd"), meta(Some("3.x"))),
+            (String::from("any"), meta(None)),
+        ]);
+
+        assert_eq!(next_python_minor("3.12.4").as_deref(), Some("3.13"));
+        let violations = check_requires_python(&dag, "3.13");
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "requires-python");
+        assert_eq!(
+            violations[0].message,
+            "old 1.0 requires python >=3.7, <3.13, which excludes 3.13"
+        );
+        assert!(check_requires_python(&dag, "3.12").is_empty());
+    }
+
+    #[test]
+    fn registered_checks() {
+        let dag = DependencyDag::from([
+            (String::from("six"), Default::default()),
+            (String::from("torch"), Default::default()),
+        ]);
+        let mut registry = CheckRegistry::default();
+        registry.register(Box::new(Forbidden("six")));
+        registry.register(Box::new(Forbidden("")));
+        registry.register(Box::new(Forbidden("six")));
+
+        let (violations, failures) = registry.run(&dag);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].message, "six is forbidden");
+        assert_eq!(failures, vec![("", String::from("nothing is forbidden"))]);
+    }
+
+    #[test]
+    fn missing_extra_violations() {
+        let site = SitePackages::new("missing-extra")
+            .dist_info("app", "1.0", &["grpc-kit[GRPC,tls]>=1.0", "six[compat]"])
+            .dist_info("legacy", "0.1", &["grpc-kit==1.0"])
+            .dist_info_file(
+                "grpc-kit",
+                "1.0",
+                "METADATA",
+                "Name: grpc-kit\nVersion: 1.0\nProvides-Extra: tls\n",
+            );
+        let dag = DagBuilder::new()
+            .paths(vec![site.path().to_path_buf()])
+            .build()
+            .unwrap();
+        assert_eq!(
+            dag["app"].requested_extras["grpc-kit"],
+            BTreeSet::from([String::from("grpc"), String::from("tls")])
+        );
+
+        let violations = check_extras(&dag);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, "missing-extra");
+        assert_eq!(violations[0].package.as_deref(), Some("grpc-kit"));
+        assert_eq!(
+            violations[0].message,
+            "app requires grpc-kit[grpc], but the installed grpc-kit 1.0 does not provide \
+             extra grpc, its version is constrained by legacy (==1.0)"
+        );
+    }
+
+    #[test]
+    fn conflicting_extra_violations() {
+        let site = SitePackages::new("conflicting-extras")
+            .dist_info("app", "1.0", &["grpc-kit[grpc]>=1.0"])
+            .dist_info("legacy", "0.1", &["protobuf<4", "grpc-kit[lite]>=1.0"])
+            .dist_info_file(
+                "grpc-kit",
+                "1.0",
+                "METADATA",
+                "Name: grpc-kit\nVersion: 1.0\n\
+                 Provides-Extra: grpc\nProvides-Extra: lite\nProvides-Extra: next\n\
+                 Requires-Dist: protobuf>=4; extra == \"grpc\"\n\
+                 Requires-Dist: protobuf<5; extra == \"lite\"\n\
+                 Requires-Dist: protobuf>=5; extra == \"next\"\n",
+            )
+            .dist_info("protobuf", "3.20", &[])
+            .dist_info("tool", "0.3", &["grpc-kit[next]>=1.0"]);
+        let dag = DagBuilder::new()
+            .paths(vec![site.path().to_path_buf()])
+            .build()
+            .unwrap();
+
+        let violations = check_extras(&dag);
+        assert!(violations
+            .iter()
+            .all(|v| v.rule == "conflicting-extras" && v.package.as_deref() == Some("protobuf")));
+        let messages: Vec<&str> = violations.iter().map(|v| v.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "app requires grpc-kit[grpc], which requires protobuf>=4, while legacy \
+                 requires protobuf<4: no version of protobuf satisfies both",
+                "legacy requires protobuf<4, while tool requires grpc-kit[next], which \
+                 requires protobuf>=5: no version of protobuf satisfies both",
+                "legacy requires grpc-kit[lite], which requires protobuf<5, while tool \
+                 requires grpc-kit[next], which requires protobuf>=5: no version of \
+                 protobuf satisfies both",
+            ]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Wall time spent in every pipeline stage and on every parsed metadata file
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub stages: Vec<(&'static str, Duration)>,
+    pub files: Vec<(PathBuf, Duration)>,
+}
+
+impl Profile {
+    /// Add elapsed time to the stage, stages may be entered several times
+    pub fn add_stage(&mut self, stage: &'static str, elapsed: Duration) {
+        match self.stages.iter_mut().find(|(s, _)| *s == stage) {
+            Some((_, total)) => *total += elapsed,
+            None => self.stages.push((stage, elapsed)),
+        }
+    }
+
+    pub fn add_file(&mut self, path: PathBuf, elapsed: Duration) {
+        self.files.push((path, elapsed));
+    }
+
+    /// Run the closure, accounting its wall time to the stage
+    pub fn time<T>(&mut self, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add_stage(stage, start.elapsed());
+        result
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|(_, elapsed)| *elapsed).sum()
+    }
+
+    /// At most `count` files which took the longest to parse, slowest first
+    pub fn slowest_files(&self, count: usize) -> Vec<&(PathBuf, Duration)> {
+        let mut files: Vec<&(PathBuf, Duration)> = self.files.iter().collect();
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        files.truncate(count);
+        files
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stages_accumulate() {
+        let mut profile = Profile::default();
+        profile.add_stage("scan", Duration::from_millis(2));
+        profile.add_stage("parse", Duration::from_millis(5));
+        profile.add_stage("scan", Duration::from_millis(3));
+
+        assert_eq!(
+            profile.stages,
+            vec![
+                ("scan", Duration::from_millis(5)),
+                ("parse", Duration::from_millis(5))
+            ]
+        );
+        assert_eq!(profile.total(), Duration::from_millis(10));
+        assert_eq!(profile.time("render", || 42), 42);
+        assert_eq!(profile.stages[2].0, "render");
+    }
+
+    #[test]
+    fn slowest_files_first() {
+        let mut profile = Profile::default();
+        profile.add_file(PathBuf::from("a"), Duration::from_millis(1));
+        profile.add_file(PathBuf::from("b"), Duration::from_millis(7));
+        profile.add_file(PathBuf::from("c"), Duration::from_millis(3));
+
+        let slowest: Vec<&PathBuf> = profile.slowest_files(2).iter().map(|(p, _)| p).collect();
+        assert_eq!(slowest, vec![&PathBuf::from("b"), &PathBuf::from("c")]);
+    }
+}
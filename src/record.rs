@@ -0,0 +1,209 @@
+use crate::utils::read_text;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+const RECORD_FILE_NAME: &str = "RECORD";
+
+/// Single row of dist-info RECORD file, see
+/// https://packaging.python.org/en/latest/specifications/recording-installed-packages/#the-record-file
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordEntry {
+    /// path relative to the site-packages dir
+    pub path: String,
+    /// (algorithm, urlsafe-base64-nopad digest)
+    pub hash: Option<(String, String)>,
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum FileStatus {
+    Missing,
+    HashMismatch,
+    SizeMismatch,
+}
+
+/// Split a single csv row, honoring double quoted fields
+fn split_csv_row(row: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = row.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn parse_record_row(row: &str) -> Option<RecordEntry> {
+    let fields = split_csv_row(row);
+    let path = fields.first().filter(|p| !p.is_empty())?.to_string();
+
+    let hash = fields
+        .get(1)
+        .and_then(|h| h.split_once('='))
+        .map(|(algo, digest)| (algo.to_string(), digest.to_string()));
+    let size = fields.get(2).and_then(|s| s.parse().ok());
+
+    Some(RecordEntry { path, hash, size })
+}
+
+/// Read all entries of RECORD file from a dist-info dir
+pub fn read_record(meta_dir: &Path) -> io::Result<Vec<RecordEntry>> {
+    let content = read_text(meta_dir.join(RECORD_FILE_NAME))?;
+    Ok(content.lines().filter_map(parse_record_row).collect())
+}
+
+/// Check a single RECORD entry against the file on disk.
+/// Entries without hash (RECORD itself, *.pyc) are only checked for existence,
+/// entries hashed by an unsupported algorithm are checked by size only.
+pub fn verify_entry(site_packages: &Path, entry: &RecordEntry) -> Option<FileStatus> {
+    let data = match fs::read(site_packages.join(&entry.path)) {
+        Ok(data) => data,
+        Err(_) => return Some(FileStatus::Missing),
+    };
+
+    if let Some(size) = entry.size {
+        if data.len() as u64 != size {
+            return Some(FileStatus::SizeMismatch);
+        }
+    }
+
+    if let Some((algo, digest)) = &entry.hash {
+        if algo == "sha256" && URL_SAFE_NO_PAD.encode(Sha256::digest(&data)) != *digest {
+            return Some(FileStatus::HashMismatch);
+        }
+    }
+    None
+}
+
+/// Installed size of a distribution, as a sum of file sizes from RECORD.
+/// Files without recorded size (e.g. RECORD itself) are measured on disk.
+pub fn distribution_size(meta_dir: &Path) -> io::Result<u64> {
+    let site_packages = meta_dir.parent().unwrap_or(meta_dir);
+
+    Ok(read_record(meta_dir)?
+        .iter()
+        .map(|entry| match entry.size {
+            Some(size) => size,
+            None => fs::metadata(site_packages.join(&entry.path))
+                .map(|m| m.len())
+                .unwrap_or(0),
+        })
+        .sum())
+}
+
+/// Modification time and size of RECORD and of every file it lists, for
+/// telling whether a stored verify result still describes the files on disk.
+/// Missing files are recorded as such
+pub fn record_fingerprint(meta_dir: &Path) -> io::Result<String> {
+    let site_packages = meta_dir.parent().unwrap_or(meta_dir);
+    let stat = |path: &Path| match fs::metadata(path) {
+        Ok(metadata) => {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_nanos());
+            format!("{}:{}", modified, metadata.len())
+        }
+        Err(_) => String::from("-"),
+    };
+    let mut fingerprint = stat(&meta_dir.join(RECORD_FILE_NAME));
+    for entry in read_record(meta_dir)? {
+        fingerprint.push(' ');
+        fingerprint.push_str(&stat(&site_packages.join(&entry.path)));
+    }
+    Ok(fingerprint)
+}
+
+/// Verify all files listed in RECORD of a dist-info dir,
+/// returning only the problematic ones
+pub fn verify_distribution(meta_dir: &Path) -> io::Result<Vec<(RecordEntry, FileStatus)>> {
+    // RECORD paths are relative to the dir containing dist-info
+    let site_packages = meta_dir.parent().unwrap_or(meta_dir);
+
+    Ok(read_record(meta_dir)?
+        .into_iter()
+        .filter_map(|entry| verify_entry(site_packages, &entry).map(|status| (entry, status)))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_record_rows() {
+        assert_eq!(
+            parse_record_row("six.py,sha256=TOOfJi7dWd4-e6YU3C6ZqN9vBaxDSd35Q0M3vlxNpbM,34549"),
+            Some(RecordEntry {
+                path: String::from("six.py"),
+                hash: Some((
+                    String::from("sha256"),
+                    String::from("TOOfJi7dWd4-e6YU3C6ZqN9vBaxDSd35Q0M3vlxNpbM")
+                )),
+                size: Some(34549),
+            })
+        );
+        assert_eq!(
+            parse_record_row("six-1.16.0.dist-info/RECORD,,"),
+            Some(RecordEntry {
+                path: String::from("six-1.16.0.dist-info/RECORD"),
+                hash: None,
+                size: None,
+            })
+        );
+        assert_eq!(
+            parse_record_row("\"dir/with,comma.py\",sha256=abc,3"),
+            Some(RecordEntry {
+                path: String::from("dir/with,comma.py"),
+                hash: Some((String::from("sha256"), String::from("abc"))),
+                size: Some(3),
+            })
+        );
+        assert_eq!(parse_record_row(""), None);
+    }
+
+    #[test]
+    fn verify_entries() {
+        let dir = std::env::temp_dir().join(format!("rdeptree-record-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("module.py"), b"print('hello')\n").unwrap();
+        let digest = URL_SAFE_NO_PAD.encode(Sha256::digest(b"print('hello')\n"));
+
+        let mut entry = RecordEntry {
+            path: String::from("module.py"),
+            hash: Some((String::from("sha256"), digest)),
+            size: Some(15),
+        };
+        assert_eq!(verify_entry(&dir, &entry), None);
+
+        entry.size = Some(16);
+        assert_eq!(verify_entry(&dir, &entry), Some(FileStatus::SizeMismatch));
+
+        entry.size = None;
+        entry.hash = Some((String::from("sha256"), String::from("bogus")));
+        assert_eq!(verify_entry(&dir, &entry), Some(FileStatus::HashMismatch));
+
+        entry.path = String::from("missing.py");
+        assert_eq!(verify_entry(&dir, &entry), Some(FileStatus::Missing));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
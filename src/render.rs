@@ -1,33 +1,1101 @@
-use crate::dag::{DependencyDag, DistributionName};
+use crate::dag::{
+    get_constraints, get_requirers, split_dependency_str, split_requested_extras, DependencyDag,
+    DistributionMeta, DistributionName, DumpedLine, LinkKind, ParsedLine, RequiredDistribution,
+    RootStrategy,
+};
+use crate::fleet::VenvAudit;
+use crate::graph::{breadth_first, exclusive_dependencies, DepthFirst, Direction};
+#[cfg(feature = "network")]
+use crate::health::PackageHealth;
+use crate::i18n::{tr, trf, Msg};
+#[cfg(feature = "network")]
+use crate::index::RemoteNode;
+use crate::locator::{PythonTree, ScanPath};
+use crate::normalize::{canonical_specifier, pep503};
+use crate::origin::Origin;
+#[cfg(feature = "network")]
+use crate::plan::Pin;
+use crate::policy::Violation;
+use crate::profile::Profile;
+use crate::record::{FileStatus, RecordEntry};
+use crate::renderer::satisfied;
+use crate::requirements::Edit;
+use crate::snapshot::{PackageDiff, Snapshot, SnapshotDiff};
+use crate::table::{terminal_width, Table};
+use crate::utils::{format_age, format_unix_timestamp};
+use crate::wheelcache::CachedDistribution;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// User selected options affecting how nodes are printed
+#[derive(Debug, Default, Clone)]
+pub struct RenderOptions {
+    pub show_installer: bool,
+    pub show_requirers: bool,
+    /// paint package names according to their origin
+    pub color_origin: bool,
+    /// stop descending after this many levels, unlimited if None
+    pub max_depth: Option<usize>,
+    /// render only these distributions and their dependencies,
+    /// the ones chosen by `roots` if empty
+    pub packages: Vec<DistributionName>,
+    /// how root distributions are chosen when no package is selected
+    pub roots: RootStrategy,
+    /// group top level trees by origin of the top level distribution
+    pub group_by_origin: bool,
+    /// group top level trees sharing a name prefix, e.g. `aws-*`
+    pub group_by_prefix: bool,
+    /// annotate every package with the age of its installed release
+    pub release_ages: Option<ReleaseAges>,
+    /// newest releases on the index, shown by the markdown format
+    pub latest_versions: HashMap<DistributionName, String>,
+    /// append totals of the rendered packages, text format only
+    pub summary: bool,
+    /// wrap the json list into `{"packages": [...], "summary": {...}}`
+    pub json_summary: bool,
+    /// mark packages the user installed explicitly with a `[direct]` badge
+    pub show_requested: bool,
+    /// annotate every package with its declared Requires-Python
+    pub show_requires_python: bool,
+    /// append exclusive dependency counts of the roots, text format only
+    pub root_cost: bool,
+    /// OS packages owning distributions, annotated when queried
+    pub os_packages: Option<HashMap<DistributionName, String>>,
+}
+
+/// Release dates of installed versions, as published on the index
+#[derive(Debug, Default, Clone)]
+pub struct ReleaseAges {
+    /// upload times of installed versions, unix timestamps
+    pub released: HashMap<DistributionName, u64>,
+    /// time the ages are counted to, unix timestamp
+    pub now: u64,
+    /// flag releases older than this many days
+    pub max_age_days: Option<u64>,
+}
+
+/// Amount of dashes each tree level is indented by
+const LEVEL_INDENT: usize = 4;
+
+/// Levels left to descend below a node printed at the given indent
+fn remaining_depth(level: usize, opts: &RenderOptions) -> Option<usize> {
+    opts.max_depth
+        .map(|max_depth| max_depth.saturating_sub(level / LEVEL_INDENT))
+}
+
+/// Wrap text into ANSI color escape sequence
+fn paint(text: &str, color: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", color, text)
+}
+
+/// Package name as it should be printed, optionally painted by origin
+fn node_label(
+    node_name: &DistributionName,
+    meta: &DistributionMeta,
+    opts: &RenderOptions,
+) -> String {
+    let origin = Origin::of(meta);
+    let name = meta.display_name(node_name);
+    if opts.color_origin && origin != Origin::Index {
+        paint(name, origin.color())
+    } else {
+        name.to_string()
+    }
+}
+
+/// Optional node details, appended after the installed version
+fn node_details(
+    node_name: &DistributionName,
+    meta: &DistributionMeta,
+    opts: &RenderOptions,
+) -> String {
+    let mut details = String::new();
+    if meta.inherited {
+        details.push_str(", inherited");
+    }
+    for copy in &meta.shadowed {
+        details.push_str(", shadowed: ");
+        details.push_str(&copy.installed_version);
+    }
+    if opts.show_installer {
+        details.push_str(", installer: ");
+        details.push_str(meta.installer.as_deref().unwrap_or("unknown"));
+    }
+    if opts.show_requires_python {
+        details.push_str(", python: ");
+        details.push_str(display_specifier(
+            meta.requires_python.as_deref().unwrap_or_default(),
+        ));
+    }
+    if let Some(os_packages) = &opts.os_packages {
+        details.push_str(", os package: ");
+        details.push_str(os_packages.get(node_name).map_or("none", String::as_str));
+    }
+    if let Some(ages) = &opts.release_ages {
+        details.push_str(", age: ");
+        match ages.released.get(node_name) {
+            Some(released) => {
+                let days = ages.now.saturating_sub(*released) / 86400;
+                details.push_str(&format_age(days));
+                if ages.max_age_days.is_some_and(|max| days > max) {
+                    details.push_str(", outdated");
+                }
+            }
+            None => details.push_str("unknown"),
+        }
+    }
+    details
+}
+
+/// Optional badge of explicitly installed packages, appended after the node
+fn node_badge(meta: &DistributionMeta, opts: &RenderOptions) -> &'static str {
+    if opts.show_requested && meta.requested {
+        " [direct]"
+    } else {
+        ""
+    }
+}
+
+/// Optional compact list of direct reverse dependencies, appended after the node
+fn node_requirers(
+    dag: &DependencyDag,
+    node_name: &DistributionName,
+    opts: &RenderOptions,
+) -> String {
+    if !opts.show_requirers {
+        return String::new();
+    }
+
+    let mut requirers: Vec<&str> = get_requirers(dag, node_name)
+        .into_iter()
+        .map(|(k, _)| k.as_str())
+        .collect();
+    requirers.dedup();
+
+    if requirers.is_empty() {
+        String::new()
+    } else {
+        format!(" required by: {}", requirers.join(", "))
+    }
+}
+
+/// Requirements without version specifier allow any version
+fn display_specifier(specifier: &str) -> &str {
+    if specifier.is_empty() {
+        "any"
+    } else {
+        specifier
+    }
+}
 
 /// Print results of the program, i.e. the list of installed
-/// packages and interpreter path
+/// packages and interpreter path. Dependency cycles are printed only once
 pub fn render_dag(
+    w: &mut dyn Write,
     dag: &DependencyDag,
     node_name: &DistributionName,
     node_required_ver: Option<&String>,
     level: usize,
-) {
-    let prefix = "-".repeat(level);
+    opts: &RenderOptions,
+) -> io::Result<()> {
+    let traversal = DepthFirst::new(dag, node_name, Direction::Dependencies)
+        .max_depth(remaining_depth(level, opts));
+    for visit in traversal {
+        let Some(val) = visit.meta else {
+            continue;
+        };
+        let prefix = "-".repeat(level + visit.depth * LEVEL_INDENT);
+        let required_ver = visit
+            .requirement
+            .map(|r| &r.required_version)
+            .or(node_required_ver);
+        if let Some(required_ver) = required_ver {
+            writeln!(
+                w,
+                "{}{} [required: {}, installed: {}{}]{}{}{}",
+                prefix,
+                node_label(visit.name, val, opts),
+                display_specifier(required_ver),
+                val.installed_version,
+                node_details(visit.name, val, opts),
+                node_badge(val, opts),
+                node_requirers(dag, visit.name, opts),
+                if visit.cycle { " (cycle)" } else { "" }
+            )?;
+        } else {
+            writeln!(
+                w,
+                "{}{} [installed: {}{}]{}{}",
+                prefix,
+                node_label(visit.name, val, opts),
+                val.installed_version,
+                node_details(visit.name, val, opts),
+                node_badge(val, opts),
+                node_requirers(dag, visit.name, opts)
+            )?;
+        }
+    }
+    Ok(())
+}
 
-    match dag.get(node_name) {
-        Some(val) => {
-            if let Some(required_ver) = node_required_ver {
-                println!(
-                    "{}{} [required: {}, installed: {}]",
-                    prefix, node_name, required_ver, val.installed_version
-                )
-            } else {
-                println!(
-                    "{}{} [installed: {}]",
-                    prefix, node_name, val.installed_version
-                );
+/// Print the reverse tree of a distribution, i.e. the chains of
+/// distributions which require it, up to the top level ones
+pub fn render_reverse_dag(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    node_name: &DistributionName,
+    node_required_ver: Option<&String>,
+    level: usize,
+    opts: &RenderOptions,
+) -> io::Result<()> {
+    let traversal = DepthFirst::new(dag, node_name, Direction::Requirers)
+        .max_depth(remaining_depth(level, opts));
+    for visit in traversal {
+        let Some(val) = visit.meta else {
+            continue;
+        };
+        let prefix = "-".repeat(level + visit.depth * LEVEL_INDENT);
+        let required_ver = visit
+            .requirement
+            .map(|r| &r.required_version)
+            .or(node_required_ver);
+        if let Some(required_ver) = required_ver {
+            writeln!(
+                w,
+                "{}{} [requires: {}, installed: {}{}]",
+                prefix,
+                node_label(visit.name, val, opts),
+                display_specifier(required_ver),
+                val.installed_version,
+                node_details(visit.name, val, opts)
+            )?;
+        } else {
+            writeln!(
+                w,
+                "{}{} [installed: {}{}]",
+                prefix,
+                node_label(visit.name, val, opts),
+                val.installed_version,
+                node_details(visit.name, val, opts)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Print names and installed versions of given distributions, one per line
+pub fn render_list(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    names: &[&DistributionName],
+) -> io::Result<()> {
+    let width = names
+        .iter()
+        .map(|name| dag[*name].display_name(name).chars().count())
+        .max()
+        .unwrap_or_default();
+    for name in names {
+        let meta = &dag[*name];
+        writeln!(
+            w,
+            "{:<width$}  {}",
+            meta.display_name(name),
+            meta.installed_version
+        )?;
+    }
+    Ok(())
+}
+
+/// Print installed distributions as `name==version` pins, in the given order
+pub fn render_vendor_list(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    names: &[&DistributionName],
+) -> io::Result<()> {
+    for name in names {
+        writeln!(w, "{}=={}", name, dag[*name].installed_version)?;
+    }
+    Ok(())
+}
+
+/// Print dirs python may import packages from, marking the ones rdeptree scans
+pub fn render_scan_paths(w: &mut dyn Write, paths: &[ScanPath]) -> io::Result<()> {
+    for path in paths {
+        let state = if path.scanned { "scanned" } else { "skipped" };
+        let missing = if path.exists { "" } else { ", does not exist" };
+        writeln!(
+            w,
+            "{} {} ({}{})",
+            state,
+            path.path.display(),
+            path.reason,
+            missing
+        )?;
+    }
+    Ok(())
+}
+
+/// Print for every root the number of installed transitive dependencies it
+/// requires and how many of them no other root requires, most expensive first
+pub fn render_root_costs(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    roots: &[&DistributionName],
+) -> io::Result<()> {
+    let mut costs: Vec<(&DistributionName, usize, usize)> = exclusive_dependencies(dag, roots)
+        .into_iter()
+        .map(|(root, exclusive)| {
+            let total = breadth_first(dag, [root], Direction::Dependencies)
+                .into_iter()
+                .filter(|(name, _)| *name != root && dag.contains_key(*name))
+                .count();
+            (root, exclusive.len(), total)
+        })
+        .collect();
+    costs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut table = Table::new(&["root", "exclusive", "total"])
+        .align_right(1)
+        .align_right(2);
+    for (root, exclusive, total) in costs {
+        table.row([
+            dag[root].display_name(root),
+            &exclusive.to_string(),
+            &total.to_string(),
+        ]);
+    }
+    table.render(w, terminal_width())
+}
+
+/// Print cached distributions with their versions, oldest first
+pub fn render_cache(w: &mut dyn Write, cached: &[CachedDistribution]) -> io::Result<()> {
+    let mut table = Table::new(&["package", "versions"]);
+    for versions in cached.chunk_by(|a, b| a.name == b.name) {
+        let numbers: Vec<&str> = versions.iter().map(|c| c.version.as_str()).collect();
+        table.row([versions[0].display_name.as_str(), &numbers.join(", ")]);
+    }
+    table.render(w, terminal_width())
+}
+
+/// Print requirements of cached versions of a distribution, markers included
+pub fn render_cached_requirements(
+    w: &mut dyn Write,
+    versions: &[&CachedDistribution],
+) -> io::Result<()> {
+    for cached in versions {
+        writeln!(
+            w,
+            "{} {} ({})",
+            cached.display_name,
+            cached.version,
+            cached.location.display()
+        )?;
+        for dep in &cached.dependencies {
+            write!(
+                w,
+                "    {} {}",
+                dep.name,
+                display_specifier(&dep.required_version)
+            )?;
+            match &dep.marker {
+                Some(marker) => writeln!(w, "; {}", marker)?,
+                None => writeln!(w)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print python minor versions with their site-packages dirs
+pub fn render_python_trees(w: &mut dyn Write, trees: &[PythonTree]) -> io::Result<()> {
+    for tree in trees {
+        let dirs: Vec<String> = tree
+            .site_packages
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect();
+        write!(w, "{}: {}", tree.version, dirs.join(", "))?;
+        match &tree.interpreter {
+            Some(interpreter) => writeln!(w, " ({})", interpreter.display())?,
+            None => writeln!(w)?,
+        }
+    }
+    Ok(())
+}
+
+/// Print details of a single installed distribution
+pub fn render_info(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    node_name: &DistributionName,
+) -> io::Result<()> {
+    if let Some(val) = dag.get(node_name) {
+        writeln!(w, "Name: {}", val.display_name(node_name))?;
+        writeln!(w, "Version: {}", val.installed_version)?;
+        writeln!(
+            w,
+            "Metadata-Version: {}",
+            val.metadata_version.as_deref().unwrap_or("unknown")
+        )?;
+        if let Some(license_expression) = &val.license_expression {
+            writeln!(w, "License-Expression: {}", license_expression)?;
+        }
+        if let Some(requires_python) = &val.requires_python {
+            writeln!(w, "Requires-Python: {}", requires_python)?;
+        }
+        if !val.license_files.is_empty() {
+            writeln!(w, "License-Files: {}", val.license_files.join(", "))?;
+        }
+        if !val.dynamic.is_empty() {
+            writeln!(w, "Dynamic: {}", val.dynamic.join(", "))?;
+        }
+        writeln!(
+            w,
+            "Installer: {}",
+            val.installer.as_deref().unwrap_or("unknown")
+        )?;
+        writeln!(w, "Location: {}", val.meta_dir.display())?;
+        for copy in &val.shadowed {
+            writeln!(
+                w,
+                "Shadowed-Copy: {} ({})",
+                copy.installed_version,
+                copy.meta_dir.display()
+            )?;
+        }
+
+        for kind in LinkKind::ALL {
+            if let Some(url) = val.project_url(kind) {
+                writeln!(w, "{}: {}", kind, url)?;
+            }
+        }
+
+        let mut requires: Vec<String> = val
+            .dependencies
+            .iter()
+            .map(|d| format!("{} {}", d.name, d.required_version))
+            .collect();
+        requires.sort();
+        writeln!(w, "Requires: {}", requires.join(", "))?;
+        let mut excluded: Vec<String> = val
+            .excluded_by_marker
+            .iter()
+            .map(|d| format!("{} ({})", d.name, d.marker.as_deref().unwrap_or_default()))
+            .collect();
+        if !excluded.is_empty() {
+            excluded.sort();
+            writeln!(w, "Excluded-By-Marker: {}", excluded.join(", "))?;
+        }
+
+        let mut requirers: Vec<&str> = get_requirers(dag, node_name)
+            .into_iter()
+            .map(|(k, _)| k.as_str())
+            .collect();
+        requirers.dedup();
+        writeln!(w, "Required-by: {}", requirers.join(", "))?;
+    }
+    Ok(())
+}
+
+/// Print problems found while verifying installed files of a distribution
+pub fn render_verify_report(
+    w: &mut dyn Write,
+    node_name: &DistributionName,
+    problems: &[(RecordEntry, FileStatus)],
+) -> io::Result<()> {
+    for (entry, status) in problems {
+        let status_str = match status {
+            FileStatus::Missing => "missing",
+            FileStatus::HashMismatch => "hash mismatch",
+            FileStatus::SizeMismatch => "size mismatch",
+        };
+        writeln!(w, "{}: {} [{}]", node_name, entry.path, status_str)?;
+    }
+    Ok(())
+}
+
+/// Print structured result of parsing a single METADATA line
+pub fn render_parsed_line(w: &mut dyn Write, parsed_line: &ParsedLine) -> io::Result<()> {
+    match parsed_line {
+        ParsedLine::Meta(key, value) => {
+            writeln!(w, "field: {}", key)?;
+            writeln!(w, "value: {}", value)?;
+            if key == "name" {
+                writeln!(w, "normalized name: {}", pep503(value))?;
+            }
+        }
+        ParsedLine::Dependency(name, dependency_str) => {
+            writeln!(w, "field: requires-dist")?;
+            writeln!(w, "name: {}", name)?;
+            writeln!(w, "normalized name: {}", pep503(name))?;
+            let (extras, _) = split_requested_extras(dependency_str);
+            if !extras.is_empty() {
+                writeln!(w, "extras: {}", extras.join(","))?;
+            }
+            match split_dependency_str(dependency_str) {
+                Ok((specifier, marker)) => {
+                    writeln!(w, "specifier: {}", specifier)?;
+                    writeln!(w, "canonical specifier: {}", canonical_specifier(specifier))?;
+                    writeln!(w, "marker: {}", marker.unwrap_or(""))?;
+                }
+                Err(err) => writeln!(w, "error: {}", err)?,
             }
+        }
+    }
+    Ok(())
+}
+
+/// Print Name, Version and Requires-Dist lines of a metadata dir the way
+/// `parse-line` does, each with its location, extras of requirements and
+/// lines the grammar did not recognize
+pub fn render_meta_dump(
+    w: &mut dyn Write,
+    meta_dir: &Path,
+    lines: &[DumpedLine],
+) -> io::Result<()> {
+    writeln!(w, "location: {}", meta_dir.display())?;
+    for dumped in lines {
+        writeln!(w)?;
+        writeln!(w, "line: {}", dumped.source)?;
+        match &dumped.parsed {
+            Some(parsed_line) => {
+                render_parsed_line(w, parsed_line)?;
+                if let ParsedLine::Dependency(..) = parsed_line {
+                    writeln!(w, "extras: {}", dumped.extras.join(", "))?;
+                }
+            }
+            None => {
+                writeln!(w, "not recognized: {}", dumped.line)?;
+                if let Some(error) = &dumped.error {
+                    writeln!(w, "error: {}", error)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print changes between two snapshots of an environment
+pub fn render_snapshot_diff(w: &mut dyn Write, diff: &SnapshotDiff) -> io::Result<()> {
+    if diff.is_empty() {
+        writeln!(w, "{}", tr(Msg::NoChanges))?;
+        return Ok(());
+    }
+    let mut table = Table::new(&["package", "before", "after", "status"]);
+    for (name, version) in &diff.added {
+        table.row([name, "-", version, "added"]);
+    }
+    for (name, version) in &diff.removed {
+        table.row([name, version, "-", "removed"]);
+    }
+    for (name, old_version, new_version) in &diff.changed {
+        table.row([name, old_version, new_version, "changed"]);
+    }
+    for name in &diff.requirements_changed {
+        table.row([name, "", "", tr(Msg::RequirementsChanged)]);
+    }
+    table.render(w, terminal_width())
+}
+
+/// Print distinct specifiers placed on a package with their requirers,
+/// the intersection of them and whether the installed version is inside it
+pub fn render_constraints(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    name: &DistributionName,
+) -> io::Result<()> {
+    let installed = dag.get(name).map(|meta| meta.installed_version.as_str());
+    writeln!(
+        w,
+        "{} [installed: {}]",
+        name,
+        installed.unwrap_or("not installed")
+    )?;
+    let constraints = get_constraints(dag, name);
+    if constraints.specifiers.is_empty() {
+        return writeln!(w, "{}", trf(Msg::NoRequirers, &[name]));
+    }
+
+    let width = constraints
+        .specifiers
+        .keys()
+        .map(|s| display_specifier(s).len())
+        .max()
+        .unwrap_or_default();
+    for (specifier, requirers) in &constraints.specifiers {
+        let requirers: Vec<&str> = requirers.iter().map(|r| r.as_str()).collect();
+        writeln!(
+            w,
+            "{:<width$}  {}",
+            display_specifier(specifier),
+            requirers.join(", ")
+        )?;
+    }
+    writeln!(
+        w,
+        "intersection: {}",
+        display_specifier(&constraints.intersection)
+    )?;
+    let Some(installed) = installed else {
+        return Ok(());
+    };
+    let inside = if constraints.intersection.is_empty() {
+        Some(true)
+    } else {
+        satisfied(&constraints.intersection, installed)
+    };
+    let verdict = match inside {
+        Some(true) => Msg::ConstraintsSatisfied,
+        Some(false) => Msg::ConstraintsViolated,
+        None => Msg::ConstraintsUnchecked,
+    };
+    writeln!(w, "{}", trf(verdict, &[&installed]))
+}
+
+/// Print version change of a package and its changed direct requirements,
+/// specifiers of both environments side by side
+pub fn render_package_diff(
+    w: &mut dyn Write,
+    name: &DistributionName,
+    diff: &PackageDiff,
+) -> io::Result<()> {
+    let old_version = diff.old_version.as_deref().unwrap_or("not installed");
+    let new_version = diff.new_version.as_deref().unwrap_or("not installed");
+    writeln!(w, "{} {} -> {}", name, old_version, new_version)?;
+
+    let mut table = Table::new(&["requirement", "before", "after", "status"]);
+    for (requirement, specifier) in &diff.added {
+        table.row([requirement, "-", display_specifier(specifier), "added"]);
+    }
+    for (requirement, specifier) in &diff.removed {
+        table.row([requirement, display_specifier(specifier), "-", "removed"]);
+    }
+    for (requirement, old_specifier, new_specifier) in &diff.changed {
+        table.row([
+            requirement,
+            display_specifier(old_specifier),
+            display_specifier(new_specifier),
+            "changed",
+        ]);
+    }
+    if table.is_empty() {
+        if diff.old_version == diff.new_version {
+            writeln!(w, "{}", tr(Msg::NoChanges))?;
+        }
+        return Ok(());
+    }
+    table.render(w, terminal_width())
+}
 
-            for dep in &val.dependencies {
-                render_dag(dag, &dep.name, Some(&dep.required_version), level + 4);
+/// Graphviz digraph of both snapshots: added packages and requirements are
+/// green, removed ones red, packages with changed version and requirements
+/// with changed specifier yellow
+pub fn render_snapshot_diff_dot(
+    w: &mut dyn Write,
+    old: &Snapshot,
+    new: &Snapshot,
+) -> io::Result<()> {
+    const ADDED: &str = "green";
+    const REMOVED: &str = "red";
+    const CHANGED: &str = "yellow";
+
+    let edges = |snapshot: &Snapshot| -> BTreeMap<(DistributionName, DistributionName), String> {
+        snapshot
+            .packages
+            .iter()
+            .flat_map(|(name, package)| {
+                package
+                    .requires
+                    .iter()
+                    .map(|r| ((name.clone(), r.name.clone()), r.specifier.clone()))
+            })
+            .collect()
+    };
+    let (old_edges, new_edges) = (edges(old), edges(new));
+
+    writeln!(w, "digraph {{")?;
+    let names: BTreeSet<&DistributionName> =
+        old.packages.keys().chain(new.packages.keys()).collect();
+    for name in names {
+        let (label, color) = match (old.packages.get(name), new.packages.get(name)) {
+            (None, Some(added)) => (added.version.clone(), Some(ADDED)),
+            (Some(removed), None) => (removed.version.clone(), Some(REMOVED)),
+            (Some(old), Some(new)) if old.version != new.version => {
+                (format!("{} -> {}", old.version, new.version), Some(CHANGED))
+            }
+            (_, package) => (package.map(|p| p.version.clone()).unwrap_or_default(), None),
+        };
+        write!(w, "    \"{}\" [label=\"{}\\n{}\"", name, name, label)?;
+        if let Some(color) = color {
+            write!(w, ", style=filled, fillcolor={}", color)?;
+        }
+        writeln!(w, "]")?;
+    }
+
+    let all_edges: BTreeSet<&(DistributionName, DistributionName)> =
+        old_edges.keys().chain(new_edges.keys()).collect();
+    for edge in all_edges {
+        let (label, color) = match (old_edges.get(edge), new_edges.get(edge)) {
+            (None, Some(added)) => (added.clone(), Some(ADDED)),
+            (Some(removed), None) => (removed.clone(), Some(REMOVED)),
+            (Some(old), Some(new)) if old != new => (format!("{} -> {}", old, new), Some(CHANGED)),
+            (_, specifier) => (specifier.cloned().unwrap_or_default(), None),
+        };
+        write!(
+            w,
+            "    \"{}\" -> \"{}\" [label=\"{}\"",
+            edge.0, edge.1, label
+        )?;
+        if let Some(color) = color {
+            write!(w, ", color={}", color)?;
+        }
+        writeln!(w, "]")?;
+    }
+    writeln!(w, "}}")
+}
+
+/// Print list of recorded snapshots with their indexes
+pub fn render_history(w: &mut dyn Write, snapshots: &[Snapshot]) -> io::Result<()> {
+    for (idx, snapshot) in snapshots.iter().enumerate() {
+        writeln!(
+            w,
+            "{}: {} {} packages [{}]",
+            idx,
+            format_unix_timestamp(snapshot.created_at),
+            snapshot.packages.len(),
+            snapshot.env_path
+        )?;
+    }
+    Ok(())
+}
+
+/// Print policy violations found by `check` command
+pub fn render_violations(w: &mut dyn Write, violations: &[Violation]) -> io::Result<()> {
+    for violation in violations {
+        writeln!(w, "[{}] {}", violation.rule, violation.message)?;
+    }
+    if violations.is_empty() {
+        writeln!(w, "{}", tr(Msg::NoViolations))?;
+    }
+    Ok(())
+}
+
+/// Print edits of a requirements file and the files it includes as removed
+/// and added lines, added packages go to the given file
+pub fn render_requirement_edits(w: &mut dyn Write, file: &Path, edits: &[Edit]) -> io::Result<()> {
+    for edit in edits {
+        match edit {
+            Edit::Update {
+                file,
+                line,
+                old,
+                new,
+                ..
+            } => {
+                writeln!(w, "{}:{}: -{}", file.display(), line, old)?;
+                writeln!(w, "{}:{}: +{}", file.display(), line, new)?;
             }
+            Edit::Remove {
+                file, line, old, ..
+            } => writeln!(w, "{}:{}: -{}", file.display(), line, old)?,
+            Edit::Add { new, .. } => writeln!(w, "{}: +{}", file.display(), new)?,
+        }
+    }
+    Ok(())
+}
+
+/// Print top level trees grouped by origin of the top level distribution
+pub fn render_grouped_by_origin(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    top_level: &[&DistributionName],
+    opts: &RenderOptions,
+) -> io::Result<()> {
+    let mut groups: BTreeMap<Origin, Vec<&DistributionName>> = BTreeMap::new();
+    for name in top_level {
+        if let Some(meta) = dag.get(*name) {
+            groups.entry(Origin::of(meta)).or_default().push(name);
+        }
+    }
+
+    for (origin, names) in groups {
+        let header = format!("# {}", origin);
+        if opts.color_origin && origin != Origin::Index {
+            writeln!(w, "{}", paint(&header, origin.color()))?;
+        } else {
+            writeln!(w, "{}", header)?;
+        }
+        for name in names {
+            render_dag(w, dag, name, None, 0, opts)?;
         }
-        None => return,
+    }
+    Ok(())
+}
+
+/// Top level distributions clustered by the part of the name before the first
+/// dash, e.g. `aws` for `aws-xray-sdk`, in order of the prefixes. Only prefixes
+/// shared by several distributions make a group, the rest come with no prefix
+pub fn prefix_groups<'a>(
+    names: &[&'a DistributionName],
+) -> Vec<(Option<&'a str>, Vec<&'a DistributionName>)> {
+    let mut groups: BTreeMap<&str, Vec<&DistributionName>> = BTreeMap::new();
+    for name in names {
+        let prefix = name.split('-').next().unwrap_or(name);
+        groups.entry(prefix).or_default().push(name);
+    }
+    groups
+        .into_iter()
+        .map(|(prefix, names)| match names.len() {
+            1 => (None, names),
+            _ => (Some(prefix), names),
+        })
+        .collect()
+}
+
+/// Print dependency trees of top level distributions,
+/// the ones sharing a name prefix under a `# <prefix>-*` header
+pub fn render_grouped_by_prefix(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    top_level: &[&DistributionName],
+    opts: &RenderOptions,
+) -> io::Result<()> {
+    for (prefix, names) in prefix_groups(top_level) {
+        if let Some(prefix) = prefix {
+            writeln!(w, "# {}-*", prefix)?;
+        }
+        for name in names {
+            render_dag(w, dag, name, None, 0, opts)?;
+        }
+    }
+    Ok(())
+}
+
+/// Print neighborhood of a distribution: its requirers up to `up` levels
+/// and its dependencies down to `down` levels
+pub fn render_focus(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    node_name: &DistributionName,
+    up: usize,
+    down: usize,
+    opts: &RenderOptions,
+) -> io::Result<()> {
+    writeln!(w, "{}", trf(Msg::RequiredByLevelsUp, &[&up]))?;
+    let up_opts = RenderOptions {
+        max_depth: Some(up),
+        ..opts.clone()
+    };
+    render_reverse_dag(w, dag, node_name, None, 0, &up_opts)?;
+
+    writeln!(w, "{}", trf(Msg::RequiresLevelsDown, &[&down]))?;
+    let down_opts = RenderOptions {
+        max_depth: Some(down),
+        ..opts.clone()
+    };
+    render_dag(w, dag, node_name, None, 0, &down_opts)
+}
+
+/// Names of the given distributions and all distributions reachable from them,
+/// required distributions which are not installed included
+fn reachable_from<'a>(
+    dag: &'a DependencyDag,
+    names: impl IntoIterator<Item = &'a DistributionName>,
+) -> BTreeSet<&'a DistributionName> {
+    breadth_first(dag, names, Direction::Dependencies)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Print declared extras of a distribution, each with the subtrees of its
+/// requirements and the number of packages it adds to the tree of the distribution
+pub fn render_extras(
+    w: &mut dyn Write,
+    dag: &DependencyDag,
+    node_name: &DistributionName,
+    opts: &RenderOptions,
+) -> io::Result<()> {
+    let Some(val) = dag.get(node_name) else {
+        return Ok(());
     };
+    // without marker evaluation requirements of extras are edges of the tree too
+    let mut base = reachable_from(
+        dag,
+        val.dependencies
+            .iter()
+            .filter(|d| !val.extras.values().any(|r| r.contains(*d)))
+            .map(|d| &d.name),
+    );
+    base.insert(node_name);
+    for (extra, requirements) in &val.extras {
+        let additional = reachable_from(dag, requirements.iter().map(|d| &d.name))
+            .into_iter()
+            .filter(|name| !base.contains(name))
+            .count();
+        writeln!(
+            w,
+            "{}",
+            trf(
+                Msg::ExtraAdditionalPackages,
+                &[node_name, extra, &additional]
+            )
+        )?;
+
+        let mut requirements: Vec<&RequiredDistribution> = requirements.iter().collect();
+        requirements.sort_by_key(|d| (&d.name, &d.required_version));
+        for dep in requirements {
+            if dag.contains_key(&dep.name) {
+                render_dag(
+                    w,
+                    dag,
+                    &dep.name,
+                    Some(&dep.required_version),
+                    LEVEL_INDENT,
+                    opts,
+                )?;
+            } else {
+                writeln!(
+                    w,
+                    "{}{} [required: {}, not installed]",
+                    "-".repeat(LEVEL_INDENT),
+                    dep.name,
+                    display_specifier(&dep.required_version)
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print dependency tree resolved against a package index
+#[cfg(feature = "network")]
+pub fn render_remote_tree(w: &mut dyn Write, node: &RemoteNode, level: usize) -> io::Result<()> {
+    let prefix = "-".repeat(level);
+    let version = node.version.as_deref().unwrap_or("no matching version");
+    let cycle = if node.cycle { " (cycle)" } else { "" };
+    if level == 0 {
+        writeln!(w, "{} [resolved: {}]{}", node.name, version, cycle)?;
+    } else {
+        writeln!(
+            w,
+            "{}{} [required: {}, resolved: {}]{}",
+            prefix,
+            node.name,
+            display_specifier(&node.required_version),
+            version,
+            cycle
+        )?;
+    }
+    for dependency in &node.dependencies {
+        render_remote_tree(w, dependency, level + LEVEL_INDENT)?;
+    }
+    Ok(())
+}
+
+/// Print pins of an upgrade plan in requirements file format
+#[cfg(feature = "network")]
+pub fn render_upgrade_plan(w: &mut dyn Write, pins: &[Pin]) -> io::Result<()> {
+    for pin in pins {
+        match &pin.installed {
+            Some(installed) => writeln!(w, "{}=={}  # from {}", pin.name, pin.version, installed)?,
+            None => writeln!(w, "{}=={}  # new", pin.name, pin.version)?,
+        }
+    }
+    Ok(())
+}
+
+/// Print health report as a table, `window_months` is the period
+/// recent releases are counted in
+#[cfg(feature = "network")]
+pub fn render_health(
+    w: &mut dyn Write,
+    report: &[PackageHealth],
+    window_months: u64,
+) -> io::Result<()> {
+    let releases = format!("releases/{}m", window_months);
+    let mut table = Table::new(&[
+        "package",
+        "installed",
+        "last release",
+        &releases,
+        "yanked",
+        "score",
+    ])
+    .align_right(3)
+    .align_right(5);
+    for health in report {
+        let last_release = health
+            .last_release_days
+            .map_or(String::from("unknown"), format_age);
+        table.row([
+            health.name.to_string(),
+            health.installed_version.clone(),
+            last_release,
+            health.recent_releases.to_string(),
+            String::from(if health.installed_yanked { "yes" } else { "no" }),
+            health.score.to_string(),
+        ]);
+    }
+    table.render(w, terminal_width())
+}
+
+/// Print one row per audited virtual env, paths relative to the scanned root,
+/// followed by the number of envs with problems
+pub fn render_fleet(w: &mut dyn Write, root: &Path, audits: &[VenvAudit]) -> io::Result<()> {
+    let envs: Vec<String> = audits
+        .iter()
+        .map(|audit| {
+            let venv = audit.venv.strip_prefix(root).unwrap_or(&audit.venv);
+            venv.display().to_string()
+        })
+        .collect();
+    let width = envs
+        .iter()
+        .map(String::len)
+        .chain(["env".len()])
+        .max()
+        .unwrap_or_default();
+    writeln!(
+        w,
+        "{:<width$}  {:>8}  {:>9}  {:>7}  {:>10}",
+        "env", "packages", "conflicts", "missing", "violations"
+    )?;
+    for (env, audit) in envs.iter().zip(audits) {
+        match &audit.summary {
+            Ok(summary) => writeln!(
+                w,
+                "{:<width$}  {:>8}  {:>9}  {:>7}  {:>10}",
+                env,
+                summary.packages,
+                summary.conflicts,
+                summary.missing,
+                audit.violations.len()
+            )?,
+            Err(err) => writeln!(w, "{:<width$}  error: {}", env, err)?,
+        }
+    }
+    let unhealthy = audits.iter().filter(|audit| !audit.healthy()).count();
+    writeln!(w, "{} envs, {} with problems", audits.len(), unhealthy)
+}
+
+/// Print timing summary collected with `--profile` to stderr,
+/// so it doesn't mix with the regular output
+pub fn render_profile(profile: &Profile, slowest_files: usize) {
+    let millis = |elapsed: &std::time::Duration| elapsed.as_secs_f64() * 1000.0;
+
+    eprintln!("{:<10} {:>12}", "stage", "time, ms");
+    for (stage, elapsed) in &profile.stages {
+        eprintln!("{:<10} {:>12.3}", stage, millis(elapsed));
+    }
+    eprintln!("{:<10} {:>12.3}", "total", millis(&profile.total()));
+
+    if !profile.files.is_empty() {
+        eprintln!();
+        eprintln!("{} metadata files parsed, slowest:", profile.files.len());
+        for (path, elapsed) in profile.slowest_files(slowest_files) {
+            eprintln!("{:>12.3} {}", millis(elapsed), path.display());
+        }
+    }
 }
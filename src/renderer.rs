@@ -0,0 +1,1100 @@
+use crate::dag::{
+    DependencyDag, DistributionMeta, DistributionName, LinkKind, RequiredDistribution, RootStrategy,
+};
+use crate::graph::{breadth_first, DepthFirst, Direction};
+use crate::marker::{release_allowed, MarkerExpr};
+use crate::origin::requirement_line;
+use crate::render::{
+    prefix_groups, render_dag, render_grouped_by_origin, render_grouped_by_prefix,
+    render_root_costs, RenderOptions,
+};
+use crate::utils::compare_versions;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::io::{self, Write};
+
+/// Output format of the dependency dag, selected with `--format`
+pub trait Renderer {
+    /// name used to select the renderer
+    fn name(&self) -> &'static str;
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()>;
+
+    /// Warnings the format prints next to its output, e.g. pipdeptree's
+    /// conflict blocks. Where they go is up to the caller
+    fn warnings(&self, _dag: &DependencyDag, _opts: &RenderOptions) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Root distributions of the output, either selected by user
+/// or chosen by the root strategy
+fn roots<'a>(dag: &'a DependencyDag, opts: &'a RenderOptions) -> Vec<&'a DistributionName> {
+    if opts.packages.is_empty() {
+        opts.roots.select(dag)
+    } else {
+        opts.packages
+            .iter()
+            .filter(|p| dag.contains_key(*p))
+            .collect()
+    }
+}
+
+/// Names of the roots and all distributions reachable from them, sorted
+fn reachable<'a>(
+    dag: &'a DependencyDag,
+    opts: &'a RenderOptions,
+) -> BTreeSet<&'a DistributionName> {
+    // distributions inside dependency cycles are never top level,
+    // so without selection the whole dag is rendered
+    let whole_dag = opts.packages.is_empty() && matches!(opts.roots, RootStrategy::Auto);
+    let starts: Vec<&DistributionName> = if whole_dag {
+        dag.keys().collect()
+    } else {
+        roots(dag, opts)
+    };
+    breadth_first(dag, starts, Direction::Dependencies)
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| dag.contains_key(*name))
+        .collect()
+}
+
+/// Totals of the rendered packages
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub packages: usize,
+    /// root packages, either selected by user or top level ones
+    pub direct: usize,
+    /// packages installed more than once
+    pub conflicts: usize,
+    /// required packages which are not installed
+    pub missing: usize,
+}
+
+impl Summary {
+    pub fn of(dag: &DependencyDag, opts: &RenderOptions) -> Self {
+        let names = reachable(dag, opts);
+        let missing: BTreeSet<&DistributionName> = names
+            .iter()
+            .flat_map(|name| &dag[*name].dependencies)
+            .map(|d| &d.name)
+            .filter(|name| !dag.contains_key(*name))
+            .collect();
+        Self {
+            packages: names.len(),
+            direct: roots(dag, opts).len(),
+            conflicts: names
+                .iter()
+                .filter(|name| dag[**name].has_conflicts())
+                .count(),
+            missing: missing.len(),
+        }
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} packages, {} direct, {} conflicts, {} missing",
+            self.packages, self.direct, self.conflicts, self.missing
+        )
+    }
+}
+
+/// Indented trees, the default output
+pub struct TextRenderer;
+
+impl Renderer for TextRenderer {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        let roots = roots(dag, opts);
+        if opts.group_by_origin {
+            render_grouped_by_origin(w, dag, &roots, opts)?;
+        } else if opts.group_by_prefix {
+            render_grouped_by_prefix(w, dag, &roots, opts)?;
+        } else {
+            for root in &roots {
+                render_dag(w, dag, root, None, 0, opts)?;
+            }
+        }
+        if opts.summary {
+            writeln!(w, "{}", Summary::of(dag, opts))?;
+        }
+        if opts.root_cost {
+            render_root_costs(w, dag, &roots)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct JsonDependency<'a> {
+    name: &'a str,
+    required_version: &'a str,
+    /// None when the dependency is not installed
+    installed_version: Option<&'a str>,
+    /// raw environment marker of the requirement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    marker: Option<&'a str>,
+    /// structure of the marker, None if it is not understood by the parser
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parsed_marker: Option<MarkerExpr>,
+}
+
+#[derive(Serialize)]
+struct JsonPackage<'a> {
+    name: &'a str,
+    installed_version: &'a str,
+    /// installed explicitly by the user, not pulled in as a dependency
+    requested: bool,
+    /// found in site-packages of the base interpreter of the virtual env
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    inherited: bool,
+    dependencies: Vec<JsonDependency<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    packages: Vec<JsonPackage<'a>>,
+    summary: Summary,
+}
+
+/// Flat list of packages with their direct dependencies. With `json_summary`
+/// the list is wrapped into `{"packages": [...], "summary": {...}}`
+pub struct JsonRenderer;
+
+impl Renderer for JsonRenderer {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        let packages: Vec<JsonPackage> = reachable(dag, opts)
+            .into_iter()
+            .map(|name| {
+                let meta = &dag[name];
+                let dependencies: Vec<JsonDependency> = meta
+                    .sorted_dependencies()
+                    .into_iter()
+                    .map(|d| JsonDependency {
+                        name: &d.name,
+                        required_version: &d.required_version,
+                        installed_version: dag.get(&d.name).map(|m| m.installed_version.as_str()),
+                        marker: d.marker.as_deref(),
+                        parsed_marker: d.marker.as_deref().and_then(|m| MarkerExpr::parse(m).ok()),
+                    })
+                    .collect();
+                JsonPackage {
+                    name,
+                    installed_version: &meta.installed_version,
+                    requested: meta.requested,
+                    inherited: meta.inherited,
+                    dependencies,
+                }
+            })
+            .collect();
+
+        if opts.json_summary {
+            let report = JsonReport {
+                packages,
+                summary: Summary::of(dag, opts),
+            };
+            serde_json::to_writer_pretty(&mut *w, &report)?;
+        } else {
+            serde_json::to_writer_pretty(&mut *w, &packages)?;
+        }
+        writeln!(w)
+    }
+}
+
+/// Graphviz digraph, e.g. `rdeptree --format dot | dot -Tsvg > deps.svg`.
+/// Nodes are identified by normalized names and labelled with declared ones
+pub struct DotRenderer;
+
+impl Renderer for DotRenderer {
+    fn name(&self) -> &'static str {
+        "dot"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(w, "digraph {{")?;
+        let names = reachable(dag, opts);
+        for name in &names {
+            let meta = &dag[*name];
+            writeln!(
+                w,
+                "    \"{}\" [label=\"{}\\n{}\"]",
+                name,
+                meta.display_name(name),
+                meta.installed_version
+            )?;
+        }
+        for name in &names {
+            for dep in dag[*name]
+                .sorted_dependencies()
+                .into_iter()
+                .filter(|d| dag.contains_key(&d.name))
+            {
+                writeln!(
+                    w,
+                    "    \"{}\" -> \"{}\" [label=\"{}\"]",
+                    name, dep.name, dep.required_version
+                )?;
+            }
+        }
+        writeln!(w, "}}")
+    }
+}
+
+/// `package: dep1 dep2` lines for awk/sort/comm one-liners,
+/// names only and every required name listed, installed or not
+pub struct AdjacencyRenderer;
+
+impl Renderer for AdjacencyRenderer {
+    fn name(&self) -> &'static str {
+        "adjacency"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        for name in reachable(dag, opts) {
+            let dependencies: BTreeSet<&str> = dag[name]
+                .dependencies
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect();
+            write!(w, "{}:", name)?;
+            for dependency in dependencies {
+                write!(w, " {}", dependency)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+/// Requirements file pinning every package to what is installed, e.g.
+/// `rdeptree --format freeze > constraints.txt`. Packages installed from
+/// a direct reference keep it, pinned to the installed commit or archive
+pub struct FreezeRenderer;
+
+impl Renderer for FreezeRenderer {
+    fn name(&self) -> &'static str {
+        "freeze"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        for name in reachable(dag, opts) {
+            writeln!(w, "{}", requirement_line(name, &dag[name]))?;
+        }
+        Ok(())
+    }
+}
+
+/// Field of a CSV row, quoted when it has separators or quotes
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Whether the installed version satisfies the specifier, None if unknown.
+/// Same answer with and without `pep508`, see `release_allowed`
+pub(crate) fn satisfied(specifier: &str, installed_version: &str) -> Option<bool> {
+    release_allowed(specifier, installed_version)
+}
+
+/// N×N adjacency matrix of rendered packages for spreadsheets: row
+/// package requires column package when the cell is 1
+pub struct MatrixCsvRenderer;
+
+impl Renderer for MatrixCsvRenderer {
+    fn name(&self) -> &'static str {
+        "matrix-csv"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        let names = reachable(dag, opts);
+        write!(w, "requirer")?;
+        for name in &names {
+            write!(w, ",{}", csv_field(name))?;
+        }
+        writeln!(w)?;
+        for name in &names {
+            let required: BTreeSet<&str> = dag[*name]
+                .dependencies
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect();
+            write!(w, "{}", csv_field(name))?;
+            for column in &names {
+                let cell = if required.contains(column.as_str()) {
+                    1
+                } else {
+                    0
+                };
+                write!(w, ",{}", cell)?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+/// Long format CSV with a row per requirement. Missing packages are never
+/// satisfied, the column is empty when the specifier can not be evaluated
+pub struct EdgesCsvRenderer;
+
+impl Renderer for EdgesCsvRenderer {
+    fn name(&self) -> &'static str {
+        "edges-csv"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(w, "requirer,requirement,specifier,satisfied")?;
+        for name in reachable(dag, opts) {
+            for dep in dag[name].sorted_dependencies() {
+                let satisfied = match dag.get(&dep.name) {
+                    Some(meta) => satisfied(&dep.required_version, &meta.installed_version),
+                    None => Some(false),
+                };
+                writeln!(
+                    w,
+                    "{},{},{},{}",
+                    csv_field(name),
+                    csv_field(&dep.name),
+                    csv_field(&dep.required_version),
+                    satisfied.map_or(String::new(), |s| s.to_string())
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Report for merge requests and issues: a table of rendered packages with their
+/// status, missing requirements included, and a collapsible section per root tree
+pub struct MarkdownRenderer;
+
+impl MarkdownRenderer {
+    /// Collapsible tree of every root
+    fn render_trees(
+        dag: &DependencyDag,
+        roots: &[&DistributionName],
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        for root in roots {
+            writeln!(w, "\n<details>")?;
+            writeln!(
+                w,
+                "<summary>{} {}</summary>\n",
+                root, dag[*root].installed_version
+            )?;
+            writeln!(w, "```")?;
+            render_dag(w, dag, root, None, 0, opts)?;
+            writeln!(w, "```\n")?;
+            writeln!(w, "</details>")?;
+        }
+        Ok(())
+    }
+
+    /// Latest release, linked to the release notes when it is newer than the installed one
+    fn latest(meta: &DistributionMeta, latest: Option<&String>) -> String {
+        let Some(latest) = latest else {
+            return String::from("-");
+        };
+        let notes = meta
+            .project_url(LinkKind::Changelog)
+            .or_else(|| meta.project_url(LinkKind::Repository));
+        match notes {
+            Some(url) if *latest != meta.installed_version => format!("[{}]({})", latest, url),
+            _ => latest.clone(),
+        }
+    }
+
+    /// Worst problem of an installed package: installed more than once,
+    /// excluded by a rendered requirer or older than the latest release
+    fn status(
+        dag: &DependencyDag,
+        names: &BTreeSet<&DistributionName>,
+        name: &DistributionName,
+        opts: &RenderOptions,
+    ) -> &'static str {
+        let meta = &dag[name];
+        let unsatisfied = names
+            .iter()
+            .flat_map(|requirer| &dag[*requirer].dependencies)
+            .filter(|d| d.name == *name)
+            .any(|d| satisfied(&d.required_version, &meta.installed_version) == Some(false));
+        if meta.has_conflicts() {
+            "conflict"
+        } else if unsatisfied {
+            "unsatisfied"
+        } else if opts
+            .latest_versions
+            .get(name)
+            .and_then(|latest| compare_versions(&meta.installed_version, latest))
+            == Some(Ordering::Less)
+        {
+            "outdated"
+        } else {
+            "ok"
+        }
+    }
+}
+
+impl Renderer for MarkdownRenderer {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        let names = reachable(dag, opts);
+        let missing: BTreeSet<&DistributionName> = names
+            .iter()
+            .flat_map(|name| &dag[*name].dependencies)
+            .map(|d| &d.name)
+            .filter(|name| !dag.contains_key(*name))
+            .collect();
+        writeln!(w, "| package | installed | latest | status |")?;
+        writeln!(w, "| --- | --- | --- | --- |")?;
+        for name in &names {
+            writeln!(
+                w,
+                "| {} | {} | {} | {} |",
+                name,
+                dag[*name].installed_version,
+                Self::latest(&dag[*name], opts.latest_versions.get(*name)),
+                Self::status(dag, &names, name, opts)
+            )?;
+        }
+        for name in missing {
+            writeln!(w, "| {} | - | - | missing |", name)?;
+        }
+        if opts.summary {
+            writeln!(w, "\n{}", Summary::of(dag, opts))?;
+        }
+        let roots = roots(dag, opts);
+        if !opts.group_by_prefix {
+            return Self::render_trees(dag, &roots, opts, w);
+        }
+        // every group of trees is collapsible as a whole too
+        for (prefix, names) in prefix_groups(&roots) {
+            match prefix {
+                Some(prefix) => {
+                    writeln!(w, "\n<details>")?;
+                    writeln!(w, "<summary>{}-* ({})</summary>", prefix, names.len())?;
+                    Self::render_trees(dag, &names, opts, w)?;
+                    writeln!(w, "\n</details>")?;
+                }
+                None => Self::render_trees(dag, &names, opts, w)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// pipdeptree's plain text layout, for scripts parsing its output: `name==version`
+/// roots with `  - dep [required: spec, installed: version]` children.
+/// Conflicting requirements are reported on stderr in its warning block
+pub struct PipdeptreeRenderer;
+
+/// Rule closing pipdeptree warning blocks
+const PIPDEPTREE_RULE_WIDTH: usize = 72;
+
+impl PipdeptreeRenderer {
+    /// `dep [required: spec, installed: version]`, `?` for missing dependencies
+    fn branch(dag: &DependencyDag, requirement: &RequiredDistribution) -> String {
+        let spec = match requirement.required_version.as_str() {
+            "" => "Any",
+            spec => spec,
+        };
+        match dag.get(&requirement.name) {
+            Some(meta) => format!(
+                "{} [required: {}, installed: {}]",
+                meta.display_name(&requirement.name),
+                spec,
+                meta.installed_version
+            ),
+            None => format!("{} [required: {}, installed: ?]", requirement.name, spec),
+        }
+    }
+
+    /// Warning block listing requirements which are missing or not satisfied
+    /// by the installed version, None when there are none
+    fn conflicts(dag: &DependencyDag) -> Option<String> {
+        let mut names: Vec<&DistributionName> = dag.keys().collect();
+        names.sort();
+        let mut block = String::new();
+        for name in names {
+            let meta = &dag[name];
+            let mut conflicting: Vec<&RequiredDistribution> = meta
+                .dependencies
+                .iter()
+                .filter(|d| match dag.get(&d.name) {
+                    Some(dep) => {
+                        satisfied(&d.required_version, &dep.installed_version) == Some(false)
+                    }
+                    None => true,
+                })
+                .collect();
+            if conflicting.is_empty() {
+                continue;
+            }
+            conflicting.sort_by_key(|d| (&d.name, &d.required_version));
+            block.push_str(&format!(
+                "* {}=={}\n",
+                meta.display_name(name),
+                meta.installed_version
+            ));
+            for requirement in conflicting {
+                block.push_str(&format!(" - {}\n", Self::branch(dag, requirement)));
+            }
+        }
+        (!block.is_empty()).then(|| {
+            format!(
+                "Warning!!! Possibly conflicting dependencies found:\n{}{}",
+                block,
+                "-".repeat(PIPDEPTREE_RULE_WIDTH)
+            )
+        })
+    }
+
+    /// Shortest path of requirements leading from the distribution back to it
+    fn cycle_from<'a>(
+        dag: &'a DependencyDag,
+        start: &'a DistributionName,
+    ) -> Option<Vec<&'a DistributionName>> {
+        let mut parents: HashMap<&DistributionName, &DistributionName> = HashMap::new();
+        let mut queue: VecDeque<&DistributionName> = VecDeque::from([start]);
+        while let Some(name) = queue.pop_front() {
+            for requirement in &dag[name].dependencies {
+                let next = &requirement.name;
+                if next == start {
+                    let mut cycle = vec![start, name];
+                    while let Some(parent) = parents.get(cycle[cycle.len() - 1]) {
+                        cycle.push(parent);
+                    }
+                    cycle.reverse();
+                    return Some(cycle);
+                }
+                if dag.contains_key(next) && !parents.contains_key(next) {
+                    parents.insert(next, name);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Warning block listing a dependency cycle through every distribution on
+    /// one, ordered by the distribution closing the cycle like pipdeptree does,
+    /// None when there are none
+    fn cycles(dag: &DependencyDag) -> Option<String> {
+        let mut names: Vec<&DistributionName> = dag.keys().collect();
+        names.sort();
+        let mut cycles: Vec<Vec<&DistributionName>> = names
+            .into_iter()
+            .filter_map(|name| Self::cycle_from(dag, name))
+            .collect();
+        if cycles.is_empty() {
+            return None;
+        }
+        cycles.sort_by_key(|cycle| cycle[cycle.len() - 2]);
+        let block: String = cycles
+            .iter()
+            .map(|cycle| {
+                let names: Vec<&str> = cycle
+                    .iter()
+                    .map(|name| dag[*name].display_name(name))
+                    .collect();
+                format!("* {}\n", names.join(" => "))
+            })
+            .collect();
+        Some(format!(
+            "Warning!!! Cyclic dependencies found:\n{}{}",
+            block,
+            "-".repeat(PIPDEPTREE_RULE_WIDTH)
+        ))
+    }
+}
+
+impl Renderer for PipdeptreeRenderer {
+    fn name(&self) -> &'static str {
+        "pipdeptree"
+    }
+
+    fn render(
+        &self,
+        dag: &DependencyDag,
+        opts: &RenderOptions,
+        w: &mut dyn Write,
+    ) -> io::Result<()> {
+        for root in roots(dag, opts) {
+            // pipdeptree leaves dependency cycles out instead of marking them
+            for visit in DepthFirst::new(dag, root, Direction::Dependencies).filter(|v| !v.cycle) {
+                match visit.requirement {
+                    None => {
+                        let meta = &dag[root];
+                        writeln!(w, "{}=={}", meta.display_name(root), meta.installed_version)?
+                    }
+                    Some(requirement) => writeln!(
+                        w,
+                        "{}- {}",
+                        " ".repeat(2 * visit.depth),
+                        Self::branch(dag, requirement)
+                    )?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn warnings(&self, dag: &DependencyDag, _opts: &RenderOptions) -> Vec<String> {
+        [Self::conflicts(dag), Self::cycles(dag)]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+/// Set of available output formats, library users may register their own
+pub struct RendererRegistry {
+    renderers: Vec<Box<dyn Renderer>>,
+}
+
+impl Default for RendererRegistry {
+    fn default() -> Self {
+        Self {
+            renderers: vec![
+                Box::new(TextRenderer),
+                Box::new(JsonRenderer),
+                Box::new(DotRenderer),
+                Box::new(AdjacencyRenderer),
+                Box::new(FreezeRenderer),
+                Box::new(MatrixCsvRenderer),
+                Box::new(EdgesCsvRenderer),
+                Box::new(MarkdownRenderer),
+                Box::new(PipdeptreeRenderer),
+            ],
+        }
+    }
+}
+
+impl RendererRegistry {
+    /// Add a renderer, replacing already registered one with the same name
+    pub fn register(&mut self, renderer: Box<dyn Renderer>) {
+        self.renderers.retain(|r| r.name() != renderer.name());
+        self.renderers.push(renderer);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Renderer> {
+        self.renderers
+            .iter()
+            .find(|r| r.name() == name)
+            .map(|r| r.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.renderers.iter().map(|r| r.name()).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::ProjectUrl;
+    use crate::render::ReleaseAges;
+    use std::collections::{HashMap, HashSet};
+
+    fn sample_dag() -> DependencyDag {
+        let meta = |version: &str, deps: &[(&str, &str)]| DistributionMeta {
+            installed_version: version.to_string(),
+            dependencies: deps
+                .iter()
+                .map(|(name, spec)| RequiredDistribution {
+                    name: name.to_string(),
+                    required_version: spec.to_string(),
+                    marker: None,
+                    source: None,
+                })
+                .collect::<HashSet<_>>(),
+            ..Default::default()
+        };
+        let mut lib = meta("2.1", &[("missing", "<1")]);
+        lib.project_urls = vec![ProjectUrl {
+            label: String::from("Changelog"),
+            url: String::from("https://lib.org/changes"),
+        }];
+        DependencyDag::from([
+            ("app".to_string(), meta("1.0", &[("lib", ">=2")])),
+            ("lib".to_string(), lib),
+            ("other".to_string(), meta("0.1", &[])),
+        ])
+    }
+
+    fn render_to_string(format: &str, opts: &RenderOptions) -> String {
+        let registry = RendererRegistry::default();
+        let mut out = Vec::new();
+        registry
+            .get(format)
+            .unwrap()
+            .render(&sample_dag(), opts, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_format() {
+        let opts = RenderOptions {
+            packages: vec!["app".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            render_to_string("text", &opts),
+            "app [installed: 1.0]\n----lib [required: >=2, installed: 2.1]\n"
+        );
+    }
+
+    #[test]
+    fn text_format_with_release_ages() {
+        let now = 1_700_000_000;
+        let opts = RenderOptions {
+            packages: vec!["app".to_string()],
+            release_ages: Some(ReleaseAges {
+                released: HashMap::from([
+                    ("app".to_string(), now - 3 * 86400),
+                    ("lib".to_string(), now - 800 * 86400),
+                ]),
+                now,
+                max_age_days: Some(365),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            render_to_string("text", &opts),
+            "app [installed: 1.0, age: 3 days]\n\
+             ----lib [required: >=2, installed: 2.1, age: 2 years, outdated]\n"
+        );
+    }
+
+    #[test]
+    fn json_format() {
+        let opts = RenderOptions {
+            packages: vec!["lib".to_string()],
+            ..Default::default()
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&render_to_string("json", &opts)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "name": "lib",
+                "installed_version": "2.1",
+                "requested": false,
+                "dependencies": [
+                    {"name": "missing", "required_version": "<1", "installed_version": null}
+                ]
+            }])
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let opts = RenderOptions {
+            summary: true,
+            ..Default::default()
+        };
+        let out = render_to_string("text", &opts);
+        assert!(out.ends_with(
+            "----lib [required: >=2, installed: 2.1]\n\
+             other [installed: 0.1]\n\
+             3 packages, 2 direct, 0 conflicts, 1 missing\n"
+        ));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render_to_string("json", &opts)).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 3);
+
+        let opts = RenderOptions {
+            json_summary: true,
+            ..Default::default()
+        };
+        let json: serde_json::Value =
+            serde_json::from_str(&render_to_string("json", &opts)).unwrap();
+        assert_eq!(json["packages"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            json["summary"],
+            serde_json::json!({"packages": 3, "direct": 2, "conflicts": 0, "missing": 1})
+        );
+    }
+
+    #[test]
+    fn dot_format() {
+        let out = render_to_string("dot", &RenderOptions::default());
+        assert!(out.starts_with("digraph {\n"));
+        assert!(out.contains("    \"app\" -> \"lib\" [label=\">=2\"]\n"));
+        assert!(out.contains("    \"other\" [label=\"other\\n0.1\"]\n"));
+        assert!(!out.contains("missing"));
+    }
+
+    #[test]
+    fn exports_are_byte_identical() {
+        // the same requirements inserted in different orders into sets with their own hashers
+        let dag = |order: &[usize]| {
+            let specifiers = ["<3", ">=1", "!=2.0", ">=2"];
+            let mut dag = sample_dag();
+            let app = dag.get_mut("app").unwrap();
+            app.declared_name = Some(String::from("App"));
+            app.dependencies = order
+                .iter()
+                .map(|i| RequiredDistribution {
+                    name: String::from(if *i % 2 == 0 { "lib" } else { "other" }),
+                    required_version: specifiers[*i].to_string(),
+                    marker: None,
+                    source: None,
+                })
+                .collect();
+            dag
+        };
+        let export = |format: &str, dag: &DependencyDag| {
+            let mut out = Vec::new();
+            RendererRegistry::default()
+                .get(format)
+                .unwrap()
+                .render(dag, &RenderOptions::default(), &mut out)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        for format in ["json", "dot", "edges-csv", "adjacency"] {
+            let expected = export(format, &dag(&[0, 1, 2, 3]));
+            for order in [[3, 2, 1, 0], [1, 3, 0, 2], [2, 0, 3, 1]] {
+                assert_eq!(export(format, &dag(&order)), expected, "{}", format);
+            }
+        }
+        let dot = export("dot", &dag(&[0, 1, 2, 3]));
+        assert!(dot.contains("    \"app\" [label=\"App\\n1.0\"]\n"));
+        assert!(dot.contains(
+            "    \"app\" -> \"lib\" [label=\"!=2.0\"]\n\
+             \x20   \"app\" -> \"lib\" [label=\"<3\"]\n\
+             \x20   \"app\" -> \"other\" [label=\">=1\"]\n\
+             \x20   \"app\" -> \"other\" [label=\">=2\"]\n"
+        ));
+    }
+
+    #[test]
+    fn adjacency_format() {
+        assert_eq!(
+            render_to_string("adjacency", &RenderOptions::default()),
+            "app: lib\nlib: missing\nother:\n"
+        );
+    }
+
+    #[test]
+    fn csv_formats() {
+        assert_eq!(
+            render_to_string("matrix-csv", &RenderOptions::default()),
+            "requirer,app,lib,other\napp,0,1,0\nlib,0,0,0\nother,0,0,0\n"
+        );
+
+        let mut dag = sample_dag();
+        dag.get_mut("app")
+            .unwrap()
+            .dependencies
+            .insert(RequiredDistribution {
+                name: "other".to_string(),
+                required_version: ">=1,<2".to_string(),
+                marker: None,
+                source: None,
+            });
+        let mut out = Vec::new();
+        EdgesCsvRenderer
+            .render(&dag, &RenderOptions::default(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "requirer,requirement,specifier,satisfied\n\
+             app,lib,>=2,true\n\
+             app,other,\">=1,<2\",false\n\
+             lib,missing,<1,false\n"
+        );
+    }
+
+    #[test]
+    fn freeze_format() {
+        let mut dag = sample_dag();
+        dag.get_mut("lib").unwrap().direct_url = Some(
+            serde_json::from_str(
+                r#"{"url": "https://github.com/org/lib.git", "vcs_info": {"vcs": "git", "commit_id": "7e5a"}}"#,
+            )
+            .unwrap(),
+        );
+        let mut out = Vec::new();
+        FreezeRenderer
+            .render(&dag, &RenderOptions::default(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "app==1.0\nlib @ git+https://github.com/org/lib.git@7e5a\nother==0.1\n"
+        );
+    }
+
+    #[test]
+    fn markdown_format() {
+        let opts = RenderOptions {
+            latest_versions: HashMap::from([
+                ("lib".to_string(), "3.0".to_string()),
+                // the installed release spelled differently
+                ("other".to_string(), "0.01".to_string()),
+            ]),
+            summary: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_to_string("markdown", &opts),
+            "| package | installed | latest | status |\n\
+             | --- | --- | --- | --- |\n\
+             | app | 1.0 | - | ok |\n\
+             | lib | 2.1 | [3.0](https://lib.org/changes) | outdated |\n\
+             | other | 0.1 | 0.01 | ok |\n\
+             | missing | - | - | missing |\n\
+             \n\
+             3 packages, 2 direct, 0 conflicts, 1 missing\n\
+             \n\
+             <details>\n\
+             <summary>app 1.0</summary>\n\
+             \n\
+             ```\n\
+             app [installed: 1.0]\n\
+             ----lib [required: >=2, installed: 2.1]\n\
+             ```\n\
+             \n\
+             </details>\n\
+             \n\
+             <details>\n\
+             <summary>other 0.1</summary>\n\
+             \n\
+             ```\n\
+             other [installed: 0.1]\n\
+             ```\n\
+             \n\
+             </details>\n"
+        );
+    }
+
+    #[test]
+    fn pipdeptree_format() {
+        let mut dag = sample_dag();
+        dag.get_mut("lib").unwrap().declared_name = Some("Lib".to_string());
+        dag.get_mut("other")
+            .unwrap()
+            .dependencies
+            .insert(RequiredDistribution {
+                name: "lib".to_string(),
+                required_version: String::new(),
+                marker: None,
+                source: None,
+            });
+        let mut out = Vec::new();
+        PipdeptreeRenderer
+            .render(&dag, &RenderOptions::default(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "app==1.0\n\
+             \x20 - Lib [required: >=2, installed: 2.1]\n\
+             \x20   - missing [required: <1, installed: ?]\n\
+             other==0.1\n\
+             \x20 - Lib [required: Any, installed: 2.1]\n\
+             \x20   - missing [required: <1, installed: ?]\n"
+        );
+
+        dag.get_mut("lib").unwrap().installed_version = "1.5".to_string();
+        assert_eq!(
+            PipdeptreeRenderer::conflicts(&dag).unwrap(),
+            format!(
+                "Warning!!! Possibly conflicting dependencies found:\n\
+                 * app==1.0\n\
+                 \x20- Lib [required: >=2, installed: 1.5]\n\
+                 * Lib==1.5\n\
+                 \x20- missing [required: <1, installed: ?]\n\
+                 {}",
+                "-".repeat(72)
+            )
+        );
+    }
+
+    #[test]
+    fn registry_replaces_by_name() {
+        let mut registry = RendererRegistry::default();
+        registry.register(Box::new(TextRenderer));
+        assert_eq!(
+            registry.names(),
+            vec![
+                "json",
+                "dot",
+                "adjacency",
+                "freeze",
+                "matrix-csv",
+                "edges-csv",
+                "markdown",
+                "pipdeptree",
+                "text"
+            ]
+        );
+        assert!(registry.get("yaml").is_none());
+    }
+}
@@ -0,0 +1,556 @@
+//! Drift between a requirements file, with the files it includes,
+//! and the installed environment
+
+use crate::dag::{get_top_level_distributions, DependencyDag, DistributionName};
+use crate::graph::DEFAULT_TOOLING;
+use crate::marker::{compare_releases, release_numbers};
+use crate::normalize::pep503;
+use crate::origin::requirement_line;
+use crate::policy::Violation;
+use crate::utils::{normalize_text, retry_read};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::{fs, io};
+
+/// Name, optional extras, version specifier and optional marker
+/// of a requirement line, inline comment excluded
+const REQUIREMENT_LINE_REGEX: &str =
+    r"^\s*([A-Za-z0-9][A-Za-z0-9._-]*)\s*(\[[^\]]*\])?\s*([<>=!~@(][^;#]*?)?\s*(;[^#]*)?(\s+#.*)?$";
+
+static REQUIREMENT_LINE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(REQUIREMENT_LINE_REGEX).unwrap());
+
+/// Requirement parsed out of a single line of the file
+#[derive(Debug, PartialEq)]
+struct RequirementLine<'a> {
+    name: DistributionName,
+    specifier: &'a str,
+    has_marker: bool,
+}
+
+impl<'a> RequirementLine<'a> {
+    /// None for blank lines, comments, options (-r, -e, --index-url, ...)
+    /// and anything else which is not a plain named requirement
+    fn parse(line: &'a str) -> Option<Self> {
+        let captures = REQUIREMENT_LINE_RE.captures(line)?;
+        Some(Self {
+            name: pep503(&captures[1]),
+            specifier: captures.get(3).map_or("", |m| m.as_str()),
+            has_marker: captures.get(4).is_some(),
+        })
+    }
+
+    /// Version of a `==` pin, None for other specifiers
+    fn pin(&self) -> Option<&'a str> {
+        let version = self.specifier.strip_prefix("==")?.trim();
+        (!version.is_empty() && !version.contains([',', '*', '='])).then_some(version)
+    }
+}
+
+/// Path named by a `-r`/`--requirement` or `-c`/`--constraint` line,
+/// with whether it is a constraints file
+fn include_line(line: &str) -> Option<(&str, bool)> {
+    let line = line.split(" #").next()?.trim();
+    for (option, constraints) in [
+        ("--requirement", false),
+        ("--constraint", true),
+        ("-r", false),
+        ("-c", true),
+    ] {
+        let Some(rest) = line.strip_prefix(option) else {
+            continue;
+        };
+        if option.starts_with("--") && !rest.starts_with(['=', ' ', '\t']) {
+            continue;
+        }
+        let path = rest.trim_start_matches(['=', ' ', '\t']);
+        return (!path.is_empty()).then_some((path, constraints));
+    }
+    None
+}
+
+/// Versions are the same if they are equal strings or dotted
+/// numbers which differ only in trailing zeros, e.g. 2.0 and 2.0.0
+fn same_version(lhs: &str, rhs: &str) -> bool {
+    match (release_numbers(lhs), release_numbers(rhs)) {
+        (Some(lhs), Some(rhs)) => compare_releases(&lhs, &rhs) == Ordering::Equal,
+        _ => lhs == rhs,
+    }
+}
+
+/// Line with the version after `==` replaced, names can't contain `=`
+fn replace_pin(line: &str, pin: &str, version: &str) -> String {
+    let operator = line.find("==").unwrap_or_default();
+    let start = operator + line[operator..].find(pin).unwrap_or_default();
+    format!(
+        "{}{}{}",
+        &line[..start],
+        version,
+        &line[start + pin.len()..]
+    )
+}
+
+/// Single change making the files describe the installed environment,
+/// line numbers start from 1
+#[derive(Debug, PartialEq)]
+pub enum Edit {
+    /// pin differs from the installed version
+    Update {
+        file: PathBuf,
+        line: usize,
+        name: DistributionName,
+        pinned: String,
+        installed: String,
+        old: String,
+        new: String,
+    },
+    /// required package is not installed
+    Remove {
+        file: PathBuf,
+        line: usize,
+        name: DistributionName,
+        old: String,
+    },
+    /// installed top level package is not listed, packages installed
+    /// from a direct reference are added with it, to the including file
+    Add { name: DistributionName, new: String },
+}
+
+/// `path:line` of an edited line, just the line number for parsed content
+fn location(file: &Path, line: usize) -> String {
+    if file.as_os_str().is_empty() {
+        format!("line {}", line)
+    } else {
+        format!("{}:{}", file.display(), line)
+    }
+}
+
+impl Edit {
+    pub fn to_violation(&self) -> Violation {
+        let (package, message) = match self {
+            Edit::Update {
+                file,
+                line,
+                name,
+                pinned,
+                installed,
+                ..
+            } => (
+                name,
+                format!(
+                    "{} is pinned to {}, installed {} ({})",
+                    name,
+                    pinned,
+                    installed,
+                    location(file, *line)
+                ),
+            ),
+            Edit::Remove {
+                file, line, name, ..
+            } => (
+                name,
+                format!(
+                    "{} is listed but not installed ({})",
+                    name,
+                    location(file, *line)
+                ),
+            ),
+            Edit::Add { name, .. } => (name, format!("{} is installed but not listed", name)),
+        };
+        Violation {
+            rule: String::from("requirements-drift"),
+            package: Some(package.clone()),
+            message,
+        }
+    }
+}
+
+/// Requirements file kept line by line, so edits preserve comments,
+/// options, ordering, line endings and the final newline. Files included with `-r` and `-c` are loaded
+/// with it, paths relative to the including file
+#[derive(Debug, Default)]
+pub struct RequirementsFile {
+    /// empty for parsed content
+    path: PathBuf,
+    lines: Vec<String>,
+    /// written with CRLF endings
+    crlf: bool,
+    /// the last line is terminated
+    final_newline: bool,
+    /// included with `-c`, pins only restrict versions of packages required elsewhere
+    constraints: bool,
+    included: Vec<RequirementsFile>,
+}
+
+impl RequirementsFile {
+    /// File of the given content, includes are not followed
+    pub fn parse(content: &str) -> Self {
+        Self {
+            lines: normalize_text(content.to_string())
+                .lines()
+                .map(String::from)
+                .collect(),
+            crlf: content.contains("\r\n"),
+            final_newline: content.is_empty() || content.ends_with('\n'),
+            ..Default::default()
+        }
+    }
+
+    /// Read the file and the files it includes, recursively. Each file
+    /// is read once, remote includes (urls) are skipped
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut loaded = HashSet::new();
+        Self::load_included(path, false, &mut loaded)
+    }
+
+    fn load_included(
+        path: &Path,
+        constraints: bool,
+        loaded: &mut HashSet<PathBuf>,
+    ) -> io::Result<Self> {
+        let with_path =
+            |err: io::Error| io::Error::new(err.kind(), format!("{}: {}", path.display(), err));
+        loaded.insert(fs::canonicalize(path).map_err(with_path)?);
+        let mut file = Self {
+            path: path.to_path_buf(),
+            constraints,
+            ..Self::parse(&retry_read(path, || fs::read_to_string(path)).map_err(with_path)?)
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for (included, constraints) in file.lines.iter().filter_map(|l| include_line(l)) {
+            if included.contains("://") {
+                continue;
+            }
+            let included = dir.join(included);
+            let already_loaded = fs::canonicalize(&included).is_ok_and(|p| loaded.contains(&p));
+            if !already_loaded {
+                let included = Self::load_included(&included, constraints, loaded)?;
+                file.included.push(included);
+            }
+        }
+        Ok(file)
+    }
+
+    /// Edits of pins and requirements of this file and the included ones,
+    /// names of requirements are collected into `listed`
+    fn line_edits(
+        &self,
+        dag: &DependencyDag,
+        edits: &mut Vec<Edit>,
+        listed: &mut HashSet<DistributionName>,
+    ) {
+        for (idx, line) in self.lines.iter().enumerate() {
+            let Some(requirement) = RequirementLine::parse(line) else {
+                continue;
+            };
+            if !self.constraints {
+                listed.insert(requirement.name.clone());
+            }
+            match dag.get(&requirement.name) {
+                Some(meta) => {
+                    let Some(pin) = requirement.pin() else {
+                        continue;
+                    };
+                    if !same_version(pin, &meta.installed_version) {
+                        edits.push(Edit::Update {
+                            file: self.path.clone(),
+                            line: idx + 1,
+                            name: requirement.name.clone(),
+                            pinned: pin.to_string(),
+                            installed: meta.installed_version.clone(),
+                            old: line.clone(),
+                            new: replace_pin(line, pin, &meta.installed_version),
+                        });
+                    }
+                }
+                // constraints of packages nobody requires are fine
+                None if requirement.has_marker || self.constraints => (),
+                None => edits.push(Edit::Remove {
+                    file: self.path.clone(),
+                    line: idx + 1,
+                    name: requirement.name.clone(),
+                    old: line.clone(),
+                }),
+            }
+        }
+        for included in &self.included {
+            included.line_edits(dag, edits, listed);
+        }
+    }
+
+    /// Edits making the files match the installed env: pins are updated
+    /// to installed versions, requirements of missing packages are removed
+    /// (unless guarded by a marker) and top level packages listed
+    /// in none of the requirements files are added to this one
+    pub fn drift(&self, dag: &DependencyDag) -> Vec<Edit> {
+        let mut edits = Vec::new();
+        let mut listed: HashSet<DistributionName> = HashSet::new();
+        self.line_edits(dag, &mut edits, &mut listed);
+
+        for name in get_top_level_distributions(dag) {
+            if listed.contains(name) || DEFAULT_TOOLING.contains(&name.as_str()) {
+                continue;
+            }
+            edits.push(Edit::Add {
+                name: name.clone(),
+                new: requirement_line(name, &dag[name]),
+            });
+        }
+        edits
+    }
+
+    /// Content of the file with its edits applied, edits of included files are skipped
+    pub fn apply(&self, edits: &[Edit]) -> String {
+        self.apply_own(edits, true)
+    }
+
+    fn apply_own(&self, edits: &[Edit], with_added: bool) -> String {
+        let mut lines: Vec<Option<&str>> = self.lines.iter().map(|l| Some(l.as_str())).collect();
+        let mut added = Vec::new();
+        for edit in edits {
+            match edit {
+                Edit::Update {
+                    file, line, new, ..
+                } if *file == self.path => lines[line - 1] = Some(new),
+                Edit::Remove { file, line, .. } if *file == self.path => lines[line - 1] = None,
+                Edit::Add { new, .. } if with_added => added.push(new.as_str()),
+                _ => (),
+            }
+        }
+        let newline = if self.crlf { "\r\n" } else { "\n" };
+        let lines: Vec<&str> = lines.into_iter().flatten().chain(added).collect();
+        let mut content = lines.join(newline);
+        if self.final_newline && !content.is_empty() {
+            content.push_str(newline);
+        }
+        content
+    }
+
+    /// Write edited files, included ones only when they have edits
+    pub fn save(&self, edits: &[Edit]) -> io::Result<()> {
+        fs::write(&self.path, self.apply(edits))?;
+        self.save_included(edits)
+    }
+
+    fn save_included(&self, edits: &[Edit]) -> io::Result<()> {
+        for included in &self.included {
+            let edited = edits.iter().any(|edit| match edit {
+                Edit::Update { file, .. } | Edit::Remove { file, .. } => *file == included.path,
+                Edit::Add { .. } => false,
+            });
+            if edited {
+                fs::write(&included.path, included.apply_own(edits, false))?;
+            }
+            included.save_included(edits)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::{DistributionMeta, RequiredDistribution};
+    use crate::fixture::SitePackages;
+
+    fn meta(version: &str, deps: &[&str]) -> DistributionMeta {
+        DistributionMeta {
+            installed_version: version.to_string(),
+            dependencies: deps
+                .iter()
+                .map(|d| RequiredDistribution {
+                    name: d.to_string(),
+                    required_version: String::new(),
+                    marker: None,
+                    source: None,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    const REQUIREMENTS: &str = "\
+# web stack
+--index-url https://pypi.org/simple
+Requests[socks]==2.30.0  # http client
+idna>=3
+numpy==1.26
+gone==1.0
+pywin32==306; sys_platform == 'win32'
+";
+
+    #[test]
+    fn parse_requirement_lines() {
+        let line =
+            RequirementLine::parse("Requests[socks] == 2.30.0 ; python_version > '3' # x").unwrap();
+        assert_eq!(line.name, "requests");
+        assert_eq!(line.pin(), Some("2.30.0"));
+        assert!(line.has_marker);
+        assert_eq!(RequirementLine::parse("idna>=3,<4").unwrap().pin(), None);
+        assert_eq!(RequirementLine::parse("idna==3.*").unwrap().pin(), None);
+        assert_eq!(RequirementLine::parse("idna===3").unwrap().pin(), None);
+        assert_eq!(replace_pin("x1==1  # 1", "1", "2"), "x1==2  # 1");
+        for line in [
+            "# comment",
+            "",
+            "-r base.txt",
+            "-e .",
+            "./local/pkg",
+            "https://x/a.whl",
+        ] {
+            assert_eq!(RequirementLine::parse(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn drift_edits() {
+        let dag = DependencyDag::from([
+            ("requests".to_string(), meta("2.31.0", &["idna"])),
+            ("idna".to_string(), meta("3.6", &[])),
+            ("numpy".to_string(), meta("1.26.0", &[])),
+            ("six".to_string(), meta("1.16.0", &[])),
+            ("pip".to_string(), meta("24.0", &[])),
+        ]);
+        let file = RequirementsFile::parse(REQUIREMENTS);
+        let edits = file.drift(&dag);
+        assert_eq!(
+            edits,
+            vec![
+                Edit::Update {
+                    file: PathBuf::new(),
+                    line: 3,
+                    name: "requests".to_string(),
+                    pinned: "2.30.0".to_string(),
+                    installed: "2.31.0".to_string(),
+                    old: "Requests[socks]==2.30.0  # http client".to_string(),
+                    new: "Requests[socks]==2.31.0  # http client".to_string(),
+                },
+                Edit::Remove {
+                    file: PathBuf::new(),
+                    line: 6,
+                    name: "gone".to_string(),
+                    old: "gone==1.0".to_string(),
+                },
+                Edit::Add {
+                    name: "six".to_string(),
+                    new: "six==1.16.0".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            file.apply(&edits),
+            "\
+# web stack
+--index-url https://pypi.org/simple
+Requests[socks]==2.31.0  # http client
+idna>=3
+numpy==1.26
+pywin32==306; sys_platform == 'win32'
+six==1.16.0
+"
+        );
+        assert_eq!(
+            edits[0].to_violation().message,
+            "requests is pinned to 2.30.0, installed 2.31.0 (line 3)"
+        );
+        assert_eq!(
+            edits[2].to_violation().message,
+            "six is installed but not listed"
+        );
+    }
+
+    #[test]
+    fn included_files_followed() {
+        let dir = SitePackages::new("requirements-included")
+            .file(
+                "requirements.txt",
+                "-r base/base.txt\n--constraint=constraints.txt\napp==1.0\n",
+            )
+            .file(
+                "base/base.txt",
+                "-r ../requirements.txt\nidna==3.4\ngone>=1\n",
+            )
+            .file(
+                "constraints.txt",
+                "idna==3.6\nunused==1.0\nnumpy==1.0  # old\n",
+            );
+        let dag = DependencyDag::from([
+            ("app".to_string(), meta("1.0", &["idna"])),
+            ("idna".to_string(), meta("3.6", &[])),
+            ("numpy".to_string(), meta("1.26", &[])),
+        ]);
+        let root = dir.path().join("requirements.txt");
+        let file = RequirementsFile::load(&root).unwrap();
+        let edits = file.drift(&dag);
+        let messages: Vec<String> = edits.iter().map(|e| e.to_violation().message).collect();
+        assert_eq!(
+            messages,
+            [
+                format!(
+                    "idna is pinned to 3.4, installed 3.6 ({}:2)",
+                    dir.path().join("base/base.txt").display()
+                ),
+                format!(
+                    "gone is listed but not installed ({}:3)",
+                    dir.path().join("base/base.txt").display()
+                ),
+                format!(
+                    "numpy is pinned to 1.0, installed 1.26 ({}:3)",
+                    dir.path().join("constraints.txt").display()
+                ),
+                String::from("numpy is installed but not listed"),
+            ]
+        );
+
+        file.save(&edits).unwrap();
+        let read = |path: &str| fs::read_to_string(dir.path().join(path)).unwrap();
+        assert_eq!(
+            read("requirements.txt"),
+            "-r base/base.txt\n--constraint=constraints.txt\napp==1.0\nnumpy==1.26\n"
+        );
+        assert_eq!(read("base/base.txt"), "-r ../requirements.txt\nidna==3.6\n");
+        assert_eq!(
+            read("constraints.txt"),
+            "idna==3.6\nunused==1.0\nnumpy==1.26  # old\n"
+        );
+
+        assert!(RequirementsFile::load(&dir.path().join("missing.txt")).is_err());
+        assert_eq!(include_line("-rbase.txt"), Some(("base.txt", false)));
+        assert_eq!(include_line("-c c.txt  # pins"), Some(("c.txt", true)));
+        assert_eq!(include_line("--requirements.txt"), None);
+    }
+
+    #[test]
+    fn line_endings_and_final_newline_kept() {
+        let dir = SitePackages::new("requirements-crlf")
+            .file(
+                "requirements.txt",
+                "\u{feff}# pins\r\n-r base.txt\r\napp==0.9\r\n",
+            )
+            .file("base.txt", "idna==3.4\ngone==1.0");
+        let dag = DependencyDag::from([
+            ("app".to_string(), meta("1.0", &["idna"])),
+            ("idna".to_string(), meta("3.6", &[])),
+        ]);
+        let root = dir.path().join("requirements.txt");
+        let file = RequirementsFile::load(&root).unwrap();
+        let edits = file.drift(&dag);
+        assert_eq!(edits.len(), 3);
+
+        file.save(&edits).unwrap();
+        let read = |path: &str| fs::read_to_string(dir.path().join(path)).unwrap();
+        assert_eq!(
+            read("requirements.txt"),
+            "# pins\r\n-r base.txt\r\napp==1.0\r\n"
+        );
+        assert_eq!(read("base.txt"), "idna==3.6");
+
+        // untouched content is written back byte for byte
+        let content = "a==1\r\n\r\n# end";
+        assert_eq!(RequirementsFile::parse(content).apply(&[]), content);
+        assert_eq!(RequirementsFile::parse("").apply(&[]), "");
+    }
+}
@@ -0,0 +1,171 @@
+//! Distributions streamed as their metadata dirs are parsed, for consumers
+//! with their own storage which don't need the whole dag in memory
+
+use crate::dag::{parse_meta_dir, scan_meta_dirs, DistributionMeta, DistributionName};
+use crate::deadline;
+use crate::marker::MarkerEnv;
+use crate::profile::Profile;
+use crate::source::OpenedRoots;
+use crate::utils::MetaDirEntry;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// Parsed distribution with its normalized name
+pub type Distribution = (DistributionName, DistributionMeta);
+
+/// Created with `DagBuilder::scanner`. Every copy of a distribution found
+/// in the scanned roots is yielded, the merge strategy is not applied
+///
+/// ```no_run
+/// use rdeptree::builder::DagBuilder;
+///
+/// let scanner = DagBuilder::new()
+///     .paths(["/venv/lib/python3.12/site-packages"])
+///     .scanner()
+///     .unwrap();
+/// for distribution in scanner.iter() {
+///     let (name, meta) = distribution.unwrap();
+///     println!("{}=={}", name, meta.installed_version);
+/// }
+/// ```
+pub struct EnvScanner {
+    /// scanned roots, with distributions of the second ones marked as inherited
+    sources: OpenedRoots,
+    marker_env: Option<MarkerEnv>,
+    follow_egg_info: bool,
+    strict: bool,
+}
+
+impl EnvScanner {
+    pub(crate) fn new(
+        sources: OpenedRoots,
+        marker_env: Option<MarkerEnv>,
+        follow_egg_info: bool,
+        strict: bool,
+    ) -> Self {
+        Self {
+            sources,
+            marker_env,
+            follow_egg_info,
+            strict,
+        }
+    }
+
+    /// Metadata dirs of a scanned root, listed without parsing
+    fn meta_dirs(&self, source_idx: usize) -> Vec<MetaDirEntry> {
+        let source = self.sources[source_idx].0.as_ref();
+        scan_meta_dirs(source, self.follow_egg_info, &mut Profile::default())
+    }
+
+    /// Parse a single metadata dir, None if its metadata file is unreadable
+    fn parse(&self, source_idx: usize, dir: MetaDirEntry) -> Result<Option<Distribution>, String> {
+        if let Err(err) = deadline::check() {
+            return Err(format!("{} before parsing {}", err, dir.path.display()));
+        }
+        let (source, inherited) = &self.sources[source_idx];
+        let parsed = parse_meta_dir(
+            source.as_ref(),
+            dir,
+            self.marker_env.as_ref(),
+            self.strict,
+            &mut Profile::default(),
+        )?;
+        Ok(parsed.map(|(name, mut meta)| {
+            meta.inherited = *inherited;
+            (name, meta)
+        }))
+    }
+
+    /// Distributions in the order `DagBuilder::build` parses them: roots in
+    /// order, metadata dirs of each root by path. A root is listed when the
+    /// iteration reaches it, every dir is parsed when its item is requested
+    pub fn iter(&self) -> impl Iterator<Item = Result<Distribution, String>> + '_ {
+        (0..self.sources.len()).flat_map(move |source_idx| {
+            self.meta_dirs(source_idx)
+                .into_iter()
+                .filter_map(move |dir| self.parse(source_idx, dir).transpose())
+        })
+    }
+
+    /// Same as `iter`, but metadata dirs of all roots are listed up front and
+    /// parsed on `jobs` threads, distributions come in the order they are
+    /// parsed. At most `jobs` parsed distributions wait for the consumer,
+    /// dropping the iterator stops the threads
+    pub fn par_iter(self, jobs: usize) -> impl Iterator<Item = Result<Distribution, String>> {
+        let jobs = jobs.max(1);
+        let dirs: Vec<(usize, MetaDirEntry)> = (0..self.sources.len())
+            .flat_map(|source_idx| {
+                self.meta_dirs(source_idx)
+                    .into_iter()
+                    .map(move |dir| (source_idx, dir))
+            })
+            .collect();
+        let work = Arc::new((self, dirs, AtomicUsize::new(0)));
+        let (sender, receiver) = mpsc::sync_channel(jobs);
+        for _ in 0..jobs {
+            let work = Arc::clone(&work);
+            let sender = sender.clone();
+            thread::spawn(move || {
+                let (scanner, dirs, next) = &*work;
+                while let Some((source_idx, dir)) = dirs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let Some(result) = scanner.parse(*source_idx, dir.clone()).transpose() else {
+                        continue;
+                    };
+                    // the consumer dropped the iterator
+                    if sender.send(result).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        receiver.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::DagBuilder;
+    use crate::fixture::SitePackages;
+
+    #[test]
+    fn distributions_streamed_sequentially_and_in_parallel() {
+        let site = SitePackages::new("scanner")
+            .dist_info("app", "1.0", &["lib>=2.0"])
+            .dist_info("lib", "2.1", &["six<2,>=1.5"])
+            .dist_info("six", "1.16.0", &[])
+            .egg_info("legacy", "1.0", "six>=1.0\n");
+        let inherited = SitePackages::new("scanner-base").dist_info("six", "1.15.0", &[]);
+        let scanner = DagBuilder::new()
+            .paths([site.path()])
+            .inherited_paths([inherited.path()])
+            .scanner()
+            .unwrap();
+
+        let streamed: Vec<(String, String, bool)> = scanner
+            .iter()
+            .map(|d| d.unwrap())
+            .map(|(name, meta)| (name, meta.installed_version, meta.inherited))
+            .collect();
+        let expected = [
+            ("app", "1.0", false),
+            ("lib", "2.1", false),
+            ("six", "1.16.0", false),
+            ("six", "1.15.0", true),
+        ];
+        assert_eq!(
+            streamed,
+            expected.map(|(n, v, i)| (n.to_string(), v.to_string(), i))
+        );
+
+        let mut parallel: Vec<(String, String, bool)> = scanner
+            .par_iter(3)
+            .map(|d| d.unwrap())
+            .map(|(name, meta)| (name, meta.installed_version, meta.inherited))
+            .collect();
+        parallel.sort();
+        let mut sorted = streamed;
+        sorted.sort();
+        assert_eq!(parallel, sorted);
+    }
+}
@@ -0,0 +1,286 @@
+//! Minimal JSON-RPC 2.0 server answering lookups in an env scanned once,
+//! for editor plugins showing hover info of imports. Every request and
+//! response is a single line of json, requests are read until end of input
+
+use crate::dag::{DependencyDag, DistributionName};
+use crate::lookup::find_distribution;
+use crate::record::read_record;
+use crate::utils::read_text;
+use serde_json::{json, Value};
+use std::cell::OnceCell;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+const TOP_LEVEL_FILE_NAME: &str = "top_level.txt";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Server defined error, the queried package is not installed
+const PACKAGE_NOT_INSTALLED: i64 = -32001;
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Top level module of a path relative to site-packages, e.g. `yaml`
+/// of `yaml/loader.py` or `_yaml` of `_yaml.cpython-311-x86_64-linux-gnu.so`
+fn top_level_module(path: &str) -> Option<&str> {
+    let (first, rest) = path.split_once('/').unwrap_or((path, ""));
+    let module = if rest.is_empty() {
+        let (stem, extension) = first.split_once('.')?;
+        let extension = extension.rsplit('.').next()?;
+        ["py", "so", "pyd"].contains(&extension).then_some(stem)?
+    } else {
+        first
+    };
+    (is_identifier(module) && module != "__pycache__").then_some(module)
+}
+
+/// Modules a distribution makes importable, from top_level.txt when present,
+/// otherwise from the files listed in RECORD
+pub fn import_names(meta_dir: &Path) -> Vec<String> {
+    let names: BTreeSet<String> = match read_text(meta_dir.join(TOP_LEVEL_FILE_NAME)) {
+        Ok(content) => content
+            .lines()
+            .filter_map(|line| line.trim().split('/').next())
+            .filter(|name| is_identifier(name))
+            .map(str::to_string)
+            .collect(),
+        Err(_) => read_record(meta_dir)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|entry| top_level_module(&entry.path))
+            .map(str::to_string)
+            .collect(),
+    };
+    names.into_iter().collect()
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Answers `resolveImport`, `getVersion` and `getDependencies` requests
+pub struct LookupServer<'a> {
+    dag: &'a DependencyDag,
+    /// distributions by module they make importable, read on the first `resolveImport`
+    imports: OnceCell<HashMap<String, Vec<&'a DistributionName>>>,
+}
+
+impl<'a> LookupServer<'a> {
+    pub fn new(dag: &'a DependencyDag) -> Self {
+        Self {
+            dag,
+            imports: OnceCell::new(),
+        }
+    }
+
+    fn imports(&self) -> &HashMap<String, Vec<&'a DistributionName>> {
+        self.imports.get_or_init(|| {
+            let mut imports: HashMap<String, Vec<&DistributionName>> = HashMap::new();
+            for (name, meta) in self.dag {
+                for module in import_names(&meta.meta_dir) {
+                    imports.entry(module).or_default().push(name);
+                }
+            }
+            for names in imports.values_mut() {
+                names.sort();
+            }
+            imports
+        })
+    }
+
+    fn string_param<'p>(params: &'p Value, key: &str) -> Result<&'p str, RpcError> {
+        params
+            .get(key)
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing string param {}", key)))
+    }
+
+    fn package(&self, params: &Value) -> Result<&'a DistributionName, RpcError> {
+        let query = Self::string_param(params, "package")?;
+        find_distribution(self.dag, query, false)
+            .map_err(|err| RpcError::new(PACKAGE_NOT_INSTALLED, err.to_string()))
+    }
+
+    fn call(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        match method {
+            "resolveImport" => {
+                let module = Self::string_param(params, "module")?;
+                // `yaml.loader` is provided by whoever provides `yaml`
+                let top_level = module.split('.').next().unwrap_or_default();
+                let distributions: Vec<Value> = self
+                    .imports()
+                    .get(top_level)
+                    .into_iter()
+                    .flatten()
+                    .map(|name| json!({"name": name, "version": self.dag[*name].installed_version}))
+                    .collect();
+                Ok(json!({"module": top_level, "distributions": distributions}))
+            }
+            "getVersion" => {
+                let name = self.package(params)?;
+                Ok(json!({"name": name, "version": self.dag[name].installed_version}))
+            }
+            "getDependencies" => {
+                let name = self.package(params)?;
+                let dependencies: Vec<Value> = self.dag[name]
+                    .sorted_dependencies()
+                    .into_iter()
+                    .map(|dep| {
+                        json!({
+                            "name": dep.name,
+                            "specifier": dep.required_version,
+                            "marker": dep.marker,
+                            "installed": self.dag.get(&dep.name).map(|m| &m.installed_version),
+                        })
+                    })
+                    .collect();
+                Ok(json!({"name": name, "dependencies": dependencies}))
+            }
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method {}", method),
+            )),
+        }
+    }
+
+    /// Response to a single request line, None for notifications, i.e. requests without id
+    pub fn handle(&self, line: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(err) => {
+                return Some(error_response(
+                    Value::Null,
+                    RpcError::new(PARSE_ERROR, err.to_string()),
+                ))
+            }
+        };
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return Some(error_response(
+                id.unwrap_or(Value::Null),
+                RpcError::new(INVALID_REQUEST, "missing method"),
+            ));
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        let result = self.call(method, &params);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(err) => error_response(id, err),
+        })
+    }
+
+    /// Answer requests read from the input until it ends, one response line per request
+    pub fn serve(&self, input: impl BufRead, output: &mut dyn Write) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": err.code, "message": err.message},
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::builder::DagBuilder;
+    use crate::fixture::SitePackages;
+
+    #[test]
+    fn imports_resolved_to_distributions() {
+        let site = SitePackages::new("lookup-server")
+            .dist_info("pyyaml", "6.0.1", &[])
+            .dist_info_file(
+                "pyyaml",
+                "6.0.1",
+                "RECORD",
+                "yaml/__init__.py,,\n\
+                 yaml/loader.py,,\n\
+                 _yaml/__init__.py,,\n\
+                 yaml-6.0.1.dist-info/METADATA,,\n\
+                 ../../bin/yaml-tool,,\n",
+            )
+            .dist_info("six", "1.16.0", &[])
+            .dist_info_file("six", "1.16.0", "top_level.txt", "six\n")
+            .dist_info(
+                "app",
+                "1.0",
+                &["six>=1.5", "lib<2; python_version < \"3.8\""],
+            );
+        let dag = DagBuilder::new()
+            .paths(vec![site.path().to_path_buf()])
+            .build()
+            .unwrap();
+        let server = LookupServer::new(&dag);
+        let call = |request: &str| server.handle(request).unwrap();
+
+        assert_eq!(import_names(&dag["pyyaml"].meta_dir), vec!["_yaml", "yaml"]);
+        assert_eq!(
+            call(
+                r#"{"jsonrpc": "2.0", "id": 1, "method": "resolveImport", "params": {"module": "yaml.loader"}}"#
+            ),
+            json!({"jsonrpc": "2.0", "id": 1, "result": {
+                "module": "yaml",
+                "distributions": [{"name": "pyyaml", "version": "6.0.1"}],
+            }})
+        );
+        assert_eq!(
+            call(r#"{"id": 2, "method": "getVersion", "params": {"package": "Six"}}"#)["result"],
+            json!({"name": "six", "version": "1.16.0"})
+        );
+        assert_eq!(
+            call(r#"{"id": 3, "method": "getDependencies", "params": {"package": "app"}}"#)
+                ["result"],
+            json!({"name": "app", "dependencies": [
+                {"name": "lib", "specifier": "<2", "marker": "python_version < \"3.8\"", "installed": null},
+                {"name": "six", "specifier": ">=1.5", "marker": null, "installed": "1.16.0"},
+            ]})
+        );
+
+        let code = |request: &str| call(request)["error"]["code"].clone();
+        assert_eq!(
+            code(r#"{"id": 4, "method": "getVersion", "params": {"package": "nope"}}"#),
+            -32001
+        );
+        assert_eq!(code(r#"{"id": 5, "method": "getVersion"}"#), -32602);
+        assert_eq!(code(r#"{"id": 6, "method": "shutdown"}"#), -32601);
+        assert_eq!(code(r#"{"id": 7}"#), -32600);
+        assert_eq!(code("{"), -32700);
+        assert_eq!(
+            server.handle(r#"{"method": "getVersion", "params": {"package": "six"}}"#),
+            None
+        );
+    }
+}
@@ -0,0 +1,400 @@
+use crate::dag::{DependencyDag, DistributionName};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SnapshotRequirement {
+    pub name: DistributionName,
+    pub specifier: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SnapshotPackage {
+    pub version: String,
+    pub requires: Vec<SnapshotRequirement>,
+}
+
+/// Point in time state of an environment, stored as json
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    /// unix timestamp (seconds) of snapshot creation
+    pub created_at: u64,
+    /// site-packages dir(s) the snapshot was taken from
+    pub env_path: String,
+    pub packages: BTreeMap<DistributionName, SnapshotPackage>,
+    /// package the snapshot was extracted for, None for whole environments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<DistributionName>,
+}
+
+impl Snapshot {
+    pub fn from_dag(dag: &DependencyDag, env_paths: &[PathBuf]) -> Self {
+        let packages = dag
+            .iter()
+            .map(|(name, meta)| {
+                let mut requires: Vec<SnapshotRequirement> = meta
+                    .dependencies
+                    .iter()
+                    .map(|d| SnapshotRequirement {
+                        name: d.name.clone(),
+                        specifier: d.required_version.clone(),
+                    })
+                    .collect();
+                requires.sort_by(|a, b| a.name.cmp(&b.name).then(a.specifier.cmp(&b.specifier)));
+
+                (
+                    name.clone(),
+                    SnapshotPackage {
+                        version: meta.installed_version.clone(),
+                        requires,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            env_path: env_paths
+                .iter()
+                .map(|p| p.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", "),
+            packages,
+            root: None,
+        }
+    }
+
+    /// Snapshot of the given package and everything it requires,
+    /// directly or transitively
+    pub fn extract(&self, root: &DistributionName) -> Self {
+        let mut packages = BTreeMap::new();
+        let mut to_visit = vec![root];
+        while let Some(name) = to_visit.pop() {
+            let Some(package) = self.packages.get(name) else {
+                continue;
+            };
+            if packages.insert(name.clone(), package.clone()).is_none() {
+                to_visit.extend(package.requires.iter().map(|r| &r.name));
+            }
+        }
+        Self {
+            created_at: self.created_at,
+            env_path: self.env_path.clone(),
+            packages,
+            root: Some(root.clone()),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        writeln!(file)
+    }
+}
+
+/// Changes of an environment between two snapshots
+#[derive(Debug, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub added: Vec<(DistributionName, String)>,
+    pub removed: Vec<(DistributionName, String)>,
+    /// (name, old version, new version)
+    pub changed: Vec<(DistributionName, String, String)>,
+    /// packages with the same version but different requirements
+    pub requirements_changed: Vec<DistributionName>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.requirements_changed.is_empty()
+    }
+}
+
+/// Compare two snapshots, all lists in result are sorted by name
+pub fn diff_snapshots(old: &Snapshot, new: &Snapshot) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+
+    for (name, old_pkg) in &old.packages {
+        match new.packages.get(name) {
+            None => diff.removed.push((name.clone(), old_pkg.version.clone())),
+            Some(new_pkg) if new_pkg.version != old_pkg.version => diff.changed.push((
+                name.clone(),
+                old_pkg.version.clone(),
+                new_pkg.version.clone(),
+            )),
+            Some(new_pkg) if new_pkg.requires != old_pkg.requires => {
+                diff.requirements_changed.push(name.clone())
+            }
+            Some(_) => (),
+        }
+    }
+    for (name, new_pkg) in &new.packages {
+        if !old.packages.contains_key(name) {
+            diff.added.push((name.clone(), new_pkg.version.clone()));
+        }
+    }
+    diff
+}
+
+/// Changes of a single package between two environments
+#[derive(Debug, Default, PartialEq)]
+pub struct PackageDiff {
+    /// None if the package is not installed in the environment
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    /// (name, specifier) of direct requirements
+    pub added: Vec<(DistributionName, String)>,
+    pub removed: Vec<(DistributionName, String)>,
+    /// (name, old specifier, new specifier)
+    pub changed: Vec<(DistributionName, String, String)>,
+}
+
+/// Direct requirements of a package keyed by name, several
+/// requirements of the same name are joined with ` | `
+fn requirements_by_name(package: Option<&SnapshotPackage>) -> BTreeMap<&str, String> {
+    let mut by_name: BTreeMap<&str, String> = BTreeMap::new();
+    for requirement in package.iter().flat_map(|p| &p.requires) {
+        by_name
+            .entry(&requirement.name)
+            .and_modify(|s| {
+                s.push_str(" | ");
+                s.push_str(&requirement.specifier);
+            })
+            .or_insert_with(|| requirement.specifier.clone());
+    }
+    by_name
+}
+
+/// Compare a package of two snapshots, requirement lists in result are sorted by name
+pub fn diff_package(old: &Snapshot, new: &Snapshot, name: &DistributionName) -> PackageDiff {
+    let old_pkg = old.packages.get(name);
+    let new_pkg = new.packages.get(name);
+    let old_requires = requirements_by_name(old_pkg);
+    let new_requires = requirements_by_name(new_pkg);
+
+    let mut diff = PackageDiff {
+        old_version: old_pkg.map(|p| p.version.clone()),
+        new_version: new_pkg.map(|p| p.version.clone()),
+        ..Default::default()
+    };
+    for (requirement, old_specifier) in &old_requires {
+        match new_requires.get(requirement) {
+            None => diff
+                .removed
+                .push((requirement.to_string(), old_specifier.clone())),
+            Some(new_specifier) if new_specifier != old_specifier => diff.changed.push((
+                requirement.to_string(),
+                old_specifier.clone(),
+                new_specifier.clone(),
+            )),
+            Some(_) => (),
+        }
+    }
+    for (requirement, new_specifier) in &new_requires {
+        if !old_requires.contains_key(requirement) {
+            diff.added
+                .push((requirement.to_string(), new_specifier.clone()));
+        }
+    }
+    diff
+}
+
+/// Default location of history store: $XDG_DATA_HOME/rdeptree/history.jsonl
+/// falling back to ~/.local/share/rdeptree/history.jsonl
+pub fn default_history_path() -> Option<PathBuf> {
+    let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+            PathBuf::from(home).join(".local").join("share")
+        }
+    };
+    Some(data_dir.join("rdeptree").join("history.jsonl"))
+}
+
+/// Append a snapshot to the history store, one json document per line
+pub fn append_history(store: &Path, snapshot: &Snapshot) -> io::Result<()> {
+    if let Some(parent) = store.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(store)?;
+    serde_json::to_writer(&mut file, snapshot)?;
+    writeln!(file)
+}
+
+/// Read all snapshots of the history store, oldest first
+pub fn read_history(store: &Path) -> io::Result<Vec<Snapshot>> {
+    let reader = BufReader::new(File::open(store)?);
+    let mut snapshots = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            snapshots.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(snapshots)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type PackageSpec<'a> = (&'a str, &'a str, &'a [(&'a str, &'a str)]);
+
+    fn snapshot(packages: &[PackageSpec]) -> Snapshot {
+        Snapshot {
+            created_at: 0,
+            env_path: String::from("/env"),
+            packages: packages
+                .iter()
+                .map(|(name, version, requires)| {
+                    (
+                        name.to_string(),
+                        SnapshotPackage {
+                            version: version.to_string(),
+                            requires: requires
+                                .iter()
+                                .map(|(n, s)| SnapshotRequirement {
+                                    name: n.to_string(),
+                                    specifier: s.to_string(),
+                                })
+                                .collect(),
+                        },
+                    )
+                })
+                .collect(),
+            root: None,
+        }
+    }
+
+    #[test]
+    fn extract_reachable_packages() {
+        let env = snapshot(&[
+            (
+                "torch",
+                "2.2.0",
+                &[("numpy", ""), ("sympy", ""), ("missing", "")],
+            ),
+            ("sympy", "1.12", &[("mpmath", ">=0.19"), ("torch", "")]),
+            ("mpmath", "1.3.0", &[]),
+            ("numpy", "1.26.0", &[]),
+            ("requests", "2.31.0", &[("idna", "<4")]),
+            ("idna", "3.6", &[]),
+        ]);
+        let sub = env.extract(&String::from("sympy"));
+        assert_eq!(sub.root.as_deref(), Some("sympy"));
+        assert_eq!(
+            sub.packages.keys().collect::<Vec<_>>(),
+            vec!["mpmath", "numpy", "sympy", "torch"]
+        );
+        assert_eq!(sub.packages["torch"], env.packages["torch"]);
+
+        let json = serde_json::to_string(&sub).unwrap();
+        assert_eq!(serde_json::from_str::<Snapshot>(&json).unwrap(), sub);
+        assert!(!serde_json::to_string(&env).unwrap().contains("root"));
+    }
+
+    #[test]
+    fn diff_detects_all_kinds_of_changes() {
+        let old = snapshot(&[
+            ("numpy", "1.26.0", &[]),
+            ("pandas", "2.0.0", &[("numpy", ">=1.22")]),
+            ("six", "1.16.0", &[]),
+            ("requests", "2.31.0", &[("idna", "<4")]),
+        ]);
+        let new = snapshot(&[
+            ("numpy", "2.0.0", &[]),
+            ("pandas", "2.0.0", &[("numpy", ">=1.22")]),
+            ("requests", "2.31.0", &[("idna", "<5")]),
+            ("tzdata", "2024.1", &[]),
+        ]);
+
+        let diff = diff_snapshots(&old, &new);
+        assert_eq!(
+            diff.added,
+            vec![(String::from("tzdata"), String::from("2024.1"))]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![(String::from("six"), String::from("1.16.0"))]
+        );
+        assert_eq!(
+            diff.changed,
+            vec![(
+                String::from("numpy"),
+                String::from("1.26.0"),
+                String::from("2.0.0")
+            )]
+        );
+        assert_eq!(diff.requirements_changed, vec![String::from("requests")]);
+        assert!(diff_snapshots(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn diff_of_single_package() {
+        let old = snapshot(&[(
+            "pandas",
+            "1.5.3",
+            &[("numpy", ">=1.21.0"), ("pytz", ">=2020.1")],
+        )]);
+        let new = snapshot(&[(
+            "pandas",
+            "2.2.2",
+            &[("numpy", ">=1.22.4"), ("tzdata", ">=2022.7")],
+        )]);
+        let pandas = String::from("pandas");
+
+        let diff = diff_package(&old, &new, &pandas);
+        assert_eq!(diff.old_version.as_deref(), Some("1.5.3"));
+        assert_eq!(diff.new_version.as_deref(), Some("2.2.2"));
+        assert_eq!(
+            diff.added,
+            vec![(String::from("tzdata"), String::from(">=2022.7"))]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![(String::from("pytz"), String::from(">=2020.1"))]
+        );
+        assert_eq!(
+            diff.changed,
+            vec![(
+                String::from("numpy"),
+                String::from(">=1.21.0"),
+                String::from(">=1.22.4")
+            )]
+        );
+
+        let diff = diff_package(&snapshot(&[]), &new, &pandas);
+        assert_eq!(diff.old_version, None);
+        assert_eq!(diff.added.len(), 2);
+    }
+
+    #[test]
+    fn history_round_trip() {
+        let store = std::env::temp_dir()
+            .join(format!("rdeptree-history-{}", std::process::id()))
+            .join("history.jsonl");
+        let first = snapshot(&[("numpy", "1.26.0", &[])]);
+        let second = snapshot(&[("numpy", "2.0.0", &[])]);
+
+        append_history(&store, &first).unwrap();
+        append_history(&store, &second).unwrap();
+
+        assert_eq!(read_history(&store).unwrap(), vec![first, second]);
+        fs::remove_dir_all(store.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+//! Places metadata dirs are read from: site-packages dirs and
+//! archives of them (build artifacts), read without extraction
+
+use crate::utils::{
+    get_meta_dirs, metadata_header, read_header, read_text, MetaDirEntry, EGG_INFO_IN_EGG,
+};
+#[cfg(feature = "archive")]
+use crate::utils::{normalize_text, MetaDirKind};
+#[cfg(feature = "archive")]
+use flate2::read::GzDecoder;
+#[cfg(feature = "archive")]
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "archive")]
+use std::fs::File;
+use std::io;
+#[cfg(feature = "archive")]
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Files of metadata dirs which are ever read while building the dag
+#[cfg(feature = "archive")]
+const METADATA_FILES: [&str; 6] = [
+    "METADATA",
+    "PKG-INFO",
+    "INSTALLER",
+    "REQUESTED",
+    "direct_url.json",
+    "requires.txt",
+];
+
+/// Source of metadata dirs of a single scanned root,
+/// shared by threads parsing its dirs in parallel
+pub trait MetadataSource: Send + Sync {
+    /// Metadata dirs sorted by path
+    fn meta_dirs(&self) -> Vec<MetaDirEntry>;
+
+    /// Content of a file of a metadata dir returned by `meta_dirs`,
+    /// with leading BOM dropped and LF line endings
+    fn read_file(&self, meta_dir: &Path, file_name: &str) -> io::Result<String>;
+
+    /// Header of a core metadata file (METADATA, PKG-INFO), i.e. the fields
+    /// before the first empty line, without the description body
+    fn read_header(&self, meta_dir: &Path, file_name: &str) -> io::Result<String> {
+        let mut content = self.read_file(meta_dir, file_name)?;
+        content.truncate(metadata_header(&content).len());
+        Ok(content)
+    }
+}
+
+/// Opened scanned roots in order, flagged when they are inherited
+pub(crate) type OpenedRoots = Vec<(Box<dyn MetadataSource>, bool)>;
+
+/// Site-packages dir on disk, zipped eggs in it are read in place
+pub struct SitePackagesDir(pub PathBuf);
+
+/// Zipped egg the metadata dir belongs to, if any
+fn zipped_egg(meta_dir: &Path) -> Option<&Path> {
+    meta_dir
+        .parent()
+        .filter(|egg| meta_dir.ends_with(EGG_INFO_IN_EGG) && egg.is_file())
+}
+
+#[cfg(feature = "archive")]
+fn read_zipped_egg(egg: &Path, file_name: &str) -> io::Result<String> {
+    let mut zip = zip::ZipArchive::new(BufReader::new(File::open(egg)?))?;
+    let mut file = zip.by_name(&format!("{}/{}", EGG_INFO_IN_EGG, file_name))?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(normalize_text(content))
+}
+
+#[cfg(not(feature = "archive"))]
+fn read_zipped_egg(_egg: &Path, _file_name: &str) -> io::Result<String> {
+    Err(archive_unsupported())
+}
+
+/// Error of reading an archive by a build without the `archive` feature
+#[cfg(not(feature = "archive"))]
+fn archive_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "reading archives needs rdeptree built with the archive feature",
+    )
+}
+
+impl MetadataSource for SitePackagesDir {
+    fn meta_dirs(&self) -> Vec<MetaDirEntry> {
+        get_meta_dirs(&self.0)
+    }
+
+    fn read_file(&self, meta_dir: &Path, file_name: &str) -> io::Result<String> {
+        match zipped_egg(meta_dir) {
+            Some(egg) => read_zipped_egg(egg, file_name),
+            None => read_text(meta_dir.join(file_name)),
+        }
+    }
+
+    fn read_header(&self, meta_dir: &Path, file_name: &str) -> io::Result<String> {
+        if zipped_egg(meta_dir).is_some() {
+            let mut content = self.read_file(meta_dir, file_name)?;
+            content.truncate(metadata_header(&content).len());
+            return Ok(content);
+        }
+        read_header(meta_dir.join(file_name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        // wheels are zips with the metadata dir at the top
+        if name.ends_with(".zip") || name.ends_with(".whl") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Zip or tar (optionally gzipped) archive containing a site-packages layout
+/// at any depth. Metadata files are loaded into memory when the archive is
+/// opened, metadata dirs are reported as `<archive path>/<path inside archive>`
+#[cfg(feature = "archive")]
+pub struct ArchiveSource {
+    dirs: BTreeMap<PathBuf, (MetaDirKind, HashMap<String, String>)>,
+}
+
+#[cfg(feature = "archive")]
+impl ArchiveSource {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let format = ArchiveFormat::of(path).ok_or(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unsupported archive format, expected .zip, .whl, .tar, .tar.gz or .tgz",
+        ))?;
+        let mut source = Self {
+            dirs: BTreeMap::new(),
+        };
+        let file = BufReader::new(File::open(path)?);
+        match format {
+            ArchiveFormat::Zip => source.read_zip(path, file)?,
+            ArchiveFormat::Tar => source.read_tar(path, file)?,
+            ArchiveFormat::TarGz => source.read_tar(path, GzDecoder::new(file))?,
+        }
+        Ok(source)
+    }
+
+    /// Keep a file if it is one of the metadata files of a metadata dir
+    fn add_entry(
+        &mut self,
+        archive: &Path,
+        entry: &Path,
+        read: impl FnOnce() -> io::Result<String>,
+    ) -> io::Result<()> {
+        let (Some(dir), Some(file_name)) = (entry.parent(), entry.file_name()) else {
+            return Ok(());
+        };
+        let file_name = file_name.to_string_lossy();
+        let dir_name = dir.file_name().map(|n| n.to_string_lossy());
+        let Some(kind) = dir_name.and_then(|n| MetaDirKind::classify(&n)) else {
+            return Ok(());
+        };
+        if !METADATA_FILES.contains(&file_name.as_ref()) {
+            return Ok(());
+        }
+
+        let content = normalize_text(read()?);
+        self.dirs
+            .entry(archive.join(dir))
+            .or_insert_with(|| (kind, HashMap::new()))
+            .1
+            .insert(file_name.to_string(), content);
+        Ok(())
+    }
+
+    fn read_zip(&mut self, archive: &Path, reader: impl Read + io::Seek) -> io::Result<()> {
+        let mut zip = zip::ZipArchive::new(reader)?;
+        for idx in 0..zip.len() {
+            let mut file = zip.by_index(idx)?;
+            // names with `..` or absolute paths are skipped
+            let Some(entry) = file.enclosed_name().filter(|_| file.is_file()) else {
+                continue;
+            };
+            self.add_entry(archive, &entry, || {
+                let mut content = String::new();
+                file.read_to_string(&mut content)?;
+                Ok(content)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn read_tar(&mut self, archive: &Path, reader: impl Read) -> io::Result<()> {
+        let mut tar = tar::Archive::new(reader);
+        for file in tar.entries()? {
+            let mut file = file?;
+            if !file.header().entry_type().is_file() {
+                continue;
+            }
+            let entry = file.path()?.into_owned();
+            self.add_entry(archive, &entry, || {
+                let mut content = String::new();
+                file.read_to_string(&mut content)?;
+                Ok(content)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "archive")]
+impl MetadataSource for ArchiveSource {
+    fn meta_dirs(&self) -> Vec<MetaDirEntry> {
+        self.dirs
+            .iter()
+            .map(|(path, (kind, _))| MetaDirEntry {
+                path: path.clone(),
+                file_name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                kind: *kind,
+            })
+            .collect()
+    }
+
+    fn read_file(&self, meta_dir: &Path, file_name: &str) -> io::Result<String> {
+        self.dirs
+            .get(meta_dir)
+            .and_then(|(_, files)| files.get(file_name))
+            .cloned()
+            .ok_or(io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+/// Source of a metadata dir found in one of the scanned roots, opened again,
+/// e.g. to read more of its files. Dirs outside of the roots are read from disk
+pub fn open_source_of(roots: &[&Path], meta_dir: &Path) -> io::Result<Box<dyn MetadataSource>> {
+    match roots.iter().find(|root| meta_dir.starts_with(root)) {
+        Some(root) => open_source(root),
+        None => Ok(Box::new(SitePackagesDir(
+            meta_dir.parent().unwrap_or(meta_dir).to_path_buf(),
+        ))),
+    }
+}
+
+/// Whether the path looks like a supported archive, judging by extension
+pub fn is_archive(path: &Path) -> bool {
+    ArchiveFormat::of(path).is_some()
+}
+
+/// Source of a scanned root, archives are recognized by extension
+pub fn open_source(path: &Path) -> io::Result<Box<dyn MetadataSource>> {
+    if !is_archive(path) || !path.is_file() {
+        return Ok(Box::new(SitePackagesDir(path.to_path_buf())));
+    }
+    #[cfg(feature = "archive")]
+    return Ok(Box::new(ArchiveSource::open(path)?));
+    #[cfg(not(feature = "archive"))]
+    Err(archive_unsupported())
+}
+
+#[cfg(all(test, feature = "archive"))]
+mod test {
+    use super::*;
+    use crate::fixture::SitePackages;
+    use std::io::Write;
+
+    const METADATA: &str = "Metadata-Version: 2.1\r\nName: six\r\nVersion: 1.16.0\r\n";
+
+    #[test]
+    fn zip_archive() {
+        let site = SitePackages::new("zip-source");
+        let path = site.path().join("site.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, content) in [
+            ("site-packages/six-1.16.0.dist-info/METADATA", METADATA),
+            ("site-packages/six-1.16.0.dist-info/RECORD", "six.py,,\n"),
+            ("site-packages/six.py", "import sys\n"),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let source = open_source(&path).unwrap();
+        let dirs = source.meta_dirs();
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(dirs[0].file_name, "six-1.16.0.dist-info");
+        assert_eq!(
+            dirs[0].path,
+            path.join("site-packages/six-1.16.0.dist-info")
+        );
+        assert_eq!(
+            source.read_file(&dirs[0].path, "METADATA").unwrap(),
+            "Metadata-Version: 2.1\nName: six\nVersion: 1.16.0\n"
+        );
+        assert!(source.read_file(&dirs[0].path, "RECORD").is_err());
+
+        // metadata of a scanned archive is read from the archive again
+        let other = site.path().join("other");
+        let reopened = open_source_of(&[&other, &path], &dirs[0].path).unwrap();
+        assert!(reopened.read_file(&dirs[0].path, "METADATA").is_ok());
+    }
+
+    #[test]
+    fn zipped_egg_in_site_packages() {
+        let site = SitePackages::new("zipped-egg").dist_info("six", "1.16.0", &[]);
+        let path = site.path().join("legacy-1.0-py3.11.egg");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, content) in [
+            ("EGG-INFO/PKG-INFO", "Name: legacy\r\nVersion: 1.0\r\n"),
+            ("EGG-INFO/requires.txt", "six\n"),
+            ("legacy/__init__.py", ""),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let source = open_source(site.path()).unwrap();
+        let dirs = source.meta_dirs();
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(dirs[0].path, path.join("EGG-INFO"));
+        assert_eq!(dirs[0].kind, MetaDirKind::EggInfo);
+        assert_eq!(
+            source.read_file(&dirs[0].path, "PKG-INFO").unwrap(),
+            "Name: legacy\nVersion: 1.0\n"
+        );
+        assert!(source.read_file(&dirs[0].path, "INSTALLER").is_err());
+    }
+
+    #[test]
+    fn tar_gz_archive() {
+        let site = SitePackages::new("tar-source");
+        let path = site.path().join("site.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(encoder);
+        for (name, content) in [
+            (
+                "lib/python3.11/site-packages/legacy-1.0.egg-info/PKG-INFO",
+                "Name: legacy\n",
+            ),
+            (
+                "lib/python3.11/site-packages/legacy-1.0.egg-info/requires.txt",
+                "six\n",
+            ),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let source = open_source(&path).unwrap();
+        let dirs = source.meta_dirs();
+        assert_eq!(dirs.len(), 1);
+        assert_eq!(dirs[0].kind, MetaDirKind::EggInfo);
+        assert_eq!(
+            source.read_file(&dirs[0].path, "requires.txt").unwrap(),
+            "six\n"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Heuristic mapping of distributions to OS packages which own them, for
+//! Debian and RPM based systems where python packages installed by apt/dnf
+//! share dist-packages with the ones installed by pip
+
+use crate::dag::{DependencyDag, DistributionName};
+use crate::locator::{describe_failure, run_with_timeout, COMMAND_TIMEOUT};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Paths passed to a single query, keeps the command line short enough
+const QUERY_BATCH: usize = 200;
+
+/// OS package database the owners are queried from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PackageManager {
+    Dpkg,
+    Rpm,
+}
+
+impl PackageManager {
+    /// Package database of the running system, None when there is neither
+    pub fn detect() -> Option<Self> {
+        if Path::new("/var/lib/dpkg/status").is_file() {
+            Some(Self::Dpkg)
+        } else if Path::new("/var/lib/rpm").is_dir() {
+            Some(Self::Rpm)
+        } else {
+            None
+        }
+    }
+
+    fn command(&self, paths: &[&Path]) -> Command {
+        let mut command = match self {
+            Self::Dpkg => {
+                let mut command = Command::new("dpkg");
+                command.arg("-S");
+                command
+            }
+            Self::Rpm => {
+                let mut command = Command::new("rpm");
+                command.args(["-qf", "--queryformat", "%{NAME}\\n"]);
+                command
+            }
+        };
+        command.args(paths);
+        command
+    }
+
+    /// OS packages owning the given paths, unowned paths are left out
+    pub fn owners(&self, paths: &[&Path]) -> Result<HashMap<PathBuf, String>, String> {
+        let mut owners = HashMap::new();
+        for batch in paths.chunks(QUERY_BATCH) {
+            let output = run_with_timeout(&mut self.command(batch), COMMAND_TIMEOUT)
+                .map_err(|err| err.to_string())?;
+            // both exit with 1 when some path is not owned, but still report the others
+            if output.status.code() != Some(0) && output.status.code() != Some(1) {
+                return Err(describe_failure(&output));
+            }
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            owners.extend(match self {
+                Self::Dpkg => parse_dpkg_owners(&stdout),
+                Self::Rpm => parse_rpm_owners(batch, &stdout),
+            });
+        }
+        Ok(owners)
+    }
+}
+
+/// Lines of `dpkg -S` look like `python3-yaml:amd64: /usr/lib/...`, a path owned
+/// by several packages lists them separated by commas, the first one is taken
+fn parse_dpkg_owners(stdout: &str) -> HashMap<PathBuf, String> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let (packages, path) = line.split_once(": /")?;
+            let package = packages.split(", ").next()?;
+            // strip the multiarch qualifier
+            let package = package.split(':').next()?.trim();
+            (!package.is_empty() && !package.contains(' '))
+                .then(|| (PathBuf::from(format!("/{}", path)), package.to_string()))
+        })
+        .collect()
+}
+
+/// `rpm -qf` prints a line per queried path, a sentence for unowned ones
+fn parse_rpm_owners(paths: &[&Path], stdout: &str) -> HashMap<PathBuf, String> {
+    paths
+        .iter()
+        .zip(stdout.lines())
+        .filter(|(_, line)| !line.is_empty() && !line.contains(' '))
+        .map(|(path, line)| (path.to_path_buf(), line.to_string()))
+        .collect()
+}
+
+/// OS packages owning metadata dirs of the distributions
+pub fn system_packages(
+    manager: PackageManager,
+    dag: &DependencyDag,
+) -> Result<HashMap<DistributionName, String>, String> {
+    let meta_dirs: Vec<&Path> = dag.values().map(|meta| meta.meta_dir.as_path()).collect();
+    let owners = manager.owners(&meta_dirs)?;
+    Ok(dag
+        .iter()
+        .filter_map(|(name, meta)| {
+            let owner = owners.get(&meta.meta_dir)?;
+            Some((name.clone(), owner.clone()))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn owners_parsed_from_query_output() {
+        let dpkg = "python3-yaml:amd64: /usr/lib/python3/dist-packages/PyYAML-6.0.1.egg-info\n\
+                    python3-six, python3-six-extra: /usr/lib/python3/dist-packages/six-1.16.0.dist-info\n\
+                    diversion by foo from: /usr/bin/x\n";
+        let owners = parse_dpkg_owners(dpkg);
+        assert_eq!(
+            owners[Path::new("/usr/lib/python3/dist-packages/PyYAML-6.0.1.egg-info")],
+            "python3-yaml"
+        );
+        assert_eq!(
+            owners[Path::new("/usr/lib/python3/dist-packages/six-1.16.0.dist-info")],
+            "python3-six"
+        );
+        assert_eq!(owners.len(), 2);
+
+        let paths = [
+            Path::new("/usr/lib/a.dist-info"),
+            Path::new("/opt/b.dist-info"),
+        ];
+        let rpm = "python3-a\nfile /opt/b.dist-info is not owned by any package\n";
+        let owners = parse_rpm_owners(&paths, rpm);
+        assert_eq!(owners.len(), 1);
+        assert_eq!(owners[Path::new("/usr/lib/a.dist-info")], "python3-a");
+    }
+}
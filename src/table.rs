@@ -0,0 +1,154 @@
+//! Aligned plain text tables shared by diff and report outputs. Columns are
+//! as wide as their widest cell, on a terminal too wide tables get their
+//! widest columns shrunk and cells cut with an ellipsis
+
+use std::io::{self, IsTerminal, Write};
+
+/// Columns are separated by this many spaces
+const GAP: usize = 2;
+/// Columns are never shrunk below this width
+const MIN_WIDTH: usize = 4;
+const ELLIPSIS: char = '…';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// Table with a header row, every row has a cell per header
+#[derive(Debug)]
+pub struct Table {
+    headers: Vec<String>,
+    align: Vec<Align>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    /// Table with left aligned columns
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            align: vec![Align::Left; headers.len()],
+            rows: Vec::new(),
+        }
+    }
+
+    /// Align a column to the right, for numbers
+    pub fn align_right(mut self, column: usize) -> Self {
+        self.align[column] = Align::Right;
+        self
+    }
+
+    /// Append a row, missing cells are left empty and extra ones dropped
+    pub fn row<I, S>(&mut self, cells: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        let mut row: Vec<String> = cells.into_iter().map(|c| c.to_string()).collect();
+        row.resize(self.headers.len(), String::new());
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Widths of the columns, shrunk to fit into max_width when given
+    fn widths(&self, max_width: Option<usize>) -> Vec<usize> {
+        let mut widths: Vec<usize> = (0..self.headers.len())
+            .map(|column| {
+                std::iter::once(&self.headers[column])
+                    .chain(self.rows.iter().map(|row| &row[column]))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let Some(max_width) = max_width else {
+            return widths;
+        };
+        let gaps = GAP * widths.len().saturating_sub(1);
+        while widths.iter().sum::<usize>() + gaps > max_width {
+            let widest = (0..widths.len()).max_by_key(|&column| widths[column]);
+            match widest {
+                Some(column) if widths[column] > MIN_WIDTH => widths[column] -= 1,
+                _ => break,
+            }
+        }
+        widths
+    }
+
+    /// Print the table, cutting it to max_width columns when given
+    pub fn render(&self, w: &mut dyn Write, max_width: Option<usize>) -> io::Result<()> {
+        let widths = self.widths(max_width);
+        for row in std::iter::once(&self.headers).chain(&self.rows) {
+            let mut line = String::new();
+            for (column, cell) in row.iter().enumerate() {
+                let width = widths[column];
+                let cell = truncate(cell, width);
+                if column > 0 {
+                    line.push_str(&" ".repeat(GAP));
+                }
+                match self.align[column] {
+                    Align::Right => line.push_str(&format!("{:>width$}", cell)),
+                    // no trailing spaces after the last column
+                    Align::Left if column + 1 == row.len() => line.push_str(&cell),
+                    Align::Left => line.push_str(&format!("{:<width$}", cell)),
+                }
+            }
+            writeln!(w, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cell cut to the width, the last kept char replaced by an ellipsis
+fn truncate(cell: &str, width: usize) -> String {
+    if cell.chars().count() <= width {
+        return cell.to_string();
+    }
+    let mut cut: String = cell.chars().take(width.saturating_sub(1)).collect();
+    cut.push(ELLIPSIS);
+    cut
+}
+
+/// Width tables are cut to: COLUMNS of the terminal stdout is printed to,
+/// None when the output is redirected or the width is unknown
+pub fn terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    std::env::var("COLUMNS").ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn columns_aligned_and_truncated() {
+        let mut table = Table::new(&["package", "before", "after", "count"]).align_right(3);
+        table.row(["requests", "2.31.0", "2.32.3", "12"]);
+        table.row(["typing-extensions-backport", "-", "4.12.2", "1"]);
+        let render = |max_width| {
+            let mut out = Vec::new();
+            table.render(&mut out, max_width).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            render(None),
+            "package                     before  after   count\n\
+             requests                    2.31.0  2.32.3     12\n\
+             typing-extensions-backport  -       4.12.2      1\n"
+        );
+        assert_eq!(
+            render(Some(36)),
+            "package        before  after   count\n\
+             requests       2.31.0  2.32.3     12\n\
+             typing-exten…  -       4.12.2      1\n"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Checking for new releases of rdeptree and replacing the running binary,
+//! for users of standalone binaries. The network is used only by these commands
+
+use crate::locator::{describe_failure, run_with_timeout, COMMAND_TIMEOUT};
+use crate::network;
+use crate::utils::version_parts;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::env::consts::{ARCH, EXE_SUFFIX, OS};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// Latest release of the project, GitHub releases API
+pub const DEFAULT_RELEASE_URL: &str =
+    "https://api.github.com/repos/GrTomato/rdeptree/releases/latest";
+
+/// Longest time a single query or download may take
+const QUERY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Version of the running binary
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Published release, as described by the releases API
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Release {
+    /// tag of the release, e.g. `v0.1.0`
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+/// File attached to a release
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// Version of the release, its tag without the `v` prefix
+    pub fn version(&self) -> &str {
+        self.tag_name.strip_prefix('v').unwrap_or(&self.tag_name)
+    }
+
+    /// Binary built for the running platform, named `rdeptree-<arch>-<os>`,
+    /// e.g. `rdeptree-x86_64-linux` or `rdeptree-x86_64-windows.exe`
+    pub fn platform_asset(&self) -> Option<&Asset> {
+        self.asset(&platform_asset_name())
+    }
+
+    /// Published sha256 of an asset, the asset named after it with `.sha256` appended
+    pub fn checksum_asset(&self, asset: &Asset) -> Option<&Asset> {
+        self.asset(&format!("{}.sha256", asset.name))
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// Name of the release asset holding the binary built for the running platform
+pub fn platform_asset_name() -> String {
+    format!("rdeptree-{}-{}{}", ARCH, OS, EXE_SUFFIX)
+}
+
+/// Query the release endpoint for the latest release
+pub fn latest_release(url: &str) -> Result<Release, String> {
+    let body = network::curl(
+        url,
+        &["--header", "Accept: application/json"],
+        QUERY_TIMEOUT,
+    )?;
+    serde_json::from_slice(&body)
+        .map_err(|err| format!("Unable to parse response of {}: {}", url, err))
+}
+
+/// Whether the candidate version is newer than the current one
+pub fn is_newer(current: &str, candidate: &str) -> bool {
+    version_parts(candidate) > version_parts(current)
+}
+
+/// Dir cargo installs binaries into, `$CARGO_HOME/bin` or `~/.cargo/bin`
+fn cargo_bin_dir() -> Option<PathBuf> {
+    let cargo_home = match env::var_os("CARGO_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?)
+            .join(".cargo"),
+    };
+    Some(cargo_home.join("bin"))
+}
+
+/// Whether the executable was installed with `cargo install`, which keeps
+/// track of the binaries it installed and should be used to update them
+fn installed_by_cargo(executable: &Path) -> bool {
+    let (Some(bin_dir), Some(exe_dir)) = (cargo_bin_dir(), executable.parent()) else {
+        return false;
+    };
+    match (bin_dir.canonicalize(), exe_dir.canonicalize()) {
+        (Ok(bin_dir), Ok(exe_dir)) => bin_dir == exe_dir,
+        _ => false,
+    }
+}
+
+/// sha256 of a release asset, the first word of its `.sha256` file
+fn published_sha256(url: &str) -> Result<String, String> {
+    let body = network::curl(url, &[], QUERY_TIMEOUT)?;
+    String::from_utf8_lossy(&body)
+        .split_whitespace()
+        .next()
+        .filter(|digest| digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()))
+        .map(str::to_lowercase)
+        .ok_or_else(|| format!("Unable to parse sha256 published at {}", url))
+}
+
+fn file_sha256(path: &Path) -> Result<String, String> {
+    let data = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(Sha256::digest(&data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Downloaded binary must start on this platform and report itself as rdeptree
+fn try_binary(binary: &Path) -> Result<(), String> {
+    let output = run_with_timeout(Command::new(binary).arg("--version"), COMMAND_TIMEOUT)
+        .map_err(|err| format!("Unable to run downloaded binary: {}", err))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Downloaded binary failed to run: {}",
+            describe_failure(&output)
+        ));
+    }
+    if !String::from_utf8_lossy(&output.stdout).starts_with("rdeptree ") {
+        return Err(String::from(
+            "Downloaded binary does not report an rdeptree version",
+        ));
+    }
+    Ok(())
+}
+
+/// Download the binary from the url and put it in place of the executable.
+/// It is downloaded next to the executable first and put in place only when
+/// its sha256 matches the published one and it runs, so a failed or tampered
+/// download leaves the executable untouched. Binaries installed with cargo
+/// are not replaced
+pub fn replace_executable(executable: &Path, url: &str, checksum_url: &str) -> Result<(), String> {
+    if installed_by_cargo(executable) {
+        return Err(format!(
+            "{} was installed with cargo, update it with `cargo install rdeptree`",
+            executable.display()
+        ));
+    }
+    let expected = published_sha256(checksum_url)?;
+    let download = with_suffix(executable, "download");
+    let download_arg = download.to_string_lossy();
+    let checked = network::curl(url, &["--output", &download_arg], QUERY_TIMEOUT)
+        .and_then(|_| file_sha256(&download))
+        .and_then(|actual| match actual == expected {
+            true => Ok(()),
+            false => Err(format!(
+                "sha256 of {} is {}, {} was published",
+                url, actual, expected
+            )),
+        })
+        .and_then(|_| make_executable(&download).map_err(|err| err.to_string()))
+        .and_then(|_| try_binary(&download));
+    if let Err(err) = checked {
+        let _ = fs::remove_file(&download);
+        return Err(err);
+    }
+    if let Err(err) = swap(executable, &download) {
+        let _ = fs::remove_file(&download);
+        return Err(format!(
+            "Unable to replace {}: {}",
+            executable.display(),
+            err
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Running binaries can be renamed but not overwritten on windows,
+/// so the old one is moved aside first
+fn swap(executable: &Path, download: &Path) -> std::io::Result<()> {
+    if cfg!(windows) {
+        let old = with_suffix(executable, "old");
+        let _ = fs::remove_file(&old);
+        fs::rename(executable, &old)?;
+    }
+    fs::rename(download, executable)
+}
+
+/// Sibling of the executable with the suffix appended to the full file name,
+/// so `rdeptree.exe` gets `rdeptree.exe.download` instead of `rdeptree.download`
+fn with_suffix(executable: &Path, suffix: &str) -> PathBuf {
+    let mut name = executable.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    executable.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn release_versions_compared_numerically() {
+        assert!(is_newer("0.0.3", "0.0.10"));
+        assert!(is_newer("0.9.1", "1.0.0"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.2.0", "0.1.9"));
+
+        let name = platform_asset_name();
+        let release: Release = serde_json::from_str(&format!(
+            r#"{{"tag_name": "v1.2.0", "assets": [
+                {{"name": "rdeptree-other-os.tar.gz", "browser_download_url": "https://x/other"}},
+                {{"name": "{name}.tar.gz", "browser_download_url": "https://x/archive"}},
+                {{"name": "{name}.sha256", "browser_download_url": "https://x/sha256"}},
+                {{"name": "{name}", "browser_download_url": "https://x/bin"}}
+            ]}}"#
+        ))
+        .unwrap();
+        assert_eq!(release.version(), "1.2.0");
+        let asset = release.platform_asset().unwrap();
+        assert_eq!(asset.browser_download_url, "https://x/bin");
+        assert_eq!(
+            release
+                .checksum_asset(asset)
+                .map(|a| a.browser_download_url.as_str()),
+            Some("https://x/sha256")
+        );
+    }
+
+    #[test]
+    fn suffix_appended_to_full_file_name() {
+        assert_eq!(
+            with_suffix(Path::new("bin/rdeptree.exe"), "download"),
+            Path::new("bin/rdeptree.exe.download")
+        );
+        assert_eq!(
+            with_suffix(Path::new("bin/rdeptree"), "old"),
+            Path::new("bin/rdeptree.old")
+        );
+    }
+}
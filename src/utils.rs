@@ -1,19 +1,205 @@
-use std::fs;
-use std::fs::DirEntry;
-use std::fs::File;
-use std::io::{self, BufRead};
+use crate::deadline;
+use crate::i18n::{debug, trf, Msg};
+use jwalk::WalkDir;
+use memmap2::Mmap;
+use std::cmp;
+use std::collections::hash_map::RandomState;
+use std::fs::{self, File};
+use std::hash::BuildHasher;
+use std::io::{self, BufRead, BufReader};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 
-const METADATA_DIR_SUFFIX: &'static str = ".dist-info";
+const METADATA_DIR_SUFFIX: &str = ".dist-info";
+const EGG_INFO_DIR_SUFFIX: &str = ".egg-info";
+const DATA_DIR_SUFFIX: &str = ".data";
+const EGG_FILE_SUFFIX: &str = ".egg";
+/// Metadata dir inside a zipped egg
+pub(crate) const EGG_INFO_IN_EGG: &str = "EGG-INFO";
+
+/// UTF-8 byte order mark, written at the start of text files by some Windows tools
+const BOM: char = '\u{feff}';
 
 /// from https://doc.rust-lang.org/rust-by-example/std_misc/file/read_lines.html
-pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
+/// Lines come without terminators, both LF and CRLF (metadata of wheels
+/// built on Windows) are accepted and leading BOM is dropped
+pub fn read_lines(filename: &Path) -> io::Result<impl Iterator<Item = io::Result<String>>> {
+    let file = File::open(filename)?;
+    Ok(io::BufReader::new(file)
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            line.map(|mut line| {
+                line.truncate(line.trim_end_matches('\r').len());
+                if i == 0 && line.starts_with(BOM) {
+                    line.remove(0);
+                }
+                line
+            })
+        }))
+}
+
+/// Attempts of a single read, the first one included
+const READ_ATTEMPTS: u32 = 4;
+/// Delay before the first retry, doubled before every next one
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(20);
+/// Retries left for the whole run, so a failing mount can't stall
+/// a scan of thousands of files by retrying every one of them
+static RETRIES_LEFT: AtomicUsize = AtomicUsize::new(64);
+
+/// Error which may go away when the read is repeated, e.g. a network
+/// filesystem interrupting the call or failing with EIO under load
+fn is_transient(err: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(
+        err.kind(),
+        Interrupted | TimedOut | WouldBlock | ResourceBusy | StaleNetworkFileHandle
+    ) || (cfg!(unix) && err.raw_os_error() == Some(5))
+}
+
+/// Backoff before the given retry (1-based): the doubled base delay,
+/// of which a random part up to a half is taken away so parallel
+/// readers of the same mount don't retry in lockstep
+fn retry_delay(retry: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY * 2u32.pow(retry - 1);
+    let jitter = RandomState::new().hash_one(retry) % (delay.as_micros() as u64 / 2 + 1);
+    deadline::limit(delay - Duration::from_micros(jitter))
+}
+
+/// Run a read of the path, repeating it with jittered exponential backoff
+/// while it fails with transient errors. Retries are reported with
+/// `--verbose` and limited per read, per run and by the time budget
+pub fn retry_read<T, F>(path: &Path, mut read: F) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    let mut retry = 0;
+    loop {
+        let err = match read() {
+            Err(err) if is_transient(&err) => err,
+            result => return result,
+        };
+        retry += 1;
+        if retry >= READ_ATTEMPTS || deadline::expired() {
+            return Err(err);
+        }
+        // taken only for a retry which is going to happen
+        let budget_left = RETRIES_LEFT
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok();
+        if !budget_left {
+            return Err(err);
+        }
+        let delay = retry_delay(retry);
+        debug(&trf(
+            Msg::ReadRetried,
+            &[&path.display(), &err, &delay.as_millis()],
+        ));
+        thread::sleep(delay);
+    }
+}
+
+/// Whole text file with leading BOM dropped and CRLF endings turned into LF.
+/// Transient errors are retried, see `retry_read`
+pub fn read_text<P>(filename: P) -> io::Result<String>
 where
     P: AsRef<Path>,
 {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+    let path = filename.as_ref();
+    Ok(normalize_text(retry_read(path, || {
+        fs::read_to_string(path)
+    })?))
+}
+
+/// Length of the header of a core metadata file: everything up to and including
+/// the first empty line (LF or CRLF ended), the whole content if there is none
+fn header_len(content: &[u8]) -> usize {
+    let mut rest = content;
+    let mut offset = 0;
+    while let Some(pos) = rest.iter().position(|b| *b == b'\n') {
+        offset += pos + 1;
+        rest = &rest[pos + 1..];
+        if rest.starts_with(b"\n") {
+            return offset + 1;
+        }
+        if rest.starts_with(b"\r\n") {
+            return offset + 2;
+        }
+    }
+    content.len()
+}
+
+/// Header of already read core metadata, the description body is dropped
+pub fn metadata_header(content: &str) -> &str {
+    &content[..header_len(content.as_bytes())]
+}
+
+/// Header of a core metadata file (METADATA, PKG-INFO) with leading BOM dropped
+/// and LF endings. The file is memory mapped and only the pages up to the end of
+/// the header are touched, so megabyte-scale descriptions in the body are never
+/// read. Files which can't be mapped are read with `read_header_buffered`.
+/// Transient errors are retried, see `retry_read`
+pub fn read_header<P>(filename: P) -> io::Result<String>
+where
+    P: AsRef<Path>,
+{
+    let path = filename.as_ref();
+    retry_read(path, || map_header(path))
+}
+
+fn map_header(path: &Path) -> io::Result<String> {
+    let file = File::open(path)?;
+    // zero-length mappings are rejected on some platforms
+    if file.metadata()?.len() == 0 {
+        return Ok(String::new());
+    }
+    // SAFETY: the map is dropped before returning and only read; METADATA
+    // files are written once by installers and not truncated while scanned
+    let map = match unsafe { Mmap::map(&file) } {
+        Ok(map) => map,
+        // e.g. special files or filesystems without mmap support
+        Err(_) => return buffered_header(BufReader::new(file)),
+    };
+    let header = std::str::from_utf8(&map[..header_len(&map)])
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(normalize_text(header.to_string()))
+}
+
+/// Same as `read_header` without memory mapping: lines are read through a
+/// `BufReader` up to the first empty one, the same part as `header_len` takes
+pub fn read_header_buffered<P>(filename: P) -> io::Result<String>
+where
+    P: AsRef<Path>,
+{
+    let path = filename.as_ref();
+    retry_read(path, || buffered_header(BufReader::new(File::open(path)?)))
+}
+
+fn buffered_header(mut reader: impl BufRead) -> io::Result<String> {
+    let mut header = Vec::new();
+    loop {
+        let start = header.len();
+        if reader.read_until(b'\n', &mut header)? == 0 {
+            break;
+        }
+        if start > 0 && matches!(&header[start..], b"\n" | b"\r\n") {
+            break;
+        }
+    }
+    let header =
+        String::from_utf8(header).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(normalize_text(header))
+}
+
+/// Drop leading BOM and turn CRLF endings into LF
+pub fn normalize_text(content: String) -> String {
+    let content = content.strip_prefix(BOM).unwrap_or(&content);
+    content.replace("\r\n", "\n")
 }
 
 pub fn get_lnreader<P, F>(
@@ -24,29 +210,532 @@ where
     P: AsRef<Path>,
     F: Fn(&Result<String, std::io::Error>) -> bool,
 {
-    let line_reader = read_lines(&filename)?;
+    let line_reader = read_lines(filename.as_ref())?;
     Ok(line_reader
         .take_while(move |line| stop_func(line))
         .map(|l| l.unwrap()))
 }
 
-/// Get iterator which filter dir entries by metadata suffix
-pub fn get_meta_dirs(env_path: &PathBuf) -> impl Iterator<Item = DirEntry> {
-    fs::read_dir(env_path)
-        .expect("Can not read site-packages dir")
-        .filter_map(|dir_path| match dir_path {
-            Ok(dir) => {
-                let dir_path_str = dir.file_name();
-                if dir_path_str
-                    .to_str()
-                    .unwrap()
-                    .ends_with(METADATA_DIR_SUFFIX)
-                {
-                    Some(dir)
-                } else {
-                    None
+/// Kind of a metadata dir found in site-packages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaDirKind {
+    /// `<name>-<version>.dist-info`, installed by modern installers
+    DistInfo,
+    /// `<name>-<version>.egg-info`, legacy setuptools installs,
+    /// or `EGG-INFO` inside a zipped `<name>-<version>.egg`
+    EggInfo,
+}
+
+impl MetaDirKind {
+    pub(crate) fn classify(file_name: &str) -> Option<Self> {
+        if file_name.ends_with(METADATA_DIR_SUFFIX) {
+            Some(MetaDirKind::DistInfo)
+        } else if file_name.ends_with(EGG_INFO_DIR_SUFFIX) {
+            Some(MetaDirKind::EggInfo)
+        } else {
+            None
+        }
+    }
+}
+
+/// Metadata dir found while scanning site-packages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaDirEntry {
+    pub path: PathBuf,
+    pub file_name: String,
+    pub kind: MetaDirKind,
+}
+
+/// Scan site-packages dir for metadata dirs. Dir is walked in parallel and
+/// `*.data` dirs are looked into one level deep, as wheels may leave
+/// metadata there. Zipped eggs in site-packages root are reported as
+/// `<egg>/EGG-INFO`. Entries are sorted by path, so the scan is deterministic.
+pub fn get_meta_dirs(env_path: &PathBuf) -> Vec<MetaDirEntry> {
+    let walker = WalkDir::new(env_path)
+        .max_depth(2)
+        .skip_hidden(false)
+        .process_read_dir(|depth, _, _, children| {
+            // depth is None for the root itself, Some(0) for site-packages content;
+            // only *.data dirs of site-packages root are descended into
+            for child in children.iter_mut().flatten() {
+                let is_data_dir = child.file_name.to_string_lossy().ends_with(DATA_DIR_SUFFIX);
+                if depth.is_some() && (depth != Some(0) || !is_data_dir) {
+                    child.read_children = None;
                 }
             }
-            Err(_) => None,
+        });
+
+    let mut entries: Vec<MetaDirEntry> = walker
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.depth > 0)
+        .filter_map(|entry| {
+            let file_name = entry.file_name.to_string_lossy().to_string();
+            if entry.file_type.is_dir() {
+                MetaDirKind::classify(&file_name).map(|kind| MetaDirEntry {
+                    path: entry.path(),
+                    file_name,
+                    kind,
+                })
+            } else if entry.depth == 1 && file_name.ends_with(EGG_FILE_SUFFIX) {
+                Some(MetaDirEntry {
+                    path: entry.path().join(EGG_INFO_IN_EGG),
+                    file_name,
+                    kind: MetaDirKind::EggInfo,
+                })
+            } else {
+                None
+            }
         })
+        .collect();
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// Dirs project-local files are looked up in: the start dir and its ancestors
+/// up to the git root. Outside of a git repository only the start dir
+pub fn project_dirs(start: &Path) -> Vec<&Path> {
+    match start.ancestors().find(|dir| dir.join(".git").exists()) {
+        Some(root) => start
+            .ancestors()
+            .take_while(|dir| *dir != root)
+            .chain([root])
+            .collect(),
+        None => vec![start],
+    }
+}
+
+/// Format unix timestamp as UTC date and time without pulling a date crate,
+/// days to civil conversion is from http://howardhinnant.github.io/date_algorithms.html
+pub fn format_unix_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let day_secs = secs % 86400;
+
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        day_secs / 3600,
+        day_secs % 3600 / 60,
+        day_secs % 60
+    )
+}
+
+/// Parse UTC date and time as served by the PyPI JSON API, e.g. `2023-05-22T15:12:44`,
+/// optionally followed by fractional seconds and `Z`. Inverse of `format_unix_timestamp`
+pub fn parse_utc_timestamp(time: &str) -> Option<u64> {
+    let (date, time) = time.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let time = time.trim_end_matches('Z');
+    let time = time.split_once('.').map_or(time, |(t, _)| t);
+    let mut time = time.splitn(3, ':').map(|p| p.parse::<u64>().ok());
+    let (hours, minutes, secs) = (time.next()??, time.next()??, time.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    if hours > 23 || minutes > 59 || secs > 60 {
+        return None;
+    }
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = u64::try_from(era * 146097 + doe - 719468).ok()?;
+    Some(days * 86400 + hours * 3600 + minutes * 60 + secs)
+}
+
+const AGE_UNITS: [(&str, u64); 4] = [("d", 1), ("w", 7), ("m", 30), ("y", 365)];
+
+/// Parse age such as "90d", "6m", "2y" or plain amount of days, to days
+pub fn parse_age(age: &str) -> Result<u64, String> {
+    let lowered = age.trim().to_lowercase();
+    let (number, multiplier) = AGE_UNITS
+        .iter()
+        .find_map(|(unit, mult)| lowered.strip_suffix(unit).map(|n| (n, *mult)))
+        .unwrap_or((&lowered, 1));
+
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or(format!(
+            "invalid age '{}', expected e.g. 90d, 6m or 2y",
+            age
+        ))
+}
+
+/// Order of two PEP 440 versions (pre, post and dev releases included),
+/// None if they can't be compared
+pub fn compare_versions(lhs: &str, rhs: &str) -> Option<cmp::Ordering> {
+    let lhs: crate::pep::Version = lhs.parse().ok()?;
+    let rhs: crate::pep::Version = rhs.parse().ok()?;
+    Some(lhs.cmp(&rhs))
+}
+
+/// Lower or upper end of a version range, the version and whether it is included
+type Bound = Option<(String, bool)>;
+
+/// Keep the narrower of two bounds, `keep` tells the order of versions where
+/// the new one is narrower. None when the versions can't be compared
+fn narrower(current: Bound, new: (String, bool), keep: cmp::Ordering) -> Option<Bound> {
+    let Some((version, inclusive)) = &current else {
+        return Some(Some(new));
+    };
+    Some(match compare_versions(&new.0, version)? {
+        order if order == keep => Some(new),
+        cmp::Ordering::Equal => Some((new.0, new.1 && *inclusive)),
+        _ => current,
+    })
+}
+
+/// No version satisfies both specifiers, e.g. `>=2` and `<2`, or `==1.0` and `~=1.1`.
+/// Only the range given by `<`, `<=`, `>`, `>=`, `==` and `~=` clauses is considered,
+/// `!=` and wildcard clauses are ignored. False when versions can't be compared
+pub fn specifiers_disjoint(lhs: &str, rhs: &str) -> bool {
+    const OPERATORS: [&str; 8] = ["===", "==", "!=", "~=", "<=", ">=", "<", ">"];
+    let mut lower: Bound = None;
+    let mut upper: Bound = None;
+    for clause in lhs.split(',').chain(rhs.split(',')).map(str::trim) {
+        let Some(op) = OPERATORS.iter().find(|op| clause.starts_with(**op)) else {
+            continue;
+        };
+        let version = clause[op.len()..].trim().to_string();
+        if version.ends_with(".*") {
+            continue;
+        }
+        let compatible_upper = || {
+            let mut parts = version_parts(&version);
+            parts.pop();
+            *parts.last_mut()? += 1;
+            let parts: Vec<String> = parts.iter().map(u64::to_string).collect();
+            Some(parts.join("."))
+        };
+        let bounds = match *op {
+            ">=" => (Some((version, true)), None),
+            ">" => (Some((version, false)), None),
+            "<=" => (None, Some((version, true))),
+            "<" => (None, Some((version, false))),
+            "==" => (Some((version.clone(), true)), Some((version, true))),
+            "~=" => match compatible_upper() {
+                Some(end) => (Some((version, true)), Some((end, false))),
+                None => continue,
+            },
+            _ => continue,
+        };
+        if let Some(bound) = bounds.0 {
+            let Some(narrowed) = narrower(lower, bound, cmp::Ordering::Greater) else {
+                return false;
+            };
+            lower = narrowed;
+        }
+        if let Some(bound) = bounds.1 {
+            let Some(narrowed) = narrower(upper, bound, cmp::Ordering::Less) else {
+                return false;
+            };
+            upper = narrowed;
+        }
+    }
+    match (lower, upper) {
+        (Some((low, low_inclusive)), Some((high, high_inclusive))) => {
+            match compare_versions(&low, &high) {
+                Some(cmp::Ordering::Greater) => true,
+                Some(cmp::Ordering::Equal) => !(low_inclusive && high_inclusive),
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Numeric components of a `X.Y.Z` version, anything after
+/// the first non-numeric component is ignored
+pub fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+/// Format age in days roughly, in the largest fitting unit
+pub fn format_age(days: u64) -> String {
+    let (amount, unit) = match days {
+        d if d >= 730 => (d / 365, "years"),
+        d if d >= 60 => (d / 30, "months"),
+        d => (d, "days"),
+    };
+    match amount {
+        1 => format!("1 {}", unit.trim_end_matches('s')),
+        _ => format!("{} {}", amount, unit),
+    }
+}
+
+const SIZE_UNITS: [(&str, u64); 7] = [
+    ("kib", 1 << 10),
+    ("mib", 1 << 20),
+    ("gib", 1 << 30),
+    ("kb", 1_000),
+    ("mb", 1_000_000),
+    ("gb", 1_000_000_000),
+    ("b", 1),
+];
+
+/// Parse human readable size such as "500MB", "1.5 GiB" or "1024"
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let lowered = size.trim().to_lowercase();
+    let (number, multiplier) = SIZE_UNITS
+        .iter()
+        .find_map(|(unit, mult)| lowered.strip_suffix(unit).map(|n| (n, *mult)))
+        .unwrap_or((&lowered, 1));
+
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n >= 0.0)
+        .map(|n| (n * multiplier as f64) as u64)
+        .ok_or(format!("invalid size '{}', expected e.g. 500MB", size))
+}
+
+/// Format size in bytes with decimal units
+pub fn format_size(size: u64) -> String {
+    match size {
+        s if s >= 1_000_000_000 => format!("{:.1} GB", s as f64 / 1e9),
+        s if s >= 1_000_000 => format!("{:.1} MB", s as f64 / 1e6),
+        s if s >= 1_000 => format!("{:.1} KB", s as f64 / 1e3),
+        s => format!("{} B", s),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture::SitePackages;
+
+    #[test]
+    fn crlf_and_bom_are_dropped() {
+        let site = SitePackages::new("crlf").file(
+            "METADATA",
+            "\u{feff}Name: six\r\nVersion: 1.16.0\r\n\r\nbody\n",
+        );
+        let path = site.path().join("METADATA");
+
+        let lines: Vec<String> = read_lines(&path).unwrap().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, vec!["Name: six", "Version: 1.16.0", "", "body"]);
+        assert_eq!(
+            read_text(&path).unwrap(),
+            "Name: six\nVersion: 1.16.0\n\nbody\n"
+        );
+    }
+
+    #[test]
+    fn header_ends_at_first_empty_line() {
+        assert_eq!(
+            metadata_header("Name: six\nVersion: 1\n\nName: body\n"),
+            "Name: six\nVersion: 1\n\n"
+        );
+        assert_eq!(
+            metadata_header("Name: six\r\n\r\nbody"),
+            "Name: six\r\n\r\n"
+        );
+        assert_eq!(
+            metadata_header("Name: six\nVersion: 1"),
+            "Name: six\nVersion: 1"
+        );
+        assert_eq!(metadata_header(""), "");
+
+        let body = "Requires-Dist: not-a-field\n".repeat(10_000);
+        let site = SitePackages::new("header")
+            .file(
+                "METADATA",
+                &format!("\u{feff}Name: six\r\nVersion: 1.16.0\r\n\r\n{}", body),
+            )
+            .file("PKG-INFO", "Name: six\nVersion: 1")
+            .file("EMPTY", "");
+        for read in [read_header::<PathBuf>, read_header_buffered::<PathBuf>] {
+            let header = |file| read(site.path().join(file)).unwrap();
+            assert_eq!(header("METADATA"), "Name: six\nVersion: 1.16.0\n\n");
+            assert_eq!(header("PKG-INFO"), "Name: six\nVersion: 1");
+            assert_eq!(header("EMPTY"), "");
+        }
+    }
+
+    #[test]
+    fn transient_read_errors_are_retried() {
+        let path = Path::new("METADATA");
+        let mut failures = 2;
+        let result = retry_read(path, || {
+            if failures == 0 {
+                return Ok("Name: six");
+            }
+            failures -= 1;
+            Err(io::Error::from(io::ErrorKind::Interrupted))
+        });
+        assert_eq!(result.unwrap(), "Name: six");
+
+        let mut attempts = 0;
+        let retries_left = RETRIES_LEFT.load(Ordering::Relaxed);
+        let result: io::Result<()> = retry_read(path, || {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::TimedOut))
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert_eq!(attempts, READ_ATTEMPTS);
+        // the last failed attempt isn't followed by a retry, so it takes no budget
+        assert_eq!(
+            RETRIES_LEFT.load(Ordering::Relaxed),
+            retries_left - (READ_ATTEMPTS as usize - 1)
+        );
+
+        let mut attempts = 0;
+        let result: io::Result<()> = retry_read(path, || {
+            attempts += 1;
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn meta_dirs_scan() {
+        let site = SitePackages::new("scan");
+        let root = site.path().to_path_buf();
+        for dir in [
+            "six-1.16.0.dist-info",
+            "six",
+            "legacy-1.0.egg-info",
+            "pkg-1.0.data/nested-2.0.dist-info",
+            "pkg/deep-3.0.dist-info",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("file.dist-info"), b"").unwrap();
+        std::fs::write(root.join("zipped-0.5-py3.11.egg"), b"").unwrap();
+        std::fs::write(root.join("pkg/nested-0.1.egg"), b"").unwrap();
+
+        let entries = get_meta_dirs(&root);
+        let found: Vec<(&str, MetaDirKind)> = entries
+            .iter()
+            .map(|e| (e.file_name.as_str(), e.kind))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("legacy-1.0.egg-info", MetaDirKind::EggInfo),
+                ("nested-2.0.dist-info", MetaDirKind::DistInfo),
+                ("six-1.16.0.dist-info", MetaDirKind::DistInfo),
+                ("zipped-0.5-py3.11.egg", MetaDirKind::EggInfo),
+            ]
+        );
+        assert_eq!(
+            entries[3].path,
+            root.join("zipped-0.5-py3.11.egg").join(EGG_INFO_IN_EGG)
+        );
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_size("500MB"), Ok(500_000_000));
+        assert_eq!(parse_size("1.5 GiB"), Ok(1_610_612_736));
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("10kb"), Ok(10_000));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("-1MB").is_err());
+    }
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(12), "12 B");
+        assert_eq!(format_size(1_500), "1.5 KB");
+        assert_eq!(format_size(100_000_000), "100.0 MB");
+    }
+
+    #[test]
+    fn format_timestamps() {
+        assert_eq!(format_unix_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_unix_timestamp(951782400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_unix_timestamp(1700000000), "2023-11-14 22:13:20 UTC");
+    }
+
+    #[test]
+    fn parse_timestamps() {
+        for secs in [0, 951782400, 1700000000] {
+            let time = format_unix_timestamp(secs).replace(' ', "T");
+            assert_eq!(
+                parse_utc_timestamp(time.trim_end_matches("TUTC")),
+                Some(secs)
+            );
+        }
+        assert_eq!(
+            parse_utc_timestamp("2023-11-14T22:13:20.123456Z"),
+            Some(1700000000)
+        );
+        assert_eq!(parse_utc_timestamp("2023-13-14T22:13:20"), None);
+        assert_eq!(parse_utc_timestamp("2023-11-14"), None);
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(parse_age("90d"), Ok(90));
+        assert_eq!(parse_age("2Y"), Ok(730));
+        assert_eq!(parse_age("30"), Ok(30));
+        assert!(parse_age("old").is_err());
+        assert!(parse_age("99999999999999999y").is_err());
+        assert_eq!(format_age(1), "1 day");
+        assert_eq!(format_age(59), "59 days");
+        assert_eq!(format_age(400), "13 months");
+        assert_eq!(format_age(1100), "3 years");
+    }
+
+    #[test]
+    fn versions_compared() {
+        use std::cmp::Ordering;
+        assert_eq!(compare_versions("2.1", "3.0"), Some(Ordering::Less));
+        assert_eq!(compare_versions("1.10", "1.9"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("1.0", "1"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("2.0rc1", "2.0"), Some(Ordering::Less));
+        assert_eq!(compare_versions("2.0.dev1", "2.0a1"), Some(Ordering::Less));
+        assert_eq!(compare_versions("2.0b2", "2.0rc1"), Some(Ordering::Less));
+        assert_eq!(
+            compare_versions("2.0", "2.0.post1.dev0"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            compare_versions("2.0.post1.dev0", "2.0.post1"),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare_versions("2.0.post1", "2.0.1"), Some(Ordering::Less));
+        assert_eq!(compare_versions("latest", "2.0"), None);
+    }
+
+    #[test]
+    fn disjoint_specifiers() {
+        assert!(specifiers_disjoint(">=2", "<2"));
+        assert!(specifiers_disjoint(">2.0", "<=2"));
+        assert!(specifiers_disjoint("==1.0", "~=1.1"));
+        assert!(specifiers_disjoint("~=1.4.5", ">=1.5"));
+        assert!(specifiers_disjoint(">=1,<2", "==2.10"));
+        assert!(!specifiers_disjoint(">=2", "<=2"));
+        assert!(!specifiers_disjoint(">=1.0", "<2,!=1.5"));
+        assert!(!specifiers_disjoint("~=1.4", "<1.9"));
+        assert!(!specifiers_disjoint("==1.*", ">=2"));
+        assert!(!specifiers_disjoint("", "<2"));
+        assert!(!specifiers_disjoint(">=latest", "<2"));
+    }
 }
@@ -0,0 +1,178 @@
+//! Distributions available offline in wheel caches: wheels cached by pip
+//! (`~/.cache/pip/wheels`) and wheels unpacked by uv (`~/.cache/uv/archive-v0`).
+//! Metadata of `.whl` files is read in place, without extraction
+
+use crate::builder::DagBuilder;
+use crate::dag::{DistributionName, RequiredDistribution};
+use crate::deadline;
+use crate::i18n::{trf, warn, Msg};
+use crate::utils::compare_versions;
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const WHEEL_EXTENSION: &str = "whl";
+const DIST_INFO_SUFFIX: &str = ".dist-info";
+
+/// Single version of a distribution found in a cache
+#[derive(Debug)]
+pub struct CachedDistribution {
+    pub name: DistributionName,
+    /// name as declared in the metadata
+    pub display_name: String,
+    pub version: String,
+    /// every requirement, markers are kept but not evaluated
+    pub dependencies: Vec<RequiredDistribution>,
+    /// wheel file or unpacked wheel dir
+    pub location: PathBuf,
+}
+
+/// Wheel files and unpacked wheel dirs, i.e. dirs holding a `.dist-info` dir, under the root
+pub fn find_cached_wheels(root: &Path) -> Vec<PathBuf> {
+    let mut wheels = Vec::new();
+    let mut to_visit = vec![root.to_path_buf()];
+    while let Some(dir) = to_visit.pop() {
+        if deadline::expired() {
+            break;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut subdirs = Vec::new();
+        let mut unpacked = false;
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                unpacked |= entry
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(DIST_INFO_SUFFIX);
+                subdirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == WHEEL_EXTENSION) {
+                wheels.push(path);
+            }
+        }
+        if unpacked {
+            wheels.push(dir);
+        } else {
+            to_visit.extend(subdirs);
+        }
+    }
+    wheels.sort();
+    wheels
+}
+
+/// Order of versions where the ones which can't be compared come last, by text
+fn version_order(lhs: &str, rhs: &str) -> Ordering {
+    compare_versions(lhs, rhs).unwrap_or_else(|| {
+        let unknown = |version| compare_versions(version, version).is_none();
+        unknown(lhs).cmp(&unknown(rhs))
+    })
+}
+
+/// Distributions of the wheels cached under the roots, sorted by name and
+/// version. The same version cached more than once is listed once, wheels
+/// which can't be read (e.g. truncated downloads) are skipped with a warning
+pub fn scan_cache(roots: &[PathBuf]) -> Vec<CachedDistribution> {
+    let mut cached = Vec::new();
+    for wheel in roots.iter().flat_map(|root| find_cached_wheels(root)) {
+        let dag = match DagBuilder::new()
+            .paths([&wheel])
+            .evaluate_markers(false)
+            .build()
+        {
+            Ok(dag) => dag,
+            Err(err) => {
+                warn(&trf(Msg::CachedWheelUnreadable, &[&wheel.display(), &err]));
+                continue;
+            }
+        };
+        for (name, meta) in dag {
+            cached.push(CachedDistribution {
+                display_name: meta.display_name(&name).to_string(),
+                dependencies: meta.sorted_dependencies().into_iter().cloned().collect(),
+                version: meta.installed_version,
+                location: wheel.clone(),
+                name,
+            });
+        }
+    }
+    cached.sort_by(|a, b| {
+        a.name
+            .cmp(&b.name)
+            .then_with(|| version_order(&a.version, &b.version))
+            .then_with(|| a.version.cmp(&b.version))
+    });
+    cached.dedup_by(|a, b| a.name == b.name && a.version == b.version);
+    cached
+}
+
+#[cfg(all(test, feature = "archive"))]
+mod test {
+    use super::*;
+    use crate::fixture::SitePackages;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn cached_wheels_scanned() {
+        let cache = SitePackages::new("wheel-cache")
+            .file(
+                "archive-v0/abc/six-1.16.0.dist-info/METADATA",
+                "Name: six\nVersion: 1.16.0\n",
+            )
+            .file("archive-v0/abc/six.py", "");
+        let wheels = cache.path().join("wheels/ab/cd");
+        fs::create_dir_all(&wheels).unwrap();
+        for (version, requires) in [
+            ("2.10", "six>=1.5"),
+            ("2.10.post1", "six>=1.5"),
+            ("2.10rc1", "six>=1.5"),
+            ("2.9", "six<2"),
+        ] {
+            let path = wheels.join(format!("lib-{}-py3-none-any.whl", version));
+            let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+            zip.start_file(
+                format!("lib-{}.dist-info/METADATA", version),
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+            write!(
+                zip,
+                "Name: Lib\nVersion: {}\nRequires-Dist: {}; python_version < \"3.8\"\n",
+                version, requires
+            )
+            .unwrap();
+            zip.finish().unwrap();
+        }
+
+        // truncated download
+        fs::write(wheels.join("broken-1.0-py3-none-any.whl"), b"PK\x03\x04").unwrap();
+
+        assert_eq!(find_cached_wheels(cache.path()).len(), 6);
+        let cached = scan_cache(&[cache.path().to_path_buf()]);
+        let versions: Vec<(&str, &str)> = cached
+            .iter()
+            .map(|c| (c.display_name.as_str(), c.version.as_str()))
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                ("Lib", "2.9"),
+                ("Lib", "2.10rc1"),
+                ("Lib", "2.10"),
+                ("Lib", "2.10.post1"),
+                ("six", "1.16.0")
+            ]
+        );
+        assert_eq!(cached[2].dependencies[0].name, "six");
+        assert_eq!(
+            cached[2].dependencies[0].marker.as_deref(),
+            Some("python_version < \"3.8\"")
+        );
+        assert!(cached[0].location.ends_with("lib-2.9-py3-none-any.whl"));
+    }
+}
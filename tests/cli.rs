@@ -0,0 +1,1790 @@
+#[path = "../src/fixture.rs"]
+mod fixture;
+
+use assert_cmd::cargo::cargo_bin_cmd;
+use assert_cmd::Command;
+use fixture::SitePackages;
+use predicates::prelude::PredicateBooleanExt;
+use predicates::str::contains;
+
+/// rdeptree scanning given dirs, with english diagnostics
+fn rdeptree(sites: &[&SitePackages]) -> Command {
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en"]);
+    for site in sites {
+        cmd.arg("--path").arg(site.path());
+    }
+    cmd
+}
+
+fn chain_site() -> SitePackages {
+    SitePackages::new("chain")
+        .dist_info("app", "1.0", &["lib>=2.0"])
+        .dist_info("lib", "2.1", &["six<2,>=1.5"])
+        .dist_info("six", "1.16.0", &[])
+        .dist_info("tool", "0.3", &[])
+}
+
+#[test]
+fn tree() {
+    let site = chain_site();
+    rdeptree(&[&site]).assert().success().stdout(
+        "app [installed: 1.0]\n\
+         ----lib [required: >=2.0, installed: 2.1]\n\
+         --------six [required: <2,>=1.5, installed: 1.16.0]\n\
+         tool [installed: 0.3]\n\
+         4 packages, 2 direct, 0 conflicts, 0 missing\n",
+    );
+}
+
+#[test]
+fn env_vars_under_flags() {
+    let site = chain_site();
+    let extra = SitePackages::new("env-extra").dist_info("extra", "0.1", &[]);
+    let paths = std::env::join_paths([site.path(), extra.path()]).unwrap();
+    cargo_bin_cmd!("rdeptree")
+        .env("RDEPTREE_PATH", &paths)
+        .env("RDEPTREE_OUTPUT", "adjacency")
+        .env("RDEPTREE_LANG", "en")
+        .args(["--no-summary"])
+        .assert()
+        .success()
+        .stdout("app: lib\nextra:\nlib: six\nsix:\ntool:\n");
+
+    rdeptree(&[&site])
+        .env("RDEPTREE_PATH", extra.path())
+        .env("RDEPTREE_OUTPUT", "adjacency")
+        .args(["--format", "text", "--no-summary"])
+        .assert()
+        .success()
+        .stdout(
+            "app [installed: 1.0]\n\
+             ----lib [required: >=2.0, installed: 2.1]\n\
+             --------six [required: <2,>=1.5, installed: 1.16.0]\n\
+             tool [installed: 0.3]\n",
+        );
+}
+
+#[test]
+fn tree_of_selected_package() {
+    let site = chain_site();
+    rdeptree(&[&site])
+        .args(["-p", "LIB"])
+        .assert()
+        .success()
+        .stdout(
+            "lib [installed: 2.1]\n\
+             ----six [required: <2,>=1.5, installed: 1.16.0]\n\
+             2 packages, 1 direct, 0 conflicts, 0 missing\n",
+        );
+}
+
+#[test]
+fn unknown_package_suggests_names() {
+    let site = chain_site();
+    rdeptree(&[&site])
+        .args(["-p", "sx"])
+        .assert()
+        .failure()
+        .stderr(contains("six"));
+}
+
+#[test]
+fn reverse() {
+    let site = chain_site();
+    rdeptree(&[&site])
+        .args(["why", "six"])
+        .assert()
+        .success()
+        .stdout(
+            "six [installed: 1.16.0]\n\
+         ----lib [requires: <2,>=1.5, installed: 2.1]\n\
+         --------app [requires: >=2.0, installed: 1.0]\n",
+        );
+}
+
+#[test]
+fn json() {
+    let site = chain_site();
+    let output = rdeptree(&[&site])
+        .args(["--format", "json", "-p", "lib"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let packages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        packages,
+        serde_json::json!([
+            {
+                "name": "lib",
+                "installed_version": "2.1",
+                "requested": false,
+                "dependencies": [
+                    {"name": "six", "required_version": "<2,>=1.5", "installed_version": "1.16.0"}
+                ]
+            },
+            {"name": "six", "installed_version": "1.16.0", "requested": false, "dependencies": []}
+        ])
+    );
+}
+
+#[test]
+fn json_markers() {
+    let site = SitePackages::new("markers")
+        .dist_info(
+            "app",
+            "1.0",
+            &["tomli>=1.1; python_version < \"3.11\" and os_name == \"posix\""],
+        )
+        .dist_info("tomli", "2.0.1", &[]);
+    let output = rdeptree(&[&site])
+        .args(["--format", "json", "-p", "app"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let packages: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        packages[0]["dependencies"][0],
+        serde_json::json!({
+            "name": "tomli",
+            "required_version": ">=1.1",
+            "installed_version": "2.0.1",
+            "marker": "python_version < \"3.11\" and os_name == \"posix\"",
+            "parsed_marker": {"and": [
+                {"variable": "python_version", "op": "<", "value": "3.11"},
+                {"variable": "os_name", "op": "==", "value": "posix"}
+            ]}
+        })
+    );
+}
+
+#[test]
+fn conflict_modes() {
+    let first = SitePackages::new("first").dist_info("six", "1.16.0", &[]);
+    let second = SitePackages::new("second").dist_info("six", "1.15.0", &[]);
+
+    rdeptree(&[&first, &second]).assert().success().stdout(
+        "six [installed: 1.16.0, shadowed: 1.15.0]\n\
+             1 packages, 1 direct, 1 conflicts, 0 missing\n",
+    );
+    rdeptree(&[&first, &second])
+        .args(["--merge-strategy", "last"])
+        .assert()
+        .success()
+        .stdout(
+            "six [installed: 1.15.0, shadowed: 1.16.0]\n\
+             1 packages, 1 direct, 1 conflicts, 0 missing\n",
+        );
+    rdeptree(&[&first, &second])
+        .args(["--merge-strategy", "error"])
+        .assert()
+        .failure()
+        .stderr(contains("six is installed twice"));
+}
+
+#[test]
+fn egg_info() {
+    let site = SitePackages::new("egg")
+        .egg_info("legacy", "1.0", "six>=1.0\n\n[test]\npytest>=7\n")
+        .dist_info("six", "1.16.0", &[])
+        .dist_info_file("six", "1.16.0", "INSTALLER", "uv\n");
+
+    rdeptree(&[&site])
+        .args(["--follow-egg-info", "--show-installer", "-p", "legacy"])
+        .args(["--evaluate-markers"])
+        .assert()
+        .success()
+        .stdout(
+            "legacy [installed: 1.0, installer: unknown]\n\
+             ----six [required: >=1.0, installed: 1.16.0, installer: uv]\n\
+             2 packages, 1 direct, 0 conflicts, 0 missing\n",
+        );
+    rdeptree(&[&site])
+        .arg("--no-summary")
+        .assert()
+        .success()
+        .stdout("six [installed: 1.16.0]\n");
+}
+
+#[test]
+fn check_reports_leftover_copies_whatever_the_merge_strategy() {
+    let prefix = SitePackages::new("leftover")
+        .file(
+            "lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA",
+            "Name: six\nVersion: 1.16.0\n",
+        )
+        .file(
+            "lib/python3.10/site-packages/six-1.15.0.dist-info/METADATA",
+            "Name: six\nVersion: 1.15.0\n",
+        );
+    for strategy in ["first", "last", "error"] {
+        cargo_bin_cmd!("rdeptree")
+            .args(["--lang", "en", "--merge-strategy", strategy, "--path"])
+            .arg(prefix.path().join("lib/python3.11/site-packages"))
+            .arg("--path")
+            .arg(prefix.path().join("lib/python3.10/site-packages"))
+            .arg("check")
+            .assert()
+            .failure()
+            .stdout(contains("[duplicate-install] six"));
+    }
+}
+
+#[test]
+fn snapshot_check_runs_size_rules() {
+    let site = chain_site().dist_info_file("six", "1.16.0", "RECORD", "six.py,,5000\n");
+    rdeptree(&[&site])
+        .args(["snapshot", "check", "--max-package-size", "10KB"])
+        .assert()
+        .success();
+    rdeptree(&[&site])
+        .args(["snapshot", "check", "--max-package-size", "1KB"])
+        .assert()
+        .failure()
+        .stdout(contains("[max-package-size] six"));
+}
+
+#[test]
+fn snapshot_fingerprint_matches_fingerprint() {
+    let site = chain_site();
+    let fingerprint = rdeptree(&[&site])
+        .arg("fingerprint")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    assert!(fingerprint.starts_with(b"sha256:"));
+    rdeptree(&[&site])
+        .args(["snapshot", "fingerprint"])
+        .assert()
+        .success()
+        .stdout(fingerprint);
+}
+
+#[cfg(unix)]
+#[test]
+fn check_plugin() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let site = chain_site().file(
+        "plugins/no-six.sh",
+        "#!/bin/sh\n\
+         grep -q '\"name\":\"six\"' && \
+         echo '[{\"rule\": \"no-six\", \"package\": \"six\", \"message\": \"six is banned\"}]'\n",
+    );
+    let plugin = site.path().join("plugins/no-six.sh");
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    rdeptree(&[&site])
+        .arg("check")
+        .arg("--plugin")
+        .arg(&plugin)
+        .assert()
+        .failure()
+        .stdout("[no-six] six is banned\n");
+    rdeptree(&[&site])
+        .args(["check", "--plugin", "/nonexistent/plugin"])
+        .assert()
+        .failure()
+        .stderr(contains("/nonexistent/plugin"));
+}
+
+#[cfg(unix)]
+#[test]
+fn check_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let site = chain_site().file("tool/__init__.py", "").dist_info_file(
+        "tool",
+        "0.3",
+        "RECORD",
+        "tool/__init__.py,,0\n",
+    );
+    rdeptree(&[&site])
+        .args(["check", "--permissions"])
+        .assert()
+        .success();
+
+    let module = site.path().join("tool/__init__.py");
+    std::fs::set_permissions(&module, std::fs::Permissions::from_mode(0o666)).unwrap();
+    rdeptree(&[&site])
+        .args(["check", "--permissions"])
+        .assert()
+        .failure()
+        .stdout(format!(
+            "[world-writable] tool has 1 world-writable paths, e.g. {}\n",
+            module.display()
+        ));
+}
+
+#[test]
+fn check_permissions_of_record_entries_with_parent_dirs() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let site = SitePackages::new("permissions-parent-dirs")
+        .dist_info("tool", "0.3", &[])
+        .file("bin/tool", "")
+        .file("tool/cache/.keep", "");
+    let site_name = site
+        .path()
+        .file_name()
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+    let site = site.dist_info_file(
+        "tool",
+        "0.3",
+        "RECORD",
+        &format!(
+            "../{}/bin/tool,,0\n../../../../../../../../../../tmp/absent,,0\ntool/cache/.keep,,0\n",
+            site_name
+        ),
+    );
+    let cache = site.path().join("tool/cache");
+    std::fs::set_permissions(&cache, std::fs::Permissions::from_mode(0o1777)).unwrap();
+    rdeptree(&[&site])
+        .args(["check", "--permissions"])
+        .assert()
+        .success();
+
+    let script = site.path().join("bin/tool");
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o666)).unwrap();
+    rdeptree(&[&site])
+        .args(["check", "--permissions"])
+        .assert()
+        .failure()
+        .stdout(format!(
+            "[world-writable] tool has 1 world-writable paths, e.g. {}\n",
+            script.display()
+        ));
+}
+
+#[test]
+fn check_source_drift() {
+    let site = chain_site();
+    let gone = site.path().join("gone");
+    let site = site.dist_info_file(
+        "tool",
+        "0.3",
+        "direct_url.json",
+        &format!(
+            r#"{{"url": "file://{}", "dir_info": {{"editable": true}}}}"#,
+            gone.display()
+        ),
+    );
+    rdeptree(&[&site]).arg("check").assert().success();
+    rdeptree(&[&site])
+        .args(["check", "--source-drift"])
+        .assert()
+        .failure()
+        .stdout(format!(
+            "[source-drift] tool was installed from {}, which no longer exists\n",
+            gone.display()
+        ));
+
+    let direct_url = |dir: &std::path::Path| {
+        format!(
+            r#"{{"url": "file://{}", "dir_info": {{"editable": true}}}}"#,
+            dir.display()
+        )
+    };
+    let existing = direct_url(site.path());
+    let site = site
+        .dist_info_file("app", "1.0", "direct_url.json", &existing)
+        .dist_info_file("lib", "2.1", "direct_url.json", &existing);
+    rdeptree(&[&site])
+        .args(["check", "--source-drift"])
+        .env("PATH", "")
+        .assert()
+        .failure()
+        .stdout(format!(
+            "[source-drift] tool was installed from {}, which no longer exists\n",
+            gone.display()
+        ))
+        .stderr(contains("is not checked: can not run git").count(1));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn export_to_sqlite() {
+    let site = chain_site();
+    let db = site.path().join("env.db");
+    for _ in 0..2 {
+        rdeptree(&[&site])
+            .args(["export", "--sqlite"])
+            .arg(&db)
+            .assert()
+            .success();
+    }
+    let connection = rusqlite::Connection::open(&db).unwrap();
+    let mut edges = connection
+        .prepare("SELECT requirer, dependency, specifier FROM rdeptree_edges ORDER BY requirer")
+        .unwrap();
+    let edges: Vec<(String, String, String)> = edges
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        edges,
+        [("app", "lib", ">=2.0"), ("lib", "six", "<2,>=1.5")].map(|(a, b, c)| (
+            a.to_string(),
+            b.to_string(),
+            c.to_string()
+        ))
+    );
+}
+
+#[cfg(not(feature = "sqlite"))]
+#[test]
+fn export_needs_sqlite_feature() {
+    let site = chain_site();
+    rdeptree(&[&site])
+        .args(["export", "--sqlite"])
+        .arg(site.path().join("env.db"))
+        .assert()
+        .failure()
+        .stderr(contains("built with the sqlite feature"));
+}
+
+#[test]
+fn verify_reuses_cached_results() {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use sha2::Digest;
+
+    let digest = URL_SAFE_NO_PAD.encode(sha2::Sha256::digest("x = 1\n"));
+    let site = chain_site().file("six.py", "x = 1\n").dist_info_file(
+        "six",
+        "1.16.0",
+        "RECORD",
+        &format!("six.py,sha256={},6\n", digest),
+    );
+    let six = site.path().join("six.py");
+    let cache = site.path().join("cache");
+    let verify = |refresh: bool| {
+        let mut cmd = rdeptree(&[&site]);
+        cmd.args(["verify", "six", "--cache-dir"]).arg(&cache);
+        if refresh {
+            cmd.arg("--refresh");
+        }
+        cmd.assert()
+    };
+    verify(false).success();
+
+    // same size and mtime: the stored result is reused, --refresh hashes again
+    let modified = std::fs::metadata(&six).unwrap().modified().unwrap();
+    std::fs::write(&six, "x = 2\n").unwrap();
+    let file = std::fs::File::options().write(true).open(&six).unwrap();
+    file.set_modified(modified).unwrap();
+    drop(file);
+    verify(false).success();
+    verify(true).failure().stdout(contains("six.py"));
+
+    // any change to the files misses the cache
+    std::fs::write(&six, "x = 1\n").unwrap();
+    let file = std::fs::File::options().write(true).open(&six).unwrap();
+    file.set_modified(modified + std::time::Duration::from_secs(1))
+        .unwrap();
+    drop(file);
+    verify(false).success();
+    std::fs::remove_file(&six).unwrap();
+    verify(false).failure().stdout(contains("six.py"));
+}
+
+#[cfg(all(unix, feature = "network"))]
+#[test]
+fn self_update_from_release() {
+    use sha2::Digest;
+    use std::env::consts::{ARCH, OS};
+    use std::os::unix::fs::PermissionsExt;
+
+    let build = "#!/bin/sh\n\
+                 [ \"$1\" = --version ] && echo rdeptree 99.0.0 || echo new build\n";
+    let sha256: String = sha2::Sha256::digest(build)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let dir = SitePackages::new("self-update")
+        .file("new-build", build)
+        .file(
+            "new-build.sha256",
+            &format!("{sha256}  rdeptree-{ARCH}-{OS}\n"),
+        )
+        .file("bad.sha256", &format!("{}\n", "0".repeat(64)));
+    let release = |tag: &str, checksum: &str| {
+        let url = |file: &str| format!("file://{}", dir.path().join(file).display());
+        format!(
+            r#"{{"tag_name": "{tag}", "assets": [
+                {{"name": "rdeptree-{ARCH}-{OS}.tar.gz", "browser_download_url": "{}"}},
+                {{"name": "rdeptree-{ARCH}-{OS}.sha256", "browser_download_url": "{}"}},
+                {{"name": "rdeptree-{ARCH}-{OS}", "browser_download_url": "{}"}}]}}"#,
+            url("missing.tar.gz"),
+            url(checksum),
+            url("new-build"),
+        )
+    };
+    let latest = release("v99.0.0", "new-build.sha256");
+    let tampered = release("v99.0.0", "bad.sha256");
+    let current = release(env!("CARGO_PKG_VERSION"), "new-build.sha256");
+    let dir = dir
+        .file("latest.json", &latest)
+        .file("tampered.json", &tampered)
+        .file("current.json", &current);
+    let url = |file: &str| format!("file://{}", dir.path().join(file).display());
+
+    // the binary under test is copied, as it gets replaced
+    std::fs::create_dir(dir.path().join("bin")).unwrap();
+    let exe = dir.path().join("bin/rdeptree");
+    std::fs::copy(assert_cmd::cargo::cargo_bin!("rdeptree"), &exe).unwrap();
+    std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::new(&exe);
+        cmd.args(["--lang", "en"])
+            .args(args)
+            .env("CARGO_HOME", dir.path().join("cargo"));
+        cmd
+    };
+
+    run(&[
+        "self",
+        "--release-url",
+        &url("current.json"),
+        "check-update",
+    ])
+    .assert()
+    .success()
+    .stdout(format!(
+        "rdeptree {} is up to date\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    run(&["self", "--release-url", &url("latest.json"), "check-update"])
+        .assert()
+        .success()
+        .stdout(contains("rdeptree 99.0.0 is available"));
+    run(&[
+        "self",
+        "--release-url",
+        &url("missing.json"),
+        "check-update",
+    ])
+    .assert()
+    .failure()
+    .stderr(contains("Can not check for updates"));
+
+    run(&["self", "--release-url", &url("tampered.json"), "update"])
+        .assert()
+        .failure()
+        .stderr(contains("was published"));
+    run(&["self", "--release-url", &url("latest.json"), "update"])
+        .env("CARGO_HOME", dir.path())
+        .assert()
+        .failure()
+        .stderr(contains("was installed with cargo"));
+    Command::new(&exe)
+        .arg("--version")
+        .assert()
+        .success()
+        .stdout(format!("rdeptree {}\n", env!("CARGO_PKG_VERSION")));
+
+    run(&["self", "--release-url", &url("latest.json"), "update"])
+        .assert()
+        .success()
+        .stdout("rdeptree is updated to 99.0.0\n");
+    Command::new(&exe).assert().success().stdout("new build\n");
+}
+
+#[test]
+fn venv_without_python() {
+    let venv = SitePackages::new("venv").file(
+        "lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA",
+        "Metadata-Version: 2.1\nName: six\nVersion: 1.16.0\n",
+    );
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "--evaluate-markers"])
+        .env("VIRTUAL_ENV", venv.path())
+        .env("PATH", "")
+        .assert()
+        .success()
+        .stdout("six [installed: 1.16.0]\n1 packages, 1 direct, 0 conflicts, 0 missing\n")
+        .stderr(contains("site-packages are taken from VIRTUAL_ENV"))
+        .stderr(contains("evaluated for extras only"));
+}
+
+#[cfg(unix)]
+#[test]
+fn python_from_env_var() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let chosen = SitePackages::new("chosen-venv")
+        .file("pyvenv.cfg", "home = /usr/bin\n")
+        .file("bin/python3", "#!/bin/sh\nexit 1\n")
+        .file(
+            "lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA",
+            "Metadata-Version: 2.1\nName: six\nVersion: 1.16.0\n",
+        );
+    let active = SitePackages::new("active-venv").file(
+        "lib/python3.11/site-packages/app-1.0.dist-info/METADATA",
+        "Metadata-Version: 2.1\nName: app\nVersion: 1.0\n",
+    );
+    let python = chosen.path().join("bin/python3");
+    std::fs::set_permissions(&python, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "--no-summary"])
+        .env("RDEPTREE_PYTHON", &python)
+        .env("RDEPTREE_QUIET", "1")
+        .env("VIRTUAL_ENV", active.path())
+        .env("PATH", "")
+        .assert()
+        .success()
+        .stdout("six [installed: 1.16.0]\n")
+        .stderr("");
+}
+
+#[test]
+fn project_venv() {
+    let project = SitePackages::new("project")
+        .file(".git/HEAD", "ref: refs/heads/main\n")
+        .file("src/app/main.py", "")
+        .file(".venv/pyvenv.cfg", "home = /usr/bin\n")
+        .file(
+            ".venv/lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA",
+            "Metadata-Version: 2.1\nName: six\nVersion: 1.16.0\n",
+        );
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "--no-summary"])
+        .current_dir(project.path().join("src/app"))
+        .env_remove("VIRTUAL_ENV")
+        .env("PATH", "")
+        .assert()
+        .success()
+        .stdout("six [installed: 1.16.0]\n")
+        .stderr(contains("using project virtual env"));
+}
+
+#[test]
+fn quiet_mode() {
+    let venv = SitePackages::new("quiet").file(
+        "lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA",
+        "Metadata-Version: 2.1\nName: six\nVersion: 1.16.0\n",
+    );
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args([
+        "--lang",
+        "en",
+        "--machine",
+        "--no-summary",
+        "--fuzzy",
+        "-p",
+        "sx",
+    ])
+    .env("VIRTUAL_ENV", venv.path())
+    .env("PATH", "")
+    .assert()
+    .success()
+    .stdout("six [installed: 1.16.0]\n")
+    .stderr("");
+
+    rdeptree(&[&venv])
+        .args(["-q", "why", "sx"])
+        .assert()
+        .failure()
+        .stderr(contains("ERROR"));
+}
+
+#[test]
+fn json_log_format() {
+    let site = chain_site();
+    let output = rdeptree(&[&site])
+        .args(["--log-format", "json", "--fuzzy", "-p", "sx"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let event: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(event["level"], "info");
+    assert!(event["message"].as_str().unwrap().contains("six"));
+
+    let output = rdeptree(&[&site])
+        .args(["--log-format", "json", "why", "sx"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let event: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(event["level"], "error");
+}
+
+#[test]
+fn requirements_drift() {
+    let site = chain_site().file(
+        "requirements/requirements.txt",
+        "# pinned\napp==0.9  # main app\nlib>=2\ngone==1.0\n",
+    );
+    let requirements = site.path().join("requirements/requirements.txt");
+
+    rdeptree(&[&site])
+        .args(["check", "--suggest", "-r"])
+        .arg(&requirements)
+        .assert()
+        .failure()
+        .stdout(contains(format!(
+            "[requirements-drift] app is pinned to 0.9, installed 1.0 ({}:2)\n",
+            requirements.display()
+        )))
+        .stdout(contains("requirements.txt:2: +app==1.0  # main app\n"))
+        .stdout(contains("requirements.txt:4: -gone==1.0\n"))
+        .stdout(contains("requirements.txt: +tool==0.3\n"));
+
+    rdeptree(&[&site])
+        .args(["check", "--write", "-r"])
+        .arg(&requirements)
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read_to_string(&requirements).unwrap(),
+        "# pinned\napp==1.0  # main app\nlib>=2\ntool==0.3\n"
+    );
+    rdeptree(&[&site])
+        .args(["check", "-r"])
+        .arg(&requirements)
+        .assert()
+        .success();
+
+    // pins of included files are reported where they are
+    let site = site
+        .file(
+            "requirements/ci.txt",
+            "-r requirements.txt\n-c constraints.txt\n",
+        )
+        .file("requirements/constraints.txt", "six==1.15.0\n");
+    let constraints = site.path().join("requirements/constraints.txt");
+    rdeptree(&[&site])
+        .args(["check", "-r"])
+        .arg(site.path().join("requirements/ci.txt"))
+        .assert()
+        .failure()
+        .stdout(format!(
+            "[requirements-drift] six is pinned to 1.15.0, installed 1.16.0 ({}:1)\n",
+            constraints.display()
+        ));
+}
+
+#[test]
+fn extras() {
+    let site = chain_site().file(
+        "web-1.0.dist-info/METADATA",
+        "Metadata-Version: 2.1\n\
+         Name: web\n\
+         Version: 1.0\n\
+         Provides-Extra: socks\n\
+         Provides-Extra: all\n\
+         Requires-Dist: lib>=2.0\n\
+         Requires-Dist: pysocks>=1.5; extra == \"socks\"\n\
+         Requires-Dist: pysocks>=1.5; extra == \"all\"\n\
+         Requires-Dist: six>=1.0; extra == \"all\"\n",
+    );
+
+    rdeptree(&[&site])
+        .args(["extras", "web"])
+        .assert()
+        .success()
+        .stdout(
+            "# web[all] pulls in 1 additional packages\n\
+             ----pysocks [required: >=1.5, not installed]\n\
+             ----six [required: >=1.0, installed: 1.16.0]\n\
+             # web[socks] pulls in 1 additional packages\n\
+             ----pysocks [required: >=1.5, not installed]\n",
+        );
+    rdeptree(&[&site])
+        .args(["extras", "tool"])
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(contains("tool declares no extras"));
+}
+
+#[test]
+fn diff_package() {
+    let old = chain_site();
+    let new = SitePackages::new("chain-upgraded")
+        .dist_info("app", "2.0", &["lib>=2.1", "idna>=3"])
+        .dist_info("lib", "2.1", &["six<2,>=1.5"]);
+
+    rdeptree(&[&old, &new])
+        .args(["diff-package", "App"])
+        .assert()
+        .success()
+        .stdout(
+            "app 1.0 -> 2.0\n\
+             requirement  before  after  status\n\
+             idna         -       >=3    added\n\
+             lib          >=2.0   >=2.1  changed\n",
+        );
+    rdeptree(&[&old, &new])
+        .args(["diff-package", "lib"])
+        .assert()
+        .success()
+        .stdout("lib 2.1 -> 2.1\nNo changes\n");
+    rdeptree(&[&old])
+        .args(["diff-package", "app"])
+        .assert()
+        .failure()
+        .stderr(contains("exactly two environments, 1 --path given"));
+}
+
+#[test]
+fn roots() {
+    let site = chain_site()
+        .dist_info("meta-a", "1.0", &["meta-b>=1"])
+        .dist_info("meta-b", "1.0", &["meta-a>=1"])
+        .dist_info_file("lib", "2.1", "REQUESTED", "")
+        .dist_info_file("meta-a", "1.0", "REQUESTED", "");
+
+    rdeptree(&[&site])
+        .args(["--roots", "requested", "--no-summary"])
+        .assert()
+        .success()
+        .stdout(
+            "lib [installed: 2.1]\n\
+             ----six [required: <2,>=1.5, installed: 1.16.0]\n\
+             meta-a [installed: 1.0]\n\
+             ----meta-b [required: >=1, installed: 1.0]\n\
+             --------meta-a [required: >=1, installed: 1.0] (cycle)\n",
+        );
+    rdeptree(&[&site])
+        .args(["--roots", "explicit=Tool,meta_b"])
+        .assert()
+        .success()
+        .stdout(
+            "tool [installed: 0.3]\n\
+             meta-b [installed: 1.0]\n\
+             ----meta-a [required: >=1, installed: 1.0]\n\
+             --------meta-b [required: >=1, installed: 1.0] (cycle)\n\
+             3 packages, 2 direct, 0 conflicts, 0 missing\n",
+        );
+    rdeptree(&[&site])
+        .args(["--roots", "top"])
+        .assert()
+        .failure()
+        .stderr(contains("unknown root strategy 'top'"));
+}
+
+#[test]
+fn list() {
+    let site = chain_site();
+    rdeptree(&[&site]).arg("list").assert().success().stdout(
+        "app   1.0\n\
+         lib   2.1\n\
+         six   1.16.0\n\
+         tool  0.3\n",
+    );
+}
+
+#[test]
+fn list_where_version() {
+    let site = chain_site();
+    rdeptree(&[&site])
+        .args(["list", "--where", "version<1"])
+        .assert()
+        .success()
+        .stdout("tool  0.3\n");
+    rdeptree(&[&site])
+        .args(["list", "--where", "version>=1,<2.1"])
+        .assert()
+        .success()
+        .stdout("app  1.0\nsix  1.16.0\n");
+    rdeptree(&[&site])
+        .args(["list", "--where", "version=>1"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn requested_packages() {
+    let site = chain_site()
+        .dist_info_file("app", "1.0", "REQUESTED", "")
+        .dist_info_file("lib", "2.1", "REQUESTED", "");
+
+    rdeptree(&[&site])
+        .args(["--show-requested", "--no-summary", "-p", "app"])
+        .assert()
+        .success()
+        .stdout(
+            "app [installed: 1.0] [direct]\n\
+             ----lib [required: >=2.0, installed: 2.1] [direct]\n\
+             --------six [required: <2,>=1.5, installed: 1.16.0]\n",
+        );
+    rdeptree(&[&site])
+        .arg("orphans")
+        .assert()
+        .success()
+        .stdout("tool  0.3\n");
+    rdeptree(&[&chain_site()])
+        .arg("orphans")
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(contains("REQUESTED"));
+}
+
+#[test]
+fn requires_python() {
+    let site = SitePackages::new("requires-python")
+        .file(
+            "old-1.0.dist-info/METADATA",
+            "Metadata-Version: 2.1\nName: old\nVersion: 1.0\nRequires-Python: >=3.7, <3.13\n",
+        )
+        .dist_info("six", "1.16.0", &[]);
+
+    rdeptree(&[&site])
+        .args(["--show-requires-python", "--no-summary"])
+        .assert()
+        .success()
+        .stdout(
+            "old [installed: 1.0, python: >=3.7, <3.13]\n\
+             six [installed: 1.16.0, python: any]\n",
+        );
+    rdeptree(&[&site])
+        .args(["check", "--python-upgrade", "3.13"])
+        .assert()
+        .failure()
+        .stdout("[requires-python] old 1.0 requires python >=3.7, <3.13, which excludes 3.13\n");
+    rdeptree(&[&site])
+        .args(["check", "--python-upgrade"])
+        .assert()
+        .success()
+        .stderr(contains("target python version is unknown"));
+}
+
+#[test]
+fn snapshot_diff_dot() {
+    let old = SitePackages::new("diff-old")
+        .dist_info("app", "1.0", &["six>=1.0"])
+        .dist_info("six", "1.15.0", &[])
+        .dist_info("gone", "0.1", &[]);
+    let new = SitePackages::new("diff-new")
+        .dist_info("app", "1.0", &["six>=1.16", "idna>=3"])
+        .dist_info("six", "1.16.0", &[])
+        .dist_info("idna", "3.6", &[]);
+    let (old_file, new_file) = (old.path().join("old.json"), new.path().join("new.json"));
+    rdeptree(&[&old])
+        .args(["snapshot", "save"])
+        .arg(&old_file)
+        .assert()
+        .success();
+    rdeptree(&[&new])
+        .args(["snapshot", "save"])
+        .arg(&new_file)
+        .assert()
+        .success();
+
+    rdeptree(&[])
+        .args(["snapshot", "diff", "--output", "dot"])
+        .arg(&old_file)
+        .arg(&new_file)
+        .assert()
+        .success()
+        .stdout(
+            "digraph {\n    \"app\" [label=\"app\\n1.0\"]\n    \
+             \"gone\" [label=\"gone\\n0.1\", style=filled, fillcolor=red]\n    \
+             \"idna\" [label=\"idna\\n3.6\", style=filled, fillcolor=green]\n    \
+             \"six\" [label=\"six\\n1.15.0 -> 1.16.0\", style=filled, fillcolor=yellow]\n    \
+             \"app\" -> \"idna\" [label=\">=3\", color=green]\n    \
+             \"app\" -> \"six\" [label=\">=1.0 -> >=1.16\", color=yellow]\n\
+             }\n",
+        );
+    rdeptree(&[])
+        .args(["snapshot", "diff"])
+        .arg(&old_file)
+        .arg(&new_file)
+        .assert()
+        .success()
+        .stdout(
+            "package  before  after   status\n\
+             idna     -       3.6     added\n\
+             gone     0.1     -       removed\n\
+             six      1.15.0  1.16.0  changed\n\
+             app                      requirements changed\n",
+        );
+}
+
+#[test]
+fn ignore_file() {
+    let site = chain_site()
+        .dist_info("internal-shim", "0.1", &[])
+        .dist_info("internal-api", "0.2", &[])
+        .file(".git/HEAD", "ref: refs/heads/main\n")
+        .file(".rdeptreeignore", "# vendored\ninternal-*\n!internal-api\n");
+    let list = |args: &[&str]| {
+        let mut cmd = rdeptree(&[&site]);
+        cmd.current_dir(site.path()).arg("list").args(args);
+        cmd
+    };
+
+    list(&[]).assert().success().stdout(
+        "app           1.0\n\
+         internal-api  0.2\n\
+         lib           2.1\n\
+         six           1.16.0\n\
+         tool          0.3\n",
+    );
+    list(&["--exclude", "app", "--keep", "internal-shim"])
+        .assert()
+        .success()
+        .stdout("internal-api   0.2\ninternal-shim  0.1\ntool           0.3\n");
+    list(&["--no-ignore-file", "--exclude", "*"])
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn system_site_packages() {
+    let root = SitePackages::new("system-site")
+        .file(
+            "base/lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA",
+            "Metadata-Version: 2.1\nName: six\nVersion: 1.16.0\n",
+        )
+        .file(
+            "venv/lib/python3.11/site-packages/app-1.0.dist-info/METADATA",
+            "Metadata-Version: 2.1\nName: app\nVersion: 1.0\nRequires-Dist: six>=1.0\n",
+        );
+    let venv = root.path().join("venv");
+    std::fs::write(
+        venv.join("pyvenv.cfg"),
+        format!(
+            "home = {}\ninclude-system-site-packages = true\n",
+            root.path().join("base/bin").display()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "--no-summary"])
+        .env("VIRTUAL_ENV", &venv)
+        .env("PATH", "")
+        .assert()
+        .success()
+        .stdout(
+            "app [installed: 1.0]\n\
+             ----six [required: >=1.0, installed: 1.16.0, inherited]\n",
+        );
+}
+
+#[test]
+fn venv_copies_win_over_inherited_ones() {
+    let root = SitePackages::new("system-site-merge")
+        .file(
+            "base/lib/python3.11/site-packages/six-1.16.0.dist-info/METADATA",
+            "Metadata-Version: 2.1\nName: six\nVersion: 1.16.0\n",
+        )
+        .file(
+            "venv/lib/python3.11/site-packages/six-1.17.0.dist-info/METADATA",
+            "Metadata-Version: 2.1\nName: six\nVersion: 1.17.0\n",
+        );
+    let venv = root.path().join("venv");
+    std::fs::write(
+        venv.join("pyvenv.cfg"),
+        format!(
+            "home = {}\ninclude-system-site-packages = true\n",
+            root.path().join("base/bin").display()
+        ),
+    )
+    .unwrap();
+
+    for strategy in ["first", "last", "error"] {
+        let mut cmd = cargo_bin_cmd!("rdeptree");
+        cmd.args(["--lang", "en", "--no-summary", "--merge-strategy", strategy])
+            .env("VIRTUAL_ENV", &venv)
+            .env("PATH", "")
+            .assert()
+            .success()
+            .stdout("six [installed: 1.17.0, shadowed: 1.16.0]\n");
+
+        let mut cmd = cargo_bin_cmd!("rdeptree");
+        cmd.args(["--lang", "en", "--merge-strategy", strategy, "check"])
+            .env("VIRTUAL_ENV", &venv)
+            .env("PATH", "")
+            .assert()
+            .success();
+    }
+}
+
+#[test]
+fn verbose_reports_metadata_lines() {
+    let site = SitePackages::new("verbose")
+        .dist_info("app", "1.0", &["lib>=2.0", "==1.0"])
+        .dist_info("lib", "2.1", &[]);
+    rdeptree(&[&site])
+        .args(["--verbose", "info", "app"])
+        .assert()
+        .success()
+        .stderr(contains(
+            "METADATA:5: line is not understood: Requires-Dist: ==1.0",
+        ))
+        .stderr(contains("Version of app is read from "))
+        .stderr(contains("Requires-Dist lib of app is read from "))
+        .stderr(contains("METADATA:4\n"));
+
+    rdeptree(&[&site])
+        .args(["info", "app"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+#[test]
+fn dump_meta() {
+    let site = SitePackages::new("dump-meta").dist_info_file(
+        "App",
+        "1.0",
+        "METADATA",
+        "Name: App\n\
+         Version: 1.0\n\
+         Provides-Extra: full\n\
+         Requires-Dist: lib>=2.0; extra == \"full\"\n\
+         Requires-Dist: ==1.0\n",
+    );
+    let output = rdeptree(&[&site])
+        .args(["dump-meta", "app"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains(
+        "METADATA:1\n\
+         field: name\n\
+         value: App\n\
+         normalized name: app\n"
+    ));
+    assert!(output.contains(
+        "METADATA:4\n\
+         field: requires-dist\n\
+         name: lib\n\
+         normalized name: lib\n\
+         specifier: >=2.0\n\
+         canonical specifier: >=2.0\n\
+         marker: extra == \"full\"\n\
+         extras: full\n"
+    ));
+    assert!(output.contains("METADATA:5\nnot recognized: Requires-Dist: ==1.0\n"));
+    assert!(!output.contains("Provides-Extra"));
+}
+
+#[test]
+fn scan_all() {
+    let site = "lib/python3.11/site-packages";
+    let root = SitePackages::new("scan-all")
+        .file("svc-a/pyvenv.cfg", "")
+        .file(
+            &format!("svc-a/{site}/six-1.16.0.dist-info/METADATA"),
+            "Name: six\nVersion: 1.16.0\n",
+        )
+        .file("svc-b/.venv/pyvenv.cfg", "")
+        .file(
+            &format!("svc-b/.venv/{site}/app-1.0.dist-info/METADATA"),
+            "Name: app\nVersion: 1.0\nRequires-Dist: lib>=2.0\n",
+        );
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "scan-all"])
+        .arg(root.path())
+        .assert()
+        .code(1)
+        .stdout(
+            "env          packages  conflicts  missing  violations\n\
+             svc-a               1          0        0           0\n\
+             svc-b/.venv         1          0        1           0\n\
+             2 envs, 1 with problems\n",
+        );
+
+    let empty = SitePackages::new("scan-all-empty");
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "scan-all"])
+        .arg(empty.path())
+        .assert()
+        .failure()
+        .stderr(contains("No virtual envs found"));
+}
+
+#[test]
+fn vendor_list() {
+    let site = chain_site().dist_info("web", "0.5", &["six>=1", "lib>=2", "app>=1"]);
+    rdeptree(&[&site])
+        .args(["vendor-list", "web"])
+        .assert()
+        .success()
+        .stdout("six==1.16.0\nlib==2.1\napp==1.0\nweb==0.5\n");
+
+    let site = site.dist_info("broken", "1.0", &["lib>=2", "gone>=1"]);
+    rdeptree(&[&site])
+        .args(["vendor-list", "broken"])
+        .assert()
+        .failure()
+        .stdout("six==1.16.0\nlib==2.1\nbroken==1.0\n")
+        .stderr(contains(
+            "gone is required in the closure of broken but not installed",
+        ));
+}
+
+#[test]
+fn constraints() {
+    let site = chain_site()
+        .dist_info("other", "0.1", &["six>=1.16", "lib<3"])
+        .dist_info("old", "0.2", &["six<1.10"]);
+    rdeptree(&[&site])
+        .args(["constraints", "six"])
+        .assert()
+        .success()
+        .stdout(
+            "six [installed: 1.16.0]\n\
+             <1.10     old\n\
+             <2,>=1.5  lib\n\
+             >=1.16    other\n\
+             intersection: <1.10,<2,>=1.16,>=1.5\n\
+             installed 1.16.0 is outside the intersection\n",
+        );
+
+    rdeptree(&[&site])
+        .args(["constraints", "lib"])
+        .assert()
+        .success()
+        .stdout(
+            "lib [installed: 2.1]\n\
+             <3     other\n\
+             >=2.0  app\n\
+             intersection: <3,>=2.0\n\
+             installed 2.1 is inside the intersection\n",
+        );
+
+    rdeptree(&[&site])
+        .args(["constraints", "tool"])
+        .assert()
+        .success()
+        .stdout("tool [installed: 0.3]\ntool is not required by any installed package\n");
+}
+
+#[test]
+fn requirements_excluded_by_marker() {
+    let site = chain_site().dist_info(
+        "gpu-app",
+        "1.0",
+        &["six>=1.0", "cuda>=12; extra == \"gpu\""],
+    );
+    rdeptree(&[&site])
+        .args(["--evaluate-markers", "info", "gpu-app"])
+        .assert()
+        .success()
+        .stdout(contains("Excluded-By-Marker: cuda (extra == \"gpu\")\n"))
+        .stderr(contains("is left out").not());
+    rdeptree(&[&site])
+        .args(["--verbose", "--evaluate-markers", "info", "gpu-app"])
+        .assert()
+        .success()
+        .stderr(contains(
+            "cuda required by gpu-app is left out, marker is false in this environment: extra == \"gpu\"",
+        ));
+}
+
+#[test]
+fn edge_kinds() {
+    let site = chain_site().file(
+        "web-1.0.dist-info/METADATA",
+        "Name: web\n\
+         Version: 1.0\n\
+         Provides-Extra: cli\n\
+         Requires-Dist: app>=1.0\n\
+         Requires-Dist: tool>=0.1; extra == \"cli\"\n\
+         Requires-Dist: six>=1.0; sys_platform == \"win32\"\n",
+    );
+
+    rdeptree(&[&site])
+        .args(["--edge-kinds", "runtime"])
+        .assert()
+        .success()
+        .stdout(
+            "web [installed: 1.0]\n\
+             ----app [required: >=1.0, installed: 1.0]\n\
+             --------lib [required: >=2.0, installed: 2.1]\n\
+             ------------six [required: <2,>=1.5, installed: 1.16.0]\n\
+             4 packages, 1 direct, 0 conflicts, 0 missing\n",
+        );
+    rdeptree(&[&site])
+        .args(["--edge-kinds", "runtime,extra:cli", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(contains("\"required_version\": \">=0.1\""))
+        // the conditional web -> six edge is dropped, web -> app is kept
+        .stdout(contains("\"required_version\": \">=1.0\"").count(1));
+    rdeptree(&[&site])
+        .args(["--edge-kinds", "optional"])
+        .assert()
+        .failure()
+        .stderr(contains("unknown edge kind 'optional'"));
+}
+
+#[test]
+fn group_by_prefix() {
+    let site = SitePackages::new("prefix")
+        .dist_info("aws-lambda", "1.0", &["aws-core>=1.0"])
+        .dist_info("aws-core", "1.2", &[])
+        .dist_info("aws-s3", "2.0", &[])
+        .dist_info("azure-blob", "12.0", &[])
+        .dist_info("six", "1.16.0", &[]);
+
+    rdeptree(&[&site])
+        .args(["--group-by", "prefix", "--no-summary"])
+        .assert()
+        .success()
+        .stdout(
+            "# aws-*\n\
+             aws-lambda [installed: 1.0]\n\
+             ----aws-core [required: >=1.0, installed: 1.2]\n\
+             aws-s3 [installed: 2.0]\n\
+             azure-blob [installed: 12.0]\n\
+             six [installed: 1.16.0]\n",
+        );
+    rdeptree(&[&site])
+        .args(["--group-by", "prefix", "--format", "markdown"])
+        .assert()
+        .success()
+        .stdout(contains(
+            "<summary>aws-* (2)</summary>\n\
+             \n<details>\n\
+             <summary>aws-lambda 1.0</summary>\n",
+        ))
+        .stdout(contains("<summary>azure-* (").count(0));
+}
+
+#[test]
+fn declared_names_shown() {
+    let site = SitePackages::new("declared-names")
+        .dist_info("Pillow", "10.0.0", &[])
+        .dist_info("thumbnails", "1.0", &["pillow>=9", "PyYAML>=6"])
+        .dist_info("PyYAML", "6.0.1", &[]);
+
+    rdeptree(&[&site]).assert().success().stdout(
+        "thumbnails [installed: 1.0]\n\
+         ----Pillow [required: >=9, installed: 10.0.0]\n\
+         ----PyYAML [required: >=6, installed: 6.0.1]\n\
+         3 packages, 1 direct, 0 conflicts, 0 missing\n",
+    );
+    // columns are aligned by the names shown, not the normalized ones
+    let site = site.dist_info("Zope__Interface", "6.0", &[]);
+    rdeptree(&[&site]).arg("list").assert().success().stdout(
+        "Pillow           10.0.0\n\
+         PyYAML           6.0.1\n\
+         thumbnails       1.0\n\
+         Zope__Interface  6.0\n",
+    );
+    rdeptree(&[&site])
+        .args(["info", "pyyaml"])
+        .assert()
+        .success()
+        .stdout(contains("Name: PyYAML\n"));
+    rdeptree(&[&site])
+        .args(["--format", "json"])
+        .assert()
+        .success()
+        .stdout(contains("\"name\": \"pyyaml\""));
+}
+
+#[test]
+fn pipdeptree_format() {
+    let site = chain_site().dist_info("web", "1.0", &["six>=2.0"]);
+
+    rdeptree(&[&site])
+        .args(["--format", "pipdeptree", "-p", "app"])
+        .assert()
+        .success()
+        .stdout(
+            "app==1.0\n\
+             \x20 - lib [required: >=2.0, installed: 2.1]\n\
+             \x20   - six [required: <2,>=1.5, installed: 1.16.0]\n",
+        )
+        .stderr(
+            "Warning!!! Possibly conflicting dependencies found:\n\
+             * web==1.0\n\
+             \x20- six [required: >=2.0, installed: 1.16.0]\n\
+             ------------------------------------------------------------------------\n",
+        );
+
+    let site = SitePackages::new("pipdeptree-cycle")
+        .dist_info("a", "1.0", &["b>=1.0"])
+        .dist_info("b", "1.0", &["a>=1.0"]);
+    rdeptree(&[&site])
+        .args(["--format", "pipdeptree"])
+        .assert()
+        .success()
+        .stderr(
+            "Warning!!! Cyclic dependencies found:\n\
+             * b => a => b\n\
+             * a => b => a\n\
+             ------------------------------------------------------------------------\n",
+        );
+    rdeptree(&[&site])
+        .args(["--quiet", "--format", "pipdeptree"])
+        .assert()
+        .success()
+        .stderr("");
+}
+
+#[test]
+fn timeout_prints_what_was_scanned() {
+    let site = chain_site();
+    rdeptree(&[&site])
+        .args(["--timeout", "0"])
+        .assert()
+        .code(124)
+        .stdout("0 packages, 0 direct, 0 conflicts, 0 missing\n")
+        .stderr(contains(
+            "Scan stopped, time limit of 0s exceeded after parsing 0 of 4",
+        ))
+        .stderr(contains(
+            "Time limit of 0s exceeded, the results are incomplete",
+        ));
+}
+
+#[cfg(unix)]
+#[test]
+fn timeout_kills_hung_plugin() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, Instant};
+
+    let site = chain_site().file("plugins/hang.sh", "#!/bin/sh\nexec sleep 30\n");
+    let plugin = site.path().join("plugins/hang.sh");
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let started = Instant::now();
+    rdeptree(&[&site])
+        .args(["check", "--timeout", "1", "--plugin"])
+        .arg(&plugin)
+        .assert()
+        .code(124)
+        .stderr(contains("did not finish in"))
+        .stderr(contains(
+            "Time limit of 1s exceeded, the results are incomplete",
+        ));
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn badge_summarizes_env() {
+    let release = r#"{"upload_time_iso_8601": "2024-01-01T00:00:00Z"}"#;
+    let releases = |versions: &[&str]| {
+        let versions: Vec<String> = versions
+            .iter()
+            .map(|v| format!(r#""{v}": [{release}]"#))
+            .collect();
+        format!(r#"{{"releases": {{{}}}}}"#, versions.join(", "))
+    };
+    // installed app 1.0 is newer than the index one, tool 0.3 is older than 0.10
+    let site = chain_site()
+        .dist_info("web", "1.0", &["flask>=2"])
+        .file("index/six/json", &releases(&["1.16.0", "1.17.0"]))
+        .file("index/app/json", &releases(&["0.9"]))
+        .file("index/lib/json", &releases(&["2.1"]))
+        .file("index/tool/json", &releases(&["0.3", "0.10"]))
+        .file("index/web/json", &releases(&["1.0"]));
+    let index_url = format!("file://{}", site.path().join("index").display());
+    let badge = site.path().join("badge.svg");
+    let title = |site: &SitePackages| {
+        rdeptree(&[site])
+            .args(["badge", "--out"])
+            .arg(&badge)
+            .env("RDEPTREE_INDEX_URL", &index_url)
+            .assert()
+            .success()
+            .stdout("");
+        let svg = std::fs::read_to_string(&badge).unwrap();
+        svg.lines()
+            .find(|line| line.starts_with("<title>"))
+            .unwrap()
+            .to_string()
+    };
+
+    if cfg!(feature = "network") {
+        assert_eq!(title(&site), "<title>deps: 2 outdated / 1 missing</title>");
+        // releases of extra are not known
+        let site = chain_site().dist_info("extra", "1.0", &[]);
+        assert_eq!(title(&site), "<title>deps: unknown</title>");
+    } else {
+        assert_eq!(title(&site), "<title>deps: 1 missing</title>");
+    }
+}
+
+#[test]
+fn lookup_server_answers_stdin_requests() {
+    let site = chain_site().dist_info_file("six", "1.16.0", "top_level.txt", "six\n");
+
+    rdeptree(&[&site])
+        .args(["lookup", "--stdin-jsonrpc"])
+        .write_stdin(
+            "{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"resolveImport\", \"params\": {\"module\": \"six.moves\"}}\n\
+             \n\
+             {\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"getVersion\", \"params\": {\"package\": \"LIB\"}}\n",
+        )
+        .assert()
+        .success()
+        .stdout(
+            "{\"id\":1,\"jsonrpc\":\"2.0\",\"result\":{\"distributions\":[{\"name\":\"six\",\"version\":\"1.16.0\"}],\"module\":\"six\"}}\n\
+             {\"id\":2,\"jsonrpc\":\"2.0\",\"result\":{\"name\":\"lib\",\"version\":\"2.1\"}}\n",
+        );
+    rdeptree(&[&site]).arg("lookup").assert().failure();
+}
+
+#[test]
+fn offline_refuses_network_commands() {
+    let site = chain_site();
+
+    #[cfg(feature = "network")]
+    rdeptree(&[&site])
+        .args(["--offline", "self", "check-update"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(contains(
+            "self needs the network, which is disabled by --offline",
+        ));
+
+    let badge = site.path().join("badge.svg");
+    rdeptree(&[&site])
+        .args(["badge", "--out"])
+        .arg(&badge)
+        .env("RDEPTREE_OFFLINE", "true")
+        .env("RDEPTREE_INDEX_URL", "http://127.0.0.1:9")
+        .assert()
+        .success()
+        .stderr("");
+    assert!(std::fs::read_to_string(&badge)
+        .unwrap()
+        .contains("<title>deps: ok</title>"));
+}
+
+#[test]
+fn info_shows_release_notes_links() {
+    let site = SitePackages::new("project-urls").dist_info_file(
+        "lib",
+        "2.1",
+        "METADATA",
+        "Metadata-Version: 2.1\n\
+         Name: lib\n\
+         Version: 2.1\n\
+         Project-URL: Documentation, https://lib.readthedocs.io\n\
+         Project-URL: Changelog, https://github.com/lib/lib/releases\n\
+         Project-URL: Source Code, https://github.com/lib/lib\n",
+    );
+
+    rdeptree(&[&site])
+        .args(["info", "lib"])
+        .assert()
+        .success()
+        .stdout(contains(
+            "Changelog: https://github.com/lib/lib/releases\n\
+             Repository: https://github.com/lib/lib\n\
+             Documentation: https://lib.readthedocs.io\n",
+        ));
+}
+
+#[test]
+fn check_reports_missing_extras() {
+    let site = chain_site().dist_info("web", "1.0", &["lib[http2]>=2.0"]);
+
+    rdeptree(&[&site])
+        .arg("check")
+        .assert()
+        .code(1)
+        .stdout(contains(
+            "web requires lib[http2], but the installed lib 2.1 does not provide extra http2, \
+             its version is constrained by app (>=2.0)",
+        ));
+
+    // extras asked for by requirements of extras which are not requested are not needed
+    let site = chain_site().dist_info("web", "1.0", &["lib[http2]>=2.0; extra == \"dev\""]);
+    rdeptree(&[&site]).arg("check").assert().success();
+}
+
+#[test]
+fn events_written_to_side_channel() {
+    let site = chain_site().dist_info("web", "1.0", &["gone>=1"]);
+    let events_file = site.path().join("events.jsonl");
+
+    rdeptree(&[&site])
+        .args(["--quiet", "--roots", "requested", "check", "--events-file"])
+        .arg(&events_file)
+        .assert()
+        .success()
+        .stderr("");
+    let events: Vec<serde_json::Value> = std::fs::read_to_string(&events_file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let names: Vec<&str> = events
+        .iter()
+        .map(|event| event["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(names[0], "scan_started");
+    assert_eq!(events[0]["meta_dirs"], 5);
+    assert_eq!(
+        names
+            .iter()
+            .filter(|name| **name == "package_parsed")
+            .count(),
+        5
+    );
+    assert_eq!(events[5]["name"], "web");
+    assert_eq!(events[5]["parsed"], 5);
+    // warnings reach the side channel even when stderr is quiet
+    assert_eq!(names[6], "warning");
+    assert!(events[6]["message"].as_str().unwrap().contains("REQUESTED"));
+    assert_eq!(names.last(), Some(&"check_finished"));
+    assert_eq!(events.last().unwrap()["passed"], true);
+}
+
+#[test]
+fn paths_lists_scanned_and_skipped_dirs() {
+    let site = chain_site();
+    let extra = site.path().join("src");
+    rdeptree(&[&site])
+        .arg("paths")
+        .env("PYTHONPATH", &extra)
+        .assert()
+        .success()
+        .stdout(contains(format!(
+            "scanned {} (given with --path)\n",
+            site.path().display()
+        )))
+        .stdout(contains(format!(
+            "skipped {} (PYTHONPATH entry, add it with --path, does not exist)\n",
+            extra.display()
+        )));
+}
+
+#[cfg(unix)]
+#[test]
+fn paths_follow_python_version() {
+    let prefix = SitePackages::new("paths-python-version")
+        .file("bin/python3", "")
+        .file("lib/python3.11/site-packages/.keep", "")
+        .file("lib/python3.12/site-packages/.keep", "");
+    let scanned = prefix.path().join("lib/python3.12/site-packages");
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "--python-version", "3.12", "paths"])
+        .env("RDEPTREE_PYTHON", prefix.path().join("bin/python3"))
+        .env_remove("PYTHONPATH")
+        .assert()
+        .success()
+        .stdout(format!(
+            "scanned {} (site-packages of the chosen --python-version)\n",
+            scanned.display()
+        ));
+}
+
+#[test]
+fn python_version_scans_debian_shared_dist_packages() {
+    let prefix = SitePackages::new("python-version-shared")
+        .file("bin/python3", "")
+        .file("bin/python3.11", "")
+        .file("lib/python3.11/os.py", "")
+        .file(
+            "lib/python3/dist-packages/six-1.16.0.dist-info/METADATA",
+            "Name: six\nVersion: 1.16.0\n",
+        );
+    let shared = prefix.path().join("lib/python3/dist-packages");
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "--python-version", "3.11", "paths"])
+        .env("RDEPTREE_PYTHON", prefix.path().join("bin/python3"))
+        .env_remove("PYTHONPATH")
+        .assert()
+        .success()
+        .stdout(format!(
+            "scanned {} (site-packages of the chosen --python-version)\n",
+            shared.display()
+        ));
+    let mut cmd = cargo_bin_cmd!("rdeptree");
+    cmd.args(["--lang", "en", "--python-version", "3.11", "list"])
+        .env("RDEPTREE_PYTHON", prefix.path().join("bin/python3"))
+        .env_remove("PYTHONPATH")
+        .assert()
+        .success()
+        .stdout(contains("six"));
+}
+
+#[cfg(feature = "network")]
+#[test]
+fn health_rejects_unbounded_months() {
+    let site = chain_site();
+    for months in ["0", "1201", "3074457345618258603"] {
+        rdeptree(&[&site])
+            .args(["--offline", "health", "--months", months])
+            .assert()
+            .failure()
+            .stderr(contains(format!("{} is not in 1..=1200", months)));
+    }
+}
+
+#[test]
+fn root_cost_counts_exclusive_dependencies() {
+    let site = chain_site().dist_info("cli", "1.0", &["six>=1.0"]);
+    rdeptree(&[&site])
+        .arg("--root-cost")
+        .assert()
+        .success()
+        .stdout(contains(
+            "5 packages, 3 direct, 0 conflicts, 0 missing\n\
+             root  exclusive  total\n\
+             app           1      2\n\
+             cli           0      1\n\
+             tool          0      0\n",
+        ));
+}
+
+#[test]
+fn cache_scan_lists_cached_versions() {
+    let cache = SitePackages::new("uv-cache")
+        .file(
+            "archive-v0/a1/lib-2.1.dist-info/METADATA",
+            "Name: Lib\nVersion: 2.1\nRequires-Dist: six<2,>=1.5\n",
+        )
+        .file(
+            "archive-v0/b2/lib-2.0.dist-info/METADATA",
+            "Name: Lib\nVersion: 2.0\nRequires-Dist: six>=1.5; python_version < \"3.8\"\n",
+        )
+        .file(
+            "archive-v0/c3/six-1.16.0.dist-info/METADATA",
+            "Name: six\nVersion: 1.16.0\n",
+        )
+        .file("wheels/d4/lib-2.2-py3-none-any.whl", "PK\x03\x04");
+    rdeptree(&[])
+        .arg("cache-scan")
+        .arg(cache.path())
+        .assert()
+        .success()
+        .stderr(contains("Skipping cached wheel"))
+        .stderr(contains("lib-2.2-py3-none-any.whl"))
+        .stdout(
+            "package  versions\n\
+             Lib      2.0, 2.1\n\
+             six      1.16.0\n",
+        );
+    rdeptree(&[])
+        .args(["cache-scan", "--requires", "lib"])
+        .arg(cache.path())
+        .assert()
+        .success()
+        .stdout(contains(
+            "    six >=1.5; python_version < \"3.8\"\nLib 2.1 (",
+        ))
+        .stdout(contains("    six <2,>=1.5\n"));
+    rdeptree(&[])
+        .args(["cache-scan", "--requires", "idna"])
+        .arg(cache.path())
+        .assert()
+        .failure()
+        .stderr(contains("idna is not in the cache"));
+}